@@ -1,21 +1,29 @@
-//! Simplified `Arc`.
+//! Simplified `Arc` and `Weak`.
 //!
 //! See the `Arc` documentation for more details and specification.
 
 use std::fmt;
 use std::marker::PhantomData;
-use std::mem;
+use std::mem::{self, ManuallyDrop};
 use std::ops::Deref;
-use std::ptr::NonNull;
+use std::process;
+use std::ptr::{self, NonNull};
 
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread::yield_now;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
 #[cfg(not(feature = "check-loom"))]
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::yield_now;
 
 const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 
-/// Simplified `Arc` without `Weak` support.
+/// The weak count of an `Arc` whose uniqueness is being checked.
+const LOCKED: usize = usize::MAX;
+
+/// Simplified `Arc`.
 ///
 /// The main correctness guarantee of `Arc` is that the deallocation of its data and counter field
 /// happens-after all accesses to those fields.  An access (by `Deref::deref`, `get_mut`, ...) to an
@@ -33,6 +41,14 @@ const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 /// `try_unwrap` also provides a similar guarantee as it returns the exclusive ownership of the
 /// data.
 ///
+/// With `Weak`, there are two counts.  The strong count is the number of `Arc`s, and the data is
+/// dropped when it reaches zero.  The weak count is the number of `Weak`s plus one, which is held
+/// collectively by all the `Arc`s, and the allocation is freed when it reaches zero.  So the
+/// guarantees above apply to each count: dropping the data happens-after all the accesses through
+/// `Arc`s, and freeing the allocation happens-after dropping the data and all the accesses to the
+/// counts through `Weak`s.  A `Weak` can become an `Arc` again by `Weak::upgrade`, which fails once
+/// the strong count has reached zero.
+///
 /// The above explanation is based on the paper [RustBelt Meets Relaxed Memory by Dang et
 /// al.](https://plv.mpi-sws.org/rustbelt/rbrlx/).
 pub struct Arc<T> {
@@ -52,9 +68,21 @@ impl<T> Arc<T> {
     }
 }
 
+/// Non-owning reference to the allocation of an `Arc`, which doesn't keep the data alive.
+///
+/// See `Arc::downgrade` and `Weak::upgrade`.
+pub struct Weak<T> {
+    ptr: NonNull<ArcInner<T>>,
+}
+
+unsafe impl<T: Sync + Send> Send for Weak<T> {}
+unsafe impl<T: Sync + Send> Sync for Weak<T> {}
+
 struct ArcInner<T> {
-    count: AtomicUsize,
-    data: T,
+    strong: AtomicUsize,
+    weak: AtomicUsize,
+    /// Dropped when the strong count reaches zero, before the allocation is freed.
+    data: ManuallyDrop<T>,
 }
 
 unsafe impl<T: Sync + Send> Send for ArcInner<T> {}
@@ -65,14 +93,54 @@ impl<T> Arc<T> {
     #[inline]
     pub fn new(data: T) -> Arc<T> {
         let x = Box::new(ArcInner {
-            count: AtomicUsize::new(1),
-            data,
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            data: ManuallyDrop::new(data),
         });
         Self::from_inner(Box::leak(x).into())
     }
 
+    /// Creates a new `Weak` pointer to this allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let weak_five = Arc::downgrade(&five);
+    /// assert_eq!(*weak_five.upgrade().unwrap(), 5);
+    /// ```
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let inner = this.inner();
+        let mut cur = inner.weak.load(Ordering::Relaxed);
+        loop {
+            // Waits while `is_unique` checks the strong count.
+            if cur == LOCKED {
+                yield_now();
+                cur = inner.weak.load(Ordering::Relaxed);
+                continue;
+            }
+            if cur > MAX_REFCOUNT {
+                process::abort();
+            }
+
+            // Acquire synchronizes with the release in `is_unique`, so that a later `get_mut` of
+            // another `Arc` can't miss this `Weak`.
+            match inner.weak.compare_exchange_weak(
+                cur,
+                cur + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Weak { ptr: this.ptr },
+                Err(old) => cur = old,
+            }
+        }
+    }
+
     /// Returns a mutable reference into the given `Arc` if there are
-    /// no other `Arc`. Otherwise, return `None`.
+    /// no other `Arc` or `Weak`. Otherwise, return `None`.
     ///
     /// # Examples
     ///
@@ -88,12 +156,19 @@ impl<T> Arc<T> {
     ///
     /// drop(y);
     /// assert!(Arc::get_mut(&mut x).is_some());
+    ///
+    /// let z = Arc::downgrade(&x);
+    /// assert!(Arc::get_mut(&mut x).is_none());
     /// ```
     #[inline]
     pub fn get_mut(this: &mut Self) -> Option<&mut T> {
         if this.is_unique() {
-            unsafe{Some(&mut (*this.ptr.as_ptr()).data)}
-        }else{
+            // This unsafety is ok because we're guaranteed that the pointer returned is the
+            // *only* pointer that will ever be returned to T. Our reference count is guaranteed to
+            // be 1 at this point, and we required the Arc itself to be `mut`, so we're returning
+            // the only possible reference to the inner data.
+            unsafe { Some(Arc::get_mut_unchecked(this)) }
+        } else {
             None
         }
     }
@@ -102,16 +177,36 @@ impl<T> Arc<T> {
     // underlying data.
     #[inline]
     fn is_unique(&mut self) -> bool {
-        Arc::count(self)==1
+        // Locks the weak count if this is the only `Weak`-like reference (the one held by the
+        // `Arc`s), so that no `Arc` can be upgraded from a `Weak` while the strong count is read.
+        // Acquire synchronizes with the release in `Weak::drop`, so that the accesses of the
+        // dropped `Weak`s to the counts happen-before.
+        if self
+            .inner()
+            .weak
+            .compare_exchange(1, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            // Acquire synchronizes with the release in `drop`, so that the accesses through the
+            // dropped `Arc`s happen-before.
+            let unique = self.inner().strong.load(Ordering::Acquire) == 1;
+
+            // Release synchronizes with the acquire in `downgrade`, so that the read of the strong
+            // count above happens-before the new `Weak` is created.
+            self.inner().weak.store(1, Ordering::Release);
+            unique
+        } else {
+            false
+        }
     }
 
     /// Returns a mutable reference into the given `Arc` without any check.
     ///
     /// # Safety
     ///
-    /// Any other `Arc` to the same allocation must not be dereferenced for the duration of the
-    /// returned borrow.  Specifically, call to this function must happen-after destruction of all
-    /// the other `Arc` to the same allocation.
+    /// Any other `Arc` or `Weak` to the same allocation must not be dereferenced for the duration
+    /// of the returned borrow.  Specifically, call to this function must happen-after destruction
+    /// of all the other `Arc` to the same allocation.
     ///
     /// # Examples
     ///
@@ -153,8 +248,32 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn count(this: &Self) -> usize {
-        unsafe{
-            (*this.ptr.as_ptr()).count.load(Ordering::Acquire)
+        this.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// Gets the number of `Weak`s to this allocation. It has the same caveat as `count`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let _weak_five = Arc::downgrade(&five);
+    ///
+    /// // This assertion is deterministic because we haven't shared
+    /// // the `Arc` or `Weak` between threads.
+    /// assert_eq!(1, Arc::weak_count(&five));
+    /// ```
+    #[inline]
+    pub fn weak_count(this: &Self) -> usize {
+        let cnt = this.inner().weak.load(Ordering::Acquire);
+        // If the weak count is locked, the count was 1 before, i.e., there is no `Weak`. The `Arc`s
+        // hold one more than the number of `Weak`s.
+        if cnt == LOCKED {
+            0
+        } else {
+            cnt - 1
         }
     }
 
@@ -188,7 +307,8 @@ impl<T> Arc<T> {
         this.ptr.as_ptr() == other.ptr.as_ptr()
     }
 
-    /// Returns the inner value, if the given `Arc` is unique.
+    /// Returns the inner value, if the given `Arc` is unique. The `Weak`s to it can't be upgraded
+    /// afterwards.
     ///
     /// Otherwise, an `Err` is returned with the same `Arc` that was passed in.
     ///
@@ -206,15 +326,27 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn try_unwrap(this: Self) -> Result<T, Self> {
-        if Arc::count(&this)==1 {
-            unsafe{
-            let b = Box::from_raw(this.ptr.as_ptr());
-            let data = b.data;
+        // Once the strong count is zero, no `Arc` can be upgraded from a `Weak`.
+        if this
+            .inner()
+            .strong
+            .compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+
+        // Synchronizes with the release in `drop`, so that the accesses through the dropped `Arc`s
+        // happen-before.
+        fence(Ordering::Acquire);
+
+        unsafe {
+            let data = ptr::read(&*this.inner().data);
+
+            // Releases the weak count held by the `Arc`s.
+            let _weak = Weak { ptr: this.ptr };
             mem::forget(this);
             Ok(data)
-            }
-        }else{
-            Err(this)
         }
     }
 }
@@ -226,6 +358,9 @@ impl<T: Clone> Arc<T> {
     /// allocation and invoke `clone` on the inner value to ensure unique ownership. This is also
     /// referred to as clone-on-write.
     ///
+    /// If there are no other `Arc` but some `Weak`s to the same allocation, then the `Weak`s will
+    /// be disassociated, i.e., the inner value is moved to a new allocation without cloning.
+    ///
     /// See also `get_mut`, which will fail rather than cloning.
     ///
     /// # Examples
@@ -244,24 +379,41 @@ impl<T: Clone> Arc<T> {
     /// // Now `data` and `other_data` point to different allocations.
     /// assert_eq!(*data, 8);
     /// assert_eq!(*other_data, 12);
+    ///
+    /// let weak = Arc::downgrade(&data);
+    /// *Arc::make_mut(&mut data) += 1;         // Disassociates `weak`
+    /// assert!(weak.upgrade().is_none());
     /// ```
     #[inline]
     pub fn make_mut(this: &mut Self) -> &mut T {
-        if(this.is_unique()){
-            unsafe { &mut (*this.ptr.as_ptr()).data }
-        }else{
-            let p = this.ptr.as_ptr();
-            unsafe{
-                (*p).count.fetch_sub(1,Ordering::AcqRel);
-                let x=Box::new(ArcInner{
-                    count: AtomicUsize::new(1),
-                    data: T::clone(&(*p).data),
-                });
-                this.ptr = Box::leak(x).into();
-
-                &mut (*this.ptr.as_ptr()).data 
+        // Takes the strong count to zero as in `try_unwrap`, so that no `Arc` can be upgraded from
+        // a `Weak` in the meantime.  Acquire synchronizes with the release in `drop`, so that the
+        // accesses through the dropped `Arc`s happen-before.
+        if this
+            .inner()
+            .strong
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Another `Arc` exists, so clones the data.
+            *this = Arc::new((**this).clone());
+        } else if this.inner().weak.load(Ordering::Relaxed) != 1 {
+            // Only `Weak`s exist, so moves the data out of them.
+            unsafe {
+                // Releases the weak count held by the `Arc`s.
+                let _weak = Weak { ptr: this.ptr };
+                let fresh = Arc::new(ptr::read(&*this.inner().data));
+                ptr::write(this, fresh);
             }
+        } else {
+            // This is the unique reference, so restores the strong count.  No other `Arc` or `Weak`
+            // can read it in the meantime.
+            this.inner().strong.store(1, Ordering::Release);
         }
+
+        // As with `get_mut`, the unsafety is ok because our reference was either unique to begin
+        // with, or became one upon cloning the contents.
+        unsafe { Arc::get_mut_unchecked(this) }
     }
 }
 
@@ -271,9 +423,9 @@ impl<T> Clone for Arc<T> {
     /// This creates another pointer to the same allocation, increasing the
     /// reference count.
     ///
-    /// # Panics
+    /// # Aborts
     ///
-    /// This panics if the number of `Arc`s is larger than `isize::Max`.
+    /// This aborts if the number of `Arc`s is larger than `isize::MAX`.
     ///
     /// # Examples
     ///
@@ -286,9 +438,15 @@ impl<T> Clone for Arc<T> {
     /// ```
     #[inline]
     fn clone(&self) -> Arc<T> {
-        unsafe{
-            (*self.ptr.as_ptr()).count.fetch_add(1,Ordering::AcqRel);
+        // Relaxed is enough, as the new `Arc` is created from an existing one, whose accesses are
+        // ordered before its own `drop`.
+        let old_size = self.inner().strong.fetch_add(1, Ordering::Relaxed);
+
+        // Panicking would let the other threads keep cloning, and overflow the count.
+        if old_size > MAX_REFCOUNT {
+            process::abort();
         }
+
         Arc::from_inner(self.ptr)
     }
 }
@@ -328,14 +486,152 @@ impl<T> Drop for Arc<T> {
     /// drop(foo2);   // Prints "dropped!"
     /// ```
     fn drop(&mut self) {
-        let p = self.ptr.as_ptr();
-        unsafe{
-            let cnt = (*p).count.fetch_sub(1,Ordering::AcqRel);
-            if cnt == 1 {
-                let data = Box::from_raw(p);
+        // Release orders the accesses through this `Arc` before the drop of the data.
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // Synchronizes with the releases of all the other `drop`s, so that the accesses through
+        // them happen-before the drop of the data.
+        fence(Ordering::Acquire);
+
+        unsafe {
+            ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).data);
+        }
+
+        // Releases the weak count held by the `Arc`s, which frees the allocation if there is no
+        // `Weak`.
+        drop(Weak { ptr: self.ptr });
+    }
+}
+
+impl<T> Weak<T> {
+    #[inline]
+    fn inner(&self) -> &ArcInner<T> {
+        // The allocation is alive while the weak count is positive.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Attempts to upgrade the `Weak` pointer to an `Arc`, delaying dropping of the inner value if
+    /// successful.
+    ///
+    /// Returns `None` if the inner value has since been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let weak_five = Arc::downgrade(&five);
+    ///
+    /// let strong_five = weak_five.upgrade();
+    /// assert!(strong_five.is_some());
+    ///
+    /// // Destroy all strong pointers.
+    /// drop(strong_five);
+    /// drop(five);
+    ///
+    /// assert!(weak_five.upgrade().is_none());
+    /// ```
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        // A CAS loop instead of `fetch_add`, as the strong count must not increase from zero.
+        // Relaxed is enough as in `Arc::clone`, since a non-zero strong count means that there is
+        // an `Arc` whose accesses are ordered before its own `drop`.
+        let inner = self.inner();
+        let mut n = inner.strong.load(Ordering::Relaxed);
+        loop {
+            if n == 0 {
+                return None;
+            }
+            if n > MAX_REFCOUNT {
+                process::abort();
+            }
+
+            match inner
+                .strong
+                .compare_exchange_weak(n, n + 1, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return Some(Arc::from_inner(self.ptr)),
+                Err(old) => n = old,
             }
         }
     }
+
+    /// Gets the number of `Arc`s to this allocation. It has the same caveat as `Arc::count`.
+    pub fn strong_count(&self) -> usize {
+        self.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// Gets the number of `Weak`s to this allocation, or 0 if there is no `Arc`. It has the same
+    /// caveat as `Arc::count`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs492_concur_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let weak_five = Arc::downgrade(&five);
+    /// assert_eq!(weak_five.weak_count(), 1);
+    ///
+    /// drop(five);
+    /// assert_eq!(weak_five.weak_count(), 0);
+    /// ```
+    pub fn weak_count(&self) -> usize {
+        let weak = self.inner().weak.load(Ordering::Acquire);
+        let strong = self.inner().strong.load(Ordering::Acquire);
+        if strong == 0 {
+            0
+        } else {
+            // The `Arc`s hold one more than the number of `Weak`s. The weak count can't be locked
+            // while this `Weak` is alive.
+            weak - 1
+        }
+    }
+
+    /// Returns `true` if the two `Weak`s point to the same allocation.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.ptr.as_ptr() == other.ptr.as_ptr()
+    }
+}
+
+impl<T> Clone for Weak<T> {
+    /// Makes a clone of the `Weak` pointer that points to the same allocation.
+    ///
+    /// # Aborts
+    ///
+    /// This aborts if the number of `Weak`s is larger than `isize::MAX`.
+    #[inline]
+    fn clone(&self) -> Weak<T> {
+        // Relaxed is enough as in `Arc::clone`. The weak count can't be locked while this `Weak` is
+        // alive.
+        let old_size = self.inner().weak.fetch_add(1, Ordering::Relaxed);
+        if old_size > MAX_REFCOUNT {
+            process::abort();
+        }
+        Weak { ptr: self.ptr }
+    }
+}
+
+impl<T> Drop for Weak<T> {
+    /// Drops the `Weak` pointer. If it is the last reference to the allocation, including the one
+    /// held by the `Arc`s, frees the allocation.
+    fn drop(&mut self) {
+        // Release orders the accesses to the counts through this `Weak` (and the drop of the data
+        // if this is the weak count held by the `Arc`s) before the deallocation.
+        if self.inner().weak.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // Synchronizes with the releases of all the other `Weak::drop`s.
+        fence(Ordering::Acquire);
+
+        // The data is already dropped, and `ManuallyDrop` doesn't drop it again.
+        unsafe {
+            drop(Box::from_raw(self.ptr.as_ptr()));
+        }
+    }
 }
 
 impl<T: fmt::Display> fmt::Display for Arc<T> {
@@ -355,3 +651,9 @@ impl<T> fmt::Pointer for Arc<T> {
         fmt::Pointer::fmt(&(&**self as *const T), f)
     }
 }
+
+impl<T> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
@@ -0,0 +1,134 @@
+use crossbeam_epoch as epoch;
+use cs492_concur_homework::{FcPriorityQueue, FcQueue};
+use lockfree::Queue;
+use rand::prelude::*;
+use std::collections::{BinaryHeap, VecDeque};
+use std::env;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+mod harness;
+
+const USAGE: &str = "usage: fc_bench [THREADS] [SECONDS]";
+
+/// Operations of the queues under benchmark.
+trait Bench: Sync {
+    fn new() -> Self;
+    fn push(&self, t: usize);
+    fn pop(&self) -> Option<usize>;
+}
+
+impl Bench for FcQueue<usize> {
+    fn new() -> Self {
+        Self::new()
+    }
+    fn push(&self, t: usize) {
+        self.push(t)
+    }
+    fn pop(&self) -> Option<usize> {
+        self.pop()
+    }
+}
+
+impl Bench for Queue<usize> {
+    fn new() -> Self {
+        Self::new()
+    }
+    fn push(&self, t: usize) {
+        self.push(t, &epoch::pin())
+    }
+    fn pop(&self) -> Option<usize> {
+        self.try_pop(&epoch::pin())
+    }
+}
+
+impl Bench for Mutex<VecDeque<usize>> {
+    fn new() -> Self {
+        Self::default()
+    }
+    fn push(&self, t: usize) {
+        self.lock().unwrap().push_back(t)
+    }
+    fn pop(&self) -> Option<usize> {
+        self.lock().unwrap().pop_front()
+    }
+}
+
+impl Bench for FcPriorityQueue<usize> {
+    fn new() -> Self {
+        Self::new()
+    }
+    fn push(&self, t: usize) {
+        self.push(t)
+    }
+    fn pop(&self) -> Option<usize> {
+        self.pop()
+    }
+}
+
+impl Bench for Mutex<BinaryHeap<usize>> {
+    fn new() -> Self {
+        Self::default()
+    }
+    fn push(&self, t: usize) {
+        self.lock().unwrap().push(t)
+    }
+    fn pop(&self) -> Option<usize> {
+        self.lock().unwrap().pop()
+    }
+}
+
+/// Runs 50% `push` of random elements and 50% `pop` on `threads` threads for `duration`, starting
+/// with 1000 elements, and returns the operations per second.
+fn bench<Q: Bench>(threads: usize, duration: Duration) -> f64 {
+    let queue = Q::new();
+    let mut rng = thread_rng();
+    for _ in 0..1000 {
+        queue.push(rng.gen());
+    }
+
+    harness::run(threads, duration, thread_rng, |rng| {
+        if rng.gen() {
+            queue.push(rng.gen());
+        } else {
+            let _ = queue.pop();
+        }
+    })
+}
+
+fn main() -> io::Result<()> {
+    // For example, `cargo run --release --bin fc_bench 8 2` runs each queue on 8 threads for 2
+    // seconds.
+    let mut args = env::args().skip(1);
+    let threads = harness::arg(args.next(), 4, "number of threads", USAGE)?;
+    let duration = Duration::from_secs(harness::arg(args.next(), 1, "number of seconds", USAGE)?);
+
+    println!("[fc_bench] {} threads, {:?}, 50% push\n", threads, duration);
+    let mutex = bench::<Mutex<VecDeque<usize>>>(threads, duration);
+    println!("Mutex<VecDeque>:       {:>12.0} ops/s", mutex);
+    let lockfree = bench::<Queue<usize>>(threads, duration);
+    println!(
+        "lockfree::Queue:       {:>12.0} ops/s ({:.2}x)",
+        lockfree,
+        lockfree / mutex
+    );
+    let fc = bench::<FcQueue<usize>>(threads, duration);
+    println!(
+        "FcQueue:               {:>12.0} ops/s ({:.2}x)",
+        fc,
+        fc / mutex
+    );
+
+    // There is no lock-free priority queue to compare with.
+    let mutex = bench::<Mutex<BinaryHeap<usize>>>(threads, duration);
+    println!("Mutex<BinaryHeap>:     {:>12.0} ops/s", mutex);
+    let fc = bench::<FcPriorityQueue<usize>>(threads, duration);
+    println!(
+        "FcPriorityQueue:       {:>12.0} ops/s ({:.2}x)",
+        fc,
+        fc / mutex
+    );
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Helpers shared by the benchmark binaries.
+
+use crossbeam_utils::thread::scope;
+use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Parses a command-line argument, or returns `default` if it is absent. On a parse error, the
+/// error message describes `what` the argument is, followed by `usage`.
+pub fn arg<T: FromStr>(arg: Option<String>, default: T, what: &str, usage: &str) -> io::Result<T> {
+    match arg {
+        None => Ok(default),
+        Some(arg) => arg.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid {}\n{}", what, usage),
+            )
+        }),
+    }
+}
+
+/// Calls `op` repeatedly on `threads` threads for `duration`, and returns the calls per second.
+/// Each thread passes its own state created by `init` to `op`, e.g. a random number generator.
+pub fn run<T, I, F>(threads: usize, duration: Duration, init: I, op: F) -> f64
+where
+    I: Fn() -> T + Sync,
+    F: Fn(&mut T) + Sync,
+{
+    let done = AtomicBool::new(false);
+    let start = Instant::now();
+    let ops = scope(|s| {
+        let handles = (0..threads)
+            .map(|_| {
+                s.spawn(|_| {
+                    let mut state = init();
+                    let mut ops = 0usize;
+                    while !done.load(Ordering::Relaxed) {
+                        op(&mut state);
+                        ops += 1;
+                    }
+                    ops
+                })
+            })
+            .collect::<Vec<_>>();
+        std::thread::sleep(duration);
+        done.store(true, Ordering::Relaxed);
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>()
+    })
+    .unwrap();
+    ops as f64 / start.elapsed().as_secs_f64()
+}
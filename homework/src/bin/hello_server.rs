@@ -1,11 +1,14 @@
-use crossbeam_channel::{bounded, unbounded};
+#[cfg(feature = "tls")]
+use cs492_concur_homework::hello_server::TlsAcceptor;
 use cs492_concur_homework::hello_server::{
-    CancellableTcpListener, Handler, Statistics, ThreadPool,
+    AsyncLogger, Compression, Handler, ResponseCache, Server, StdoutLogger,
 };
 use std::io;
-use std::sync::Arc;
+use std::time::Duration;
 
 const ADDR: &str = "localhost:7878";
+#[cfg(feature = "tls")]
+const TLS_ADDR: &str = "localhost:7879";
 
 fn main() -> io::Result<()> {
     // Use a browser that doesn't cache too eagerly so that request is always sent. For example,
@@ -13,70 +16,54 @@ fn main() -> io::Result<()> {
     // run it on the lab server, you may need to change the port number to something else.
     println!("Browse [http://{}]\n", ADDR);
 
-    // The thread pool.
+    // The server.
     //
-    // In the thread pool, we'll execute:
-    //
-    // - A listener: it accepts incoming connections, and creates a new worker for each connection.
+    // The server accepts incoming connections in a thread for each address, and in its thread
+    // pool, executes:
     //
     // - Workers (once for each incoming connection): a worker handles an incoming connection and
     //   sends a corresponding report to the reporter.
     //
     // - A reporter: it aggregates the reports the reports from the workers and processes the
     //   statistics.  When it ends, it sends the statistics to the main thread.
-    let pool = Arc::new(ThreadPool::new(7));
-
-    // The (MPSC) channel of reports between workers and the reporter.
-    let (report_sender, report_receiver) = unbounded();
+    //
+    // The requests are logged in a separate thread so that the workers are not blocked by logging.
+    // The responses are cached for a minute, and compressed if the client accepts gzip or deflate.
+    // The statistics are served at `/stats`, and the Prometheus metrics at `/metrics`.
+    let builder = Server::builder()
+        .bind(ADDR)
+        .workers(7)
+        .read_timeout(Duration::from_secs(5))
+        .write_timeout(Duration::from_secs(5))
+        .middleware(Compression::default())
+        .middleware(ResponseCache::new(Duration::from_secs(60)));
 
-    // The (SPSC one-shot) channel of stats between the reporter and the main thread.
-    let (stat_sender, stat_receiver) = bounded(0);
+    // Also serves HTTPS if the certificate and key PEM files are given.
+    #[cfg(feature = "tls")]
+    let builder = match (std::env::var_os("TLS_CERT"), std::env::var_os("TLS_KEY")) {
+        (Some(cert), Some(key)) => {
+            println!("Browse [https://{}]\n", TLS_ADDR);
+            builder.bind_tls(TLS_ADDR, TlsAcceptor::from_pem_files(cert, key)?)
+        }
+        _ => builder,
+    };
 
-    // Listens to the address.
-    let listener = Arc::new(CancellableTcpListener::bind(ADDR)?);
+    let server = builder
+        .build()?
+        .handler(Handler::default().logger(AsyncLogger::new(StdoutLogger)))
+        .stats_endpoint("/stats")
+        .metrics_endpoint("/metrics");
 
-    // Installs a Ctrl-C handler.
-    let ctrlc_listner_handle = listener.clone();
+    // Installs a Ctrl-C (SIGINT) and SIGTERM handler.
+    let shutdown_handle = server.shutdown_handle();
     ctrlc::set_handler(move || {
-        ctrlc_listner_handle.cancel().unwrap();
+        shutdown_handle.shutdown().unwrap();
     })
     .expect("Error setting Ctrl-C handler");
 
-    // Executes the listener.
-    let listener_pool = pool.clone();
-    pool.execute(move || {
-        // Creates the request handler.
-        let handler = Handler::default();
-
-        // For each incoming connection...
-        for (id, stream) in listener.incoming().enumerate() {
-            // send a job to the thread pool.
-            let report_sender = report_sender.clone();
-            let handler = handler.clone();
-            listener_pool.execute(move || {
-                let report = handler.handle_conn(id, stream.unwrap());
-                report_sender.send(report).unwrap();
-            });
-        }
-    });
-
-    // Executes the reporter.
-    pool.execute(move || {
-        let mut stats = Statistics::default();
-        for report in report_receiver {
-            println!("[report] {:?}", report);
-            stats.add_report(report);
-        }
-
-        println!("[sending stat]");
-        stat_sender.send(stats).unwrap();
-        println!("[sent stat]");
-    });
-
-    // Blocks until the reporter sends the statistics.
-    let stat = stat_receiver.recv().unwrap();
+    // Blocks until the server is shut down and the in-flight connections are handled.
+    let stat = server.run();
     println!("[stat] {:?}", stat);
 
     Ok(())
-    // When the pool is dropped, all worker threads are joined.
 }
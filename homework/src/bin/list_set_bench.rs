@@ -0,0 +1,145 @@
+use cs492_concur_homework::{LazyListSet, LockFreeListSet, OrderedListSet, RawRwLock};
+use lock::{McsParkingLock, SpinLock, TicketLock, TtasLock};
+use rand::prelude::*;
+use std::env;
+use std::io;
+use std::time::Duration;
+
+mod harness;
+
+const USAGE: &str = "usage: list_set_bench [THREADS] [KEYS] [SECONDS]";
+
+/// Operations of the sets under benchmark.
+trait Set: Sync {
+    fn new() -> Self;
+    fn contains(&self, key: &usize) -> bool;
+    fn insert(&self, key: usize) -> bool;
+    fn remove(&self, key: &usize) -> bool;
+}
+
+impl<L: RawRwLock> Set for OrderedListSet<usize, L> {
+    fn new() -> Self {
+        Self::with_lock()
+    }
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key).is_ok()
+    }
+}
+
+/// `OrderedListSet` recycling the nodes.
+struct Pooled(OrderedListSet<usize>);
+
+impl Set for Pooled {
+    fn new() -> Self {
+        Pooled(OrderedListSet::new().node_pool(1024))
+    }
+    fn contains(&self, key: &usize) -> bool {
+        self.0.contains(key)
+    }
+    fn insert(&self, key: usize) -> bool {
+        self.0.insert(key).is_ok()
+    }
+    fn remove(&self, key: &usize) -> bool {
+        self.0.remove(key).is_ok()
+    }
+}
+
+impl Set for LazyListSet<usize> {
+    fn new() -> Self {
+        Self::new()
+    }
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key).is_ok()
+    }
+}
+
+impl Set for LockFreeListSet<usize> {
+    fn new() -> Self {
+        Self::new()
+    }
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key).is_ok()
+    }
+}
+
+/// Runs 90% `contains`, 5% `insert`, and 5% `remove` of random keys in `0..keys` on `threads`
+/// threads for `duration`, starting with half of the keys, and returns the operations per second.
+fn bench<S: Set>(threads: usize, keys: usize, duration: Duration) -> f64 {
+    let set = S::new();
+    for key in (0..keys).step_by(2) {
+        let _ = set.insert(key);
+    }
+
+    harness::run(threads, duration, thread_rng, |rng| {
+        let key = rng.gen_range(0, keys);
+        match rng.gen_range(0, 100) {
+            0..=4 => {
+                let _ = set.insert(key);
+            }
+            5..=9 => {
+                let _ = set.remove(&key);
+            }
+            _ => {
+                let _ = set.contains(&key);
+            }
+        }
+    })
+}
+
+fn main() -> io::Result<()> {
+    // For example, `cargo run --release --bin list_set_bench 8 1000 2` runs each set on 8
+    // threads with the keys in `0..1000` for 2 seconds.
+    let mut args = env::args().skip(1);
+    let threads = harness::arg(args.next(), 4, "number of threads", USAGE)?;
+    let keys = harness::arg(args.next(), 1000, "number of keys", USAGE)?;
+    let duration = Duration::from_secs(harness::arg(args.next(), 1, "number of seconds", USAGE)?);
+
+    println!(
+        "[list_set_bench] {} threads, {} keys, {:?}, 90% contains\n",
+        threads, keys, duration
+    );
+    let ordered = bench::<OrderedListSet<usize>>(threads, keys, duration);
+    println!("OrderedListSet:                 {:>12.0} ops/s", ordered);
+    let pooled = bench::<Pooled>(threads, keys, duration);
+    println!("OrderedListSet (node pool):     {:>12.0} ops/s", pooled);
+    let spin = bench::<OrderedListSet<usize, SpinLock>>(threads, keys, duration);
+    println!("OrderedListSet<SpinLock>:       {:>12.0} ops/s", spin);
+    let ttas = bench::<OrderedListSet<usize, TtasLock>>(threads, keys, duration);
+    println!("OrderedListSet<TtasLock>:       {:>12.0} ops/s", ttas);
+    let ticket = bench::<OrderedListSet<usize, TicketLock>>(threads, keys, duration);
+    println!("OrderedListSet<TicketLock>:     {:>12.0} ops/s", ticket);
+    let mcs = bench::<OrderedListSet<usize, McsParkingLock>>(threads, keys, duration);
+    println!("OrderedListSet<McsParkingLock>: {:>12.0} ops/s", mcs);
+    let lazy = bench::<LazyListSet<usize>>(threads, keys, duration);
+    println!(
+        "LazyListSet:                    {:>12.0} ops/s ({:.2}x)",
+        lazy,
+        lazy / ordered
+    );
+    let lockfree = bench::<LockFreeListSet<usize>>(threads, keys, duration);
+    println!(
+        "LockFreeListSet:                {:>12.0} ops/s ({:.2}x)",
+        lockfree,
+        lockfree / ordered
+    );
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use cs492_concur_homework::hello_server::LoadGen;
+use std::env;
+use std::io;
+use std::net::ToSocketAddrs;
+
+const USAGE: &str = "usage: loadgen [ADDR] [CONNECTIONS] [REQUESTS] [PATH[=WEIGHT]]...";
+const ADDR: &str = "localhost:7878";
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid {}\n{}", what, USAGE),
+    )
+}
+
+fn main() -> io::Result<()> {
+    // Run the server with `cargo run --release --bin hello_server` first. For example,
+    // `cargo run --release --bin loadgen localhost:7878 8 100 /alice=3 /bob` sends 100 requests
+    // over each of 8 connections, three times as many for `/alice` as for `/bob`.
+    let mut args = env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| ADDR.to_string());
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| invalid("address"))?;
+    let mut loadgen = LoadGen::new(addr);
+    if let Some(connections) = args.next() {
+        match connections.parse() {
+            Ok(connections) if connections > 0 => loadgen = loadgen.connections(connections),
+            _ => return Err(invalid("number of connections")),
+        }
+    }
+    if let Some(requests) = args.next() {
+        let requests = requests
+            .parse()
+            .map_err(|_| invalid("number of requests"))?;
+        loadgen = loadgen.requests(requests);
+    }
+    for path in args {
+        let mut split = path.splitn(2, '=');
+        let path = split.next().unwrap();
+        let weight = match split.next() {
+            Some(weight) => weight.parse().map_err(|_| invalid("weight"))?,
+            None => 1,
+        };
+        loadgen = loadgen.path(path, weight);
+    }
+
+    println!("[loadgen] {:?}\n", loadgen);
+    let report = loadgen.run();
+    println!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use cs492_concur_homework::hazard_pointer::queue::{Node, Queue};
+use cs492_concur_homework::hazard_pointer::reclaimer::{Epoch, Hp, Ibr, Reclaimer};
+use std::env;
+use std::io;
+use std::time::Duration;
+
+mod harness;
+
+const USAGE: &str = "usage: reclaimer_bench [THREADS] [SECONDS]";
+
+/// Runs pairs of `push` and `pop` on `threads` threads for `duration`, starting with 1024
+/// elements, and returns the pairs per second.
+fn bench<R: Reclaimer<Node<usize>>>(threads: usize, duration: Duration) -> f64 {
+    let queue = Queue::<usize, R>::with_reclaimer();
+    for i in 0..1024 {
+        queue.push(i);
+    }
+
+    harness::run(
+        threads,
+        duration,
+        || 0,
+        |i| {
+            queue.push(*i);
+            let _ = queue.pop();
+            *i += 1;
+        },
+    )
+}
+
+fn main() -> io::Result<()> {
+    // For example, `cargo run --release --bin reclaimer_bench 8 2` runs the queue with each
+    // reclamation scheme on 8 threads for 2 seconds.
+    let mut args = env::args().skip(1);
+    let threads = harness::arg(args.next(), 4, "number of threads", USAGE)?;
+    let duration = Duration::from_secs(harness::arg(args.next(), 1, "number of seconds", USAGE)?);
+
+    println!(
+        "[reclaimer_bench] {} threads, {:?}, push-pop pairs on a queue\n",
+        threads, duration
+    );
+    let hp = bench::<Hp>(threads, duration);
+    println!("Queue<Hp>:    {:>12.0} pairs/s", hp);
+    let epoch = bench::<Epoch>(threads, duration);
+    println!("Queue<Epoch>: {:>12.0} pairs/s ({:.2}x)", epoch, epoch / hp);
+    let ibr = bench::<Ibr>(threads, duration);
+    println!("Queue<Ibr>:   {:>12.0} pairs/s ({:.2}x)", ibr, ibr / hp);
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::hazard_pointer::Retired;
+
+/// Bag of retired pointers, sealed with the global epoch once it is full. The pointers are freed
+/// once the global epoch is two epochs ahead of the seal.
+#[derive(Debug)]
+pub(crate) struct SealedBag {
+    pub(crate) epoch: usize,
+    pub(crate) inner: Vec<Retired>,
+}
+
+impl SealedBag {
+    pub(crate) fn is_expired(&self, global_epoch: usize) -> bool {
+        global_epoch.wrapping_sub(self.epoch) >= 2
+    }
+
+    /// Frees the pointers in the bag, which must be expired.
+    pub(crate) fn free(self) {
+        for (data, free, _) in self.inner {
+            unsafe { free.call(data) };
+        }
+    }
+}
+
+/// Thread-local bags of retired pointers: the current one, and the sealed ones.
+#[derive(Debug, Default)]
+pub(crate) struct Bags {
+    current: Vec<Retired>,
+    sealed: Vec<SealedBag>,
+}
+
+impl Bags {
+    /// The current bag is sealed when its length becomes larger than this value.
+    pub(crate) const BAG_SIZE: usize = 64;
+
+    /// Adds a pointer to the current bag. Returns `true` if the bag is full.
+    pub(crate) fn push(&mut self, retired: Retired) -> bool {
+        self.current.push(retired);
+        self.current.len() > Self::BAG_SIZE
+    }
+
+    /// Seals the current bag with the given epoch, which must be loaded after the pointers in it
+    /// are unlinked.
+    pub(crate) fn seal(&mut self, epoch: usize) {
+        if !self.current.is_empty() {
+            let inner = mem::take(&mut self.current);
+            self.sealed.push(SealedBag { epoch, inner });
+        }
+    }
+
+    /// Adds sealed bags, e.g. of the exited threads.
+    pub(crate) fn adopt(&mut self, bags: Vec<SealedBag>) {
+        self.sealed.extend(bags);
+    }
+
+    /// Takes the expired bags, to be freed by `SealedBag::free` once the bags are not borrowed
+    /// anymore, since the destructors may retire more pointers.
+    pub(crate) fn take_expired(&mut self, global_epoch: usize) -> Vec<SealedBag> {
+        let (expired, sealed) = mem::take(&mut self.sealed)
+            .into_iter()
+            .partition::<Vec<_>, _>(|bag| bag.is_expired(global_epoch));
+        self.sealed = sealed;
+        expired
+    }
+
+    /// Returns `true` if there is no pointer in the bags.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.current.is_empty() && self.sealed.is_empty()
+    }
+
+    /// Takes the sealed bags.
+    pub(crate) fn take_sealed(&mut self) -> Vec<SealedBag> {
+        mem::take(&mut self.sealed)
+    }
+}
+
+/// Global list of the sealed bags left by the exited threads.
+///
+/// As `GlobalRetirees`, it is a lock-free stack of batches that are pushed one by one and taken all
+/// at once, so there is no ABA problem.
+#[derive(Debug)]
+pub(crate) struct Orphans {
+    head: AtomicPtr<Batch>,
+}
+
+#[derive(Debug)]
+struct Batch {
+    inner: Vec<SealedBag>,
+    next: *mut Batch,
+}
+
+impl Orphans {
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    pub(crate) fn push(&self, inner: Vec<SealedBag>) {
+        let batch = Box::into_raw(Box::new(Batch {
+            inner,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*batch).next = head };
+            match self
+                .head
+                .compare_exchange(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    pub(crate) fn take(&self) -> Vec<SealedBag> {
+        let mut inner = Vec::new();
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return inner;
+        }
+        let mut batch = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        while !batch.is_null() {
+            let batch_ref = unsafe { Box::from_raw(batch) };
+            batch = batch_ref.next;
+            inner.extend(batch_ref.inner);
+        }
+        inner
+    }
+}
+
+impl Drop for Orphans {
+    fn drop(&mut self) {
+        for bag in self.take() {
+            for (data, free, _) in bag.inner {
+                unsafe { free.call(data) };
+            }
+        }
+    }
+}
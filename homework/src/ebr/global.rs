@@ -0,0 +1,160 @@
+use core::iter;
+use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use super::bag::{Orphans, SealedBag};
+
+/// The local epoch of a participant that is not pinned. A pinned participant's local epoch is
+/// `(epoch << 1) | PINNED`.
+const UNPINNED: usize = 0;
+const PINNED: usize = 1;
+
+/// Epoch-based reclamation domain: the global epoch, the local epochs of the participants, and the
+/// bags left by the exited participants.
+///
+/// The participants are kept in an append-only lock-free list, and the entry of an exited
+/// participant is reused by the next one, as with `Hazards::register`.
+#[derive(Debug)]
+pub struct Global {
+    epoch: AtomicUsize,
+    participants: AtomicPtr<Participant>,
+    orphans: Orphans,
+}
+
+/// The local epoch of a participant, handed out by `Global::register`.
+#[derive(Debug)]
+pub struct Participant {
+    next: AtomicPtr<Participant>,
+    /// Whether a thread owns the entry, i.e. it is registered and not yet unregistered.
+    active: AtomicBool,
+    epoch: AtomicUsize,
+}
+
+impl Participant {
+    /// Pins the participant at the current global epoch. The pointers loaded afterwards are not
+    /// freed until it is unpinned.
+    pub(crate) fn pin(&self, global: &Global) {
+        let epoch = global.epoch.load(Ordering::Relaxed);
+        self.epoch.store((epoch << 1) | PINNED, Ordering::Relaxed);
+        // Either `try_advance` sees the local epoch, or this participant sees the unlinks before
+        // the epoch is advanced. See the module documentation.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Unpins the participant. The pointers loaded while pinned must not be used afterwards.
+    pub(crate) fn unpin(&self) {
+        self.epoch.store(UNPINNED, Ordering::Release);
+    }
+}
+
+impl Global {
+    /// Creates a new domain.
+    pub const fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            participants: AtomicPtr::new(ptr::null_mut()),
+            orphans: Orphans::new(),
+        }
+    }
+
+    /// Returns the global epoch, after a fence so that it is loaded after the preceding unlinks.
+    pub fn epoch(&self) -> usize {
+        fence(Ordering::SeqCst);
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Returns a participant entry owned by the calling thread until it is `unregister`ed. An entry
+    /// unregistered by another thread is reused if any.
+    pub(crate) fn register(&self) -> &Participant {
+        let mut prev = &self.participants;
+        let mut cur = prev.load(Ordering::Acquire);
+        loop {
+            if cur.is_null() {
+                let new = Box::into_raw(Box::new(Participant {
+                    next: AtomicPtr::new(ptr::null_mut()),
+                    active: AtomicBool::new(true),
+                    epoch: AtomicUsize::new(UNPINNED),
+                }));
+                match prev.compare_exchange(cur, new, Ordering::Release, Ordering::Acquire) {
+                    Ok(_) => return unsafe { &*new },
+                    Err(current) => {
+                        unsafe { drop(Box::from_raw(new)) };
+                        cur = current;
+                        continue;
+                    }
+                }
+            }
+            let cur_ref = unsafe { &*cur };
+            if cur_ref
+                .active
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return cur_ref;
+            }
+            prev = &cur_ref.next;
+            cur = prev.load(Ordering::Acquire);
+        }
+    }
+
+    /// Releases an unpinned participant entry, to be reused by another thread.
+    pub(crate) fn unregister(&self, participant: &Participant) {
+        debug_assert_eq!(participant.epoch.load(Ordering::Relaxed), UNPINNED);
+        participant.active.store(false, Ordering::Release);
+    }
+
+    fn participants(&self) -> impl Iterator<Item = &Participant> {
+        iter::successors(
+            unsafe { self.participants.load(Ordering::Acquire).as_ref() },
+            |p| unsafe { p.next.load(Ordering::Acquire).as_ref() },
+        )
+    }
+
+    /// Advances the global epoch if all pinned participants are pinned at it. Returns the global
+    /// epoch.
+    pub fn try_advance(&self) -> usize {
+        let epoch = self.epoch();
+        for participant in self.participants() {
+            let local = participant.epoch.load(Ordering::Relaxed);
+            if local & PINNED != 0 && local >> 1 != epoch {
+                return epoch;
+            }
+        }
+        // The accesses of the participants unpinned before happen before the frees.
+        fence(Ordering::Acquire);
+
+        match self.epoch.compare_exchange(
+            epoch,
+            epoch.wrapping_add(1),
+            Ordering::Release,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => epoch.wrapping_add(1),
+            Err(current) => current,
+        }
+    }
+
+    pub(crate) fn push_orphans(&self, bags: Vec<SealedBag>) {
+        self.orphans.push(bags);
+    }
+
+    pub(crate) fn take_orphans(&self) -> Vec<SealedBag> {
+        self.orphans.take()
+    }
+}
+
+impl Default for Global {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Global {
+    fn drop(&mut self) {
+        let mut cur = *self.participants.get_mut();
+        while !cur.is_null() {
+            let participant = unsafe { Box::from_raw(cur) };
+            cur = participant.next.load(Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+
+use super::bag::Bags;
+use super::global::{Global, Participant};
+use crate::hazard_pointer::Retired;
+
+/// The state of a thread in a domain: its participant entry, the number of its guards, and its
+/// bags of retired pointers.
+#[derive(Debug)]
+pub(super) struct Local {
+    global: &'static Global,
+    participant: &'static Participant,
+    guards: Cell<usize>,
+    bags: RefCell<Bags>,
+}
+
+impl Local {
+    pub(super) fn new(global: &'static Global) -> Self {
+        Self {
+            global,
+            participant: global.register(),
+            guards: Cell::new(0),
+            bags: RefCell::new(Bags::default()),
+        }
+    }
+
+    pub(super) fn pin(&self) {
+        let guards = self.guards.get();
+        if guards == 0 {
+            self.participant.pin(self.global);
+        }
+        self.guards.set(guards + 1);
+    }
+
+    pub(super) fn unpin(&self) {
+        let guards = self.guards.get();
+        debug_assert!(guards > 0);
+        if guards == 1 {
+            self.participant.unpin();
+        }
+        self.guards.set(guards - 1);
+    }
+
+    pub(super) fn is_pinned(&self) -> bool {
+        self.guards.get() > 0
+    }
+
+    pub(super) fn retire(&self, retired: Retired) {
+        let full = self.bags.borrow_mut().push(retired);
+        if full {
+            self.collect();
+        }
+    }
+
+    /// Seals the current bag, tries to advance the global epoch, and frees the expired bags of the
+    /// current thread and of the exited threads.
+    pub(super) fn collect(&self) {
+        let expired = {
+            let mut bags = self.bags.borrow_mut();
+            bags.seal(self.global.epoch());
+            bags.adopt(self.global.take_orphans());
+            let epoch = self.global.try_advance();
+            bags.take_expired(epoch)
+        };
+        // The destructors and the deferred functions may retire or defer more.
+        for bag in expired {
+            bag.free();
+        }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // The bags not expired yet are left to the other threads, instead of waiting for the epoch
+        // to advance.
+        self.collect();
+        let mut bags = self.bags.borrow_mut();
+        if !bags.is_empty() {
+            self.global.push_orphans(bags.take_sealed());
+        }
+        // A guard may be leaked, or outlive the thread-local.
+        if self.is_pinned() {
+            self.participant.unpin();
+        }
+        self.global.unregister(self.participant);
+    }
+}
+
+/// A witness that the current thread is pinned. The pointers loaded while a guard is alive are not
+/// freed until it is dropped.
+///
+/// The guards may be nested, and the thread is unpinned when the last one is dropped.
+#[derive(Debug)]
+pub struct Guard {
+    /// The guard belongs to the current thread.
+    _marker: PhantomData<*const ()>,
+}
+
+impl Guard {
+    pub(super) fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        // The thread-local may be destroyed already if the guard is dropped by another
+        // thread-local's destructor, in which case it is unpinned and unregistered anyway.
+        let _ = super::LOCAL.try_with(|l| l.unpin());
+    }
+}
@@ -0,0 +1,114 @@
+//! Epoch-based reclamation, an alternative to the hazard pointers of `hazard_pointer`.
+//!
+//! It uses the same pointer types as `hazard_pointer`, so that a data structure can be ported from
+//! one scheme to the other by replacing the shields with a guard.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use cs492_concur_homework::ebr::{collect, pin, retire, Atomic, Shared};
+//!
+//! let atomic = Atomic::new(1);
+//! let guard = pin();
+//! let shared = atomic.load(Ordering::Acquire);
+//! assert_eq!(unsafe { *shared.deref() }, 1);
+//!
+//! // unlink the block and retire
+//! atomic.store(Shared::null(), Ordering::Relaxed);
+//! retire(shared);
+//! drop(guard);
+//!
+//! // manually trigger reclamation (not necessary)
+//! collect();
+//! ```
+//!
+//! # Algorithm and Synchronization
+//!
+//! Instead of protecting each pointer, a thread is pinned during an operation, and publishes the
+//! global epoch it is pinned at as its local epoch. The retired pointers are collected into bags,
+//! and a full bag is sealed with the global epoch. The global epoch is advanced only when every
+//! pinned thread is pinned at it, so once it is two epochs ahead of a bag, the threads that were
+//! pinned when the pointers in the bag were unlinked are all unpinned, and the pointers are freed.
+//!
+//! ```text
+//! (T1-1) publish the local epoch e (pin())    | (T2-1) unlink b (and retire b)
+//! (T1-2) load b and deref it                  | (T2-2) seal b with the global epoch s
+//! (T1-3) unpin (Guard::drop)                  | (T2-3) free b once the global epoch is s + 2
+//! ```
+//!
+//! As with the hazard pointers, the argument needs a SC fence between `T1-1` and `T1-2`, and one
+//! between `T2-1` and `T2-2`. If `T1`'s fence is first, `e <= s`, and advancing the global epoch
+//! from `s + 1` needs `T1-3` or `T1` to be pinned again at `s + 1`, after `T1-3`. Otherwise, `T1`
+//! doesn't load `b` in `T1-2`.
+//!
+//! Unlike the hazard pointers, a thread that stalls while pinned blocks the reclamation of all
+//! pointers retired afterwards, so the garbage is unbounded. In exchange, the readers don't
+//! validate the pointers they load, and a fence is needed only when a thread is pinned, not for
+//! each pointer.
+
+use core::mem;
+
+pub(crate) mod bag;
+pub(crate) mod global;
+mod local;
+
+pub use super::hazard_pointer::{Atomic, Owned, Shared};
+pub use global::Global;
+pub use local::Guard;
+
+use super::hazard_pointer::Free;
+use local::Local;
+
+/// Global domain of all threads.
+pub static GLOBAL: Global = Global::new();
+
+thread_local! {
+    /// The state of the current thread in `GLOBAL`.
+    static LOCAL: Local = Local::new(&GLOBAL);
+}
+
+/// Pins the current thread. The pointers loaded while the returned guard is alive are not freed
+/// until it is dropped.
+pub fn pin() -> Guard {
+    LOCAL.with(|l| l.pin());
+    Guard::new()
+}
+
+/// Returns `true` if the current thread is pinned.
+pub fn is_pinned() -> bool {
+    LOCAL.with(|l| l.is_pinned())
+}
+
+/// Retires a pointer. It is freed once all threads pinned now are unpinned, possibly by another
+/// thread, so `T` must be `Send`.
+pub fn retire<T: Send>(pointer: Shared<T>) {
+    unsafe fn free<T>(data: *mut ()) {
+        drop(Box::from_raw(data as *mut T))
+    }
+    retire_with(pointer, free::<T>);
+}
+
+/// Retires a pointer to an object not allocated as a `Box<T>`. It is freed by calling `free` with
+/// the pointer without tag, cast to `*mut ()`.
+pub fn retire_with<T: Send>(pointer: Shared<T>, free: unsafe fn(*mut ())) {
+    let data = pointer.as_raw() as *mut ();
+    LOCAL.with(|l| l.retire((data, Free::Object(free), mem::size_of::<T>())));
+}
+
+/// Defers `f` until all threads pinned now are unpinned, e.g. to update the state associated with
+/// the retired pointers. `f` may retire or defer more.
+pub fn defer<F: FnOnce() + Send + 'static>(f: F) {
+    unsafe fn call<F: FnOnce()>(data: *mut ()) {
+        let f = Box::from_raw(data as *mut F);
+        f()
+    }
+    let pointer = Shared::from(Box::into_raw(Box::new(f)) as *const F);
+    retire_with(pointer, call::<F>);
+}
+
+/// Tries to advance the global epoch, and frees the pointers retired by the current thread or left
+/// by the exited threads that are not accessible anymore.
+pub fn collect() {
+    LOCAL.with(|l| l.collect());
+}
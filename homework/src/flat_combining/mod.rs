@@ -0,0 +1,246 @@
+//! Flat combining.
+//!
+//! A thread publishes its operation in a record of the publication array, and then either waits
+//! for the result or, if the lock is free, becomes the combiner: it applies all of the published
+//! operations to the sequential data structure, and writes back their results. So the data
+//! structure is accessed by a single thread at a time, with its cache lines staying in that
+//! thread's cache, and the lock is acquired once for many operations.
+//!
+//! If an operation panics, the combiner catches the panic and hands it to the thread that published
+//! the operation, which resumes it.
+//!
+//! # Example
+//!
+//! ```
+//! use cs492_concur_homework::{FlatCombining, Sequential};
+//!
+//! #[derive(Default)]
+//! struct Counter(usize);
+//!
+//! impl Sequential for Counter {
+//!     type Op = usize;
+//!     type Result = usize;
+//!
+//!     fn apply(&mut self, op: usize) -> usize {
+//!         self.0 += op;
+//!         self.0
+//!     }
+//! }
+//!
+//! let counter = FlatCombining::new(Counter::default());
+//! assert_eq!(counter.apply(2), 2);
+//! assert_eq!(counter.apply(3), 5);
+//! ```
+//!
+//! Reference: Danny Hendler, Itai Incze, Nir Shavit, and Moran Tzafrir. Flat Combining and the
+//! Synchronization-Parallelism Tradeoff. SPAA 2010.
+
+mod priority_queue;
+mod queue;
+
+pub use priority_queue::{FcPriorityQueue, PriorityQueueOp};
+pub use queue::{FcQueue, QueueOp};
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crossbeam_utils::CachePadded;
+use lock::Backoff;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+/// The default number of records.
+const DEFAULT_CAPACITY: usize = 64;
+
+/// The record is not used.
+const FREE: usize = 0;
+/// The owner is writing the operation.
+const WRITING: usize = 1;
+/// The operation is published.
+const PENDING: usize = 2;
+/// The result is written back.
+const DONE: usize = 3;
+
+/// The seeds of the threads' first records to try, so that the threads use different records.
+static NEXT_HINT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HINT: usize = NEXT_HINT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Sequential data structure whose operations are applied by the combiner.
+pub trait Sequential {
+    /// The operation.
+    type Op;
+
+    /// The result of an operation.
+    type Result;
+
+    /// Applies an operation.
+    fn apply(&mut self, op: Self::Op) -> Self::Result;
+}
+
+/// A record of the publication array.
+struct Record<S: Sequential> {
+    state: AtomicUsize,
+    /// Written by the owner while `WRITING`, and taken by the combiner while `PENDING`.
+    op: UnsafeCell<Option<S::Op>>,
+    /// Written by the combiner while `PENDING`, and taken by the owner while `DONE`. It is the
+    /// panic of the operation if it panicked.
+    result: UnsafeCell<Option<thread::Result<S::Result>>>,
+}
+
+impl<S: Sequential> Default for Record<S> {
+    fn default() -> Self {
+        Self {
+            state: AtomicUsize::new(FREE),
+            op: UnsafeCell::new(None),
+            result: UnsafeCell::new(None),
+        }
+    }
+}
+
+/// Sequential data structure of type `S` made concurrent by flat combining.
+pub struct FlatCombining<S: Sequential> {
+    /// Held by the combiner.
+    lock: CachePadded<AtomicBool>,
+    data: UnsafeCell<S>,
+    records: Box<[CachePadded<Record<S>>]>,
+}
+
+unsafe impl<S: Sequential + Send> Send for FlatCombining<S>
+where
+    S::Op: Send,
+    S::Result: Send,
+{
+}
+
+unsafe impl<S: Sequential + Send> Sync for FlatCombining<S>
+where
+    S::Op: Send,
+    S::Result: Send,
+{
+}
+
+impl<S: Sequential> FlatCombining<S> {
+    /// Creates a new flat combining data structure with 64 records.
+    pub fn new(data: S) -> Self {
+        Self::with_capacity(data, DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new flat combining data structure with `capacity` records, i.e. up to `capacity`
+    /// operations are published at once. The others wait for a free record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(data: S, capacity: usize) -> Self {
+        assert!(capacity > 0, "no record to publish the operations");
+        Self {
+            lock: CachePadded::new(AtomicBool::new(false)),
+            data: UnsafeCell::new(data),
+            records: (0..capacity).map(|_| CachePadded::default()).collect(),
+        }
+    }
+
+    /// Applies `op` to the data structure, and returns its result.
+    pub fn apply(&self, op: S::Op) -> S::Result {
+        let record = self.publish(op);
+        let backoff = Backoff::new();
+        loop {
+            if record.state.load(Ordering::Acquire) == DONE {
+                let result = unsafe { (*record.result.get()).take().unwrap() };
+                record.state.store(FREE, Ordering::Release);
+                return result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+            }
+            if !self.try_combine() {
+                backoff.snooze();
+            }
+        }
+    }
+
+    /// Publishes `op` in a free record.
+    fn publish(&self, op: S::Op) -> &Record<S> {
+        let start = HINT.with(|hint| *hint);
+        let backoff = Backoff::new();
+        loop {
+            for i in 0..self.records.len() {
+                let record = &self.records[(start + i) % self.records.len()];
+                if record.state.load(Ordering::Relaxed) == FREE
+                    && record
+                        .state
+                        .compare_exchange(FREE, WRITING, Ordering::Acquire, Ordering::Relaxed)
+                        .is_ok()
+                {
+                    unsafe { *record.op.get() = Some(op) };
+                    record.state.store(PENDING, Ordering::Release);
+                    return record;
+                }
+            }
+
+            // All of the records are used. Frees them by combining, or waits for the combiner.
+            if !self.try_combine() {
+                backoff.snooze();
+            }
+        }
+    }
+
+    /// Applies the published operations if the lock is free. Returns `true` if it has combined.
+    fn try_combine(&self) -> bool {
+        if self.lock.load(Ordering::Relaxed) || self.lock.swap(true, Ordering::Acquire) {
+            return false;
+        }
+        let _guard = CombinerGuard { lock: &self.lock };
+
+        let data = unsafe { &mut *self.data.get() };
+        for record in self.records.iter() {
+            if record.state.load(Ordering::Acquire) != PENDING {
+                continue;
+            }
+            unsafe {
+                let op = (*record.op.get()).take().unwrap();
+                // The data may be left inconsistent by the operation, as with a panic in any
+                // `&mut self` method.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| data.apply(op)));
+                *record.result.get() = Some(result);
+            }
+            record.state.store(DONE, Ordering::Release);
+        }
+        true
+    }
+
+    /// Returns the sequential data structure.
+    pub fn into_inner(self) -> S {
+        self.data.into_inner()
+    }
+
+    /// Returns a mutable reference to the sequential data structure.
+    pub fn get_mut(&mut self) -> &mut S {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+/// Releases the combiner lock, also if the combiner unwinds.
+struct CombinerGuard<'l> {
+    lock: &'l AtomicBool,
+}
+
+impl Drop for CombinerGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.store(false, Ordering::Release);
+    }
+}
+
+impl<S: Sequential + Default> Default for FlatCombining<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S: Sequential> fmt::Debug for FlatCombining<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlatCombining")
+            .field("capacity", &self.records.len())
+            .finish()
+    }
+}
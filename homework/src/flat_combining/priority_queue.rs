@@ -0,0 +1,69 @@
+use core::fmt;
+use std::collections::BinaryHeap;
+
+use super::{FlatCombining, Sequential};
+
+/// The operations of `FcPriorityQueue`.
+#[derive(Debug)]
+pub enum PriorityQueueOp<T> {
+    /// Pushes an element.
+    Push(T),
+    /// Pops an element.
+    Pop,
+}
+
+impl<T: Ord> Sequential for BinaryHeap<T> {
+    type Op = PriorityQueueOp<T>;
+    type Result = Option<T>;
+
+    fn apply(&mut self, op: PriorityQueueOp<T>) -> Option<T> {
+        match op {
+            PriorityQueueOp::Push(t) => {
+                self.push(t);
+                None
+            }
+            PriorityQueueOp::Pop => self.pop(),
+        }
+    }
+}
+
+/// Max-priority queue by flat combining.
+pub struct FcPriorityQueue<T: Ord> {
+    inner: FlatCombining<BinaryHeap<T>>,
+}
+
+impl<T: Ord> Default for FcPriorityQueue<T> {
+    fn default() -> Self {
+        Self {
+            inner: FlatCombining::default(),
+        }
+    }
+}
+
+impl<T: Ord> FcPriorityQueue<T> {
+    /// Creates a new priority queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes `t`.
+    pub fn push(&self, t: T) {
+        let _ = self.inner.apply(PriorityQueueOp::Push(t));
+    }
+
+    /// Pops the greatest element, if any.
+    pub fn pop(&self) -> Option<T> {
+        self.inner.apply(PriorityQueueOp::Pop)
+    }
+
+    /// Returns the elements in ascending order.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.inner.into_inner().into_sorted_vec()
+    }
+}
+
+impl<T: Ord> fmt::Debug for FcPriorityQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FcPriorityQueue").finish()
+    }
+}
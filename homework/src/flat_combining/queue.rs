@@ -0,0 +1,69 @@
+use core::fmt;
+use std::collections::VecDeque;
+
+use super::{FlatCombining, Sequential};
+
+/// The operations of `FcQueue`.
+#[derive(Debug)]
+pub enum QueueOp<T> {
+    /// Pushes an element.
+    Push(T),
+    /// Pops an element.
+    Pop,
+}
+
+impl<T> Sequential for VecDeque<T> {
+    type Op = QueueOp<T>;
+    type Result = Option<T>;
+
+    fn apply(&mut self, op: QueueOp<T>) -> Option<T> {
+        match op {
+            QueueOp::Push(t) => {
+                self.push_back(t);
+                None
+            }
+            QueueOp::Pop => self.pop_front(),
+        }
+    }
+}
+
+/// FIFO queue by flat combining.
+pub struct FcQueue<T> {
+    inner: FlatCombining<VecDeque<T>>,
+}
+
+impl<T> Default for FcQueue<T> {
+    fn default() -> Self {
+        Self {
+            inner: FlatCombining::default(),
+        }
+    }
+}
+
+impl<T> FcQueue<T> {
+    /// Creates a new queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes `t` at the back of the queue.
+    pub fn push(&self, t: T) {
+        let _ = self.inner.apply(QueueOp::Push(t));
+    }
+
+    /// Pops the element at the front of the queue, if any.
+    pub fn pop(&self) -> Option<T> {
+        self.inner.apply(QueueOp::Pop)
+    }
+
+    /// Returns the elements in FIFO order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.inner.into_inner().into()
+    }
+}
+
+impl<T> fmt::Debug for FcQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FcQueue").finish()
+    }
+}
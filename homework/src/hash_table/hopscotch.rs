@@ -0,0 +1,335 @@
+//! Hopscotch hash map.
+//!
+//! An open-addressing hash map where each key is stored within the neighborhood of `H` buckets
+//! from its home bucket, and each bucket has a bitmap of the buckets in its neighborhood that hold
+//! its keys. So a lookup checks at most `H` buckets, mostly in the same cache lines. An insertion
+//! probes linearly for a free bucket, and moves it closer to the home bucket by displacing the
+//! keys between them within their own neighborhoods, hopping back until the free bucket is in the
+//! neighborhood. If there is no free bucket nearby or no key to displace, the table is resized if
+//! it is at least half full. Otherwise, e.g. if more than `H` keys share a hash, no resizing would
+//! separate them, so the key is put in a small overflow list instead.
+//!
+//! The map is split into segments by the upper bits of the hashes, each of which is a hopscotch
+//! table with its own lock, and is resized independently. Unlike the concurrent hopscotch map of
+//! the paper, the lookups lock the segment too.
+//!
+//! Reference: Maurice Herlihy, Nir Shavit, and Moran Tzafrir. Hopscotch Hashing. DISC 2008.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use crossbeam_epoch::Guard;
+use lock::{Lock, SpinLock};
+use std::collections::hash_map::RandomState;
+
+use crate::map::{defer_drop, NonblockingMap};
+
+/// The size of a neighborhood, i.e. the bits of a bitmap.
+const H: usize = 32;
+/// The maximum distance to probe for a free bucket.
+const ADD_RANGE: usize = 8 * H;
+/// The default number of segments.
+const DEFAULT_SEGMENTS: usize = 16;
+/// The initial number of buckets of a segment.
+const INITIAL_BUCKETS: usize = 2 * H;
+/// The segment of a hash is chosen by the bits from this.
+const SEGMENT_SHIFT: u32 = 48;
+
+/// A key-value pair, with the hash of the key not to compute it again when resizing. The value is
+/// boxed, so that the references to it stay valid while it is moved between the buckets.
+struct Entry<K, V> {
+    hash: u64,
+    key: K,
+    value: Box<V>,
+}
+
+struct Bucket<K, V> {
+    /// The `i`-th bit is set if the `i`-th bucket from this holds a key whose home bucket is this.
+    hop: u32,
+    entry: Option<Entry<K, V>>,
+}
+
+impl<K, V> Default for Bucket<K, V> {
+    fn default() -> Self {
+        Self {
+            hop: 0,
+            entry: None,
+        }
+    }
+}
+
+/// A segment, a hopscotch table whose neighborhoods wrap around its end.
+struct Segment<K, V> {
+    buckets: Vec<Bucket<K, V>>,
+    /// The entries that don't fit in their neighborhoods while the table is less than half full.
+    overflow: Vec<Entry<K, V>>,
+    /// The number of the entries, including the overflown ones.
+    len: usize,
+}
+
+impl<K: Eq, V> Segment<K, V> {
+    fn new(buckets: usize) -> Self {
+        Self {
+            buckets: (0..buckets).map(|_| Bucket::default()).collect(),
+            overflow: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    fn home(&self, hash: u64) -> usize {
+        hash as usize & self.mask()
+    }
+
+    /// Returns the index of the bucket of the key.
+    fn find_bucket(&self, hash: u64, key: &K) -> Option<usize> {
+        let home = self.home(hash);
+        let mut hop = self.buckets[home].hop;
+        while hop != 0 {
+            let i = (home + hop.trailing_zeros() as usize) & self.mask();
+            let entry = self.buckets[i].entry.as_ref().unwrap();
+            if entry.hash == hash && entry.key == *key {
+                return Some(i);
+            }
+            hop &= hop - 1;
+        }
+        None
+    }
+
+    /// Returns the index of the key in the overflow list.
+    fn find_overflow(&self, hash: u64, key: &K) -> Option<usize> {
+        self.overflow
+            .iter()
+            .position(|entry| entry.hash == hash && entry.key == *key)
+    }
+
+    /// Returns the entry of the key.
+    fn find(&self, hash: u64, key: &K) -> Option<&Entry<K, V>> {
+        match self.find_bucket(hash, key) {
+            Some(i) => self.buckets[i].entry.as_ref(),
+            None => self.find_overflow(hash, key).map(|i| &self.overflow[i]),
+        }
+    }
+
+    /// Inserts an entry whose key is absent, resizing the segment while it is at least half full
+    /// and the entry doesn't fit.
+    fn insert(&mut self, mut entry: Entry<K, V>) {
+        loop {
+            entry = some_or!(self.try_insert(entry).err(), return);
+            if 2 * (self.len - self.overflow.len()) < self.buckets.len() {
+                self.push_overflow(entry);
+                return;
+            }
+            self.resize();
+        }
+    }
+
+    fn push_overflow(&mut self, entry: Entry<K, V>) {
+        self.overflow.push(entry);
+        self.len += 1;
+    }
+
+    /// Inserts an entry whose key is absent. Gives it back if the segment is to be resized.
+    fn try_insert(&mut self, entry: Entry<K, V>) -> Result<(), Entry<K, V>> {
+        let mask = self.mask();
+        let home = self.home(entry.hash);
+        let range = ADD_RANGE.min(self.buckets.len());
+        let mut distance = some_or!(
+            (0..range).find(|d| self.buckets[(home + d) & mask].entry.is_none()),
+            return Err(entry)
+        );
+
+        // Hops the free bucket back into the neighborhood.
+        while distance >= H {
+            let free = (home + distance) & mask;
+            distance -= some_or!(self.displace(free), return Err(entry));
+        }
+
+        let free = (home + distance) & mask;
+        self.buckets[free].entry = Some(entry);
+        self.buckets[home].hop |= 1 << distance;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Moves a key in the `H - 1` buckets before the free bucket into it, keeping the key in its
+    /// neighborhood. Returns how far the free bucket has moved back.
+    fn displace(&mut self, free: usize) -> Option<usize> {
+        let mask = self.mask();
+        // The farthest bucket first, to move the free bucket back the most.
+        for k in (1..H).rev() {
+            let base = free.wrapping_sub(k) & mask;
+            let hop = self.buckets[base].hop & ((1 << k) - 1);
+            if hop == 0 {
+                continue;
+            }
+            let i = hop.trailing_zeros() as usize;
+            let from = (base + i) & mask;
+            self.buckets[free].entry = self.buckets[from].entry.take();
+            self.buckets[base].hop = self.buckets[base].hop & !(1 << i) | 1 << k;
+            return Some(k - i);
+        }
+        None
+    }
+
+    /// Removes the key, and returns its entry.
+    fn remove(&mut self, hash: u64, key: &K) -> Option<Entry<K, V>> {
+        let entry = match self.find_bucket(hash, key) {
+            Some(index) => {
+                let entry = self.buckets[index].entry.take().unwrap();
+                let home = self.home(entry.hash);
+                let distance = index.wrapping_sub(home) & self.mask();
+                self.buckets[home].hop &= !(1 << distance);
+                entry
+            }
+            None => {
+                let index = self.find_overflow(hash, key)?;
+                self.overflow.swap_remove(index)
+            }
+        };
+        self.len -= 1;
+        Some(entry)
+    }
+
+    /// Doubles the buckets. The entries that still don't fit, including the overflown ones, are
+    /// put in the overflow list.
+    fn resize(&mut self) {
+        let mut segment = Self::new(self.buckets.len() * 2);
+        let buckets = self.buckets.drain(..).filter_map(|bucket| bucket.entry);
+        for entry in buckets.chain(self.overflow.drain(..)) {
+            if let Err(entry) = segment.try_insert(entry) {
+                segment.push_overflow(entry);
+            }
+        }
+        *self = segment;
+    }
+}
+
+/// Hopscotch hash map with per-segment locks.
+///
+/// As with `MutexHashMap`, the references to the values stay valid until the guard is unpinned.
+pub struct HopscotchMap<K, V, S = RandomState> {
+    segments: Box<[Lock<SpinLock, Segment<K, V>>]>,
+    hash_builder: S,
+}
+
+impl<K: Eq, V> Default for HopscotchMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq, V> HopscotchMap<K, V> {
+    /// Creates a new map with 16 segments.
+    pub fn new() -> Self {
+        Self::with_segments(DEFAULT_SEGMENTS)
+    }
+
+    /// Creates a new map with `segments` segments, rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segments` is 0 or more than `2^16`.
+    pub fn with_segments(segments: usize) -> Self {
+        Self::with_segments_and_hasher(segments, RandomState::new())
+    }
+}
+
+impl<K: Eq, V, S> HopscotchMap<K, V, S> {
+    /// Creates a new map with `segments` segments, which hashes the keys with `hash_builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segments` is 0 or more than `2^16`.
+    pub fn with_segments_and_hasher(segments: usize, hash_builder: S) -> Self {
+        assert!(segments > 0 && segments <= 1 << (64 - SEGMENT_SHIFT));
+        Self {
+            segments: (0..segments.next_power_of_two())
+                .map(|_| Lock::new(Segment::new(INITIAL_BUCKETS)))
+                .collect(),
+            hash_builder,
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> HopscotchMap<K, V, S> {
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn segment(&self, hash: u64) -> &Lock<SpinLock, Segment<K, V>> {
+        &self.segments[(hash >> SEGMENT_SHIFT) as usize & (self.segments.len() - 1)]
+    }
+
+    /// Returns the number of the keys.
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|segment| segment.lock().len).sum()
+    }
+
+    /// Returns `true` if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V, S> NonblockingMap<K, V> for HopscotchMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Send + Sync,
+    S: BuildHasher,
+{
+    fn lookup<'a>(&'a self, key: &K, _guard: &'a Guard) -> Option<&'a V> {
+        let hash = self.hash(key);
+        let segment = self.segment(hash).lock();
+        let value = &segment.find(hash, key)?.value;
+        Some(unsafe { &*(&**value as *const V) })
+    }
+
+    fn insert(&self, key: &K, value: V, _guard: &Guard) -> Result<(), V> {
+        let hash = self.hash(key);
+        let mut segment = self.segment(hash).lock();
+        if segment.find(hash, key).is_some() {
+            return Err(value);
+        }
+
+        segment.insert(Entry {
+            hash,
+            key: key.clone(),
+            value: Box::new(value),
+        });
+        Ok(())
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let hash = self.hash(key);
+        let mut segment = self.segment(hash).lock();
+        let entry = segment.remove(hash, key).ok_or(())?;
+        Ok(unsafe { defer_drop(entry.value, guard) })
+    }
+
+    fn for_each<'a, F>(&'a self, _guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        for segment in self.segments.iter() {
+            let segment = segment.lock();
+            let buckets = segment.buckets.iter().filter_map(|b| b.entry.as_ref());
+            for entry in buckets.chain(segment.overflow.iter()) {
+                f(&entry.key, unsafe { &*(&*entry.value as *const V) });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K, V, S> fmt::Debug for HopscotchMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HopscotchMap")
+            .field("segments", &self.segments.len())
+            .finish()
+    }
+}
@@ -0,0 +1,446 @@
+//! Lock-free open-addressing hash map for integer keys, after Cliff Click's non-blocking hash map.
+//!
+//! A slot is a key word and a value pointer, each of which moves through a small state machine by
+//! CAS. A key word only goes from empty to a key, so a key is never moved within a table, and a
+//! lookup stops at the first empty key word. A value goes between empty or deleted (a null
+//! pointer, tagged `TOMBSTONE` if deleted) and a value, until the table is resized:
+//!
+//! ```text
+//!                  insert                            copy_slot
+//! null / TOMBSTONE <----> value --> value | PRIME ---------------> TOMBSTONE | PRIME
+//!                  delete                     (copied to `next`)
+//!        |                                                              ^
+//!        +--------------------------------------------------------------+
+//! ```
+//!
+//! Resizing allocates the next table, and copies the slots to it incrementally: each operation on
+//! the old table first helps copy a chunk of the slots, and the slot it accesses. A slot is copied
+//! by priming its value, so that it is not changed in the old table anymore, inserting the value in
+//! the next table unless the slot there has a value already, and then sealing the old slot as
+//! `TOMBSTONE | PRIME`. The operations that find a primed value retry in the next table. Once all
+//! of the slots are copied, the next table replaces the old one.
+//!
+//! Reference: Cliff Click. A Lock-Free Wait-Free Hash Table. Stanford EE380, 2007.
+
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+use crate::map::NonblockingMap;
+
+/// The minimum number of slots of a table.
+const MIN_LEN: usize = 16;
+/// The number of slots to copy at a time when helping a resize.
+const COPY_CHUNK: usize = 16;
+/// The key word of an empty slot. The key words are the keys plus 1.
+const EMPTY_KEY: usize = 0;
+/// The tag of a deleted value.
+const TOMBSTONE: usize = 1;
+/// The tag of a value being copied to the next table.
+const PRIME: usize = 2;
+
+/// A value, aligned for the tags.
+#[repr(align(4))]
+struct Value<V>(V);
+
+struct Table<V> {
+    keys: Box<[AtomicUsize]>,
+    values: Box<[Atomic<Value<V>>]>,
+    /// The number of the key words that are not empty.
+    used: AtomicUsize,
+    /// The table being resized into.
+    next: Atomic<Table<V>>,
+    /// The slots from this are not claimed for copying yet.
+    copy_claimed: AtomicUsize,
+    /// The number of the slots sealed as copied.
+    copied: AtomicUsize,
+}
+
+impl<V> Table<V> {
+    fn new(len: usize) -> Self {
+        Self {
+            keys: (0..len).map(|_| AtomicUsize::new(EMPTY_KEY)).collect(),
+            values: (0..len).map(|_| Atomic::null()).collect(),
+            used: AtomicUsize::new(0),
+            next: Atomic::null(),
+            copy_claimed: AtomicUsize::new(0),
+            copied: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The maximum number of the slots to probe for a key, beyond which the table is resized.
+    fn reprobe_limit(&self) -> usize {
+        (10 + self.len() / 4).min(self.len())
+    }
+
+    /// Returns `true` if the table is to be resized.
+    fn is_full(&self) -> bool {
+        self.used.load(Ordering::Relaxed) >= self.len() / 4 * 3
+    }
+
+    /// Returns the index of the first slot to probe for `key`.
+    fn home(&self, key: usize) -> usize {
+        // The finalizer of MurmurHash3, to spread the consecutive keys.
+        let mut hash = key as u64;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        hash as usize & (self.len() - 1)
+    }
+
+    /// Returns the index of the slot of `key`, or `None` if `key` is not in the table.
+    fn find(&self, key: usize) -> Option<usize> {
+        let mut index = self.home(key);
+        for _ in 0..self.reprobe_limit() {
+            match self.keys[index].load(Ordering::Acquire) {
+                EMPTY_KEY => return None,
+                k if k == key.wrapping_add(1) => return Some(index),
+                _ => index = (index + 1) & (self.len() - 1),
+            }
+        }
+        None
+    }
+
+    /// Returns the index of the slot of `key`, claiming an empty slot for it if it is not in the
+    /// table. Returns `None` if there is no slot for it within the reprobe limit.
+    fn find_or_claim(&self, key: usize) -> Option<usize> {
+        let mut index = self.home(key);
+        for _ in 0..self.reprobe_limit() {
+            let mut k = self.keys[index].load(Ordering::Acquire);
+            if k == EMPTY_KEY {
+                match self.keys[index].compare_exchange(
+                    EMPTY_KEY,
+                    key + 1,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        let _ = self.used.fetch_add(1, Ordering::Relaxed);
+                        return Some(index);
+                    }
+                    Err(current) => k = current,
+                }
+            }
+            if k == key + 1 {
+                return Some(index);
+            }
+            index = (index + 1) & (self.len() - 1);
+        }
+        None
+    }
+}
+
+/// Lock-free map from `usize` in range [0, `usize::MAX` - 1] to `V` by open addressing.
+///
+/// The values are boxed, and a deleted value is dropped when the guard is unpinned.
+#[derive(Debug)]
+pub struct LockFreeIntMap<V> {
+    table: Atomic<Table<V>>,
+    len: AtomicUsize,
+}
+
+impl<V> Default for LockFreeIntMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> LockFreeIntMap<V> {
+    /// Creates a new map.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a new map with room for `capacity` keys before resizing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let len = (capacity * 2).next_power_of_two().max(MIN_LEN);
+        Self {
+            table: Atomic::new(Table::new(len)),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of the keys.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lookup_in<'g>(&self, table: &'g Table<V>, key: usize, guard: &'g Guard) -> Option<&'g V> {
+        let index = table.find(key);
+        if let Some(index) = index {
+            let value = table.values[index].load(Ordering::Acquire, guard);
+            if value.tag() & PRIME == 0 {
+                return unsafe { value.as_ref() }.map(|value| &value.0);
+            }
+        }
+
+        // The key is either copied to the next table or not in the map.
+        let next = unsafe { table.next.load(Ordering::Acquire, guard).as_ref() }?;
+        if let Some(index) = index {
+            self.copy_slot(table, index, next, guard);
+        }
+        self.lookup_in(next, key, guard)
+    }
+
+    /// Puts `value` in the slot of `key` if it has no value. If `copy` is `true`, a deleted value
+    /// counts as a value, as the slot is deleted after the value is copied.
+    fn put_in<'g>(
+        &self,
+        table: &'g Table<V>,
+        key: usize,
+        value: Shared<'g, Value<V>>,
+        copy: bool,
+        guard: &'g Guard,
+    ) -> Result<(), ()> {
+        let index = match table.find_or_claim(key) {
+            Some(index) => index,
+            None => {
+                let next = self.resize(table, guard);
+                return self.put_in(next, key, value, copy, guard);
+            }
+        };
+
+        if !table.next.load(Ordering::Acquire, guard).is_null() || table.is_full() {
+            let next = self.resize(table, guard);
+            self.copy_slot(table, index, next, guard);
+            return self.put_in(next, key, value, copy, guard);
+        }
+
+        let slot = &table.values[index];
+        let mut current = slot.load(Ordering::Acquire, guard);
+        loop {
+            if current.tag() & PRIME != 0 {
+                let next = unsafe { table.next.load(Ordering::Acquire, guard).deref() };
+                self.copy_slot(table, index, next, guard);
+                return self.put_in(next, key, value, copy, guard);
+            }
+            if !current.is_null() || (copy && current.tag() == TOMBSTONE) {
+                return Err(());
+            }
+            match slot.compare_and_set(current, value, Ordering::AcqRel, guard) {
+                Ok(_) => return Ok(()),
+                Err(e) => current = e.current,
+            }
+        }
+    }
+
+    fn delete_in<'g>(
+        &self,
+        table: &'g Table<V>,
+        key: usize,
+        guard: &'g Guard,
+    ) -> Result<&'g V, ()> {
+        let index = table.find(key);
+        let next = table.next.load(Ordering::Acquire, guard);
+        if let Some(next) = unsafe { next.as_ref() } {
+            if let Some(index) = index {
+                self.copy_slot(table, index, next, guard);
+            }
+            return self.delete_in(next, key, guard);
+        }
+        let index = index.ok_or(())?;
+
+        let slot = &table.values[index];
+        let mut current = slot.load(Ordering::Acquire, guard);
+        loop {
+            if current.tag() & PRIME != 0 {
+                let next = unsafe { table.next.load(Ordering::Acquire, guard).deref() };
+                self.copy_slot(table, index, next, guard);
+                return self.delete_in(next, key, guard);
+            }
+            if current.is_null() {
+                return Err(());
+            }
+            match slot.compare_and_set(
+                current,
+                Shared::null().with_tag(TOMBSTONE),
+                Ordering::AcqRel,
+                guard,
+            ) {
+                Ok(_) => unsafe {
+                    guard.defer_destroy(current);
+                    return Ok(&current.deref().0);
+                },
+                Err(e) => current = e.current,
+            }
+        }
+    }
+
+    /// Returns the table being resized into, allocating it if there is none, after helping copy a
+    /// chunk of the slots.
+    fn resize<'g>(&self, table: &'g Table<V>, guard: &'g Guard) -> &'g Table<V> {
+        let mut next = table.next.load(Ordering::Acquire, guard);
+        if next.is_null() {
+            // The deleted keys are not copied, so the next table may be as large as the old one.
+            let len = (self.len() * 4).next_power_of_two().max(MIN_LEN);
+            next = match table.next.compare_and_set(
+                Shared::null(),
+                Owned::new(Table::new(len)),
+                Ordering::AcqRel,
+                guard,
+            ) {
+                Ok(next) => next,
+                Err(e) => e.current,
+            };
+        }
+        let next = unsafe { next.deref() };
+
+        let start = table.copy_claimed.fetch_add(COPY_CHUNK, Ordering::Relaxed);
+        for index in start..table.len().min(start + COPY_CHUNK) {
+            self.copy_slot(table, index, next, guard);
+        }
+        next
+    }
+
+    /// Copies the slot to the next table, unless it is copied already.
+    fn copy_slot<'g>(
+        &self,
+        table: &'g Table<V>,
+        index: usize,
+        next: &'g Table<V>,
+        guard: &'g Guard,
+    ) {
+        let slot = &table.values[index];
+        let sealed = Shared::null().with_tag(TOMBSTONE | PRIME);
+
+        // Primes the value, so that it is not changed in this table anymore.
+        let mut current = slot.load(Ordering::Acquire, guard);
+        while current.tag() & PRIME == 0 {
+            // An empty or deleted slot is sealed right away.
+            let primed = if current.is_null() {
+                sealed
+            } else {
+                current.with_tag(PRIME)
+            };
+            match slot.compare_and_set(current, primed, Ordering::AcqRel, guard) {
+                Ok(_) if current.is_null() => {
+                    self.slot_copied(table, guard);
+                    return;
+                }
+                Ok(_) => current = primed,
+                Err(e) => current = e.current,
+            }
+        }
+        if current.is_null() {
+            return;
+        }
+
+        let key = table.keys[index].load(Ordering::Relaxed) - 1;
+        let _ = self.put_in(next, key, current.with_tag(0), true, guard);
+        if slot
+            .compare_and_set(current, sealed, Ordering::AcqRel, guard)
+            .is_ok()
+        {
+            self.slot_copied(table, guard);
+        }
+    }
+
+    /// Counts a copied slot, and replaces the tables whose slots are all copied.
+    fn slot_copied(&self, table: &Table<V>, guard: &Guard) {
+        if table.copied.fetch_add(1, Ordering::AcqRel) + 1 < table.len() {
+            return;
+        }
+
+        // The tables are replaced in order, as a table may be copied before its predecessor.
+        loop {
+            let current = self.table.load(Ordering::Acquire, guard);
+            let current_ref = unsafe { current.deref() };
+            if current_ref.copied.load(Ordering::Acquire) < current_ref.len() {
+                return;
+            }
+            let next = current_ref.next.load(Ordering::Acquire, guard);
+            if self
+                .table
+                .compare_and_set(current, next, Ordering::AcqRel, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(current) };
+            }
+        }
+    }
+}
+
+impl<V> Drop for LockFreeIntMap<V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+
+            // Finishes the resizes, so that each value is in a single table.
+            loop {
+                let table = self.table.load(Ordering::Relaxed, guard).deref();
+                let next = some_or!(table.next.load(Ordering::Relaxed, guard).as_ref(), break);
+                for index in 0..table.len() {
+                    self.copy_slot(table, index, next, guard);
+                }
+            }
+
+            let table = mem::replace(&mut self.table, Atomic::null()).into_owned();
+            for value in table.values.iter() {
+                let value = value.load(Ordering::Relaxed, guard);
+                if !value.is_null() {
+                    drop(value.into_owned());
+                }
+            }
+        }
+    }
+}
+
+impl<V> NonblockingMap<usize, V> for LockFreeIntMap<V> {
+    fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
+        let table = unsafe { self.table.load(Ordering::Acquire, guard).deref() };
+        self.lookup_in(table, *key, guard)
+    }
+
+    fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
+        assert!(*key != usize::MAX, "`usize::MAX` is not a valid key");
+        let value = Owned::new(Value(value)).into_shared(guard);
+        let table = unsafe { self.table.load(Ordering::Acquire, guard).deref() };
+        match self.put_in(table, *key, value, false, guard) {
+            Ok(()) => {
+                let _ = self.len.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(()) => Err(unsafe { value.into_owned() }.into_box().0),
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
+        let table = unsafe { self.table.load(Ordering::Acquire, guard).deref() };
+        let value = self.delete_in(table, *key, guard)?;
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    fn for_each<'a, F>(&'a self, guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&usize, &'a V),
+    {
+        let table = unsafe { self.table.load(Ordering::Acquire, guard).deref() };
+        for (key, value) in table.keys.iter().zip(table.values.iter()) {
+            let key = key.load(Ordering::Acquire);
+            if key == EMPTY_KEY {
+                continue;
+            }
+            let key = key - 1;
+            let value = value.load(Ordering::Acquire, guard);
+            if let Some(value) = unsafe { value.as_ref() } {
+                f(&key, &value.0);
+            } else if value.tag() & PRIME != 0 {
+                // Copied to the next table.
+                if let Some(value) = self.lookup(&key, guard) {
+                    f(&key, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
 //! Lock-free hash table Based on https://dl.acm.org/doi/abs/10.1145/1147954.1147958
 
 mod growable_array;
+mod hopscotch;
+mod lockfree_int_map;
 mod split_ordered_list;
+mod striped_hash_map;
 
 pub use growable_array::GrowableArray;
+pub use hopscotch::HopscotchMap;
+pub use lockfree_int_map::LockFreeIntMap;
 pub use split_ordered_list::SplitOrderedList;
+pub use striped_hash_map::StripedHashMap;
@@ -3,7 +3,7 @@
 use core::mem;
 use core::sync::atomic::{AtomicUsize, Ordering};
 use crossbeam_epoch::{unprotected, Guard, Shared, Owned};
-use lockfree::list::{Cursor, List, Node};
+use lockfree::list::{Cursor, Find, List, Node};
 
 use super::growable_array::GrowableArray;
 use crate::map::NonblockingMap;
@@ -13,14 +13,16 @@ use crate::map::NonblockingMap;
 /// NOTE: We don't care about hashing in this homework for simplicity.
 #[derive(Debug)]
 pub struct SplitOrderedList<V> {
-    /// Lock-free list sorted by recursive-split order. Use `None` sentinel node value.
+    /// Lock-free list sorted by recursive-split order. Use `None` sentinel node value. The items
+    /// are inserted and deleted with `insert_at` and `delete_at`, and the sentinel nodes with the
+    /// cursor alone, so its `len_hint` is the number of items.
     list: List<usize, Option<V>>,
     /// array of pointers to the buckets
     buckets: GrowableArray<Node<usize, Option<V>>>,
     /// number of buckets
     size: AtomicUsize,
-    /// number of items
-    count: AtomicUsize,
+    /// how to find a key from its bucket
+    find: Find,
 }
 
 impl<V> Default for SplitOrderedList<V> {
@@ -28,20 +30,20 @@ impl<V> Default for SplitOrderedList<V> {
         let new_list = List::new();
         let new_buckets=GrowableArray::new();
         unsafe{
-            new_list.harris_insert(0,None,unprotected());
+            new_list.head(unprotected()).insert(Owned::new(Node::new(0,None)),unprotected()).unwrap();
             new_buckets.get(0,unprotected()).store(new_list.head(unprotected()).curr(),Ordering::Relaxed);
         }
         Self {
             list: new_list,
             buckets: new_buckets,
             size: AtomicUsize::new(2),
-            count: AtomicUsize::new(0),
+            find: Find::Harris,
         }
     }
 }
 
 impl<V> SplitOrderedList<V> {
-    /// `size` is doubled when `count > size * LOAD_FACTOR`.
+    /// `size` is doubled when `list.len_hint() > size * LOAD_FACTOR`.
     const LOAD_FACTOR: usize = 2;
 
     /// Creates a new split ordered list.
@@ -49,6 +51,15 @@ impl<V> SplitOrderedList<V> {
         Self::default()
     }
 
+    /// Creates a new split ordered list that finds the keys with the given strategy. Insertions
+    /// and deletions use `find.for_update()`.
+    pub fn with_find(find: Find) -> Self {
+        Self {
+            find,
+            ..Self::default()
+        }
+    }
+
     /// Creates a cursor and moves it to the bucket for the given index.  If the bucket doesn't
     /// exist, recursively initializes the buckets.
     fn lookup_bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Cursor<'s, usize, Option<V>> {
@@ -106,24 +117,82 @@ impl<V> SplitOrderedList<V> {
         index.reverse_bits() | 1
     }
 
+    /// Inverse of `ord_key`.
+    fn key_of(ord_key: usize) -> usize {
+        ord_key.reverse_bits() & (usize::MAX >> 1)
+    }
+
     /// Moves the bucket cursor returned from `lookup_bucket` to the position of the given key.
     /// Returns `(size, found, cursor)`
     fn find<'s>(
         &'s self,
         key: &usize,
+        find: Find,
         guard: &'s Guard,
     ) -> (usize, bool, Cursor<'s, usize, Option<V>>) {
         let size = self.size.load(Ordering::Acquire);
         let index= key % size;
         loop{
             let mut cursor=self.lookup_bucket(index,guard);
-            match cursor.find_harris(&(self.ord_key(key)), guard){
+            match cursor.find(&(self.ord_key(key)), find, guard){
                 Ok(found) => return (size,found,cursor),
                 Err(_) => continue,
             }
         }
     }
 
+    /// Moves a cursor to the position of each key in split order, and calls `op` with the index of
+    /// the key in `keys`, `size`, whether the key is found, and the cursor. The cursor is reused
+    /// for the next key in the same bucket, so that each bucket is traversed once. If `op` returns
+    /// `false`, e.g. after a failed CAS, the key is found again from its bucket.
+    fn find_all<'s, F>(&'s self, keys: &[usize], find: Find, guard: &'s Guard, mut op: F)
+    where
+        F: FnMut(usize, usize, bool, &mut Cursor<'s, usize, Option<V>>) -> bool,
+    {
+        let size = self.size.load(Ordering::Acquire);
+        let mut order = (0..keys.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.ord_key(&keys[i]));
+
+        // The bucket of the previous key, and the cursor at it.
+        let mut prev: Option<(usize, Cursor<'s, usize, Option<V>>)> = None;
+        for i in order {
+            Self::assert_valid_key(keys[i]);
+            let index = keys[i] % size;
+            loop {
+                // The previous node may have been removed, or the next one inserted.
+                let mut cursor = match prev.take() {
+                    Some((bucket, mut cursor)) if bucket == index => {
+                        if cursor.reload(guard) {
+                            cursor
+                        } else {
+                            self.lookup_bucket(index, guard)
+                        }
+                    }
+                    _ => self.lookup_bucket(index, guard),
+                };
+                let found = match cursor.find(&self.ord_key(&keys[i]), find, guard) {
+                    Ok(found) => found,
+                    Err(()) => continue,
+                };
+                let done = op(i, size, found, &mut cursor);
+                prev = Some((index, cursor));
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Doubles `size` if the list is full.
+    fn grow(&self, size: usize) {
+        if self.list.len_hint() > size * Self::LOAD_FACTOR {
+            let doubled = size << 1;
+            let _ = self
+                .size
+                .compare_exchange(size, doubled, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+
     fn assert_valid_key(key: usize) {
         assert!(key.leading_zeros() != 0);
     }
@@ -132,7 +201,7 @@ impl<V> SplitOrderedList<V> {
 impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
     fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
         Self::assert_valid_key(*key);
-        let (_, found, cursor) = self.find(key,guard);
+        let (_, found, cursor) = self.find(key, self.find, guard);
 
         if found {
             cursor.lookup().unwrap().as_ref()
@@ -143,18 +212,15 @@ impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
 
     fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
         Self::assert_valid_key(*key);
-        let (size, found, mut cursor) = self.find(key,guard);
+        let (size, found, mut cursor) = self.find(key, self.find.for_update(), guard);
 
         if found{
             Err(value)
         }else{
             let node = Owned::new(Node::new(self.ord_key(key),Some(value)));
-            match cursor.insert(node,guard){
+            match self.list.insert_at(&mut cursor,node,guard){
                 Ok(_) => {
-                    let count=self.count.fetch_add(1,Ordering::Relaxed);
-                    if count > size* Self::LOAD_FACTOR {
-                        self.size.compare_and_swap(size,size<<1,Ordering::Relaxed);
-                    }
+                    self.grow(size);
                     Ok(())
                 },
                 Err(e) => Err((*(e.into_box())).into_value().unwrap()),
@@ -164,16 +230,89 @@ impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
 
     fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
         Self::assert_valid_key(*key);
-        let (_, found, cursor) = self.find(key,guard);
+        let (_, found, cursor) = self.find(key, self.find.for_update(), guard);
 
         if found{
-            let ret=cursor.delete(guard).map(|n| n.as_ref().unwrap());
-            if ret.is_ok(){
-                self.count.fetch_sub(1,Ordering::Relaxed);
-            }
-            ret
+            self.list.delete_at(cursor,guard).map(|n| n.as_ref().unwrap())
         }else{
             Err(())
         }
     }
+
+    fn for_each<'a, F>(&'a self, guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&usize, &'a V),
+    {
+        // The entries are in split order, skipping the sentinel nodes of the buckets.
+        for (ord_key, value) in self.list.iter(guard) {
+            if let Some(value) = value {
+                f(&Self::key_of(*ord_key), value);
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup_all<'a>(&'a self, keys: &[usize], guard: &'a Guard) -> Vec<Option<&'a V>> {
+        let mut values = vec![None; keys.len()];
+        self.find_all(keys, self.find, guard, |i, _, found, cursor| {
+            if found {
+                values[i] = cursor.lookup().unwrap().as_ref();
+            }
+            true
+        });
+        values
+    }
+
+    fn insert_all(&self, keys: &[usize], values: Vec<V>, guard: &Guard) -> Vec<Result<(), V>> {
+        assert_eq!(keys.len(), values.len());
+        let mut values = values.into_iter().map(Some).collect::<Vec<_>>();
+        let mut results = (0..keys.len()).map(|_| Ok(())).collect::<Vec<_>>();
+        self.find_all(
+            keys,
+            self.find.for_update(),
+            guard,
+            |i, size, found, cursor| {
+                let value = values[i].take().unwrap();
+                if found {
+                    results[i] = Err(value);
+                    return true;
+                }
+                let node = Owned::new(Node::new(self.ord_key(&keys[i]), Some(value)));
+                match self.list.insert_at(cursor, node, guard) {
+                    Ok(()) => {
+                        self.grow(size);
+                        true
+                    }
+                    Err(node) => {
+                        values[i] = node.into_box().into_value();
+                        false
+                    }
+                }
+            },
+        );
+        results
+    }
+
+    fn delete_all<'a>(&'a self, keys: &[usize], guard: &'a Guard) -> Vec<Result<&'a V, ()>> {
+        let mut results = vec![Err(()); keys.len()];
+        self.find_all(
+            keys,
+            self.find.for_update(),
+            guard,
+            |i, _, found, cursor| {
+                if !found {
+                    return true;
+                }
+                match self.list.delete_at(cursor.clone(), guard) {
+                    Ok(value) => {
+                        results[i] = Ok(value.as_ref().unwrap());
+                        true
+                    }
+                    // Deleted concurrently.
+                    Err(()) => false,
+                }
+            },
+        );
+        results
+    }
 }
@@ -0,0 +1,274 @@
+//! Lock-striped hash map.
+
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::FromIterator;
+use crossbeam_epoch::Guard;
+use crossbeam_utils::CachePadded;
+use std::collections::hash_map::{self, HashMap, RandomState};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::map::ConcurrentMap;
+
+/// The default number of shards.
+const DEFAULT_SHARDS: usize = 16;
+
+type Shard<K, V, S> = CachePadded<Mutex<HashMap<K, V, S>>>;
+
+/// Hash map split into shards by the hashes of the keys, each of which is a `Mutex<HashMap>`.
+///
+/// The operations on the keys in different shards don't block each other. It is the practical
+/// baseline for the concurrent maps of this crate. The values can't be borrowed out of a shard, so
+/// the lookups return clones of them, or pass them to closures.
+pub struct StripedHashMap<K, V, S = RandomState> {
+    shards: Box<[Shard<K, V, S>]>,
+    /// Chooses the shard of a key. It is seeded independently of the hasher of the shards, so the
+    /// keys in a shard are still spread over its buckets.
+    shard_builder: RandomState,
+}
+
+impl<K, V> StripedHashMap<K, V> {
+    /// Creates a new map with 16 shards.
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates a new map with `shards` shards, rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is 0.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K, V, S: Clone> StripedHashMap<K, V, S> {
+    /// Creates a new map with `shards` shards, each of which hashes the keys with `hash_builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is 0.
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        assert!(shards > 0, "no shard to store the keys");
+        Self {
+            shards: (0..shards.next_power_of_two())
+                .map(|_| CachePadded::new(Mutex::new(HashMap::with_hasher(hash_builder.clone()))))
+                .collect(),
+            shard_builder: RandomState::new(),
+        }
+    }
+}
+
+impl<K, V, S> StripedHashMap<K, V, S> {
+    fn lock(shard: &Mutex<HashMap<K, V, S>>) -> MutexGuard<'_, HashMap<K, V, S>> {
+        // A panic in a closure of the user doesn't leave a shard inconsistent.
+        shard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the number of the keys. The shards are counted one by one, so it is not atomic.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| Self::lock(shard).len())
+            .sum()
+    }
+
+    /// Returns `true` if the map is empty, counting the shards one by one.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| Self::lock(shard).is_empty())
+    }
+
+    /// Removes all of the keys, from one shard to another.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            Self::lock(shard).clear();
+        }
+    }
+
+    /// Retains only the pairs for which `f` returns `true`, from one shard to another.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&self, mut f: F)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        for shard in self.shards.iter() {
+            Self::lock(shard).retain(&mut f);
+        }
+    }
+
+    /// Calls `f` with each pair, from one shard to another. The shard is locked while `f` runs.
+    pub fn for_each<F: FnMut(&K, &V)>(&self, mut f: F) {
+        for shard in self.shards.iter() {
+            for (k, v) in Self::lock(shard).iter() {
+                f(k, v);
+            }
+        }
+    }
+
+    /// Returns the map.
+    pub fn into_inner(self) -> HashMap<K, V, S>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        let mut shards = self.shards.into_vec().into_iter().map(|shard| {
+            CachePadded::into_inner(shard)
+                .into_inner()
+                .unwrap_or_else(|e| e.into_inner())
+        });
+        let mut map = shards.next().unwrap();
+        for shard in shards {
+            map.extend(shard);
+        }
+        map
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> StripedHashMap<K, V, S> {
+    fn shard<Q: ?Sized + Hash>(&self, key: &Q) -> MutexGuard<'_, HashMap<K, V, S>> {
+        let mut hasher = self.shard_builder.build_hasher();
+        key.hash(&mut hasher);
+        let index = hasher.finish() as usize & (self.shards.len() - 1);
+        Self::lock(&self.shards[index])
+    }
+
+    /// Returns `true` if the map contains the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    /// Returns a clone of the value of the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+        V: Clone,
+    {
+        self.shard(key).get(key).cloned()
+    }
+
+    /// Calls `f` with the value of the key, if any, while its shard is locked.
+    pub fn get_with<Q, F, R>(&self, key: &Q, f: F) -> R
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+        F: FnOnce(Option<&V>) -> R,
+    {
+        f(self.shard(key).get(key))
+    }
+
+    /// Calls `f` with the value of the key, if any, while its shard is locked, and returns the
+    /// result.
+    pub fn update<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+        F: FnOnce(&mut V) -> R,
+    {
+        self.shard(key).get_mut(key).map(f)
+    }
+
+    /// Inserts a key-value pair, and returns the old value of the key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).insert(key, value)
+    }
+
+    /// Removes the key, and returns its value.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+    {
+        self.shard(key).remove(key)
+    }
+
+    /// Removes the key if `f` returns `true` for its value, and returns the value. The value is
+    /// checked and removed while the shard is locked.
+    pub fn remove_if<Q, F>(&self, key: &Q, f: F) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+        F: FnOnce(&V) -> bool,
+    {
+        let mut shard = self.shard(key);
+        if shard.get(key).map_or(false, f) {
+            shard.remove(key)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a clone of the value of the key, inserting the value returned by `f` if it is
+    /// absent. `f` runs while the shard is locked, so it is called at most once for a key.
+    pub fn get_or_insert_with<F>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> V,
+        V: Clone,
+    {
+        self.shard(&key).entry(key).or_insert_with(f).clone()
+    }
+
+    /// Inserts the pairs of `iter`.
+    pub fn extend<I: IntoIterator<Item = (K, V)>>(&self, iter: I) {
+        for (key, value) in iter {
+            let _ = self.insert(key, value);
+        }
+    }
+}
+
+impl<K, V> Default for StripedHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash, V> FromIterator<(K, V)> for StripedHashMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for StripedHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each(|k, v| {
+            let _ = map.entry(k, v);
+        });
+        map.finish()
+    }
+}
+
+impl<K, V, S> ConcurrentMap<K, V> for StripedHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    fn lookup<'a, F, R>(&'a self, key: &'a K, _guard: &'a Guard, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R,
+    {
+        self.get_with(key, f)
+    }
+
+    fn insert<'a>(&'a self, key: &'a K, value: V, _guard: &'a Guard) -> Result<(), V> {
+        match self.shard(key).entry(key.clone()) {
+            hash_map::Entry::Occupied(_) => Err(value),
+            hash_map::Entry::Vacant(e) => {
+                let _ = e.insert(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&self, key: &K, _guard: &Guard) -> Result<V, ()> {
+        self.remove(key).ok_or(())
+    }
+}
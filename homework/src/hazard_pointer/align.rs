@@ -6,16 +6,29 @@ pub fn low_bits<T>() -> usize {
     (1 << mem::align_of::<T>().trailing_zeros()) - 1
 }
 
-/// Given a tagged pointer `data`, returns the same pointer, but tagged with `tag`.
+/// Decomposes a tagged pointer `data` into the pointer and the tag.
+#[inline]
+pub fn decompose_tag<T>(data: usize) -> (usize, usize) {
+    (data & !low_bits::<T>(), data & low_bits::<T>())
+}
+
+/// Given a tagged pointer `ptr`, returns the same pointer, but tagged with `tag`, keeping its
+/// provenance.
 ///
 /// `tag` is truncated to fit into the unused bits of the pointer to `T`.
 #[inline]
-pub fn compose_tag<T>(data: usize, tag: usize) -> usize {
-    (data & !low_bits::<T>()) | (tag & low_bits::<T>())
+pub fn compose_tag_ptr<T>(ptr: *mut T, tag: usize) -> *mut T {
+    let (_, old) = decompose_tag::<T>(ptr as usize);
+    // Offsets the pointer within its unused bits, as a pointer cast from an integer would have no
+    // provenance.
+    (ptr as *mut u8)
+        .wrapping_sub(old)
+        .wrapping_add(tag & low_bits::<T>()) as *mut T
 }
 
-/// Decomposes a tagged pointer `data` into the pointer and the tag.
+/// Decomposes a tagged pointer `ptr` into the pointer and the tag, keeping its provenance.
 #[inline]
-pub fn decompose_tag<T>(data: usize) -> (usize, usize) {
-    (data & !low_bits::<T>(), data & low_bits::<T>())
+pub fn decompose_tag_ptr<T>(ptr: *mut T) -> (*mut T, usize) {
+    let (_, tag) = decompose_tag::<T>(ptr as usize);
+    ((ptr as *mut u8).wrapping_sub(tag) as *mut T, tag)
 }
@@ -1,13 +1,24 @@
+//! Tagged pointers.
+//!
+//! The pointers are kept as raw pointers, so that they keep their provenance, e.g. under Miri's
+//! strict provenance checks. The tags are put in and out by offsetting the pointers within their
+//! unused bits. `Shared::into_usize` and `Shared::from_usize` remain as the fallback for the
+//! pointers that have been stored as integers, where the pointer gets the exposed provenance of
+//! the address.
+
+use alloc::boxed::Box;
 use core::marker::PhantomData;
 use core::mem;
 use core::ops::{Deref, DerefMut};
+use core::ptr;
 
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicPtr, Ordering};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::atomic::{AtomicPtr, Ordering};
 
 use super::align;
+use super::hazard::Shield;
 
 /// An owned heap-allocated object.
 ///
@@ -17,10 +28,13 @@ use super::align;
 /// least significant bits of the address.
 #[derive(Debug)]
 pub struct Owned<T> {
-    data: usize,
+    data: *mut T,
     _marker: PhantomData<Box<T>>,
 }
 
+unsafe impl<T: Send> Send for Owned<T> {}
+unsafe impl<T: Sync> Sync for Owned<T> {}
+
 /// An atomic pointer that can be safely shared between threads.
 ///
 /// The pointer must be properly aligned. Since it is aligned, a tag can be stored into the unused
@@ -28,7 +42,7 @@ pub struct Owned<T> {
 /// should be less than `(1 << mem::align_of::<T>().trailing_zeros())`.
 #[derive(Debug)]
 pub struct Atomic<T> {
-    data: AtomicUsize,
+    data: AtomicPtr<T>,
     _marker: PhantomData<*const T>,
 }
 
@@ -38,7 +52,7 @@ pub struct Atomic<T> {
 /// least significant bits of the address.
 #[derive(Debug)]
 pub struct Shared<T> {
-    data: usize,
+    data: *mut T,
     _marker: PhantomData<*const T>,
 }
 
@@ -57,24 +71,24 @@ impl<T> Owned<T> {
     /// Allocates `data` on the heap and returns a new owned pointer pointing to it.
     pub fn new(data: T) -> Self {
         Self {
-            data: Box::into_raw(Box::new(data)) as usize,
+            data: Box::into_raw(Box::new(data)),
             _marker: PhantomData,
         }
     }
 
     /// Returns the tag stored within the pointer.
     pub fn tag(&self) -> usize {
-        let (_, tag) = align::decompose_tag::<T>(self.data);
+        let (_, tag) = align::decompose_tag_ptr(self.data);
         tag
     }
 
     /// Returns the same pointer, but tagged with `tag`. `tag` is truncated to be fit into the
     /// unused bits of the pointer to `T`.
     pub fn with_tag(self, tag: usize) -> Self {
-        let (data, _) = align::decompose_tag::<T>(self.data);
+        let data = align::compose_tag_ptr(self.data, tag);
         mem::forget(self);
         Self {
-            data: align::compose_tag::<T>(data, tag),
+            data,
             _marker: PhantomData,
         }
     }
@@ -83,17 +97,17 @@ impl<T> Owned<T> {
     pub fn into_shared(self) -> Shared<T> {
         let data = self.data;
         mem::forget(self);
-        Shared::<T>::from_usize(data)
+        Shared::from_raw(data)
     }
 
     /// Returns a new pointer pointing to the tagged pointer `data`.
     ///
     /// # Panics
     ///
-    /// Panics if the data is zero in debug mode.
+    /// Panics if the pointer is null in debug mode.
     #[inline]
-    unsafe fn from_usize(data: usize) -> Self {
-        debug_assert!(data != 0, "converting zero into `Owned`");
+    unsafe fn from_raw(data: *mut T) -> Self {
+        debug_assert!(!data.is_null(), "converting null into `Owned`");
         Owned {
             data,
             _marker: PhantomData,
@@ -105,22 +119,22 @@ impl<T> Deref for Owned<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        unsafe { &*(data as *const T) }
+        let (data, _) = align::decompose_tag_ptr(self.data);
+        unsafe { &*data }
     }
 }
 
 impl<T> DerefMut for Owned<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        unsafe { &mut *(data as *mut T) }
+        let (data, _) = align::decompose_tag_ptr(self.data);
+        unsafe { &mut *data }
     }
 }
 
 impl<T> Drop for Owned<T> {
     fn drop(&mut self) {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        drop(unsafe { Box::<T>::from_raw(data as *mut T) });
+        let (data, _) = align::decompose_tag_ptr(self.data);
+        drop(unsafe { Box::<T>::from_raw(data) });
     }
 }
 
@@ -131,14 +145,14 @@ impl<T> Atomic<T> {
     /// Returns a new null atomic pointer.
     pub fn null() -> Self {
         Self {
-            data: AtomicUsize::new(0),
+            data: AtomicPtr::new(ptr::null_mut()),
             _marker: PhantomData,
         }
     }
 
     /// Allocates `data` on the heap and returns a new atomic pointer pointing to it.
     pub fn new(data: T) -> Self {
-        let data = AtomicUsize::new(Owned::new(data).into_shared().into_usize());
+        let data = AtomicPtr::new(Owned::new(data).into_shared().data);
         Self {
             data,
             _marker: PhantomData,
@@ -147,7 +161,7 @@ impl<T> Atomic<T> {
 
     /// Loads a `Shared` from the atomic pointer.
     pub fn load(&self, ord: Ordering) -> Shared<T> {
-        Shared::from_usize(self.data.load(ord))
+        Shared::from_raw(self.data.load(ord))
     }
 
     /// Stores a `Shared` into the atomic pointer.
@@ -171,15 +185,49 @@ impl<T> Atomic<T> {
         self.data
             .compare_exchange(cur.data, new.data, ord_succ, ord_fail)
             .map(|_| ())
-            .map_err(Shared::from_usize)
+            .map_err(Shared::from_raw)
+    }
+
+    /// Stores `new` if the current value is `cur` as `compare_and_set`, and returns the pointer
+    /// then protected by `shield`: `new` on success, and the current value on failure.
+    ///
+    /// It closes the window between a CAS and the protection of its result, e.g. when a CAS loop
+    /// retries with the value it failed with. `new` must be owned or protected by the caller, and
+    /// it is protected before it is published, so it needs no validation. The current value on
+    /// failure is loaded again and validated as `Shield::protect` does, so it may be newer than the
+    /// value the CAS failed with.
+    pub fn compare_exchange_protected(
+        &self,
+        cur: Shared<T>,
+        new: Shared<T>,
+        ord_succ: Ordering,
+        ord_fail: Ordering,
+        shield: &mut Shield<'_, T>,
+    ) -> Result<Shared<T>, Shared<T>> {
+        shield.set(new);
+        match self.compare_and_set(cur, new, ord_succ, ord_fail) {
+            Ok(()) => Ok(new),
+            Err(_) => Err(shield.protect(self)),
+        }
     }
 
     /// Performs a bitwise "or" operation on the current tag and the argument `tag`, and sets the
     /// new tag to the result. Returns the previous pointer.
     pub fn fetch_or(&self, tag: usize, ord: Ordering) -> Shared<T> {
-        let tag = tag & align::low_bits::<T>();
-        let old = self.data.fetch_or(tag, ord);
-        Shared::from_usize(old)
+        // `AtomicPtr` has no `fetch_or` in our toolchain, and `fetch_or` on the address would lose
+        // the provenance.
+        let mut old = self.data.load(Ordering::Relaxed);
+        loop {
+            let (_, old_tag) = align::decompose_tag_ptr(old);
+            let new = align::compose_tag_ptr(old, old_tag | tag);
+            match self
+                .data
+                .compare_exchange_weak(old, new, ord, Ordering::Relaxed)
+            {
+                Ok(_) => return Shared::from_raw(old),
+                Err(current) => old = current,
+            }
+        }
     }
 }
 
@@ -187,40 +235,52 @@ impl<T> Shared<T> {
     /// Returns a new null pointer.
     pub fn null() -> Shared<T> {
         Shared {
-            data: 0,
+            data: ptr::null_mut(),
             _marker: PhantomData,
         }
     }
 
     /// Returns the tag stored within the pointer.
     pub fn tag(&self) -> usize {
-        let (_, tag) = align::decompose_tag::<T>(self.data);
+        let (_, tag) = align::decompose_tag_ptr(self.data);
         tag
     }
 
     /// Returns the same pointer, but tagged with `tag`. `tag` is truncated to be fit into the
     /// unused bits of the pointer to `T`.
     pub fn with_tag(self, tag: usize) -> Self {
-        let (data, _) = align::decompose_tag::<T>(self.data);
         Self {
-            data: align::compose_tag::<T>(data, tag),
+            data: align::compose_tag_ptr(self.data, tag),
             _marker: PhantomData,
         }
     }
 
     /// Returns `true` if the pointer is null ignoring its tag.
     pub fn is_null(&self) -> bool {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        data == 0
+        let (data, _) = align::decompose_tag_ptr(self.data);
+        data.is_null()
+    }
+
+    /// Returns the raw pointer without tag.
+    pub fn as_raw(&self) -> *const T {
+        let (data, _) = align::decompose_tag_ptr(self.data);
+        data
     }
 
-    /// Returns the machine representation of the pointer.
+    /// Returns the machine representation of the pointer, e.g. to compare the addresses.
     pub fn into_usize(self) -> usize {
-        self.data
+        self.data as usize
     }
 
     /// Returns a new pointer pointing to the tagged pointer `data`.
+    ///
+    /// The pointer gets the exposed provenance of the address, so prefer converting a raw pointer
+    /// with `From`. It is the fallback for the pointers that have been stored as integers.
     pub fn from_usize(data: usize) -> Self {
+        Self::from_raw(data as *mut T)
+    }
+
+    fn from_raw(data: *mut T) -> Self {
         Self {
             data,
             _marker: PhantomData,
@@ -239,7 +299,7 @@ impl<T> Shared<T> {
     /// reference to the same object.
     pub unsafe fn into_owned(self) -> Owned<T> {
         debug_assert!(!self.is_null(), "converting a null `Shared` into `Owned`");
-        Owned::from_usize(self.data)
+        Owned::from_raw(self.data)
     }
 
     /// Dereferences the shared pointer.
@@ -249,7 +309,13 @@ impl<T> Shared<T> {
     /// The pointer should be valid and the pointee should not be concurrently accessed by the
     /// other threads.
     pub unsafe fn deref(&self) -> &T {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        &*(data as *const T)
+        &*self.as_raw()
+    }
+}
+
+impl<T> From<*const T> for Shared<T> {
+    /// Returns a new pointer pointing to the possibly tagged raw pointer.
+    fn from(raw: *const T) -> Self {
+        Self::from_raw(raw as *mut T)
     }
 }
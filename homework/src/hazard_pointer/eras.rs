@@ -0,0 +1,290 @@
+//! Hazard eras, an alternative to the hazard pointers of this module.
+//!
+//! A global clock is advanced at each retirement. Each object is stamped with the era it is
+//! allocated in (`Stamped::new`) and the era it is retired in. Instead of a pointer, a reader
+//! publishes the era it reads the pointer in, and an object is freed once no thread publishes an
+//! era within its lifetime. While the clock doesn't change, `EraShield::protect` doesn't publish
+//! anything, and it never reloads the pointer for validation. As with hazard pointers, a stalled
+//! reader only keeps alive the objects of its era, so the garbage is bounded.
+//!
+//! The eras are published in their own `Hazards`, with the same fences as the hazard pointers.
+//!
+//! Reference: Pedro Ramalhete and Andreia Correia. Brief Announcement: Hazard Eras - Non-Blocking
+//! Memory Reclamation. SPAA 2017.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use cs492_concur_homework::hazard_pointer::eras::{collect, retire, EraShield, Stamped};
+//! use cs492_concur_homework::hazard_pointer::{Atomic, Shared};
+//!
+//! let atomic = Atomic::null();
+//! atomic.store(Stamped::new(1).into_shared(), Ordering::Release);
+//!
+//! let mut shield = EraShield::new();
+//! let shared = shield.protect(&atomic);
+//! assert_eq!(**unsafe { shared.deref() }, 1);
+//!
+//! atomic.store(Shared::null(), Ordering::Relaxed);
+//! retire(shared);
+//! drop(shield);
+//! collect();
+//! ```
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
+use super::membarrier;
+use super::{Atomic, Free, GlobalRetirees, Hazards, LocalHazards, Owned, Registration, Shared};
+
+#[cfg(not(feature = "check-loom"))]
+/// The global era clock. It starts from 1, as 0 is an empty hazard slot.
+static CLOCK: AtomicUsize = AtomicUsize::new(1);
+
+#[cfg(feature = "check-loom")]
+loom::lazy_static! {
+    /// The global era clock. It starts from 1, as 0 is an empty hazard slot.
+    static ref CLOCK: AtomicUsize = AtomicUsize::new(1);
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// The eras published by all threads.
+static ERAS: Hazards = Hazards::new();
+
+#[cfg(feature = "check-loom")]
+loom::lazy_static! {
+    /// The eras published by all threads.
+    static ref ERAS: Hazards = Hazards::new();
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// The retired objects left by the exited threads. Each entry is a boxed `Vec<EraRetired>`, see
+/// `EraRetirees::drop`.
+static GLOBAL_RETIRED: GlobalRetirees = GlobalRetirees::new();
+
+#[cfg(feature = "check-loom")]
+loom::lazy_static! {
+    /// The retired objects left by the exited threads. Each entry is a boxed `Vec<EraRetired>`,
+    /// see `EraRetirees::drop`.
+    static ref GLOBAL_RETIRED: GlobalRetirees = GlobalRetirees::new();
+}
+
+thread_local! {
+    /// Thread-local list of retired objects.
+    static RETIRED: RefCell<EraRetirees> = RefCell::new(EraRetirees { inner: Vec::new() });
+
+    /// The era array of the current thread.
+    static LOCAL: Registration = Registration::new(&ERAS);
+}
+
+/// An object stamped with the era it is allocated in. The objects protected by `EraShield` must be
+/// allocated with `Stamped::new`.
+#[derive(Debug)]
+pub struct Stamped<T> {
+    birth: usize,
+    data: T,
+}
+
+impl<T> Stamped<T> {
+    /// Allocates `data` stamped with the current era.
+    pub fn new(data: T) -> Owned<Self> {
+        Owned::new(Self {
+            birth: CLOCK.load(Ordering::SeqCst),
+            data,
+        })
+    }
+
+    /// Returns the era the object is allocated in.
+    pub fn birth(&self) -> usize {
+        self.birth
+    }
+}
+
+impl<T> Deref for Stamped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Stamped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+/// Represents the ownership of an era slot of the current thread.
+///
+/// The pointers returned by `protect` are protected until the era changes, i.e. until the next
+/// `protect` that publishes a new era, `reset`, or drop. As with `Shield`, use a shield for each
+/// pointer to keep, e.g. `pred` and `curr` of a list traversal.
+#[derive(Debug)]
+pub struct EraShield {
+    hazards: &'static LocalHazards,
+    index: usize,
+    era: usize,
+    /// The slot is owned by the current thread.
+    _marker: PhantomData<*const ()>,
+}
+
+impl EraShield {
+    /// Creates a shield that publishes no era.
+    pub fn new() -> Self {
+        let local = LOCAL.with(|l| l.local);
+        let (hazards, index) = unsafe { local.alloc_growing(0) };
+        Self {
+            hazards,
+            index,
+            era: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Loads a pointer from `atomic` and protects it. Publishes the current era first if it is not
+    /// the one published by this shield, and loads again until the era doesn't change.
+    pub fn protect<T>(&mut self, atomic: &Atomic<Stamped<T>>) -> Shared<Stamped<T>> {
+        loop {
+            let pointer = atomic.load(Ordering::Acquire);
+            let era = CLOCK.load(Ordering::SeqCst);
+            if era == self.era {
+                return pointer;
+            }
+            unsafe { self.hazards.set(self.index, era) };
+            self.era = era;
+            membarrier::light();
+        }
+    }
+
+    /// Releases the protection, keeping the slot.
+    pub fn reset(&mut self) {
+        unsafe { self.hazards.set(self.index, 0) };
+        self.era = 0;
+    }
+}
+
+impl Default for EraShield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for EraShield {
+    fn drop(&mut self) {
+        unsafe { self.hazards.dealloc(self.index) };
+    }
+}
+
+/// Retires an object unlinked from the data structure. It is freed once no thread publishes an era
+/// within its lifetime.
+pub fn retire<T: Send>(pointer: Shared<Stamped<T>>) {
+    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+}
+
+/// Frees the objects that are `retire`d by the current thread or left by the exited threads, and
+/// not protected by the era of any thread.
+pub fn collect() {
+    RETIRED.with(|r| r.borrow_mut().collect());
+}
+
+/// Retired object with its lifetime.
+#[derive(Debug)]
+struct EraRetired {
+    data: *mut (),
+    free: unsafe fn(*mut ()),
+    birth: usize,
+    retire: usize,
+}
+
+impl EraRetired {
+    /// Returns `true` if one of `eras`, sorted, is within the lifetime of the object.
+    fn is_protected(&self, eras: &[usize]) -> bool {
+        match eras.binary_search(&self.birth) {
+            Ok(_) => true,
+            Err(i) => eras.get(i).map_or(false, |&era| era <= self.retire),
+        }
+    }
+}
+
+/// Thread-local list of retired objects.
+#[derive(Debug)]
+struct EraRetirees {
+    inner: Vec<EraRetired>,
+}
+
+impl EraRetirees {
+    /// Call `collect` if the length of `inner` becomes larger than this value.
+    const THRESHOLD: usize = 64;
+
+    fn retire<T: Send>(&mut self, pointer: Shared<Stamped<T>>) {
+        unsafe fn free<T>(data: *mut ()) {
+            drop(Box::from_raw(data as *mut Stamped<T>))
+        }
+
+        // The object is not freed before it is retired.
+        let birth = unsafe { pointer.deref() }.birth;
+        let retire = CLOCK.load(Ordering::SeqCst);
+        self.inner.push(EraRetired {
+            data: pointer.as_raw() as *mut (),
+            free: free::<T>,
+            birth,
+            retire,
+        });
+        // The readers from now on publish a later era. The other retiring threads may have
+        // advanced it already.
+        let _ = CLOCK.compare_exchange(retire, retire + 1, Ordering::SeqCst, Ordering::Relaxed);
+
+        if self.inner.len() > Self::THRESHOLD {
+            self.collect();
+        }
+    }
+
+    fn collect(&mut self) {
+        for (data, _, _) in GLOBAL_RETIRED.take() {
+            let batch = unsafe { Box::from_raw(data as *mut Vec<EraRetired>) };
+            self.inner.extend(*batch);
+        }
+        membarrier::heavy();
+        let eras = ERAS.sorted_hazards();
+
+        for retired in mem::take(&mut self.inner) {
+            if retired.is_protected(&eras) {
+                self.inner.push(retired);
+            } else {
+                unsafe { (retired.free)(retired.data) };
+            }
+        }
+    }
+}
+
+impl Drop for EraRetirees {
+    fn drop(&mut self) {
+        unsafe fn free_batch(data: *mut ()) {
+            for retired in *Box::from_raw(data as *mut Vec<EraRetired>) {
+                (retired.free)(retired.data);
+            }
+        }
+
+        self.collect();
+        if self.inner.is_empty() {
+            return;
+        }
+        // `GlobalRetirees` holds pointers with how to free them, so the objects still protected
+        // are moved there as one boxed batch, which only this module takes.
+        let batch = Box::into_raw(Box::new(mem::take(&mut self.inner)));
+        let size = mem::size_of::<Vec<EraRetired>>();
+        GLOBAL_RETIRED.push(vec![(batch as *mut (), Free::Object(free_batch), size)]);
+    }
+}
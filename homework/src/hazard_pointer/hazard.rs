@@ -1,22 +1,32 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use arr_macro::arr;
+use core::fmt;
+use core::iter;
 use core::marker::PhantomData;
+use core::mem;
 use core::ptr;
+#[cfg(feature = "std")]
 use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "std")]
 use std::collections::HashSet;
-use std::fmt;
+#[cfg(feature = "std")]
 use std::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
 use std::thread::ThreadId;
 
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
 
-use super::align;
-use super::atomic::Shared;
+use super::atomic::{Atomic, Shared};
+use super::membarrier;
 
 /// Per-thread array of hazard pointers.
 ///
-/// Caveat: a thread may have up to 8 hazard pointers.
+/// An array has 8 slots. When they are all occupied, another array is appended to it on demand,
+/// so a thread may have any number of hazard pointers.
 #[derive(Debug)]
 pub struct LocalHazards {
     /// Bitmap that indicates the indices of occupied slots.
@@ -24,6 +34,10 @@ pub struct LocalHazards {
 
     /// Array that contains the machine representation of hazard pointers without tag.
     elements: [AtomicUsize; 8],
+
+    /// The next array, allocated by the owner thread when this one is full. Never freed until the
+    /// owner thread's arrays are dropped.
+    next: AtomicPtr<LocalHazards>,
 }
 
 impl Default for LocalHazards {
@@ -31,6 +45,7 @@ impl Default for LocalHazards {
         Self {
             occupied: Default::default(),
             elements: Default::default(),
+            next: AtomicPtr::new(ptr::null_mut()),
         }
     }
 }
@@ -60,6 +75,39 @@ impl LocalHazards {
         }
     }
 
+    /// Allocates a slot for a hazard pointer in this array or the next ones, appending a new array
+    /// if they are all full. Returns the array containing the slot and its index.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array.
+    pub unsafe fn alloc_growing(&self, data: usize) -> (&Self, usize) {
+        let mut hazards = self;
+        loop {
+            if let Some(index) = hazards.alloc(data) {
+                return (hazards, index);
+            }
+            // Only the owner thread writes `next`.
+            let mut next = hazards.next.load(Ordering::Relaxed);
+            if next.is_null() {
+                next = Box::into_raw(Box::new(Self::new()));
+                hazards.next.store(next, Ordering::Release);
+            }
+            hazards = &*next;
+        }
+    }
+
+    /// Replaces the hazard pointer at the given index.
+    ///
+    /// # Safety
+    ///
+    /// The index must have been allocated.
+    pub unsafe fn set(&self, index: usize, data: usize) {
+        self.elements
+            .get_unchecked(index)
+            .store(data, Ordering::Release);
+    }
+
     /// Clears the hazard pointer at the given index.
     ///
     /// # Safety
@@ -72,7 +120,18 @@ impl LocalHazards {
         self.occupied.fetch_xor(1<<index,Ordering::Release);
     }
 
-    /// Returns an iterator of hazard pointers (with tags erased).
+    /// Returns the number of slots in this array and the next ones, occupied or not.
+    pub fn num_slots(&self) -> usize {
+        let mut slots = 0;
+        let mut hazards = Some(self);
+        while let Some(h) = hazards {
+            slots += h.elements.len();
+            hazards = unsafe { h.next.load(Ordering::Acquire).as_ref() };
+        }
+        slots
+    }
+
+    /// Returns an iterator of hazard pointers (with tags erased) in this array and the next ones.
     pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
         LocalHazardsIter {
             hazards: self,
@@ -91,52 +150,83 @@ impl Iterator for LocalHazardsIter<'_> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let index = self.occupied.trailing_zeros() as usize;
-        if index == 8 {
-            return None;
-        }else{
-            self.occupied = self.occupied ^ (1<<index);
-            return unsafe { Some(self.hazards.elements.get_unchecked(index).load(Ordering::Acquire)) };
+        let mut index = self.occupied.trailing_zeros() as usize;
+        while index == 8 {
+            let next = unsafe { self.hazards.next.load(Ordering::Acquire).as_ref() }?;
+            self.hazards = next;
+            self.occupied = next.occupied.load(Ordering::Acquire);
+            index = self.occupied.trailing_zeros() as usize;
+        }
+        self.occupied ^= 1 << index;
+        Some(unsafe { self.hazards.elements.get_unchecked(index) }.load(Ordering::Acquire))
+    }
+}
+
+impl Drop for LocalHazards {
+    fn drop(&mut self) {
+        let next = self.next.load(Ordering::Relaxed);
+        if !next.is_null() {
+            unsafe { drop(Box::from_raw(next)) };
         }
     }
 }
 
 /// Represents the ownership of a hazard pointer slot.
+///
+/// The slot is released when the shield is dropped, so a hazard can't be left behind, blocking the
+/// reclamation forever, unless the shield is leaked with `mem::forget`. Use `reset` to release the
+/// protection while keeping the slot for later use.
 pub struct Shield<'s, T> {
-    data: usize, // preserves the tag of original `Shared`
+    data: Shared<T>, // preserves the tag of original `Shared`
     hazards: &'s LocalHazards,
     index: usize,
     _marker: PhantomData<&'s T>,
 }
 
 impl<'s, T> Shield<'s, T> {
-    /// Creates a new shield for hazard pointer. Returns `None` if the hazard array is fully
-    /// occupied.
+    /// Creates a new shield for hazard pointer. The hazard array grows if it is fully occupied, so
+    /// it always returns `Some`.
+    ///
+    /// The pointer must not be tagged, which is checked in debug builds: a tag usually marks a
+    /// node being removed, so protecting it is likely a mistake. Use `new_tagged` otherwise.
     ///
     /// # Safety
     ///
     /// This function must be called only by the thread that owns this hazard array.
     pub unsafe fn new(pointer: Shared<T>, hazards: &'s LocalHazards) -> Option<Self> {
-        match hazards.alloc(pointer.into_usize()) {
-            Some(index) => Some(Self {
-                data: pointer.into_usize(),
-                hazards: hazards,
-                index: index,
-                _marker: PhantomData,
-            }),
-            None => None,
-        }
+        debug_assert_eq!(
+            pointer.tag(),
+            0,
+            "protecting a tagged pointer, use `Shield::new_tagged` instead"
+        );
+        Self::new_tagged(pointer, hazards)
+    }
+
+    /// Creates a new shield for a possibly tagged hazard pointer. The hazard array stores the
+    /// pointer without tag, which is what `collect` compares with the retired pointers, and
+    /// `shared` returns it with the tag.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array.
+    pub unsafe fn new_tagged(pointer: Shared<T>, hazards: &'s LocalHazards) -> Option<Self> {
+        let (hazards, index) = hazards.alloc_growing(pointer.with_tag(0).into_usize());
+        Some(Self {
+            data: pointer,
+            hazards,
+            index,
+            _marker: PhantomData,
+        })
     }
 
     /// Returns `true` if the pointer is null.
     pub fn is_null(&self) -> bool {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        data == 0
+        self.data.is_null()
     }
 
     /// Returns the `Shared` pointer protected by this shield. The original tag is preserved.
     pub fn shared(&self) -> Shared<T> {
-        Shared::from_usize(self.data)
+        self.data
     }
 
     /// Dereferences the shielded hazard pointer.
@@ -147,8 +237,7 @@ impl<'s, T> Shield<'s, T> {
     /// `validate`d. Invocations of this method should be properly synchronized with the other
     /// accesses to the object in order to avoid data race.
     pub unsafe fn deref(&self) -> &T {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        &*(data as *const T)
+        self.data.deref()
     }
 
     /// Dereferences the shielded hazard pointer is the pointer is not null.
@@ -159,18 +248,55 @@ impl<'s, T> Shield<'s, T> {
     /// `validate`d. Invocations of this method should be properly synchronized with the other
     /// accesses to the object in order to avoid data race.
     pub unsafe fn as_ref(&self) -> Option<&T> {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        if data == 0 {
+        if self.data.is_null() {
             None
         } else {
-            Some(&*(data as *const T))
+            Some(self.data.deref())
         }
     }
 
+    /// Loads a pointer from `atomic` and protects it with this shield instead of the pointer it
+    /// protected so far, and returns the pointer. The protection is already validated: after
+    /// publishing the hazard, it loads the pointer again and retries until they are equal.
+    ///
+    /// The tag of the pointer is part of the data structure's state, so it is not checked: the
+    /// hazard is the pointer without tag, and the returned pointer has the current tag.
+    pub fn protect(&mut self, atomic: &Atomic<T>) -> Shared<T> {
+        let mut pointer = atomic.load(Ordering::Acquire);
+        loop {
+            self.set(pointer);
+            let current = atomic.load(Ordering::Acquire);
+            if self.validate(current) {
+                // The tag may have changed.
+                self.data = current;
+                return current;
+            }
+            pointer = current;
+        }
+    }
+
+    /// Protects `pointer` instead of the pointer it protected so far, without validation.
+    pub(super) fn set(&mut self, pointer: Shared<T>) {
+        unsafe { self.hazards.set(self.index, pointer.with_tag(0).into_usize()) };
+        self.data = pointer;
+        membarrier::light();
+    }
+
     /// Check if `pointer` is protected by the shield. The tags are ignored.
     pub fn validate(&self, pointer: Shared<T>) -> bool {
-        let (data, _) = align::decompose_tag::<T>(self.data);
-        data == pointer.with_tag(0).into_usize()
+        self.data.as_raw() == pointer.as_raw()
+    }
+
+    /// Releases the protection, keeping the slot. The shield then protects null.
+    pub fn reset(&mut self) {
+        unsafe { self.hazards.set(self.index, 0) };
+        self.data = Shared::null();
+    }
+
+    /// Swaps the pointers protected by the shields, e.g. to move the protection of the current
+    /// node to the previous one in a traversal. The slots are not modified.
+    pub fn swap(&mut self, other: &mut Self) {
+        mem::swap(self, other);
     }
 }
 
@@ -182,29 +308,127 @@ impl<'s, T> Drop for Shield<'s, T> {
 
 impl<'s, T> fmt::Debug for Shield<'s, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (raw, tag) = align::decompose_tag::<T>(self.data);
         f.debug_struct("Shield")
-            .field("raw", &raw)
-            .field("tag", &tag)
+            .field("raw", &self.data.as_raw())
+            .field("tag", &self.data.tag())
             .field("hazards", &(self.hazards as *const _))
             .field("index", &self.index)
             .finish()
     }
 }
 
-/// Maps `ThreadId`s to their `Hazards`.
+/// Array of shields owned by a `ShieldSet`.
+///
+/// It is implemented for the arrays of up to 8 shields. Const generics are not available in our
+/// toolchain, so `ShieldSet` is generic over the array type instead of the length.
+pub trait ShieldArray<'s, T: 's>: AsRef<[Shield<'s, T>]> + AsMut<[Shield<'s, T>]> {
+    /// Creates the array of null shields.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array.
+    unsafe fn null(hazards: &'s LocalHazards) -> Self;
+}
+
+macro_rules! impl_shield_array {
+    ($($n:tt)*) => {$(
+        impl<'s, T: 's> ShieldArray<'s, T> for [Shield<'s, T>; $n] {
+            unsafe fn null(hazards: &'s LocalHazards) -> Self {
+                arr![Shield::new(Shared::null(), hazards).unwrap(); $n]
+            }
+        }
+    )*};
+}
+
+impl_shield_array!(1 2 3 4 5 6 7 8);
+
+/// Fixed set of shields, e.g. for the `pred`, `curr`, and `next` nodes of a list traversal.
+///
+/// The slots are acquired once when it is created, and reused as the traversal advances, e.g.
+/// `swap(0, 1)` moves the protection of `curr` to `pred` without touching the hazard array.
+pub struct ShieldSet<'s, T: 's, A: ShieldArray<'s, T> = [Shield<'s, T>; 3]> {
+    shields: A,
+    _marker: PhantomData<Shield<'s, T>>,
+}
+
+impl<'s, T: 's, A: ShieldArray<'s, T>> ShieldSet<'s, T, A> {
+    /// Creates a set of null shields.
+    ///
+    /// # Safety
+    ///
+    /// This function must be called only by the thread that owns this hazard array.
+    pub unsafe fn new(hazards: &'s LocalHazards) -> Self {
+        Self {
+            shields: A::null(hazards),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the `i`-th shield.
+    pub fn get(&self, i: usize) -> &Shield<'s, T> {
+        &self.shields.as_ref()[i]
+    }
+
+    /// Protects the pointer loaded from `atomic` with the `i`-th shield, as `Shield::protect`.
+    pub fn protect(&mut self, i: usize, atomic: &Atomic<T>) -> Shared<T> {
+        self.shields.as_mut()[i].protect(atomic)
+    }
+
+    /// Swaps the `i`-th and `j`-th shields.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.shields.as_mut().swap(i, j);
+    }
+
+    /// Releases the protection of the `i`-th shield, which then protects null.
+    pub fn reset(&mut self, i: usize) {
+        self.shields.as_mut()[i].reset();
+    }
+}
+
+impl<'s, T: 's, A: ShieldArray<'s, T>> fmt::Debug for ShieldSet<'s, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.shields.as_ref()).finish()
+    }
+}
+
+/// Identifies the owner of a hazard array: a thread with `std`, or e.g. a CPU or a task without it,
+/// in which case the owners must not share an id concurrently.
+#[cfg(feature = "std")]
+pub type Tid = ThreadId;
+
+/// Identifies the owner of a hazard array: a thread with `std`, or e.g. a CPU or a task without it,
+/// in which case the owners must not share an id concurrently.
+#[cfg(not(feature = "std"))]
+pub type Tid = usize;
+
+/// Maps `Tid`s to their `Hazards`.
 ///
 /// Uses a hash table based on append-only lock-free linked list for simplicity. In practice, this
 /// is implemented using a more useful and efficient lock-free data structure.
+///
+/// The arrays of `get` are never released, since a `Tid` may be used again. A thread can instead
+/// `register` an array and `unregister` it when it exits, so that the array is reused by the next
+/// thread. Then short-lived threads don't keep growing the set of arrays, which every `collect`
+/// scans.
 #[derive(Debug)]
 pub struct Hazards {
     heads: [AtomicPtr<Node>; Self::BUCKETS],
+    registered: AtomicPtr<Registered>,
 }
 
 #[derive(Debug)]
 struct Node {
     next: AtomicPtr<Node>,
-    tid: ThreadId,
+    tid: Tid,
+    hazards: LocalHazards,
+}
+
+/// Hazard array handed out by `register`.
+#[derive(Debug)]
+struct Registered {
+    next: AtomicPtr<Registered>,
+    /// Whether a thread owns the array, i.e. it is registered and not yet unregistered.
+    active: AtomicBool,
     hazards: LocalHazards,
 }
 
@@ -230,6 +454,7 @@ impl Hazards {
                 AtomicPtr::new(ptr::null_mut()),
                 AtomicPtr::new(ptr::null_mut()),
             ],
+            registered: AtomicPtr::new(ptr::null_mut()),
         }
     }
 
@@ -252,14 +477,26 @@ impl Hazards {
                 AtomicPtr::new(ptr::null_mut()),
                 AtomicPtr::new(ptr::null_mut()),
             ],
+            registered: AtomicPtr::new(ptr::null_mut()),
         }
     }
-    pub fn get(&self, tid: ThreadId) -> &LocalHazards {
-        let index = {
-            let mut s = DefaultHasher::new();
-            tid.hash(&mut s);
-            (s.finish() as usize) % Self::BUCKETS
-        };
+
+    #[cfg(feature = "std")]
+    fn bucket(tid: Tid) -> usize {
+        let mut s = DefaultHasher::new();
+        tid.hash(&mut s);
+        (s.finish() as usize) % Self::BUCKETS
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn bucket(tid: Tid) -> usize {
+        tid % Self::BUCKETS
+    }
+
+    /// Returns the hazard array of the given thread, allocating it at the first call. The array is
+    /// never released.
+    pub fn get(&self, tid: Tid) -> &LocalHazards {
+        let index = Self::bucket(tid);
 
         'start: loop {
             let mut prev = unsafe { self.heads.get_unchecked(index) };
@@ -290,18 +527,113 @@ impl Hazards {
         }
     }
 
+    /// Returns a hazard array owned by the calling thread until it is `unregister`ed. An array
+    /// unregistered by another thread is reused if any.
+    pub fn register(&self) -> &LocalHazards {
+        let mut prev = &self.registered;
+        let mut cur = prev.load(Ordering::Acquire);
+        loop {
+            if cur.is_null() {
+                let new = Box::into_raw(Box::new(Registered {
+                    next: AtomicPtr::new(ptr::null_mut()),
+                    active: AtomicBool::new(true),
+                    hazards: LocalHazards::new(),
+                }));
+                match prev.compare_exchange(cur, new, Ordering::Release, Ordering::Acquire) {
+                    Ok(_) => return unsafe { &(*new).hazards },
+                    Err(current) => {
+                        unsafe { drop(Box::from_raw(new)) };
+                        cur = current;
+                        continue;
+                    }
+                }
+            }
+            let cur_ref = unsafe { &*cur };
+            // Synchronizes with `unregister`, so the previous owner's accesses happen before ours.
+            if cur_ref
+                .active
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return &cur_ref.hazards;
+            }
+            prev = &cur_ref.next;
+            cur = prev.load(Ordering::Acquire);
+        }
+    }
+
+    /// Releases a hazard array returned by `register`, to be reused by another thread. The hazards
+    /// still in the array, e.g. of leaked shields, keep protecting their pointers.
+    ///
+    /// # Safety
+    ///
+    /// The calling thread must own the array, and must not use it, e.g. drop a shield of it,
+    /// afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the array was not returned by `register` of this set.
+    pub unsafe fn unregister(&self, hazards: &LocalHazards) {
+        let mut cur = self.registered.load(Ordering::Acquire);
+        while let Some(cur_ref) = cur.as_ref() {
+            if ptr::eq(&cur_ref.hazards, hazards) {
+                cur_ref.active.store(false, Ordering::Release);
+                return;
+            }
+            cur = cur_ref.next.load(Ordering::Acquire);
+        }
+        panic!("the hazard array is not registered");
+    }
+
+    /// Iterates over the hazard arrays of all threads, including the unregistered ones.
+    fn locals(&self) -> impl Iterator<Item = &LocalHazards> {
+        let by_tid = self
+            .heads
+            .iter()
+            .flat_map(|b| {
+                iter::successors(unsafe { b.load(Ordering::Acquire).as_ref() }, |n| unsafe {
+                    n.next.load(Ordering::Acquire).as_ref()
+                })
+            })
+            .map(|n| &n.hazards);
+        let registered = iter::successors(
+            unsafe { self.registered.load(Ordering::Acquire).as_ref() },
+            |r| unsafe { r.next.load(Ordering::Acquire).as_ref() },
+        )
+        .map(|r| &r.hazards);
+        by_tid.chain(registered)
+    }
+
+    /// Returns the number of hazard slots of all threads, occupied or not.
+    pub fn num_slots(&self) -> usize {
+        self.locals().map(LocalHazards::num_slots).sum()
+    }
+
     /// Returns all elements of `Hazards` for all threads. The tags are erased.
+    #[cfg(feature = "std")]
     pub fn all_hazards(&self) -> HashSet<usize> {
-        let mut set = HashSet::new();
+        self.sorted_hazards().into_iter().collect()
+    }
 
-        for b in &self.heads {
-            let mut cur = b.load(Ordering::Acquire);
-            while let Some(cur_ref) = unsafe { cur.as_ref() } {
-                set.extend(cur_ref.hazards.iter());
-                cur = cur_ref.next.load(Ordering::Acquire);
-            }
+    /// Returns all elements of `Hazards` for all threads, sorted and deduplicated, to be queried
+    /// with `binary_search`. The tags are erased.
+    ///
+    /// For the typical small number of hazards, it is faster to build and to query than the hash
+    /// set of `all_hazards`.
+    pub fn sorted_hazards(&self) -> Vec<usize> {
+        let mut hazards = Vec::new();
+        for local in self.locals() {
+            hazards.extend(local.iter());
         }
-        set
+        hazards.sort_unstable();
+        hazards.dedup();
+        hazards
+    }
+}
+
+impl Default for Hazards {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -313,6 +645,10 @@ impl Drop for Hazards {
                 cur = unsafe { Box::from_raw(cur).next.load(Ordering::Relaxed) };
             }
         }
+        let mut cur = self.registered.load(Ordering::Relaxed);
+        while !cur.is_null() {
+            cur = unsafe { Box::from_raw(cur).next.load(Ordering::Relaxed) };
+        }
     }
 }
 
@@ -325,6 +661,35 @@ mod tests {
     use std::sync::Arc;
     use std::thread;
 
+    // the arrays of the unregistered threads are reused
+    #[test]
+    fn register_reuse() {
+        let hazards = Hazards::new();
+        let a = hazards.register() as *const LocalHazards;
+        let b = hazards.register() as *const LocalHazards;
+        assert_ne!(a, b);
+
+        unsafe { hazards.unregister(&*a) };
+        assert_eq!(hazards.register() as *const LocalHazards, a);
+        let slots = hazards.num_slots();
+
+        // short-lived threads don't grow the arrays
+        let hazards = Arc::new(hazards);
+        for i in 0..32 {
+            let hazards = hazards.clone();
+            thread::spawn(move || {
+                let local = hazards.register();
+                let shield = unsafe { Shield::new(Owned::new(i).into_shared(), local).unwrap() };
+                unsafe { drop(shield.shared().into_owned()) };
+                drop(shield);
+                unsafe { hazards.unregister(local) };
+            })
+            .join()
+            .unwrap();
+        }
+        assert_eq!(hazards.num_slots(), slots + 8);
+    }
+
     // support at least 8 hazard slots
     #[test]
     fn local_hazards_8() {
@@ -348,6 +713,39 @@ mod tests {
             .for_each(|s| unsafe { drop(s.into_owned()) });
     }
 
+    // the hazard array grows beyond 8 slots, and the freed slots are reused
+    #[test]
+    fn local_hazards_grow() {
+        let shareds = (0..20)
+            .map(|i| Owned::new(i).into_shared())
+            .collect::<Vec<_>>();
+        let hazards = LocalHazards::new();
+        let mut shields = shareds
+            .iter()
+            .map(|&s| unsafe { Shield::new(s, &hazards).unwrap() })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hazards.iter().collect::<HashSet<_>>(),
+            shareds.iter().map(|s| s.into_usize()).collect()
+        );
+
+        drop(shields.drain(3..12));
+        assert_eq!(hazards.iter().count(), 11);
+        shields.extend(
+            shareds[3..12]
+                .iter()
+                .map(|&s| unsafe { Shield::new(s, &hazards).unwrap() }),
+        );
+        assert_eq!(hazards.iter().count(), 20);
+        assert!(hazards.iter().all(|h| h != 0));
+
+        drop(shields);
+        assert_eq!(hazards.iter().count(), 0);
+        shareds
+            .into_iter()
+            .for_each(|s| unsafe { drop(s.into_owned()) });
+    }
+
     #[test]
     fn all_hazards() {
         let global_hazards = Arc::new(Hazards::new());
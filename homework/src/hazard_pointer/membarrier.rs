@@ -0,0 +1,79 @@
+//! Asymmetric fences for the protect/collect protocol.
+//!
+//! The readers call `light()` between publishing a hazard and validating it, and `collect` calls
+//! `heavy()` between unlinking and scanning the hazards. By default, both are `fence(SeqCst)`.
+//!
+//! With the `membarrier` feature on Linux, `light()` is only a compiler fence, and `heavy()` issues
+//! the `membarrier` system call, which executes a full fence on every running thread of the
+//! process. A reader's compiler fence is then either before or after the fence run on its behalf,
+//! so the argument of the module documentation still holds, while the readers, which are much more
+//! frequent, no longer pay for a fence. If the kernel doesn't support the system call, both fall
+//! back to `fence(SeqCst)`. The feature requires `std`.
+
+#[cfg(all(feature = "membarrier", target_os = "linux", not(feature = "check-loom")))]
+mod imp {
+    use core::sync::atomic::{compiler_fence, fence, AtomicBool, Ordering};
+    use std::sync::Once;
+
+    const MEMBARRIER_CMD_QUERY: libc::c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: libc::c_int = 1 << 4;
+
+    static INIT: Once = Once::new();
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+
+    fn membarrier(cmd: libc::c_int) -> libc::c_long {
+        unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0) }
+    }
+
+    /// Returns whether the system call is used. The process is registered at the first call, so
+    /// `light()` and `heavy()` never disagree on it.
+    fn enabled() -> bool {
+        INIT.call_once(|| {
+            let supported = membarrier(MEMBARRIER_CMD_QUERY);
+            let enabled = supported >= 0
+                && supported & libc::c_long::from(MEMBARRIER_CMD_PRIVATE_EXPEDITED) != 0
+                && membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED) == 0;
+            ENABLED.store(enabled, Ordering::Relaxed);
+        });
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn light() {
+        if enabled() {
+            compiler_fence(Ordering::SeqCst);
+        } else {
+            fence(Ordering::SeqCst);
+        }
+    }
+
+    pub fn heavy() {
+        if enabled() {
+            let ret = membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED);
+            assert_eq!(ret, 0, "membarrier failed after registration");
+        }
+        fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(all(feature = "membarrier", target_os = "linux", not(feature = "check-loom"))))]
+mod imp {
+    #[cfg(not(feature = "check-loom"))]
+    use core::sync::atomic::{fence, Ordering};
+    #[cfg(feature = "check-loom")]
+    use loom::sync::atomic::{fence, Ordering};
+
+    pub fn light() {
+        fence(Ordering::SeqCst);
+    }
+
+    pub fn heavy() {
+        fence(Ordering::SeqCst);
+    }
+}
+
+/// The fence of the readers, after publishing a hazard.
+pub use imp::light;
+
+/// The fence of `collect`, before scanning the hazards.
+pub use imp::heavy;
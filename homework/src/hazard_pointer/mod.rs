@@ -60,29 +60,51 @@
 //! another SC fence. If we insert a SC fence between `T1-1` and `T1-2`, and another between `T2-1`
 //! and `T2-2`, then either `T1's fence → T2's fence` or `T2's fence → T1's fence` holds.
 //! Therefore, `T1-1 → T2-2` or `T2-1 → T1-2`.
+//!
+//! The readers are much more frequent than `collect`, so with the `membarrier` feature on Linux,
+//! the fence of `T1` is replaced with a compiler fence, and `T2` makes every thread execute a fence
+//! with the `membarrier` system call instead.
+//!
+//! # `no_std`
+//!
+//! Without the `std` feature, the module needs only `alloc`. There are no thread-locals then, so
+//! the free functions are not provided. Instead, each execution context (e.g. a CPU with
+//! interrupts disabled) gets its hazard array with `HAZARDS.get(id)` and owns a `Retirees`:
+//!
+//! A task that ends, e.g. a worker of a pool, should rather `HAZARDS.register()` its array and
+//! `unregister` it at the end, as the threads do with `std`.
+//!
+//! ```ignore
+//! let mut retirees = Retirees::new(&HAZARDS, &GLOBAL_RETIRED);
+//! let mut shield = unsafe { Shield::new(Shared::null(), HAZARDS.get(cpu_id())) }.unwrap();
+//! let shared = shield.protect(&atomic);
+//! // ... unlink `shared` from `atomic` ...
+//! retirees.retire(shared);
+//! ```
 
+#[cfg(feature = "std")]
 use core::cell::RefCell;
-use std::thread;
-
-#[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, Ordering};
-#[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, Ordering};
 
 #[cfg(feature = "check-loom")]
 use loom::thread_local;
-#[cfg(not(feature = "check-loom"))]
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
 use std::thread_local;
 
 mod align;
 mod atomic;
 mod hazard;
+#[cfg(feature = "std")]
+pub mod eras;
+mod membarrier;
+#[cfg(feature = "std")]
+pub mod queue;
+#[cfg(feature = "std")]
+pub mod reclaimer;
 mod retire;
 
 pub use atomic::{Atomic, Owned, Shared};
-use hazard::Hazards;
-pub use hazard::Shield;
-use retire::Retirees;
+pub use hazard::{Hazards, LocalHazards, Shield, ShieldArray, ShieldSet, Tid};
+pub use retire::{DropPolicy, Free, GlobalRetirees, Retired, Retirees};
 
 #[cfg(not(feature = "check-loom"))]
 /// Global set of all hazard pointers.
@@ -94,47 +116,170 @@ loom::lazy_static! {
     pub static ref HAZARDS: Hazards = Hazards::new();
 }
 
+#[cfg(not(feature = "check-loom"))]
+/// Global list of the retired pointers left by the exited threads.
+pub static GLOBAL_RETIRED: GlobalRetirees = GlobalRetirees::new();
+
+#[cfg(feature = "check-loom")]
+loom::lazy_static! {
+    /// Global list of the retired pointers left by the exited threads.
+    pub static ref GLOBAL_RETIRED: GlobalRetirees = GlobalRetirees::new();
+}
+
+/// The hazard array of a thread, registered in a `Hazards` and unregistered when the thread exits so
+/// that it is reused by the next threads.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Registration {
+    hazards: &'static Hazards,
+    local: &'static LocalHazards,
+}
+
+#[cfg(feature = "std")]
+impl Registration {
+    fn new(hazards: &'static Hazards) -> Self {
+        Self {
+            hazards,
+            local: hazards.register(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // The shields of a thread don't outlive it.
+        unsafe { self.hazards.unregister(self.local) };
+    }
+}
+
+#[cfg(feature = "std")]
 thread_local! {
-    /// Thread-local list of retired pointers. The first element of the pair is the machine
-    /// representation of the pointer and the second is the function pointer to `free::<T>`.
-    static RETIRED: RefCell<Retirees<'static>> = RefCell::new(Retirees::new(&HAZARDS));
+    /// Thread-local list of retired pointers.
+    static RETIRED: RefCell<Retirees<'static>> =
+        RefCell::new(Retirees::new(&HAZARDS, &GLOBAL_RETIRED));
+
+    /// The hazard array of the current thread.
+    static LOCAL: Registration = Registration::new(&HAZARDS);
 }
 
-/// Returns `None` if the current thread's hazard array is fully occupied. The returned shield must
-/// be validated before using.
+#[cfg(feature = "std")]
+fn local_hazards() -> &'static LocalHazards {
+    LOCAL.with(|l| l.local)
+}
+
+/// Returns a shield of the pointer, which must be validated before using. The current thread's
+/// hazard array grows on demand, so it always returns `Some`.
+#[cfg(feature = "std")]
 pub fn protect<T>(pointer: Shared<T>) -> Option<Shield<'static, T>> {
-    let ret = unsafe { Shield::new(pointer,HAZARDS.get(thread::current().id())) };
-    fence(Ordering::SeqCst);
+    let ret = unsafe { Shield::new(pointer,local_hazards()) };
+    membarrier::light();
     ret
 }
 
-/// Returns a validated shield. Returns `None` if the current thread's hazard array is fully
-/// occupied.
+/// Returns a shield of the possibly tagged pointer, which must be validated before using. The
+/// hazard is the pointer without tag, and `Shield::shared` returns it with the tag. `protect`
+/// checks in debug builds that the pointer is not tagged.
+#[cfg(feature = "std")]
+pub fn protect_tagged<T>(pointer: Shared<T>) -> Option<Shield<'static, T>> {
+    let ret = unsafe { Shield::new_tagged(pointer, local_hazards()) };
+    membarrier::light();
+    ret
+}
+
+/// Returns a validated shield. The current thread's hazard array grows on demand, so it always
+/// returns `Some`.
+#[cfg(feature = "std")]
 pub fn get_protected<T>(atomic: &Atomic<T>) -> Option<Shield<'static, T>> {
-    loop{
-        let pointer = atomic.load(Ordering::Acquire);
-        match unsafe{ Shield::new(pointer,HAZARDS.get(thread::current().id())) } {
-            Some(shield) => {
-                fence(Ordering::SeqCst);
-                if shield.validate(atomic.load(Ordering::Acquire)) {
-                    return Some(shield);
-                }else{
-                    drop(shield);
-                    continue;
-                }
-            },
-            None => return None,
-        }
-    }
+    let mut shield = unsafe { Shield::new(Shared::null(), local_hazards()) }?;
+    let _ = shield.protect(atomic);
+    Some(shield)
 }
 
-/// Retires a pointer.
-pub fn retire<T>(pointer: Shared<T>) {
+/// Returns a set of null shields, e.g. `let mut shields: ShieldSet<'_, Node> = shield_set();`.
+#[cfg(feature = "std")]
+pub fn shield_set<T, A: ShieldArray<'static, T>>() -> ShieldSet<'static, T, A> {
+    unsafe { ShieldSet::new(local_hazards()) }
+}
+
+/// Retires a pointer. It may be freed by another thread, e.g. if it is still protected when the
+/// current thread exits, so `T` must be `Send`:
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use cs492_concur_homework::hazard_pointer::{retire, Owned};
+///
+/// retire(Owned::new(Rc::new(0)).into_shared());
+/// ```
+#[cfg(feature = "std")]
+pub fn retire<T: Send>(pointer: Shared<T>) {
     RETIRED.with(|r| r.borrow_mut().retire(pointer));
 }
 
-/// Frees the pointers that are `retire`d by the current thread and not `protect`ed by any other
-/// threads.
+/// Retires a pointer as `retire`, without requiring `T: Send`.
+///
+/// # Safety
+///
+/// Dropping the object in another thread must be safe, e.g. because its `!Send` fields are already
+/// moved out.
+#[cfg(feature = "std")]
+pub unsafe fn retire_unchecked<T>(pointer: Shared<T>) {
+    RETIRED.with(|r| r.borrow_mut().retire_unchecked(pointer));
+}
+
+/// Retires a pointer to an object not allocated as a `Box<T>`, e.g. in an arena, or needing a custom
+/// teardown. It is freed by calling `free` with the pointer without tag, cast to `*mut ()`.
+#[cfg(feature = "std")]
+pub fn retire_with<T: Send>(pointer: Shared<T>, free: unsafe fn(*mut ())) {
+    RETIRED.with(|r| r.borrow_mut().retire_with(pointer, free));
+}
+
+/// Retires a pointer as `retire_with`, accounting `size` bytes for it towards the threshold of
+/// `collect`, e.g. for an object owning other allocations.
+#[cfg(feature = "std")]
+pub fn retire_with_size<T: Send>(pointer: Shared<T>, free: unsafe fn(*mut ()), size: usize) {
+    RETIRED.with(|r| r.borrow_mut().retire_with_size(pointer, free, size));
+}
+
+/// Retires a pointer to the first element of a slice of length `len` allocated as a `Box<[T]>`.
+#[cfg(feature = "std")]
+pub fn retire_slice<T: Send>(pointer: Shared<T>, len: usize) {
+    RETIRED.with(|r| r.borrow_mut().retire_slice(pointer, len));
+}
+
+/// Defers `f` to the next `collect` of the current thread, e.g. to update the state associated with
+/// the retired pointers. If the thread exits before, it is called when the thread exits.
+///
+/// `f` must not call the functions of this module that retire or collect: they would reenter the
+/// thread-local list of retired pointers, and panic.
+#[cfg(feature = "std")]
+pub fn defer<F: FnOnce() + Send + 'static>(f: F) {
+    RETIRED.with(|r| r.borrow_mut().defer(f));
+}
+
+/// Sets what happens to the pointers retired by the current thread and still protected when it
+/// exits. See `DropPolicy`.
+#[cfg(feature = "std")]
+pub fn set_drop_policy(policy: DropPolicy) {
+    RETIRED.with(|r| r.borrow_mut().set_drop_policy(policy));
+}
+
+/// Frees the pointers that are `retire`d by the current thread or left by the exited threads, and
+/// not `protect`ed by any other threads.
+#[cfg(feature = "std")]
 pub fn collect() {
     RETIRED.with(|r| r.borrow_mut().collect());
 }
+
+/// Collects regardless of the threshold, as `collect`, and returns the number of the pointers
+/// retired by the current thread and still protected.
+#[cfg(feature = "std")]
+pub fn flush() -> usize {
+    RETIRED.with(|r| r.borrow_mut().flush())
+}
+
+/// Returns the number of the retired pointers leaked by the exited threads with
+/// `DropPolicy::Leak`.
+pub fn num_leaked() -> usize {
+    GLOBAL_RETIRED.num_leaked()
+}
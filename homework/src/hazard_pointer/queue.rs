@@ -0,0 +1,159 @@
+//! Michael-Scott queue protected by hazard pointers.
+//!
+//! It is a reference use of this module: a node is read only through a validated shield, and is
+//! retired once it is unlinked. It is generic over the `Reclaimer`, so that it can be run with
+//! epochs and intervals as well for comparison.
+
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::Ordering;
+
+use super::reclaimer::{Hp, Reclaimer};
+use super::{Atomic, Shared};
+
+/// Node of `Queue`.
+#[derive(Debug)]
+pub struct Node<T> {
+    /// Uninitialized in the sentinel node. The data is moved out when the node becomes the
+    /// sentinel, so it is never dropped with the node.
+    data: MaybeUninit<T>,
+    next: Atomic<Node<T>>,
+}
+
+/// Michael-Scott lock-free queue.
+///
+/// Usable with any number of producers and consumers. The nodes are reclaimed by `R`, the hazard
+/// pointers by default.
+#[derive(Debug)]
+pub struct Queue<T: 'static, R: Reclaimer<Node<T>> = Hp> {
+    /// The sentinel node, followed by the elements.
+    head: Atomic<Node<T>>,
+    /// The last node, or a node before it that is not yet updated.
+    tail: Atomic<Node<T>>,
+    _marker: PhantomData<R>,
+}
+
+unsafe impl<T: Send, R: Reclaimer<Node<T>>> Send for Queue<T, R> {}
+unsafe impl<T: Send, R: Reclaimer<Node<T>>> Sync for Queue<T, R> {}
+
+impl<T: 'static> Queue<T> {
+    /// Creates a new, empty queue reclaimed by the hazard pointers.
+    pub fn new() -> Self {
+        Self::with_reclaimer()
+    }
+}
+
+impl<T: 'static, R: Reclaimer<Node<T>>> Queue<T, R> {
+    /// Creates a new, empty queue reclaimed by `R`, e.g. `Queue::<T, Epoch>::with_reclaimer()`.
+    pub fn with_reclaimer() -> Self {
+        let sentinel = R::alloc(Node {
+            data: MaybeUninit::uninit(),
+            next: Atomic::null(),
+        });
+        let queue = Self {
+            head: Atomic::null(),
+            tail: Atomic::null(),
+            _marker: PhantomData,
+        };
+        queue.head.store(sentinel, Ordering::Relaxed);
+        queue.tail.store(sentinel, Ordering::Relaxed);
+        queue
+    }
+
+    /// Adds a value at the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = R::alloc(Node {
+            data: MaybeUninit::new(t),
+            next: Atomic::null(),
+        });
+
+        let mut protector = R::protector();
+        let mut tail = R::protect(&mut protector, &self.tail);
+        loop {
+            let tail_ref = unsafe { tail.deref() };
+            let next = tail_ref.next.load(Ordering::Acquire);
+
+            if !next.is_null() {
+                // Helps the push that linked `next` to update the tail.
+                let _ = self
+                    .tail
+                    .compare_and_set(tail, next, Ordering::Release, Ordering::Relaxed);
+            } else if tail_ref
+                .next
+                .compare_and_set(Shared::null(), new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let _ = self
+                    .tail
+                    .compare_and_set(tail, new, Ordering::Release, Ordering::Relaxed);
+                return;
+            }
+            tail = R::protect(&mut protector, &self.tail);
+        }
+    }
+
+    /// Removes the value at the front of the queue and returns it. Returns `None` if the queue is
+    /// empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut head_protector = R::protector();
+        let mut next_protector = R::protector();
+        loop {
+            let head = R::protect(&mut head_protector, &self.head);
+            let next = R::protect(&mut next_protector, &unsafe { head.deref() }.next);
+            // `next` can't have been retired if `head` is still the sentinel.
+            if self.head.load(Ordering::Acquire).as_raw() != head.as_raw() {
+                continue;
+            }
+            if next.is_null() {
+                return None;
+            }
+
+            // Moves the tail out of the node to be retired.
+            let tail = self.tail.load(Ordering::Acquire);
+            if tail.into_usize() == head.into_usize() {
+                let _ = self
+                    .tail
+                    .compare_and_set(tail, next, Ordering::Release, Ordering::Relaxed);
+            }
+
+            if self
+                .head
+                .compare_and_set(head, next, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                // `next` is the new sentinel, so its data is read only here.
+                let data = unsafe { ptr::read(next.deref().data.as_ptr()) };
+                unsafe { R::retire(head) };
+                return Some(data);
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        let mut protector = R::protector();
+        let head = R::protect(&mut protector, &self.head);
+        unsafe { head.deref() }
+            .next
+            .load(Ordering::Acquire)
+            .is_null()
+    }
+}
+
+impl<T: 'static, R: Reclaimer<Node<T>>> Default for Queue<T, R> {
+    fn default() -> Self {
+        Self::with_reclaimer()
+    }
+}
+
+impl<T: 'static, R: Reclaimer<Node<T>>> Drop for Queue<T, R> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+        unsafe { R::dealloc(self.head.load(Ordering::Relaxed)) };
+    }
+}
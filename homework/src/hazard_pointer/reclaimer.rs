@@ -0,0 +1,139 @@
+//! Reclamation schemes that a data structure can be generic over, e.g. to compare the hazard
+//! pointers of this module with epochs and intervals head-to-head.
+//!
+//! All schemes work on the pointers of this module. A protector of the hazard pointers is a
+//! `Shield`, which protects the last pointer it loads. A protector of the epochs is a pinned
+//! `crossbeam_epoch::Guard`, which protects all the pointers loaded while it is alive. A protector
+//! of the intervals is an `ibr::Guard`, which protects the objects allocated before the last
+//! pointer it loads, and needs the objects to be allocated by `Reclaimer::alloc`.
+
+use crossbeam_epoch::{self as epoch, Guard};
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::Ordering;
+
+use super::{collect, protect, retire_unchecked, Atomic, Owned, Shared, Shield};
+use crate::ibr;
+
+/// Memory reclamation scheme for the objects of type `T`.
+pub trait Reclaimer<T> {
+    /// Protection of the pointers returned by `protect`.
+    type Protector;
+
+    /// Allocates an object to be protected by the scheme.
+    fn alloc(data: T) -> Shared<T> {
+        Owned::new(data).into_shared()
+    }
+
+    /// Frees an object allocated by `alloc` that is not shared, e.g. when the data structure is
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must not be accessed by the other threads, nor freed again.
+    unsafe fn dealloc(pointer: Shared<T>) {
+        drop(pointer.into_owned());
+    }
+
+    /// Returns a protector that protects nothing yet.
+    fn protector() -> Self::Protector;
+
+    /// Loads a pointer from `atomic` and protects it with `protector`, so that it can be
+    /// dereferenced while `protector` is alive and not used to protect another pointer.
+    fn protect(protector: &mut Self::Protector, atomic: &Atomic<T>) -> Shared<T>;
+
+    /// Retires a pointer, which is freed once no protector protects it.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be unlinked from the data structure, so that no more protectors can load
+    /// it, and must be retired only once. The object may be dropped in another thread, so that must
+    /// be safe, e.g. because `T: Send`.
+    unsafe fn retire(pointer: Shared<T>);
+
+    /// Frees the retired pointers that are not protected, as far as the scheme allows.
+    fn collect();
+}
+
+/// The hazard pointers of this module.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Hp;
+
+impl<T: 'static> Reclaimer<T> for Hp {
+    type Protector = Shield<'static, T>;
+
+    fn protector() -> Self::Protector {
+        protect(Shared::null()).unwrap()
+    }
+
+    fn protect(protector: &mut Self::Protector, atomic: &Atomic<T>) -> Shared<T> {
+        protector.protect(atomic)
+    }
+
+    unsafe fn retire(pointer: Shared<T>) {
+        retire_unchecked(pointer);
+    }
+
+    fn collect() {
+        collect();
+    }
+}
+
+/// The epoch-based reclamation of `crossbeam_epoch`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Epoch;
+
+impl<T> Reclaimer<T> for Epoch {
+    type Protector = Guard;
+
+    fn protector() -> Self::Protector {
+        epoch::pin()
+    }
+
+    fn protect(_protector: &mut Self::Protector, atomic: &Atomic<T>) -> Shared<T> {
+        // The pointer is not freed before the epoch is unpinned, so it needs no validation.
+        atomic.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire(pointer: Shared<T>) {
+        epoch::pin().defer_unchecked(move || drop(pointer.into_owned()));
+    }
+
+    fn collect() {
+        epoch::pin().flush();
+    }
+}
+
+/// The interval-based reclamation of `ibr`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Ibr;
+
+impl<T> Reclaimer<T> for Ibr {
+    type Protector = ibr::Guard;
+
+    fn alloc(data: T) -> Shared<T> {
+        ibr::alloc_data(data)
+    }
+
+    unsafe fn dealloc(pointer: Shared<T>) {
+        ibr::dealloc_data(pointer);
+    }
+
+    fn protector() -> Self::Protector {
+        ibr::pin()
+    }
+
+    fn protect(protector: &mut Self::Protector, atomic: &Atomic<T>) -> Shared<T> {
+        protector.protect_data(atomic)
+    }
+
+    unsafe fn retire(pointer: Shared<T>) {
+        ibr::retire_data(pointer);
+    }
+
+    fn collect() {
+        ibr::collect();
+    }
+}
@@ -1,76 +1,459 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+use core::ptr;
+use core::slice;
+
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, Ordering};
+use loom::thread::yield_now;
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+use std::thread::yield_now;
 
 use super::align;
 use super::atomic::Shared;
 use super::hazard::Hazards;
+use super::membarrier;
+
+/// Retired pointer. The elements are the pointer without tag, how to free it, and the size of the
+/// object in bytes.
+///
+/// The pointer is kept as a raw pointer with its provenance, rather than as an integer.
+pub type Retired = (*mut (), Free, usize);
+
+/// How to free a retired pointer.
+#[derive(Debug, Clone, Copy)]
+pub enum Free {
+    /// Calls the function with the pointer, e.g. `free::<T>` where `T` is the type of the object.
+    Object(unsafe fn(*mut ())),
+    /// Calls the function with the pointer and the length of the slice it points to.
+    Slice(unsafe fn(*mut (), usize), usize),
+}
+
+impl Free {
+    /// Frees the pointer.
+    pub(crate) unsafe fn call(self, data: *mut ()) {
+        match self {
+            Free::Object(free) => free(data),
+            Free::Slice(free, len) => free(data, len),
+        }
+    }
+}
+
+/// What `Retirees` does with the pointers still protected when it is dropped, e.g. when its thread
+/// exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Moves them to the global list, to be freed by the `collect` of the other threads. This is
+    /// the default.
+    Global,
+    /// Collects until they are all freed, i.e. waits for the other threads to release them.
+    Wait,
+    /// Leaks them, counting them in `GlobalRetirees::num_leaked`. The closures `defer`red and not
+    /// yet called are leaked as well.
+    Leak,
+}
+
+impl Default for DropPolicy {
+    fn default() -> Self {
+        DropPolicy::Global
+    }
+}
 
 /// Thread-local list of retired pointers.
+#[derive(Debug)]
 pub struct Retirees<'s> {
     hazards: &'s Hazards,
-    /// The first element of the pair is the machine representation of a pointer without tag and
-    /// the second is the function pointer to `free::<T>` where `T` is the type of the object.
-    inner: Vec<(usize, unsafe fn(usize))>,
+    global: &'s GlobalRetirees,
+    inner: Vec<Retired>,
+    /// Call `collect` if the length of `inner` becomes larger than this value.
+    threshold: usize,
+    /// The total size of the objects in `inner`.
+    bytes: usize,
+    /// Call `collect` if `bytes` becomes larger than this value.
+    max_bytes: usize,
+    drop_policy: DropPolicy,
 }
 
 impl<'s> Retirees<'s> {
-    /// The max length of retired pointer list. Call `collect` if the length becomes larger than
-    /// this value.
+    /// The min value of `threshold`. After `collect`, it is set to twice the number of hazard slots
+    /// of all threads if larger, as in Michael's paper, so that each `collect` frees at least as
+    /// many pointers as there are slots.
     const THRESHOLD: usize = 64;
 
-    pub fn new(hazards: &'s Hazards) -> Self {
+    /// The min value of `max_bytes`. After `collect`, it is set to twice the size of the objects
+    /// still protected if larger.
+    const MAX_BYTES: usize = 1 << 20;
+
+    /// Creates an empty list, which checks `hazards` before freeing a pointer, and takes the
+    /// pointers left in `global` when it collects.
+    pub fn new(hazards: &'s Hazards, global: &'s GlobalRetirees) -> Self {
         Self {
             hazards,
+            global,
             inner: Vec::new(),
+            threshold: Self::THRESHOLD,
+            bytes: 0,
+            max_bytes: Self::MAX_BYTES,
+            drop_policy: DropPolicy::default(),
         }
     }
 
-    /// Retire a pointer.
-    pub fn retire<T>(&mut self, pointer: Shared<T>) {
-        unsafe fn free<T>(data: usize) {
-            debug_assert_eq!(align::decompose_tag::<T>(data).1, 0);
+    /// Sets what happens to the pointers still protected when the list is dropped.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+    }
+
+    /// Retire a pointer. It may be freed by another thread, e.g. if it is still protected when the
+    /// list is dropped with `DropPolicy::Global`, so `T` must be `Send`.
+    pub fn retire<T: Send>(&mut self, pointer: Shared<T>) {
+        unsafe { self.retire_unchecked(pointer) };
+    }
+
+    /// Retire a pointer as `retire`, without requiring `T: Send`.
+    ///
+    /// # Safety
+    ///
+    /// Dropping the object in another thread must be safe, e.g. because its `!Send` fields are
+    /// already moved out.
+    pub unsafe fn retire_unchecked<T>(&mut self, pointer: Shared<T>) {
+        unsafe fn free<T>(data: *mut ()) {
+            debug_assert_eq!(align::decompose_tag::<T>(data as usize).1, 0);
             drop(Box::from_raw(data as *mut T))
         }
-        self.inner.push((pointer.with_tag(0).into_usize(),free::<T>));
+        self.push(
+            pointer.as_raw() as *mut (),
+            Free::Object(free::<T>),
+            mem::size_of::<T>(),
+        );
+    }
+
+    /// Retire a pointer, which is freed by calling `free` with the pointer without tag.
+    pub fn retire_with<T: Send>(&mut self, pointer: Shared<T>, free: unsafe fn(*mut ())) {
+        self.retire_with_size(pointer, free, mem::size_of::<T>());
+    }
+
+    /// Retire a pointer as `retire_with`, accounting `size` bytes for it instead of the size of
+    /// `T`, e.g. for an object owning other allocations.
+    pub fn retire_with_size<T: Send>(
+        &mut self,
+        pointer: Shared<T>,
+        free: unsafe fn(*mut ()),
+        size: usize,
+    ) {
+        self.push(pointer.as_raw() as *mut (), Free::Object(free), size);
+    }
+
+    /// Retire a pointer to the first element of a boxed slice of length `len`.
+    pub fn retire_slice<T: Send>(&mut self, pointer: Shared<T>, len: usize) {
+        unsafe fn free<T>(data: *mut (), len: usize) {
+            debug_assert_eq!(align::decompose_tag::<T>(data as usize).1, 0);
+            let slice = slice::from_raw_parts_mut(data as *mut T, len);
+            drop(Box::from_raw(slice as *mut [T]))
+        }
+        let size = len * mem::size_of::<T>();
+        self.push(pointer.as_raw() as *mut (), Free::Slice(free::<T>, len), size);
+    }
+
+    /// Defers `f` to the next `collect`. The closure is retired as a pointer that is never
+    /// protected, so it is called right after the fence of `collect`, like the pointers retired
+    /// before it and not protected anymore are freed.
+    pub fn defer<F: FnOnce() + Send + 'static>(&mut self, f: F) {
+        unsafe fn call<F: FnOnce()>(data: *mut ()) {
+            let f = Box::from_raw(data as *mut F);
+            f()
+        }
+        let data = Box::into_raw(Box::new(f)) as *mut ();
+        self.push(data, Free::Object(call::<F>), mem::size_of::<F>());
+    }
+
+    fn push(&mut self, data: *mut (), free: Free, size: usize) {
+        self.inner.push((data, free, size));
+        self.bytes += size;
 
-        if self.inner.len() > Retirees::THRESHOLD {
+        if self.inner.len() > self.threshold || self.bytes > self.max_bytes {
             self.collect();
         }
     }
 
-    /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
-    /// threads.
+    /// Free the pointers that are `retire`d by the current thread or left by the exited threads,
+    /// and not `protect`ed by any other threads.
     pub fn collect(&mut self) {
-        fence(Ordering::SeqCst);
-        //stage 1 : hazard pointer hash set implemented by Hazards struct
-        let hhs = self.hazards.all_hazards();
+        self.inner.extend(self.global.take());
+        membarrier::heavy();
+        //stage 1 : sorted hazard pointers of all threads
+        let hhs = self.hazards.sorted_hazards();
 
         //stage 2
-        let mut new_vec = Vec::<(usize,unsafe fn(usize))>::new();
+        let mut new_vec = Vec::<Retired>::new();
         while let Some(data) = self.inner.pop() {
-            if hhs.contains(&data.0) {
+            if hhs.binary_search(&(data.0 as usize)).is_ok() {
                 new_vec.push(data);
             }else{
-                unsafe { data.1(data.0); }
+                unsafe { data.1.call(data.0); }
             }
             fence(Ordering::Acquire);
         }
         self.inner = new_vec;
+
+        self.threshold = cmp::max(Self::THRESHOLD, 2 * self.hazards.num_slots());
+        self.bytes = self.inner.iter().map(|r| r.2).sum();
+        self.max_bytes = cmp::max(Self::MAX_BYTES, 2 * self.bytes);
+    }
+
+    /// Collects regardless of the threshold, e.g. before a shutdown path that shouldn't be left
+    /// with garbage, and returns the number of the retired pointers still protected.
+    pub fn flush(&mut self) -> usize {
+        self.collect();
+        self.inner.len()
     }
 }
 
-// TODO(@tomtomjhj): this triggers loom internal bug
-#[cfg(not(feature = "check-loom"))]
 impl Drop for Retirees<'_> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
-        while !self.inner.is_empty() {
-            self.collect();
+        self.collect();
+        if self.inner.is_empty() {
+            return;
+        }
+
+        match self.drop_policy {
+            // The pointers still protected are reclaimed by the other threads, instead of waiting
+            // for them to be unprotected. Since it doesn't spin, loom can check it as well.
+            DropPolicy::Global => self.global.push(mem::take(&mut self.inner)),
+            DropPolicy::Wait => {
+                while !self.inner.is_empty() {
+                    #[cfg(feature = "std")]
+                    yield_now();
+                    #[cfg(not(feature = "std"))]
+                    core::sync::atomic::spin_loop_hint();
+                    self.collect();
+                }
+            }
+            DropPolicy::Leak => {
+                let _ = self
+                    .global
+                    .leaked
+                    .fetch_add(self.inner.len(), Ordering::Relaxed);
+                self.inner.clear();
+            }
         }
     }
 }
+
+/// Global list of the retired pointers left by the exited threads.
+///
+/// It is a lock-free stack of batches of retired pointers. The batches are pushed one by one and
+/// taken all at once, so there is no ABA problem.
+#[derive(Debug)]
+pub struct GlobalRetirees {
+    head: AtomicPtr<Batch>,
+    /// The number of the pointers leaked by the lists dropped with `DropPolicy::Leak`.
+    leaked: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Batch {
+    inner: Vec<Retired>,
+    next: *mut Batch,
+}
+
+impl GlobalRetirees {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            leaked: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            leaked: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes a batch of retired pointers.
+    pub fn push(&self, inner: Vec<Retired>) {
+        let batch = Box::into_raw(Box::new(Batch {
+            inner,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*batch).next = head };
+            match self
+                .head
+                .compare_exchange(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes all retired pointers in the list.
+    pub fn take(&self) -> Vec<Retired> {
+        let mut inner = Vec::new();
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return inner;
+        }
+        let mut batch = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        while !batch.is_null() {
+            let batch_ref = unsafe { Box::from_raw(batch) };
+            batch = batch_ref.next;
+            inner.extend(batch_ref.inner);
+        }
+        inner
+    }
+
+    /// Returns the number of the retired pointers leaked so far by the lists dropped with
+    /// `DropPolicy::Leak`.
+    pub fn num_leaked(&self) -> usize {
+        self.leaked.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for GlobalRetirees {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GlobalRetirees {
+    fn drop(&mut self) {
+        for (data, free, _) in self.take() {
+            unsafe { free.call(data) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::atomic::{Owned, Shared};
+    use super::super::hazard::{Hazards, Shield};
+    use super::{DropPolicy, GlobalRetirees, Retirees};
+    use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use crossbeam_utils::thread::scope;
+    use std::thread;
+    use std::time::Duration;
+
+    // the threshold grows with the number of hazard slots
+    #[test]
+    fn threshold() {
+        let hazards = Hazards::new();
+        let global = GlobalRetirees::new();
+        let mut retirees = Retirees::new(&hazards, &global);
+        let local = hazards.get(thread::current().id());
+        let shareds = (0..40)
+            .map(|i| Owned::new(i).into_shared())
+            .collect::<Vec<_>>();
+        let shields = shareds
+            .iter()
+            .map(|&s| unsafe { Shield::new(s, local).unwrap() })
+            .collect::<Vec<_>>();
+        for &s in &shareds {
+            retirees.retire(s);
+        }
+        retirees.collect();
+        assert_eq!(retirees.inner.len(), 40);
+        assert_eq!(retirees.threshold, 80);
+
+        drop(shields);
+        retirees.collect();
+        assert!(retirees.inner.is_empty());
+    }
+
+    // `flush` collects below the threshold, and returns the number of pointers still protected
+    #[test]
+    fn flush() {
+        let hazards = Hazards::new();
+        let global = GlobalRetirees::new();
+        let mut retirees = Retirees::new(&hazards, &global);
+        let local = hazards.get(thread::current().id());
+        let protected = Owned::new(0).into_shared();
+        let shield = unsafe { Shield::new(protected, local).unwrap() };
+        retirees.retire(protected);
+        retirees.retire(Owned::new(1).into_shared());
+        assert_eq!(retirees.inner.len(), 2);
+        assert_eq!(retirees.flush(), 1);
+
+        drop(shield);
+        assert_eq!(retirees.flush(), 0);
+    }
+
+    // large objects are collected before the threshold
+    #[test]
+    fn max_bytes() {
+        let hazards = Hazards::new();
+        let global = GlobalRetirees::new();
+        let mut retirees = Retirees::new(&hazards, &global);
+        for _ in 0..2 {
+            let len = Retirees::MAX_BYTES / 2 + 1;
+            let slice = vec![0u8; len].into_boxed_slice();
+            let pointer = Box::into_raw(slice) as *mut u8;
+            retirees.retire_slice(Shared::from(pointer as *const u8), len);
+        }
+        assert!(retirees.inner.is_empty());
+        assert_eq!(retirees.bytes, 0);
+
+        // as are small objects owning large allocations
+        unsafe fn free(data: *mut ()) {
+            drop(Box::from_raw(data as *mut Vec<u8>))
+        }
+        let vec = Owned::new(vec![0u8; Retirees::MAX_BYTES + 1]).into_shared();
+        retirees.retire_with_size(vec, free, Retirees::MAX_BYTES + 1);
+        assert!(retirees.inner.is_empty());
+    }
+
+    // the pointers still protected are never freed with `Leak`, and freed before `drop` returns
+    // with `Wait`
+    #[test]
+    fn drop_policy() {
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn free(data: *mut ()) {
+            drop(Box::from_raw(data as *mut usize));
+            FREED.fetch_add(1, Relaxed);
+        }
+
+        let hazards = Hazards::new();
+        let global = GlobalRetirees::new();
+        let local = hazards.get(thread::current().id());
+
+        let shared = Owned::new(1usize).into_shared();
+        let shield = unsafe { Shield::new(shared, local).unwrap() };
+        let mut retirees = Retirees::new(&hazards, &global);
+        retirees.set_drop_policy(DropPolicy::Leak);
+        retirees.retire_with(shared, free);
+        drop(retirees);
+        assert!(global.take().is_empty());
+        assert_eq!(global.num_leaked(), 1);
+        drop(shield);
+        unsafe { drop(shared.into_owned()) };
+        assert_eq!(FREED.load(Relaxed), 0);
+
+        let shared = Owned::new(2usize).into_shared();
+        let shield = unsafe { Shield::new(shared, local).unwrap() };
+        let data = shared.into_usize();
+        scope(|s| {
+            s.spawn(|_| {
+                let mut retirees = Retirees::new(&hazards, &global);
+                retirees.set_drop_policy(DropPolicy::Wait);
+                retirees.retire_with(Shared::<usize>::from_usize(data), free);
+            });
+            thread::sleep(Duration::from_millis(10));
+            assert_eq!(FREED.load(Relaxed), 0);
+            drop(shield);
+        })
+        .unwrap();
+        assert_eq!(FREED.load(Relaxed), 1);
+        assert!(global.take().is_empty());
+    }
+}
@@ -1,14 +1,40 @@
 //! Thead-safe key/value cache.
 
-use std::collections::hash_map::{HashMap};
 use std::hash::Hash;
-use std::sync::{Arc, Mutex, RwLock};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::hash_table::StripedHashMap;
 
 /// Cache that remembers the result for each key.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Cache<K, V> {
-    // todo! Build your own cache type.
-    inner: RwLock<HashMap<K,Arc<Mutex<Option<V>>>>>,
+    /// The value of each key, locked until it is computed.
+    inner: StripedHashMap<K, Arc<Mutex<Option<V>>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Cache statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of the keys in the cache.
+    pub entries: usize,
+    /// Number of the lookups that found the key.
+    pub hits: usize,
+    /// Number of the lookups that inserted the key.
+    pub misses: usize,
+}
+
+impl<K, V> Default for Cache<K, V> {
+    fn default() -> Self {
+        Cache {
+            inner: StripedHashMap::new(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
 }
 
 impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
@@ -22,23 +48,67 @@ impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
     /// On the other hand, since `f` may consume a lot of resource (= money), it's desirable not to
     /// duplicate the work. That is, `f` should be run only once for each key. Specifically, even
     /// for the concurrent invocations of `get_or_insert_with(key, f)`, `f` is called only once.
+    ///
+    /// If `f` panics, the key is removed and the panic is propagated. The invocations waiting for
+    /// the value then try again, so one of them calls its own `f`.
     pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> V {
-        let mut hash = self.inner.write().unwrap();
-        match hash.get(&key) {
-            Some(value) => {
-                let ret = value.lock().unwrap();
-                ret.as_ref().unwrap().clone()
+        loop {
+            // The value is locked before it is inserted, so that the others wait until it is
+            // computed.
+            let working = Arc::new(Mutex::new(None));
+            let mut lock = working.lock().unwrap();
+            let value = self
+                .inner
+                .get_or_insert_with(key.clone(), || Arc::clone(&working));
+            if !Arc::ptr_eq(&value, &working) {
+                drop(lock);
+                let ret = value.lock().unwrap_or_else(|e| e.into_inner());
+                // `None` if the computation panicked.
+                if let Some(ret) = ret.as_ref() {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return ret.clone();
+                }
+                continue;
             }
-            None => {
-                let working = Arc::new(Mutex::new(None));
-                hash.insert(key.clone(), Arc::clone(&working));
-                let mut lock = working.lock().unwrap();
-                drop(hash);
-
-                let value = f(key.clone());
-                *lock = Some(value.clone());
-                value
-            },
+
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            let value = match panic::catch_unwind(AssertUnwindSafe(|| f(key.clone()))) {
+                Ok(value) => value,
+                Err(payload) => {
+                    // Removes the entry unless it is already replaced after a `remove`.
+                    let _ = self
+                        .inner
+                        .remove_if(&key, |entry| Arc::ptr_eq(entry, &working));
+                    drop(lock);
+                    panic::resume_unwind(payload);
+                }
+            };
+            *lock = Some(value.clone());
+            return value;
+        }
+    }
+
+    /// Removes the key so that the next `get_or_insert_with` computes the value again. The
+    /// invocations already waiting for the value are not affected.
+    pub fn remove(&self, key: &K) {
+        let _ = self.inner.remove(key);
+    }
+
+    /// Removes the keys whose values don't satisfy `f`. The keys whose values are being computed
+    /// are kept.
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&self, mut f: F) {
+        self.inner.retain(|key, value| match value.try_lock() {
+            Ok(value) => value.as_ref().map_or(true, |value| f(key, value)),
+            Err(_) => true,
+        });
+    }
+
+    /// Returns the statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.inner.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
         }
     }
 }
@@ -48,8 +118,10 @@ mod test {
     use super::Cache;
     use crossbeam_channel::bounded;
     use crossbeam_utils::thread::scope;
+    use std::panic::{self, AssertUnwindSafe};
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Barrier;
+    use std::thread;
     use std::time::Duration;
 
     const NUM_THREADS: usize = 8;
@@ -66,6 +138,66 @@ mod test {
         assert_eq!(cache.get_or_insert_with(3, |_| panic!()), 3);
     }
 
+    #[test]
+    fn cache_stats() {
+        let cache = Cache::default();
+        cache.get_or_insert_with(1, |_| 1);
+        cache.get_or_insert_with(2, |_| 2);
+        cache.get_or_insert_with(1, |_| panic!());
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 1, 2));
+    }
+
+    #[test]
+    fn cache_remove() {
+        let cache = Cache::default();
+        cache.get_or_insert_with(1, |_| 1);
+        cache.remove(&1);
+        assert_eq!(cache.get_or_insert_with(1, |_| 2), 2);
+    }
+
+    #[test]
+    fn cache_retain() {
+        let cache = Cache::default();
+        for key in 0..4 {
+            cache.get_or_insert_with(key, |k| k);
+        }
+        cache.retain(|_, value| value % 2 == 0);
+        assert_eq!(cache.stats().entries, 2);
+        assert_eq!(cache.get_or_insert_with(0, |_| panic!()), 0);
+        assert_eq!(cache.get_or_insert_with(1, |_| 5), 5);
+    }
+
+    #[test]
+    fn cache_panic() {
+        let cache = Cache::default();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            cache.get_or_insert_with(1, |_| panic!("oops"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.get_or_insert_with(1, |_| 1), 1);
+
+        // The waiters compute the value again.
+        let (started_sender, started_receiver) = bounded(0);
+        scope(|s| {
+            let cache = &cache;
+            s.spawn(move |_| {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    cache.get_or_insert_with(2, |_| {
+                        started_sender.send(()).unwrap();
+                        thread::sleep(Duration::from_millis(100));
+                        panic!("oops");
+                    });
+                }));
+                assert!(result.is_err());
+            });
+            started_receiver.recv().unwrap();
+            assert_eq!(cache.get_or_insert_with(2, |_| 2), 2);
+        })
+        .unwrap();
+    }
+
     #[test]
     fn cache_no_duplicate_concurrent() {
         for _ in 0..8 {
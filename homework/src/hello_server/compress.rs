@@ -0,0 +1,212 @@
+//! Response compression negotiated via `Accept-Encoding`.
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::prelude::*;
+
+use super::http::{Request, Response};
+
+/// Content coding of a compressed response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Picks the encoding from the `Accept-Encoding` header, preferring gzip.
+    fn negotiate(accept: &str) -> Option<Self> {
+        // The q-value of the coding. An explicit one, e.g. `gzip;q=0`, overrides that of `*`.
+        let quality = |name: &str| {
+            let mut wildcard = None;
+            for coding in accept.split(',') {
+                let mut params = coding.split(';').map(str::trim);
+                let coding = params.next().unwrap_or("");
+                let q = params
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                if coding.eq_ignore_ascii_case(name) {
+                    return q;
+                }
+                if coding == "*" {
+                    wildcard = Some(q);
+                }
+            }
+            wildcard.unwrap_or(0.0)
+        };
+        [Encoding::Gzip, Encoding::Deflate]
+            .iter()
+            .copied()
+            .find(|encoding| quality(encoding.name()) > 0.0)
+    }
+
+    fn encode(self, body: &[u8]) -> Vec<u8> {
+        let level = flate2::Compression::default();
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(body).unwrap();
+                encoder.finish().unwrap()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(body).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+}
+
+/// Policy of response compression.
+///
+/// A response is compressed with gzip or deflate if the client accepts it, the body is at least
+/// `min_size` bytes, and its `Content-Type` is in the allowlist. The compression runs in the
+/// connection's job, so the thread pool absorbs the CPU cost.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    min_size: usize,
+    content_types: Vec<String>,
+}
+
+impl Default for Compression {
+    /// Compresses text, JSON, and JavaScript bodies of at least 1 KiB.
+    fn default() -> Self {
+        Compression {
+            min_size: 1024,
+            content_types: vec![
+                "text/html".to_string(),
+                "text/plain".to_string(),
+                "text/css".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+            ],
+        }
+    }
+}
+
+impl Compression {
+    /// Sets the minimum size of the bodies to compress.
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Sets the allowlist of the content types to compress, e.g. `text/html`. Parameters such as
+    /// `charset` are ignored when matching.
+    pub fn content_types(mut self, content_types: &[&str]) -> Self {
+        self.content_types = content_types.iter().map(|ty| ty.to_string()).collect();
+        self
+    }
+
+    /// Compresses the response to the request if the policy allows it.
+    pub(crate) fn apply(&self, request: &Request, response: Response) -> Response {
+        if response.body_len() < self.min_size || response.get_header("Content-Encoding").is_some()
+        {
+            return response;
+        }
+        let content_type = match response.get_header("Content-Type") {
+            Some(content_type) => content_type.split(';').next().unwrap_or("").trim(),
+            None => return response,
+        };
+        if !self
+            .content_types
+            .iter()
+            .any(|ty| ty.eq_ignore_ascii_case(content_type))
+        {
+            return response;
+        }
+        let encoding = match request
+            .header("Accept-Encoding")
+            .and_then(Encoding::negotiate)
+        {
+            Some(encoding) => encoding,
+            None => return response,
+        };
+
+        let body = encoding.encode(response.body_bytes());
+        if body.len() >= response.body_len() {
+            return response;
+        }
+        response
+            .header("Content-Encoding", encoding.name())
+            .header("Vary", "Accept-Encoding")
+            .body(body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compression, Encoding};
+    use crate::hello_server::{Request, Response};
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::io::prelude::*;
+
+    fn request(accept: &str) -> Request {
+        let head = format!("GET / HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n", accept);
+        Request::parse(head.as_bytes()).unwrap()
+    }
+
+    fn response(content_type: &str, body: &str) -> Response {
+        Response::new(200)
+            .header("Content-Type", content_type)
+            .body(body)
+    }
+
+    #[test]
+    fn compression_negotiate() {
+        assert_eq!(Encoding::negotiate("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("deflate"), Some(Encoding::Deflate));
+        assert_eq!(
+            Encoding::negotiate("gzip;q=0, deflate"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(Encoding::negotiate("*"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("gzip;q=0, *"), Some(Encoding::Deflate));
+        assert_eq!(
+            Encoding::negotiate("*;q=0, deflate"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(Encoding::negotiate("gzip;q=0, deflate;q=0, *"), None);
+        assert_eq!(Encoding::negotiate("br, identity"), None);
+    }
+
+    #[test]
+    fn compression_apply() {
+        let compression = Compression::default();
+        let body = "hello ".repeat(1000);
+
+        let gzip = compression.apply(
+            &request("gzip"),
+            response("text/html; charset=utf-8", &body),
+        );
+        assert_eq!(gzip.get_header("Content-Encoding"), Some("gzip"));
+        let mut decoded = String::new();
+        GzDecoder::new(gzip.body_bytes())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        let deflate = compression.apply(&request("deflate"), response("text/plain", &body));
+        assert_eq!(deflate.get_header("Content-Encoding"), Some("deflate"));
+        let mut decoded = String::new();
+        ZlibDecoder::new(deflate.body_bytes())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        // Too small, not in the allowlist, or not accepted.
+        let small = compression.apply(&request("gzip"), response("text/html", "hello"));
+        assert_eq!(small.get_header("Content-Encoding"), None);
+        let image = compression.apply(&request("gzip"), response("image/png", &body));
+        assert_eq!(image.get_header("Content-Encoding"), None);
+        let identity = compression.apply(&request("identity"), response("text/html", &body));
+        assert_eq!(identity.get_header("Content-Encoding"), None);
+    }
+}
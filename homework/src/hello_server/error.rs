@@ -0,0 +1,111 @@
+//! Errors while serving a connection.
+
+use core::fmt;
+use std::any::Any;
+use std::error::Error;
+use std::io;
+
+/// Error while serving a connection.
+#[derive(Debug)]
+pub enum ServerError {
+    /// Reading from or writing to the connection failed, e.g. the client reset it.
+    Io(io::Error),
+    /// The request is malformed.
+    Parse(&'static str),
+    /// The request head exceeds the size limit.
+    HeadTooLarge,
+    /// The request body exceeds the size limit.
+    BodyTooLarge,
+    /// The request is not received in time.
+    Timeout,
+    /// The handler panicked with the message.
+    Panic(String),
+}
+
+impl ServerError {
+    /// Creates an error from the payload of a panic.
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic".to_string(),
+            },
+        };
+        ServerError::Panic(message)
+    }
+
+    /// Returns the status code to respond with, or `None` if the client can't be responded to.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ServerError::Io(_) => None,
+            ServerError::Parse(_) => Some(400),
+            ServerError::HeadTooLarge => Some(431),
+            ServerError::BodyTooLarge => Some(413),
+            ServerError::Timeout => Some(408),
+            ServerError::Panic(_) => Some(500),
+        }
+    }
+
+    /// Returns the class of the error, which the statistics are counted by.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerError::Io(_) => "io",
+            ServerError::Parse(_) => "parse",
+            ServerError::HeadTooLarge => "head_too_large",
+            ServerError::BodyTooLarge => "body_too_large",
+            ServerError::Timeout => "timeout",
+            ServerError::Panic(_) => "panic",
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Io(e) => write!(f, "I/O error: {}", e),
+            ServerError::Parse(reason) => write!(f, "malformed request: {}", reason),
+            ServerError::HeadTooLarge => write!(f, "request head too large"),
+            ServerError::BodyTooLarge => write!(f, "request body too large"),
+            ServerError::Timeout => write!(f, "request timed out"),
+            ServerError::Panic(message) => write!(f, "handler panicked: {}", message),
+        }
+    }
+}
+
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(e: io::Error) -> Self {
+        ServerError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ServerError;
+    use std::io;
+    use std::panic;
+
+    #[test]
+    fn server_error_status() {
+        let io = ServerError::from(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!((io.status(), io.kind()), (None, "io"));
+        assert_eq!(ServerError::Parse("bad").status(), Some(400));
+        assert_eq!(ServerError::HeadTooLarge.status(), Some(431));
+        assert_eq!(ServerError::BodyTooLarge.status(), Some(413));
+        assert_eq!(ServerError::Timeout.status(), Some(408));
+
+        let payload = panic::catch_unwind(|| panic!("oops {}", 42)).unwrap_err();
+        let error = ServerError::from_panic(payload);
+        assert_eq!(error.status(), Some(500));
+        assert_eq!(error.to_string(), "handler panicked: oops 42");
+    }
+}
@@ -0,0 +1,432 @@
+//! Event-driven server that multiplexes the connections over a readiness loop.
+
+use crossbeam_channel::{unbounded, Sender};
+use mio::net::{TcpListener, TcpStream};
+use mio::{Events, Interest, Poll, Token, Waker};
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::error::ServerError;
+use super::handler::Handler;
+use super::http::Request;
+use super::statistics::{Report, ServerStats, Statistics};
+use super::thread_pool::ThreadPool;
+
+const LISTENER: Token = Token(0);
+const WAKER: Token = Token(1);
+
+/// Hello server that multiplexes the connections over a readiness loop (epoll on Linux), as an
+/// alternative to `Server`, which occupies a pool thread for each connection.
+///
+/// The calling thread of `run` waits for the readiness of all connections, reads the requests and
+/// writes the responses without blocking, and dispatches only the complete requests to the thread
+/// pool. So a few threads can serve thousands of mostly idle keep-alive connections. One of the
+/// pool's threads is dedicated to aggregating the reports into the statistics.
+#[derive(Debug)]
+pub struct EventServer {
+    poll: Poll,
+    listener: TcpListener,
+    waker: Arc<Waker>,
+    is_shutdown: Arc<AtomicBool>,
+    num_threads: usize,
+    handler: Handler,
+}
+
+/// Handle for shutting down a running `EventServer` from another thread.
+#[derive(Debug, Clone)]
+pub struct EventShutdownHandle {
+    waker: Arc<Waker>,
+    is_shutdown: Arc<AtomicBool>,
+}
+
+impl EventShutdownHandle {
+    /// Signals the server to stop accepting new connections. The server's `run` returns after
+    /// finishing the in-flight requests.
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.is_shutdown.store(true, Ordering::Release);
+        self.waker.wake()
+    }
+}
+
+/// Connection being served.
+#[derive(Debug)]
+struct Conn {
+    stream: TcpStream,
+    peer: SocketAddr,
+    /// Bytes read but not handled yet.
+    input: Vec<u8>,
+    /// Bytes of the responses not written yet.
+    output: Vec<u8>,
+    /// Whether a request is being handled in the thread pool.
+    busy: bool,
+    /// Whether the client closed its side of the connection.
+    eof: bool,
+    /// Whether the connection should be closed after the output is written.
+    closing: bool,
+    last_active: Instant,
+}
+
+/// Response generated in the thread pool.
+#[derive(Debug)]
+struct Done {
+    token: Token,
+    bytes: Vec<u8>,
+    keep_alive: bool,
+}
+
+impl EventServer {
+    /// How long an idle keep-alive connection is kept open.
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// How often all connections are checked for the idle timeout.
+    const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Binds the server to `addr`, with a thread pool of `num_threads` threads. Panics if
+    /// `num_threads < 2`, since one thread is reserved for the reporter.
+    pub fn bind<A: ToSocketAddrs>(addr: A, num_threads: usize) -> io::Result<Self> {
+        assert!(num_threads >= 2);
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let mut listener = TcpListener::from_std(listener);
+
+        let poll = Poll::new()?;
+        poll.registry()
+            .register(&mut listener, LISTENER, Interest::READABLE)?;
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+        Ok(EventServer {
+            poll,
+            listener,
+            waker,
+            is_shutdown: Arc::new(AtomicBool::new(false)),
+            num_threads,
+            handler: Handler::default(),
+        })
+    }
+
+    /// Sets the handler of the requests.
+    pub fn handler(mut self, handler: Handler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Returns the local address that the server is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns a handle for shutting down the server.
+    pub fn shutdown_handle(&self) -> EventShutdownHandle {
+        EventShutdownHandle {
+            waker: self.waker.clone(),
+            is_shutdown: self.is_shutdown.clone(),
+        }
+    }
+
+    /// Runs the server until it is shut down, and returns the statistics.
+    pub fn run(mut self) -> Statistics {
+        let pool = ThreadPool::new(self.num_threads);
+        let stats = Arc::new(ServerStats::new(pool.monitor()));
+
+        let (report_sender, report_receiver) = unbounded::<Report>();
+        let reporter_stats = stats.clone();
+        pool.execute(move || {
+            for report in report_receiver {
+                reporter_stats.add_report(report);
+            }
+        });
+
+        let (done_sender, done_receiver) = unbounded::<Done>();
+        let mut conns = HashMap::new();
+        let mut next_token = WAKER.0 + 1;
+        let mut events = Events::with_capacity(1024);
+        let mut accepting = true;
+        // The connections that got events or responses since the last iteration.
+        let mut ready = Vec::new();
+        let mut last_sweep = Instant::now();
+
+        loop {
+            if let Err(e) = self.poll.poll(&mut events, Some(Self::SWEEP_INTERVAL)) {
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                self.handler.log_event(format!("failed to poll: {}", e));
+                break;
+            }
+
+            for event in &events {
+                match event.token() {
+                    LISTENER => {
+                        if accepting {
+                            self.accept(&mut conns, &mut next_token);
+                        }
+                    }
+                    WAKER => {}
+                    token => {
+                        if let Some(conn) = conns.get_mut(&token) {
+                            if event.is_readable() {
+                                if let Err(e) = Self::read(conn) {
+                                    let _ = report_sender.send(self.fail(token, conn, e));
+                                }
+                            }
+                            conn.last_active = Instant::now();
+                            ready.push(token);
+                        }
+                    }
+                }
+            }
+
+            // Collects the responses generated in the thread pool.
+            for done in done_receiver.try_iter() {
+                if let Some(conn) = conns.get_mut(&done.token) {
+                    conn.output.extend_from_slice(&done.bytes);
+                    conn.busy = false;
+                    conn.closing |= !done.keep_alive;
+                    conn.last_active = Instant::now();
+                    ready.push(done.token);
+                }
+            }
+
+            // The idle connections are closed on shutdown, and the expired ones periodically.
+            let now = Instant::now();
+            if accepting && self.is_shutdown.load(Ordering::Acquire) {
+                accepting = false;
+                let _ = self.poll.registry().deregister(&mut self.listener);
+                ready.extend(conns.keys().copied());
+            } else if now.duration_since(last_sweep) >= Self::SWEEP_INTERVAL {
+                last_sweep = now;
+                ready.extend(conns.keys().copied());
+            }
+            ready.sort_unstable();
+            ready.dedup();
+
+            // Writes the responses, dispatches the next requests, and closes the finished
+            // connections.
+            for token in ready.drain(..) {
+                let conn = match conns.get_mut(&token) {
+                    Some(conn) => conn,
+                    None => continue,
+                };
+                if let Err(e) = Self::write(conn) {
+                    conn.output.clear();
+                    conn.closing = true;
+                    let _ = report_sender.send(self.fail(token, conn, e));
+                }
+                if !conn.busy && !conn.closing {
+                    if let Some(request) = self.handler.take_request(&mut conn.input, conn.peer) {
+                        conn.busy = true;
+                        self.dispatch(
+                            &pool,
+                            token,
+                            request,
+                            conn.peer,
+                            &report_sender,
+                            &done_sender,
+                        );
+                    }
+                }
+                let idle = !conn.busy && conn.output.is_empty();
+                let expired = now.duration_since(conn.last_active) > Self::IDLE_TIMEOUT;
+                if idle && (conn.closing || conn.eof || expired || !accepting) {
+                    let _ = conns.remove(&token);
+                }
+            }
+
+            if !accepting && conns.is_empty() {
+                break;
+            }
+        }
+
+        drop(report_sender);
+        pool.join();
+        stats.statistics()
+    }
+
+    /// Accepts the pending connections.
+    fn accept(&self, conns: &mut HashMap<Token, Conn>, next_token: &mut usize) {
+        loop {
+            let (mut stream, peer) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    self.handler.log_event(format!("failed to accept: {}", e));
+                    return;
+                }
+            };
+            let token = Token(*next_token);
+            *next_token += 1;
+            if let Err(e) = self.poll.registry().register(
+                &mut stream,
+                token,
+                Interest::READABLE | Interest::WRITABLE,
+            ) {
+                self.handler.log_event(format!("failed to register: {}", e));
+                continue;
+            }
+            let _ = conns.insert(
+                token,
+                Conn {
+                    stream,
+                    peer,
+                    input: Vec::new(),
+                    output: Vec::new(),
+                    busy: false,
+                    eof: false,
+                    closing: false,
+                    last_active: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Logs the I/O error on the connection, and returns its report.
+    fn fail(&self, token: Token, conn: &Conn, e: io::Error) -> Report {
+        let error = ServerError::Io(e);
+        self.handler
+            .log_event(format!("connection from {} failed: {}", conn.peer, error));
+        Report::failed(token.0, &error)
+    }
+
+    /// Reads the available bytes from the connection. On error, the connection is closed.
+    fn read(conn: &mut Conn) -> io::Result<()> {
+        let mut chunk = [0; 4096];
+        loop {
+            match conn.stream.read(&mut chunk) {
+                Ok(0) => {
+                    conn.eof = true;
+                    return Ok(());
+                }
+                Ok(n) => conn.input.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    conn.eof = true;
+                    conn.closing = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Writes as many pending bytes to the connection as possible.
+    fn write(conn: &mut Conn) -> io::Result<()> {
+        while !conn.output.is_empty() {
+            match conn.stream.write(&conn.output) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    let _ = conn.output.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles the request in the thread pool, and sends the response back to the loop.
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch(
+        &self,
+        pool: &ThreadPool,
+        token: Token,
+        request: Result<Request, ServerError>,
+        peer: SocketAddr,
+        report_sender: &Sender<Report>,
+        done_sender: &Sender<Done>,
+    ) {
+        let handler = self.handler.clone();
+        let waker = self.waker.clone();
+        let report_sender = report_sender.clone();
+        let done_sender = done_sender.clone();
+        pool.execute(move || {
+            let start = Instant::now();
+            // The token identifies the connection.
+            let (response, report, keep_alive) = handler.serve(token.0, &request);
+            let _ = report_sender.send(report);
+            let mut bytes = Vec::new();
+            response.write_to(&mut bytes).unwrap();
+            handler.record(peer, request, &response, start.elapsed());
+            let _ = done_sender.send(Done {
+                token,
+                bytes,
+                keep_alive,
+            });
+            let _ = waker.wake();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EventServer;
+    use crossbeam_utils::thread::scope;
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::net::TcpStream;
+
+    /// Reads a response with `Content-Length` from the stream, and returns its status line.
+    fn read_response<R: BufRead>(reader: &mut R) -> String {
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                len = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+        status
+    }
+
+    /// Many keep-alive connections are served by a pool of two threads.
+    #[test]
+    fn event_server_many_connections() {
+        const NUM_CONNS: usize = 64;
+
+        let server = EventServer::bind("127.0.0.1:0", 2).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            let server = s.spawn(move |_| server.run());
+
+            let mut clients = (0..NUM_CONNS)
+                .map(|_| TcpStream::connect(addr).unwrap())
+                .collect::<Vec<_>>();
+            for _ in 0..2 {
+                for client in &mut clients {
+                    client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+                }
+                for client in &mut clients {
+                    let mut reader = BufReader::new(client.try_clone().unwrap());
+                    assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+                }
+            }
+
+            // Pipelined requests.
+            let client = &mut clients[0];
+            client
+                .write_all(b"GET /a/b HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(client.try_clone().unwrap());
+            assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+            assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+            assert_eq!(reader.read(&mut [0]).unwrap(), 0);
+
+            handle.shutdown().unwrap();
+            let stats = server.join().unwrap();
+            assert_eq!(stats.num_responses(404), 2 * NUM_CONNS + 2);
+        })
+        .unwrap();
+    }
+}
@@ -1,15 +1,27 @@
 //! Request handler with a cache.
 
 use lazy_static::lazy_static;
-use regex::bytes::Regex;
-use std::io::prelude::*;
-use std::net::TcpStream;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::cache::Cache;
-use super::statistics::Report;
+use super::compress::Compression;
+use super::error::ServerError;
+use super::http::{Framing, Request, Response};
+use super::log::{Logger, RequestLog, StdoutLogger};
+use super::metrics::Metrics;
+use super::middleware::{Middleware, Next};
+use super::rate_limit::RateLimiter;
+use super::router::Router;
+use super::server::{Server, ShutdownHandle};
+use super::statistics::{Report, ServerStats};
 
 /// Computes the result for the given key. So expensive, much wow.
 fn very_expensive_computation_that_takes_a_few_seconds(key: String) -> String {
@@ -18,10 +30,55 @@ fn very_expensive_computation_that_takes_a_few_seconds(key: String) -> String {
     format!("{}🐕", key)
 }
 
-/// Hello handler with a cache.
-#[derive(Debug, Default, Clone)]
+/// Request handler that dispatches requests with a router.
+#[derive(Debug, Clone)]
 pub struct Handler {
-    cache: Arc<Cache<String, String>>,
+    router: Arc<Router>,
+    /// The routers of the virtual hosts, by the lowercase host name.
+    hosts: HashMap<String, Arc<Router>>,
+    /// The middlewares around the handler, the first one being the outermost.
+    middlewares: Vec<Arc<dyn Middleware>>,
+    logger: Arc<dyn Logger>,
+    /// The cache whose statistics are reported by the stats endpoint.
+    cache: Option<Arc<Cache<String, String>>>,
+    /// The path of the stats endpoint and the statistics it reports.
+    stats: Option<(String, Arc<ServerStats>)>,
+    /// The path of the metrics endpoint and the metrics it reports.
+    metrics: Option<(String, Arc<Metrics>)>,
+    /// The server whose health is reported by the health endpoints.
+    health: Option<(Arc<ServerStats>, ShutdownHandle)>,
+    /// The server whose shutdown makes the handler stop keeping the connections alive.
+    shutdown: Option<ShutdownHandle>,
+    max_head_len: usize,
+    max_body_len: usize,
+    request_timeout: Duration,
+}
+
+impl Default for Handler {
+    /// Creates the hello handler with a cache: `GET /{key}` computes the result for `key`.
+    fn default() -> Self {
+        let cache = Arc::new(Cache::default());
+        let cache_stats = cache.clone();
+        let router = Router::new()
+            .get("/:key", move |request, _| match Self::key(request) {
+                Some(key) => {
+                    let result = cache.get_or_insert_with(
+                        key.clone(),
+                        very_expensive_computation_that_takes_a_few_seconds,
+                    );
+                    Self::page(
+                        200,
+                        &Self::OK.replace("{key}", &key).replace("{result}", &result),
+                    )
+                }
+                None => Self::page(404, Self::NOT_FOUND),
+            })
+            .fallback(|status| Self::page(status, Self::NOT_FOUND));
+        Handler {
+            cache: Some(cache_stats),
+            ..Self::new(router)
+        }
+    }
 }
 
 impl Handler {
@@ -48,35 +105,816 @@ impl Handler {
   </body>
 </html>";
 
-    /// Process the request and generate report.
-    pub fn handle_conn(&self, request_id: usize, mut stream: TcpStream) -> Report {
-        let mut buf = [0; 512];
-        let _ = stream.read(&mut buf).unwrap();
+    /// The page of the errors other than 404, apologizing with `{message}`.
+    const OOPS: &'static str = "<!DOCTYPE html>
+<html lang=\"en\">
+  <head>
+    <meta charset=\"utf-8\">
+    <title>Hello!</title>
+  </head>
+  <body>
+    <h1>Oops!</h1>
+    <p>Sorry, {message}</p>
+  </body>
+</html>";
+
+    const INVALID_HEAD: &'static str = "invalid request head";
+
+    /// The default maximum size of a request head.
+    const DEFAULT_MAX_HEAD_LEN: usize = 8192;
+
+    /// The default maximum size of a request body.
+    const DEFAULT_MAX_BODY_LEN: usize = 1 << 20;
+
+    /// The default time a client has to send a whole request.
+    const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Process the requests on the connection from `peer` and generate a report for each of them.
+    ///
+    /// The connection is kept alive until the client asks to close it with `Connection: close`,
+    /// the client closes it, or it goes idle for the read timeout set by the caller. Pipelined
+    /// requests are handled in order. A failed request is answered with the status code of its
+    /// `ServerError`, e.g. 400 if malformed or 408 if not received in time, and closes the
+    /// connection. An I/O error closes the connection without a response, and is reported with
+    /// `Report::failed`.
+    pub fn handle_conn<S: Read + Write>(
+        &self,
+        request_id: usize,
+        peer: SocketAddr,
+        stream: S,
+    ) -> Vec<Report> {
+        self.handle_conn_with(request_id, peer, stream, &AtomicBool::new(false))
+    }
+
+    /// Like `handle_conn`, but sets `idle` while waiting for the next request on a kept-alive
+    /// connection, so that the server can close the connection when draining.
+    pub(crate) fn handle_conn_with<S: Read + Write>(
+        &self,
+        request_id: usize,
+        peer: SocketAddr,
+        mut stream: S,
+        idle: &AtomicBool,
+    ) -> Vec<Report> {
+        let mut reports = Vec::new();
+        let mut buf = Vec::new();
+        loop {
+            let request = self.read_request(&mut stream, &mut buf, peer);
+            idle.store(false, Ordering::SeqCst);
+            let request = match request {
+                Ok(Some(request)) => Ok(request),
+                Ok(None) => break,
+                Err(ServerError::Io(e)) => {
+                    reports.push(self.fail(request_id, peer, e));
+                    break;
+                }
+                Err(e) => Err(e),
+            };
+            let start = Instant::now();
+            let (response, report, keep_alive) = self.serve(request_id, &request);
+            reports.push(report);
+            let written = response.write_to(&mut stream);
+            self.record(peer, request, &response, start.elapsed());
+
+            if let Err(e) = written {
+                reports.push(self.fail(request_id, peer, e));
+                break;
+            }
+            if !keep_alive {
+                break;
+            }
+            if buf.is_empty() {
+                // Set before checking the shutdown, so that either the server sees the connection
+                // idle and closes it, or the handler sees the shutdown.
+                idle.store(true, Ordering::SeqCst);
+                if self.is_draining() {
+                    break;
+                }
+            }
+        }
+        reports
+    }
+
+    /// Logs the I/O error on the connection from `peer`, and returns its report.
+    fn fail(&self, request_id: usize, peer: SocketAddr, e: io::Error) -> Report {
+        let error = ServerError::Io(e);
+        self.log_event(format!("connection from {} failed: {}", peer, error));
+        Report::failed(request_id, &error)
+    }
+
+    /// Generates the response to the request, or the error page of a request that couldn't be
+    /// read, with the `Connection` header. Returns the response, its report, and whether to keep
+    /// the connection alive.
+    pub(crate) fn serve(
+        &self,
+        request_id: usize,
+        request: &Result<Request, ServerError>,
+    ) -> (Response, Report, bool) {
+        let (response, report, keep_alive) = match request {
+            Ok(request) => {
+                let report = Report::new(request_id, Self::key(request));
+                match self.respond(request) {
+                    Ok(response) => (response, report, request.keep_alive()),
+                    Err(e) => {
+                        self.log_event(format!("{} {}: {}", request.method, request.path, e));
+                        (
+                            Self::error_page(&e),
+                            report.with_error(&e),
+                            request.keep_alive(),
+                        )
+                    }
+                }
+            }
+            Err(e) => (
+                Self::error_page(e),
+                Report::new(request_id, None).with_error(e),
+                false,
+            ),
+        };
+        let report = report.with_status(response.status());
+        let keep_alive = keep_alive && !self.is_draining();
+        let response = response.header(
+            "Connection",
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+        (response, report, keep_alive)
+    }
+
+    /// Records the metrics of the served request, and logs it.
+    pub(crate) fn record(
+        &self,
+        peer: SocketAddr,
+        request: Result<Request, ServerError>,
+        response: &Response,
+        latency: Duration,
+    ) {
+        if let Some((_, metrics)) = &self.metrics {
+            metrics.observe(response.status(), latency);
+        }
+
+        let (method, path) = match request {
+            Ok(request) => (request.method, request.path),
+            Err(_) => ("-".to_string(), "-".to_string()),
+        };
+        self.logger.log_request(RequestLog {
+            peer,
+            method,
+            path,
+            status: response.status(),
+            latency,
+            bytes: response.body_len(),
+        });
+    }
+
+    /// Takes a whole request from the front of `buf`, without blocking. Returns `None` if the
+    /// request is not completely received yet.
+    #[cfg(feature = "event-loop")]
+    pub(crate) fn take_request(
+        &self,
+        buf: &mut Vec<u8>,
+        peer: SocketAddr,
+    ) -> Option<Result<Request, ServerError>> {
+        let head_len = match buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(pos) => pos + 4,
+            None if buf.len() > self.max_head_len => return Some(Err(ServerError::HeadTooLarge)),
+            None => return None,
+        };
+        let mut request = match Request::parse(&buf[..head_len]) {
+            Some(request) => request,
+            None => return Some(Err(ServerError::Parse(Self::INVALID_HEAD))),
+        };
+        let framing = match request.framing() {
+            Ok(framing) => framing,
+            Err(reason) => return Some(Err(ServerError::Parse(reason))),
+        };
+        let (body, len) = match framing.parse(&buf[head_len..], self.max_body_len) {
+            Ok(Some(body)) => body,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        let _ = buf.drain(..head_len + len);
+        request.body = body;
+        request.peer = Some(peer);
+        Some(Ok(request))
+    }
+
+    /// Creates a new handler with the router.
+    pub fn new(router: Router) -> Self {
+        Handler {
+            router: Arc::new(router),
+            hosts: HashMap::new(),
+            middlewares: Vec::new(),
+            logger: Arc::new(StdoutLogger),
+            cache: None,
+            stats: None,
+            metrics: None,
+            health: None,
+            shutdown: None,
+            max_head_len: Self::DEFAULT_MAX_HEAD_LEN,
+            max_body_len: Self::DEFAULT_MAX_BODY_LEN,
+            request_timeout: Self::DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Dispatches the requests for the virtual host `host` with the router, instead of the
+    /// default one. The host is matched case-insensitively against the `Host` header without the
+    /// port, e.g. `example.com` matches `Host: Example.com:7878`. The requests for unknown hosts
+    /// go to the default router.
+    pub fn host(mut self, host: &str, router: Router) -> Self {
+        let _ = self
+            .hosts
+            .insert(host.to_ascii_lowercase(), Arc::new(router));
+        self
+    }
+
+    /// Adds a middleware inside the ones added before.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds the middlewares outside the ones added before.
+    pub(crate) fn wrap(mut self, middlewares: &[Arc<dyn Middleware>]) -> Self {
+        self.middlewares.splice(0..0, middlewares.iter().cloned());
+        self
+    }
+
+    /// Compresses the responses with the policy. Shorthand for adding `compression` as a
+    /// middleware.
+    pub fn compression(self, compression: Compression) -> Self {
+        self.middleware(compression)
+    }
+
+    /// Sets the maximum size of a request head. Defaults to 8 KiB.
+    pub fn max_head_len(mut self, max_head_len: usize) -> Self {
+        self.max_head_len = max_head_len;
+        self
+    }
+
+    /// Sets the maximum size of a request body, after removing the chunked transfer coding.
+    /// Defaults to 1 MiB.
+    pub fn max_body_len(mut self, max_body_len: usize) -> Self {
+        self.max_body_len = max_body_len;
+        self
+    }
+
+    /// Sets the time a client has to send a whole request after its first byte. Defaults to 10
+    /// seconds.
+    ///
+    /// This protects the server from the clients that trickle bytes to keep a worker busy (a.k.a.
+    /// slowloris). The deadline is checked whenever bytes arrive, so a silent client is cut off by
+    /// the read timeout of the stream instead.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets the logger of the requests and the server events. Defaults to `StdoutLogger`.
+    pub fn logger<L: Logger + 'static>(mut self, logger: L) -> Self {
+        self.logger = Arc::new(logger);
+        self
+    }
+
+    /// Serves the statistics as JSON at `path`.
+    pub(crate) fn stats_endpoint(mut self, path: String, stats: Arc<ServerStats>) -> Self {
+        self.stats = Some((path, stats));
+        self
+    }
+
+    /// Records the metrics of the requests, and serves them in the Prometheus text format at
+    /// `path`.
+    pub(crate) fn metrics_endpoint(mut self, path: String, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some((path, metrics));
+        self
+    }
+
+    /// Serves the liveness and readiness of the server at `Server::LIVENESS_PATH` and
+    /// `Server::READINESS_PATH`.
+    pub(crate) fn health_endpoints(
+        mut self,
+        stats: Arc<ServerStats>,
+        shutdown: ShutdownHandle,
+    ) -> Self {
+        self.health = Some((stats, shutdown));
+        self
+    }
+
+    /// Stops keeping the connections alive once the server is shut down.
+    pub(crate) fn drain_on(mut self, shutdown: ShutdownHandle) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Returns whether the server is shut down, and the connections are being drained.
+    fn is_draining(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .map_or(false, ShutdownHandle::is_shutdown)
+    }
+
+    /// Logs a server event.
+    pub(crate) fn log_event(&self, message: String) {
+        self.logger.log_event(message);
+    }
+
+    /// Limits the rate of requests from each client. Requests exceeding the limit are answered
+    /// with 429. Shorthand for adding `limiter` as a middleware.
+    pub fn rate_limit(self, limiter: RateLimiter) -> Self {
+        self.middleware(limiter)
+    }
+
+    /// Generates the response for the request by passing it through the middlewares.
+    pub fn handle(&self, request: &Request) -> Response {
+        Next::new(&self.middlewares, &|request| self.endpoint(request)).run(request)
+    }
+
+    /// Generates the response for the request, after the middlewares.
+    fn endpoint(&self, request: &Request) -> Response {
+        if let Some(response) = self.health(request) {
+            return response;
+        }
+        if let Some((path, stats)) = &self.stats {
+            if request.method == "GET" && request.path == *path {
+                return Response::new(200)
+                    .header("Content-Type", "application/json")
+                    .header("Cache-Control", "no-store")
+                    .body(self.stats_json(stats));
+            }
+        }
+        if let Some((path, metrics)) = &self.metrics {
+            if request.method == "GET" && request.path == *path {
+                return Response::new(200)
+                    .header("Content-Type", "text/plain; version=0.0.4")
+                    .header("Cache-Control", "no-store")
+                    .body(metrics.render(self.cache.as_ref().map(|cache| cache.stats())));
+            }
+        }
+        self.router(request).dispatch(request)
+    }
+
+    /// Returns the router of the request's virtual host, or the default router if the host is
+    /// unknown.
+    fn router(&self, request: &Request) -> &Router {
+        request
+            .host()
+            .and_then(|host| self.hosts.get(&host))
+            .unwrap_or(&self.router)
+    }
+
+    /// Responds to the request if it is for a health endpoint.
+    fn health(&self, request: &Request) -> Option<Response> {
+        let (stats, shutdown) = self.health.as_ref()?;
+        if request.method != "GET" {
+            return None;
+        }
+        let (code, status) = if request.path == Server::LIVENESS_PATH {
+            (200, "alive")
+        } else if request.path == Server::READINESS_PATH {
+            if shutdown.is_shutdown() {
+                (503, "shutting_down")
+            } else if stats.is_saturated() {
+                (503, "saturated")
+            } else {
+                (200, "ready")
+            }
+        } else {
+            return None;
+        };
+        Some(
+            Response::new(code)
+                .header("Content-Type", "application/json")
+                .header("Cache-Control", "no-store")
+                .body(format!(
+                    "{{\"status\":\"{}\",\"uptime_secs\":{:.3}}}",
+                    status,
+                    stats.uptime().as_secs_f64()
+                )),
+        )
+    }
+
+    /// Formats the server statistics and the cache statistics as JSON.
+    fn stats_json(&self, stats: &ServerStats) -> String {
+        let cache = match &self.cache {
+            Some(cache) => {
+                let cache = cache.stats();
+                format!(
+                    "{{\"entries\":{},\"hits\":{},\"misses\":{}}}",
+                    cache.entries, cache.hits, cache.misses
+                )
+            }
+            None => "null".to_string(),
+        };
+        format!("{{{},\"cache\":{}}}", stats.json_fields(), cache)
+    }
+
+    /// Like `handle`, but a panic is turned into `ServerError::Panic`.
+    fn respond(&self, request: &Request) -> Result<Response, ServerError> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.handle(request)))
+            .map_err(ServerError::from_panic)
+    }
 
+    /// Returns the key that the request asks for, if any.
+    fn key(request: &Request) -> Option<String> {
         lazy_static! {
-            static ref REQUEST_REGEX: Regex =
-                Regex::new(r"GET /(?P<key>\w+) HTTP/1.1\r\n").unwrap();
+            static ref PATH_REGEX: Regex = Regex::new(r"^/(?P<key>\w+)$").unwrap();
         }
-        let key = REQUEST_REGEX
-            .captures(&buf)
+        if request.method != "GET" {
+            return None;
+        }
+        PATH_REGEX
+            .captures(&request.path)
             .and_then(|cap| cap.name("key"))
-            .map(|key| String::from_utf8_lossy(key.as_bytes()));
-
-        let resp = if let Some(ref key) = key {
-            let result = self.cache.get_or_insert_with(
-                key.to_string(),
-                very_expensive_computation_that_takes_a_few_seconds,
-            );
-            format!(
-                "HTTP/1.1 200 OK\r\n\r\n{}",
-                Self::OK.replace("{key}", key).replace("{result}", &result)
-            )
-        } else {
-            format!("HTTP/1.1 404 NOT FOUND\r\n\r\n{}", Self::NOT_FOUND)
+            .map(|key| key.as_str().to_string())
+    }
+
+    /// Creates an HTML response.
+    pub(crate) fn page(status: u16, html: &str) -> Response {
+        Response::new(status)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(html)
+    }
+
+    /// Creates an error page that apologizes with the message.
+    pub(crate) fn oops_page(status: u16, message: &str) -> Response {
+        Self::page(status, &Self::OOPS.replace("{message}", message))
+    }
+
+    /// Creates the error page of a request that failed.
+    fn error_page(error: &ServerError) -> Response {
+        let status = error.status().unwrap_or(500);
+        let message = match error {
+            ServerError::Timeout => "you took too long to send your request.",
+            ServerError::HeadTooLarge => "your request head is too large.",
+            ServerError::BodyTooLarge => "your request body is too large.",
+            ServerError::Panic(_) | ServerError::Io(_) => "something went wrong.",
+            ServerError::Parse(_) => "I can't understand your request.",
+        };
+        Self::oops_page(status, message)
+    }
+
+    /// Reads a request from the stream, using `buf` for the bytes read ahead. Returns `None` if
+    /// the connection is closed, or goes idle, before the request starts.
+    fn read_request<S: Read>(
+        &self,
+        stream: &mut S,
+        buf: &mut Vec<u8>,
+        peer: SocketAddr,
+    ) -> Result<Option<Request>, ServerError> {
+        let mut deadline = None;
+        let head_len = match self.read_head(stream, buf, &mut deadline)? {
+            Some(head_len) => head_len,
+            None => return Ok(None),
+        };
+        let head = buf.drain(..head_len).collect::<Vec<_>>();
+        let mut request = Request::parse(&head).ok_or(ServerError::Parse(Self::INVALID_HEAD))?;
+        let framing = request.framing().map_err(ServerError::Parse)?;
+        request.body = self.read_body(stream, buf, framing, deadline)?;
+        request.peer = Some(peer);
+        Ok(Some(request))
+    }
+
+    /// Reads from the stream until `buf` contains a whole request head, and returns its length.
+    /// Sets `deadline` of the request once its first byte is received.
+    fn read_head<S: Read>(
+        &self,
+        stream: &mut S,
+        buf: &mut Vec<u8>,
+        deadline: &mut Option<Instant>,
+    ) -> Result<Option<usize>, ServerError> {
+        loop {
+            if deadline.is_none() && !buf.is_empty() {
+                *deadline = Some(Instant::now() + self.request_timeout);
+            }
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                return Ok(Some(pos + 4));
+            }
+            if buf.len() > self.max_head_len {
+                return Err(ServerError::HeadTooLarge);
+            }
+            if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+                return Err(ServerError::Timeout);
+            }
+            if !Self::fill(stream, buf, deadline.is_some())? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads from the stream until `buf` contains a whole body with the framing, and returns it.
+    fn read_body<S: Read>(
+        &self,
+        stream: &mut S,
+        buf: &mut Vec<u8>,
+        framing: Framing,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<u8>, ServerError> {
+        loop {
+            if let Some((body, len)) = framing.parse(buf, self.max_body_len)? {
+                let _ = buf.drain(..len);
+                return Ok(body);
+            }
+            if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+                return Err(ServerError::Timeout);
+            }
+            Self::fill(stream, buf, true)?;
+        }
+    }
+
+    /// Reads more bytes from the stream into `buf`. Returns `false` if the connection is closed,
+    /// or the read times out, before the request is `started`. Both are errors once it is.
+    fn fill<S: Read>(
+        stream: &mut S,
+        buf: &mut Vec<u8>,
+        started: bool,
+    ) -> Result<bool, ServerError> {
+        let mut chunk = [0; 512];
+        match stream.read(&mut chunk) {
+            Ok(0) if !started => Ok(false),
+            Ok(0) => Err(ServerError::Io(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => {
+                buf.extend_from_slice(&chunk[..n]);
+                Ok(true)
+            }
+            Err(e) => match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut if !started => Ok(false),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Err(ServerError::Timeout),
+                _ => Err(ServerError::Io(e)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Handler;
+    use crate::hello_server::log::test::MemoryLogger;
+    use crate::hello_server::metrics::Metrics;
+    use crate::hello_server::statistics::{Report, ServerStats};
+    use crate::hello_server::RateLimiter;
+    use crate::hello_server::{Request, Response, Router, Statistics, ThreadPool};
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Reads a response with `Content-Length` from the stream, and returns its status line.
+    fn read_response<R: BufRead>(reader: &mut R) -> String {
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                len = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+        status
+    }
+
+    /// Runs the handler on a single connection and returns the client side of it.
+    fn connect_with(handler: Handler) -> (TcpStream, thread::JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, peer) = listener.accept().unwrap();
+        let handle = thread::spawn(move || handler.handle_conn(0, peer, stream).len());
+        (client, handle)
+    }
+
+    fn connect() -> (TcpStream, thread::JoinHandle<usize>) {
+        connect_with(Handler::default())
+    }
+
+    #[test]
+    fn handler_keep_alive() {
+        let (mut client, handle) = connect();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        for _ in 0..3 {
+            client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        }
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        assert_eq!(handle.join().unwrap(), 4);
+    }
+
+    #[test]
+    fn handler_pipelined() {
+        let (mut client, handle) = connect();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client
+            .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n")
+            .unwrap();
+        for _ in 0..3 {
+            assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        }
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(handle.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn handler_bad_request() {
+        let (mut client, handle) = connect();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client.write_all(b"GET /\r\n\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 400"));
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn handler_method_not_allowed() {
+        let (mut client, handle) = connect();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client
+            .write_all(b"POST /alice HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 405"));
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn handler_rate_limit() {
+        let (mut client, handle) =
+            connect_with(Handler::default().rate_limit(RateLimiter::new(1, 0.0)));
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 429"));
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn handler_log() {
+        let logger = MemoryLogger::default();
+        let (mut client, handle) = connect_with(Handler::default().logger(logger.clone()));
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client
+            .write_all(b"GET /hello/world HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        assert_eq!(handle.join().unwrap(), 1);
+
+        let requests = logger.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].peer, client.local_addr().unwrap());
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/hello/world");
+        assert_eq!(requests[0].status, 404);
+        assert_eq!(requests[0].bytes, Handler::NOT_FOUND.len());
+    }
+
+    #[test]
+    fn handler_virtual_hosts() {
+        let site =
+            |name: &'static str| Router::new().get("/", move |_, _| Response::new(200).body(name));
+        let handler = Handler::new(site("default"))
+            .host("alice.test", site("alice"))
+            .host("Bob.test", site("bob"));
+        let get = |host: &str| {
+            let head = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+            let response = handler.handle(&Request::parse(head.as_bytes()).unwrap());
+            String::from_utf8(response.body_bytes().to_vec()).unwrap()
         };
+        assert_eq!(get("alice.test"), "alice");
+        assert_eq!(get("ALICE.test:7878"), "alice");
+        assert_eq!(get("bob.test"), "bob");
+        assert_eq!(get("carol.test"), "default");
+        let request = Request::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(handler.handle(&request).body_bytes(), b"default");
+    }
+
+    #[test]
+    fn handler_stats_endpoint() {
+        let pool = ThreadPool::new(1);
+        let stats = Arc::new(ServerStats::new(pool.monitor()));
+        stats.add_report(Report::new(0, Some("alice".to_string())).with_status(200));
+        let handler = Handler::default().stats_endpoint("/stats".to_string(), stats);
+
+        let request = Request::parse(b"GET /stats HTTP/1.1\r\n\r\n").unwrap();
+        let mut resp = Vec::new();
+        handler.handle(&request).write_to(&mut resp).unwrap();
+        let resp = String::from_utf8(resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        assert!(resp.contains("\"uptime_secs\":"));
+        assert!(resp.contains(
+            "\"statistics\":{\"requests\":1,\"invalid_requests\":0,\"keys\":{\"alice\":1},\"statuses\":{\"200\":1},\"errors\":{}}"
+        ));
+        assert!(resp.contains("\"pool\":{\"threads\":1,\"jobs\":0,\"max_jobs\":null}"));
+        assert!(resp.ends_with("\"cache\":{\"entries\":0,\"hits\":0,\"misses\":0}}"));
+    }
+
+    #[test]
+    fn handler_metrics_endpoint() {
+        let pool = ThreadPool::new(1);
+        let metrics = Arc::new(Metrics::new(pool.monitor()));
+        let handler = Handler::default().metrics_endpoint("/metrics".to_string(), metrics);
+        let (mut client, handle) = connect_with(handler);
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 404"));
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 200"));
+        assert_eq!(handle.join().unwrap(), 2);
+    }
 
-        stream.write_all(resp.as_bytes()).unwrap();
+    #[test]
+    fn handler_body() {
+        let router = Router::new().route("POST", "/echo", |request, _| {
+            let mut body = Vec::new();
+            let _ = request.body_reader().read_to_end(&mut body).unwrap();
+            Response::new(200).body(body)
+        });
+        let handler = Handler::new(router).max_body_len(16);
+
+        let (mut client, handle) = connect_with(handler.clone());
+        client
+            .write_all(
+                b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        client
+            .write_all(b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n")
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        assert!(resp.ends_with("\r\n\r\nWikipedia"));
+        assert_eq!(handle.join().unwrap(), 1);
+
+        // Rejected before the body is sent.
+        let (mut client, handle) = connect_with(handler);
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client
+            .write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 17\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 413"));
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn handler_head_too_large() {
+        let (mut client, handle) = connect_with(Handler::default().max_head_len(16));
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n")
+            .unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 431"));
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn handler_errors() {
+        let logger = MemoryLogger::default();
+        let router = Router::new().get("/panic", |_, _| panic!("oops"));
+        let handler = Handler::new(router).logger(logger.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, peer) = listener.accept().unwrap();
+        let handle = thread::spawn(move || handler.handle_conn(0, peer, stream));
+
+        // The panic is answered with 500, and the connection is still alive.
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        client.write_all(b"GET /panic HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 500"));
+        // The client closes the connection in the middle of a request.
+        client.write_all(b"GET /panic HTTP/1.1\r\n").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        let mut stats = Statistics::default();
+        for report in handle.join().unwrap() {
+            stats.add_report(report);
+        }
+        assert_eq!(stats.num_requests(), 1);
+        assert_eq!(stats.num_responses(500), 1);
+        assert_eq!(stats.num_errors("panic"), 1);
+        assert_eq!(stats.num_errors("io"), 1);
+        let events = logger.events.lock().unwrap();
+        assert_eq!(events[0], "GET /panic: handler panicked: oops");
+    }
 
-        Report::new(request_id, key.map(String::from))
+    #[test]
+    fn handler_request_timeout() {
+        let (mut client, handle) =
+            connect_with(Handler::default().request_timeout(Duration::from_millis(100)));
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        // Trickles the request.
+        client.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        client.write_all(b"Host: localhost\r\n").unwrap();
+        assert!(read_response(&mut reader).starts_with("HTTP/1.1 408"));
+        assert_eq!(handle.join().unwrap(), 1);
     }
 }
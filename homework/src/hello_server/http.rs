@@ -0,0 +1,405 @@
+//! Minimal HTTP/1.1 requests and responses.
+
+use std::io;
+use std::io::prelude::*;
+use std::net::SocketAddr;
+
+use super::error::ServerError;
+
+/// The maximum length of a chunk size line or a trailer field in a chunked body.
+const MAX_LINE_LEN: usize = 1024;
+
+/// HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// Method, e.g. `GET`.
+    pub method: String,
+    /// Request target, e.g. `/alice`.
+    pub path: String,
+    /// Version, e.g. `HTTP/1.1`.
+    pub version: String,
+    /// Header fields in the order they appear. Names are kept as they are sent.
+    pub headers: Vec<(String, String)>,
+    /// Message body, with the chunked transfer coding removed.
+    pub body: Vec<u8>,
+    /// Address of the client, if known.
+    pub peer: Option<SocketAddr>,
+}
+
+impl Request {
+    /// Parses the request head, i.e. the request line and the header fields terminated by an
+    /// empty line. The body is left empty. Returns `None` if the head is malformed.
+    pub fn parse(head: &[u8]) -> Option<Request> {
+        let head = std::str::from_utf8(head).ok()?;
+        let mut lines = head.split("\r\n");
+
+        let mut request_line = lines.next()?.split(' ');
+        let method = request_line.next().filter(|m| is_token(m))?;
+        let path = request_line.next().filter(|p| !p.is_empty())?;
+        let version = request_line
+            .next()
+            .filter(|v| *v == "HTTP/1.0" || *v == "HTTP/1.1")?;
+        if request_line.next().is_some() {
+            return None;
+        }
+
+        let mut headers = Vec::new();
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let colon = line.find(':')?;
+            let name = &line[..colon];
+            if !is_token(name) {
+                return None;
+            }
+            headers.push((name.to_string(), line[colon + 1..].trim().to_string()));
+        }
+
+        Some(Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            version: version.to_string(),
+            headers,
+            body: Vec::new(),
+            peer: None,
+        })
+    }
+
+    /// Returns the value of the first header field with the given name (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the host of the `Host` header in lowercase, without the port.
+    pub fn host(&self) -> Option<String> {
+        let host = self.header("Host")?.trim();
+        // Minds the IPv6 literals, e.g. `[::1]:7878`.
+        let host = match host.rfind(':') {
+            Some(pos) if !host[pos..].contains(']') => &host[..pos],
+            _ => host,
+        };
+        Some(host.to_ascii_lowercase())
+    }
+
+    /// Returns the length of the body specified by `Content-Length`, or 0 if it's absent. Returns
+    /// `None` if the header is malformed.
+    pub fn content_length(&self) -> Option<usize> {
+        match self.header("Content-Length") {
+            Some(len) => len.parse().ok(),
+            None => Some(0),
+        }
+    }
+
+    /// Returns how the body is delimited, or the reason if the framing headers are invalid.
+    pub(crate) fn framing(&self) -> Result<Framing, &'static str> {
+        match self.header("Transfer-Encoding") {
+            // Ambiguous, and a vector of request smuggling.
+            Some(_) if self.header("Content-Length").is_some() => {
+                Err("both Content-Length and Transfer-Encoding")
+            }
+            Some(coding) if coding.eq_ignore_ascii_case("chunked") => Ok(Framing::Chunked),
+            Some(_) => Err("unsupported Transfer-Encoding"),
+            None => self
+                .content_length()
+                .map(Framing::Length)
+                .ok_or("invalid Content-Length"),
+        }
+    }
+
+    /// Returns the body.
+    pub fn body_bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns a reader of the body.
+    pub fn body_reader(&self) -> impl Read + '_ {
+        &self.body[..]
+    }
+
+    /// Returns whether the client wants the connection to be kept alive after this request.
+    pub fn keep_alive(&self) -> bool {
+        match self.header("Connection") {
+            Some(conn) if conn.eq_ignore_ascii_case("close") => false,
+            Some(conn) if conn.eq_ignore_ascii_case("keep-alive") => true,
+            _ => self.version == "HTTP/1.1",
+        }
+    }
+}
+
+/// How the body of a request is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framing {
+    /// The number of bytes given by `Content-Length`, 0 if absent.
+    Length(usize),
+    /// The chunked transfer coding.
+    Chunked,
+}
+
+impl Framing {
+    /// Parses a body from the front of `buf`, and returns it with the number of bytes it takes.
+    /// Returns `None` if the body is not completely received yet. Fails if the body is malformed
+    /// or longer than `max_len`, which is detected as early as possible.
+    pub(crate) fn parse(
+        self,
+        buf: &[u8],
+        max_len: usize,
+    ) -> Result<Option<(Vec<u8>, usize)>, ServerError> {
+        match self {
+            Framing::Length(len) if len > max_len => Err(ServerError::BodyTooLarge),
+            Framing::Length(len) if buf.len() < len => Ok(None),
+            Framing::Length(len) => Ok(Some((buf[..len].to_vec(), len))),
+            Framing::Chunked => parse_chunked(buf, max_len),
+        }
+    }
+}
+
+/// Parses a chunked body from the front of `buf`. The chunk extensions and the trailer fields are
+/// ignored.
+fn parse_chunked(buf: &[u8], max_len: usize) -> Result<Option<(Vec<u8>, usize)>, ServerError> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let line = match line(&buf[pos..])? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        pos += line.len() + 2;
+        let size = line.split(|&b| b == b';').next().unwrap_or_default();
+        let size = String::from_utf8_lossy(size);
+        let size = size.trim();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ServerError::Parse("invalid chunked body"));
+        }
+        // Only overflows.
+        let size = usize::from_str_radix(size, 16).map_err(|_| ServerError::BodyTooLarge)?;
+        if size == 0 {
+            break;
+        }
+        if size > max_len - body.len() {
+            return Err(ServerError::BodyTooLarge);
+        }
+        if buf.len() < pos + size + 2 {
+            return Ok(None);
+        }
+        if &buf[pos + size..pos + size + 2] != b"\r\n" {
+            return Err(ServerError::Parse("invalid chunked body"));
+        }
+        body.extend_from_slice(&buf[pos..pos + size]);
+        pos += size + 2;
+    }
+    // The trailer fields, terminated by an empty line.
+    loop {
+        let line = match line(&buf[pos..])? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        pos += line.len() + 2;
+        if line.is_empty() {
+            return Ok(Some((body, pos)));
+        }
+    }
+}
+
+/// Returns the line at the front of `buf` without the CRLF, or `None` if it is not completely
+/// received yet. Fails if the line is too long.
+fn line(buf: &[u8]) -> Result<Option<&[u8]>, ServerError> {
+    match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) if end <= MAX_LINE_LEN => Ok(Some(&buf[..end])),
+        None if buf.len() <= MAX_LINE_LEN => Ok(None),
+        _ => Err(ServerError::Parse("chunked body line too long")),
+    }
+}
+
+/// Returns whether `s` is a non-empty HTTP token.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a new response with the given status code and an empty body.
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Adds a header field.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the value of the header field, if any. The name is case-insensitive.
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the body.
+    pub fn body_bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the length of the body.
+    pub fn body_len(&self) -> usize {
+        self.body.len()
+    }
+
+    /// Writes the response. `Content-Length` is added automatically.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+
+        let mut buf = head.into_bytes();
+        buf.extend_from_slice(&self.body);
+        writer.write_all(&buf)
+    }
+}
+
+/// Returns the reason phrase of the status code.
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Framing, Request, Response};
+    use crate::hello_server::ServerError;
+
+    #[test]
+    fn request_parse() {
+        let req =
+            Request::parse(b"GET /alice HTTP/1.1\r\nHost: localhost\r\nconnection: close\r\n\r\n")
+                .unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/alice");
+        assert_eq!(req.version, "HTTP/1.1");
+        assert_eq!(req.header("host"), Some("localhost"));
+        assert_eq!(req.header("Connection"), Some("close"));
+        assert!(!req.keep_alive());
+        assert_eq!(req.content_length(), Some(0));
+    }
+
+    #[test]
+    fn request_host() {
+        let host = |host: &str| {
+            let head = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+            Request::parse(head.as_bytes()).unwrap().host()
+        };
+        assert_eq!(host("Example.com"), Some("example.com".to_string()));
+        assert_eq!(host("example.com:7878"), Some("example.com".to_string()));
+        assert_eq!(host("[::1]:7878"), Some("[::1]".to_string()));
+        assert_eq!(host("[::1]"), Some("[::1]".to_string()));
+        assert_eq!(
+            Request::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap().host(),
+            None
+        );
+    }
+
+    #[test]
+    fn request_framing() {
+        let framing = |headers: &str| {
+            let head = format!("POST / HTTP/1.1\r\n{}\r\n", headers);
+            Request::parse(head.as_bytes()).unwrap().framing()
+        };
+        assert_eq!(framing(""), Ok(Framing::Length(0)));
+        assert_eq!(framing("Content-Length: 5\r\n"), Ok(Framing::Length(5)));
+        assert_eq!(
+            framing("Transfer-Encoding: Chunked\r\n"),
+            Ok(Framing::Chunked)
+        );
+        assert!(framing("Content-Length: five\r\n").is_err());
+        assert!(framing("Transfer-Encoding: gzip\r\n").is_err());
+        assert!(framing("Transfer-Encoding: chunked\r\nContent-Length: 5\r\n").is_err());
+    }
+
+    #[test]
+    fn framing_parse() {
+        assert_eq!(Framing::Length(5).parse(b"hell", 10).unwrap(), None);
+        assert_eq!(
+            Framing::Length(5).parse(b"hello, world", 10).unwrap(),
+            Some((b"hello".to_vec(), 5))
+        );
+
+        let chunked = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\nExpires: never\r\n\r\nGET";
+        let body = Framing::Chunked.parse(chunked, 10).unwrap();
+        assert_eq!(body, Some((b"Wikipedia".to_vec(), chunked.len() - 3)));
+        for len in 0..chunked.len() - 3 {
+            assert_eq!(Framing::Chunked.parse(&chunked[..len], 10).unwrap(), None);
+        }
+
+        let too_large = |result| matches!(result, Err(ServerError::BodyTooLarge));
+        assert!(too_large(Framing::Length(11).parse(b"", 10)));
+        assert!(too_large(Framing::Chunked.parse(chunked, 8)));
+        assert!(too_large(
+            Framing::Chunked.parse(b"ffffffffffffffffff\r\n", 10)
+        ));
+        let malformed = |result| matches!(result, Err(ServerError::Parse(_)));
+        assert!(malformed(Framing::Chunked.parse(b"x\r\n", 10)));
+        assert!(malformed(Framing::Chunked.parse(b"4\r\nWikiX\r\n", 10)));
+    }
+
+    #[test]
+    fn request_parse_malformed() {
+        assert!(Request::parse(b"\r\n\r\n").is_none());
+        assert!(Request::parse(b"GET /alice\r\n\r\n").is_none());
+        assert!(Request::parse(b"GET /alice HTTP/2\r\n\r\n").is_none());
+        assert!(Request::parse(b"GET /alice HTTP/1.1\r\nHost localhost\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn response_write() {
+        let mut buf = Vec::new();
+        Response::new(404)
+            .header("Connection", "close")
+            .body("oops")
+            .write_to(&mut buf)
+            .unwrap();
+        assert_eq!(
+            buf,
+            b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 4\r\n\r\noops"
+                .to_vec()
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Load generator for benchmarking the server.
+
+use core::fmt;
+use crossbeam_channel::unbounded;
+use std::collections::BTreeMap;
+use std::io::{self, prelude::*, BufReader};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use super::thread_pool::ThreadPool;
+
+/// Load generator that sends a mix of GET requests to the server over concurrent keep-alive
+/// connections, each driven by a thread of the crate's `ThreadPool`.
+///
+/// The paths of the mix are requested in proportion to their weights. The mix is deterministic,
+/// so that two runs against the same server are comparable, e.g. before and after changing the
+/// cache or the pool.
+#[derive(Debug, Clone)]
+pub struct LoadGen {
+    addr: SocketAddr,
+    connections: usize,
+    requests: usize,
+    mix: Vec<(String, usize)>,
+}
+
+/// Outcome of a request.
+#[derive(Debug)]
+enum Sample {
+    Response { status: u16, latency: Duration },
+    Error,
+}
+
+impl LoadGen {
+    /// Creates a load generator for the server at `addr`, sending 100 requests for `/` over each
+    /// of 8 connections.
+    pub fn new(addr: SocketAddr) -> Self {
+        LoadGen {
+            addr,
+            connections: 8,
+            requests: 100,
+            mix: Vec::new(),
+        }
+    }
+
+    /// Sets the number of concurrent connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `connections == 0`.
+    pub fn connections(mut self, connections: usize) -> Self {
+        assert!(connections > 0, "no connection");
+        self.connections = connections;
+        self
+    }
+
+    /// Sets the number of requests sent over each connection.
+    pub fn requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Adds `path` to the request mix with the weight. Defaults to `/` only.
+    pub fn path(mut self, path: &str, weight: usize) -> Self {
+        self.mix.push((path.to_string(), weight));
+        self
+    }
+
+    /// Sends the requests, and returns the report once all of them are answered or failed.
+    ///
+    /// A connection that fails is counted as an error for its request, and reopened for the next
+    /// one.
+    pub fn run(&self) -> LoadReport {
+        let mut paths = self
+            .mix
+            .iter()
+            .flat_map(|(path, weight)| (0..*weight).map(move |_| path.clone()))
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            paths.push("/".to_string());
+        }
+
+        let pool = ThreadPool::new(self.connections);
+        let (sample_sender, sample_receiver) = unbounded();
+        let start = Instant::now();
+        for conn in 0..self.connections {
+            let addr = self.addr;
+            let requests = self.requests;
+            let paths = paths.clone();
+            let sample_sender = sample_sender.clone();
+            pool.execute(move || {
+                let mut stream = None;
+                for i in 0..requests {
+                    // Shifts the mix for each connection so that they don't move in lockstep.
+                    let path = &paths[(conn + i) % paths.len()];
+                    let start = Instant::now();
+                    let sample = match Self::request(addr, &mut stream, path) {
+                        Ok(status) => Sample::Response {
+                            status,
+                            latency: start.elapsed(),
+                        },
+                        Err(_) => {
+                            stream = None;
+                            Sample::Error
+                        }
+                    };
+                    sample_sender.send(sample).unwrap();
+                }
+            });
+        }
+        drop(sample_sender);
+        pool.join();
+        let elapsed = start.elapsed();
+
+        let mut report = LoadReport {
+            elapsed,
+            latencies: Vec::new(),
+            statuses: BTreeMap::new(),
+            errors: 0,
+        };
+        for sample in sample_receiver {
+            match sample {
+                Sample::Response { status, latency } => {
+                    *report.statuses.entry(status).or_default() += 1;
+                    report.latencies.push(latency);
+                }
+                Sample::Error => report.errors += 1,
+            }
+        }
+        report.latencies.sort();
+        report
+    }
+
+    /// Sends a request for `path` over `stream`, connecting first if there is none, and returns
+    /// the status code of the response.
+    fn request(
+        addr: SocketAddr,
+        stream: &mut Option<BufReader<TcpStream>>,
+        path: &str,
+    ) -> io::Result<u16> {
+        if stream.is_none() {
+            let conn = TcpStream::connect(addr)?;
+            conn.set_nodelay(true)?;
+            *stream = Some(BufReader::new(conn));
+        }
+        let reader = stream.as_mut().unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+        reader.get_mut().write_all(request.as_bytes())?;
+        let (status, keep_alive) = Self::read_response(reader)?;
+        if !keep_alive {
+            *stream = None;
+        }
+        Ok(status)
+    }
+
+    /// Reads a response with `Content-Length`, and returns its status code and whether the
+    /// connection is kept alive.
+    fn read_response<R: BufRead>(reader: &mut R) -> io::Result<(u16, bool)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid response");
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let status = line
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(invalid)?;
+
+        let mut len = 0;
+        let mut keep_alive = true;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = match line.find(':') {
+                Some(pos) => (&line[..pos], line[pos + 1..].trim()),
+                None => return Err(invalid()),
+            };
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = value.parse().map_err(|_| invalid())?;
+            } else if name.eq_ignore_ascii_case("Connection") {
+                keep_alive = !value.eq_ignore_ascii_case("close");
+            }
+        }
+        io::copy(&mut reader.take(len as u64), &mut io::sink())?;
+        Ok((status, keep_alive))
+    }
+}
+
+/// Throughput, latencies, and status codes measured by `LoadGen`.
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    elapsed: Duration,
+    /// The latencies of the responses, in ascending order.
+    latencies: Vec<Duration>,
+    statuses: BTreeMap<u16, usize>,
+    errors: usize,
+}
+
+impl LoadReport {
+    /// Returns the number of responses.
+    pub fn num_responses(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Returns the number of responses with the status code.
+    pub fn num_status(&self, status: u16) -> usize {
+        self.statuses.get(&status).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of requests that failed without a response.
+    pub fn num_errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Returns the time it took to send all requests.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of responses per second.
+    pub fn throughput(&self) -> f64 {
+        self.num_responses() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Returns the latency that the `p` percent of the responses are at most, e.g. `p = 99.0`
+    /// for the 99th percentile, or `None` if there is no response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is not in `0.0..=100.0`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        assert!((0.0..=100.0).contains(&p), "invalid percentile: {}", p);
+        if self.latencies.is_empty() {
+            return None;
+        }
+        // Nearest-rank method.
+        let rank = (p / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        Some(self.latencies[rank.max(1) - 1])
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} responses, {} errors in {:.3}s ({:.1} req/s)",
+            self.num_responses(),
+            self.errors,
+            self.elapsed.as_secs_f64(),
+            self.throughput()
+        )?;
+        write!(f, "latency:")?;
+        for &p in &[50.0, 90.0, 99.0, 100.0] {
+            match self.percentile(p) {
+                Some(latency) => write!(f, " p{}={:?}", p, latency)?,
+                None => write!(f, " p{}=-", p)?,
+            }
+        }
+        writeln!(f)?;
+        write!(f, "statuses:")?;
+        for (status, count) in &self.statuses {
+            write!(f, " {}={}", status, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LoadGen, LoadReport};
+    use crate::hello_server::{Handler, NullLogger, Response, Router, Server};
+    use crossbeam_utils::thread::scope;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn loadgen_run() {
+        let router = Router::new().get("/alice", |_, _| Response::new(200).body("hello"));
+        let server = Server::bind("127.0.0.1:0", 5)
+            .unwrap()
+            .handler(Handler::new(router).logger(NullLogger));
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            let server = s.spawn(move |_| server.run());
+            let report = LoadGen::new(addr)
+                .connections(4)
+                .requests(10)
+                .path("/alice", 3)
+                .path("/bob", 1)
+                .run();
+            assert_eq!(report.num_responses(), 40);
+            assert_eq!(report.num_errors(), 0);
+            assert_eq!(report.num_status(200), 30);
+            assert_eq!(report.num_status(404), 10);
+
+            handle.shutdown().unwrap();
+            assert_eq!(server.join().unwrap().num_requests(), 40);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn load_report_percentile() {
+        let report = LoadReport {
+            elapsed: Duration::from_secs(2),
+            latencies: (1..=10).map(Duration::from_millis).collect(),
+            statuses: BTreeMap::new(),
+            errors: 0,
+        };
+        assert_eq!(report.throughput(), 5.0);
+        assert_eq!(report.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(5)));
+        assert_eq!(report.percentile(99.0), Some(Duration::from_millis(10)));
+        assert_eq!(report.percentile(100.0), Some(Duration::from_millis(10)));
+    }
+}
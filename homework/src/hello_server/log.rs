@@ -0,0 +1,171 @@
+//! Request logging.
+
+use core::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::thread_pool::ThreadPool;
+
+/// Log of a handled request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestLog {
+    /// Address of the client.
+    pub peer: SocketAddr,
+    /// Method, or `-` if the request is malformed.
+    pub method: String,
+    /// Request target, or `-` if the request is malformed.
+    pub path: String,
+    /// Status code of the response.
+    pub status: u16,
+    /// Time taken from receiving the request to sending the response.
+    pub latency: Duration,
+    /// Size of the response body.
+    pub bytes: usize,
+}
+
+impl fmt::Display for RequestLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} \"{} {}\" {} {} {}us",
+            self.peer,
+            self.method,
+            self.path,
+            self.status,
+            self.bytes,
+            self.latency.as_micros()
+        )
+    }
+}
+
+/// Sink of the server's logs.
+pub trait Logger: fmt::Debug + Send + Sync {
+    /// Logs a handled request.
+    fn log_request(&self, log: RequestLog);
+
+    /// Logs a server event, e.g. a failure to accept a connection.
+    fn log_event(&self, message: String);
+}
+
+/// Logger that prints to the standard output.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutLogger;
+
+impl Logger for StdoutLogger {
+    fn log_request(&self, log: RequestLog) {
+        println!("[request] {}", log);
+    }
+
+    fn log_event(&self, message: String) {
+        println!("[server] {}", message);
+    }
+}
+
+/// Logger that discards the logs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullLogger;
+
+impl Logger for NullLogger {
+    fn log_request(&self, _log: RequestLog) {}
+
+    fn log_event(&self, _message: String) {}
+}
+
+/// Logger that hands the logs over to the inner logger in a dedicated thread, so that logging never
+/// blocks the handlers. The logs are delivered in order, and the pending logs are flushed when
+/// dropped.
+#[derive(Debug)]
+pub struct AsyncLogger<L> {
+    inner: Arc<L>,
+    pool: ThreadPool,
+}
+
+impl<L: Logger + 'static> AsyncLogger<L> {
+    /// Creates a new asynchronous logger.
+    pub fn new(inner: L) -> Self {
+        AsyncLogger {
+            inner: Arc::new(inner),
+            pool: ThreadPool::new(1),
+        }
+    }
+
+    /// Blocks until the pending logs are delivered to the inner logger.
+    pub fn flush(&self) {
+        self.pool.join();
+    }
+}
+
+impl<L: Logger + 'static> Logger for AsyncLogger<L> {
+    fn log_request(&self, log: RequestLog) {
+        let inner = self.inner.clone();
+        self.pool.execute(move || inner.log_request(log));
+    }
+
+    fn log_event(&self, message: String) {
+        let inner = self.inner.clone();
+        self.pool.execute(move || inner.log_event(message));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{AsyncLogger, Logger, RequestLog};
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Logger that remembers the logs.
+    #[derive(Debug, Default, Clone)]
+    pub(crate) struct MemoryLogger {
+        pub(crate) requests: Arc<Mutex<Vec<RequestLog>>>,
+        pub(crate) events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Logger for MemoryLogger {
+        fn log_request(&self, log: RequestLog) {
+            self.requests.lock().unwrap().push(log);
+        }
+
+        fn log_event(&self, message: String) {
+            self.events.lock().unwrap().push(message);
+        }
+    }
+
+    fn request_log(status: u16) -> RequestLog {
+        RequestLog {
+            peer: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 1234)),
+            method: "GET".to_string(),
+            path: "/alice".to_string(),
+            status,
+            latency: Duration::from_micros(42),
+            bytes: 7,
+        }
+    }
+
+    #[test]
+    fn request_log_display() {
+        assert_eq!(
+            request_log(200).to_string(),
+            "127.0.0.1:1234 \"GET /alice\" 200 7 42us"
+        );
+    }
+
+    #[test]
+    fn async_logger_in_order() {
+        let memory = MemoryLogger::default();
+        let logger = AsyncLogger::new(memory.clone());
+        for status in 0..100 {
+            logger.log_request(request_log(status));
+        }
+        logger.flush();
+        let statuses = memory
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|log| log.status)
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, (0..100).collect::<Vec<_>>());
+    }
+}
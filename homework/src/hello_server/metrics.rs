@@ -0,0 +1,197 @@
+//! Metrics in the Prometheus text exposition format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::cache::CacheStats;
+use super::thread_pool::PoolMonitor;
+
+/// Upper bounds of the latency histogram buckets in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Metrics of a running server.
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    started: Instant,
+    /// Number of requests for each status code.
+    requests: Mutex<BTreeMap<u16, u64>>,
+    /// Number of requests in each latency bucket, and then the ones slower than all buckets.
+    latency_counts: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    /// Sum of the latencies in microseconds.
+    latency_sum: AtomicU64,
+    pool: PoolMonitor,
+}
+
+impl Metrics {
+    /// Creates the metrics of a server that starts now with the thread pool.
+    pub(crate) fn new(pool: PoolMonitor) -> Self {
+        Metrics {
+            started: Instant::now(),
+            requests: Mutex::new(BTreeMap::new()),
+            latency_counts: Default::default(),
+            latency_sum: AtomicU64::new(0),
+            pool,
+        }
+    }
+
+    /// Records a handled request.
+    pub(crate) fn observe(&self, status: u16, latency: Duration) {
+        *self.requests.lock().unwrap().entry(status).or_default() += 1;
+
+        let secs = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Renders the metrics, including the cache statistics if given.
+    pub(crate) fn render(&self, cache: Option<CacheStats>) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "requests_total",
+            "counter",
+            "Number of handled requests.",
+        );
+        for (status, count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "hello_requests_total{{status=\"{}\"}} {}",
+                status, count
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut out,
+            "request_duration_seconds",
+            "histogram",
+            "Latency of the requests.",
+        );
+        let mut count = 0;
+        for (i, counter) in self.latency_counts.iter().enumerate() {
+            count += counter.load(Ordering::Relaxed);
+            let le = LATENCY_BUCKETS
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), |le| le.to_string());
+            writeln!(
+                out,
+                "hello_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, count
+            )
+            .unwrap();
+        }
+        let sum = self.latency_sum.load(Ordering::Relaxed) as f64 / 1e6;
+        writeln!(out, "hello_request_duration_seconds_sum {}", sum).unwrap();
+        writeln!(out, "hello_request_duration_seconds_count {}", count).unwrap();
+
+        if let Some(cache) = cache {
+            metric(
+                &mut out,
+                "cache_hits_total",
+                "counter",
+                "Number of the cache lookups that found the key.",
+                cache.hits,
+            );
+            metric(
+                &mut out,
+                "cache_misses_total",
+                "counter",
+                "Number of the cache lookups that inserted the key.",
+                cache.misses,
+            );
+            metric(
+                &mut out,
+                "cache_entries",
+                "gauge",
+                "Number of the keys in the cache.",
+                cache.entries,
+            );
+        }
+
+        metric(
+            &mut out,
+            "pool_threads",
+            "gauge",
+            "Number of the threads in the pool.",
+            self.pool.size(),
+        );
+        metric(
+            &mut out,
+            "pool_jobs",
+            "gauge",
+            "Number of the jobs running or queued in the pool.",
+            self.pool.num_jobs(),
+        );
+        metric(
+            &mut out,
+            "uptime_seconds",
+            "gauge",
+            "Time since the server started.",
+            self.started.elapsed().as_secs_f64(),
+        );
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric.
+fn header(out: &mut String, name: &str, ty: &str, help: &str) {
+    writeln!(out, "# HELP hello_{} {}", name, help).unwrap();
+    writeln!(out, "# TYPE hello_{} {}", name, ty).unwrap();
+}
+
+/// Writes a metric with a single value.
+fn metric<T: ToString>(out: &mut String, name: &str, ty: &str, help: &str, value: T) {
+    header(out, name, ty, help);
+    writeln!(out, "hello_{} {}", name, value.to_string()).unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::Metrics;
+    use crate::hello_server::{CacheStats, ThreadPool};
+    use std::time::Duration;
+
+    #[test]
+    fn metrics_render() {
+        let pool = ThreadPool::new(2);
+        let metrics = Metrics::new(pool.monitor());
+        metrics.observe(200, Duration::from_micros(500));
+        metrics.observe(200, Duration::from_millis(20));
+        metrics.observe(404, Duration::from_secs(10));
+
+        let out = metrics.render(Some(CacheStats {
+            entries: 1,
+            hits: 2,
+            misses: 3,
+        }));
+        for line in &[
+            "# TYPE hello_requests_total counter",
+            "hello_requests_total{status=\"200\"} 2",
+            "hello_requests_total{status=\"404\"} 1",
+            "# TYPE hello_request_duration_seconds histogram",
+            "hello_request_duration_seconds_bucket{le=\"0.001\"} 1",
+            "hello_request_duration_seconds_bucket{le=\"0.01\"} 1",
+            "hello_request_duration_seconds_bucket{le=\"0.05\"} 2",
+            "hello_request_duration_seconds_bucket{le=\"5\"} 2",
+            "hello_request_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "hello_request_duration_seconds_sum 10.0205",
+            "hello_request_duration_seconds_count 3",
+            "hello_cache_hits_total 2",
+            "hello_cache_misses_total 3",
+            "hello_cache_entries 1",
+            "hello_pool_threads 2",
+            "hello_pool_jobs 0",
+        ] {
+            assert!(out.lines().any(|l| l == *line), "missing line: {}", line);
+        }
+    }
+}
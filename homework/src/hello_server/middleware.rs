@@ -0,0 +1,120 @@
+//! Middlewares layered around the request handler.
+
+use core::fmt;
+use std::sync::Arc;
+
+use super::compress::Compression;
+use super::handler::Handler;
+use super::http::{Request, Response};
+use super::rate_limit::RateLimiter;
+
+/// Layer around the request handler, e.g. rate limiting or compression.
+///
+/// A middleware may answer the request by itself, or pass it to the rest of the chain with `next`
+/// and post-process the response.
+pub trait Middleware: fmt::Debug + Send + Sync {
+    /// Generates the response for the request, using `next` for the rest of the chain.
+    fn call(&self, request: &Request, next: Next<'_>) -> Response;
+}
+
+/// Rest of a middleware chain, ending with the handler.
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    endpoint: &'a dyn Fn(&Request) -> Response,
+}
+
+impl fmt::Debug for Next<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("middlewares", &self.middlewares)
+            .finish()
+    }
+}
+
+impl<'a> Next<'a> {
+    /// Creates a chain of the middlewares, the first one being the outermost.
+    pub(crate) fn new(
+        middlewares: &'a [Arc<dyn Middleware>],
+        endpoint: &'a dyn Fn(&Request) -> Response,
+    ) -> Self {
+        Next {
+            middlewares,
+            endpoint,
+        }
+    }
+
+    /// Passes the request to the rest of the chain.
+    pub fn run(self, request: &Request) -> Response {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.call(request, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(request),
+        }
+    }
+}
+
+impl Middleware for RateLimiter {
+    /// Answers with 429 if the client exceeded its budget.
+    fn call(&self, request: &Request, next: Next<'_>) -> Response {
+        if let Some(peer) = request.peer {
+            if !self.check(peer.ip()) {
+                return Handler::oops_page(429, "you're asking too much. Please try again later.")
+                    .header("Retry-After", "1");
+            }
+        }
+        next.run(request)
+    }
+}
+
+impl Middleware for Compression {
+    /// Compresses the response if the policy allows it.
+    fn call(&self, request: &Request, next: Next<'_>) -> Response {
+        let response = next.run(request);
+        self.apply(request, response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Middleware, Next};
+    use crate::hello_server::{Request, Response};
+    use std::sync::Arc;
+
+    /// Appends its name to the body of the response.
+    #[derive(Debug)]
+    struct Trace(&'static str);
+
+    impl Middleware for Trace {
+        fn call(&self, request: &Request, next: Next<'_>) -> Response {
+            let response = next.run(request);
+            let body = [response.body_bytes(), self.0.as_bytes()].concat();
+            response.body(body)
+        }
+    }
+
+    /// Answers with 403 without calling the rest of the chain.
+    #[derive(Debug)]
+    struct Deny;
+
+    impl Middleware for Deny {
+        fn call(&self, _request: &Request, _next: Next<'_>) -> Response {
+            Response::new(403)
+        }
+    }
+
+    #[test]
+    fn middleware_chain_order() {
+        let request = Request::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let endpoint = |_: &Request| Response::new(200).body("h");
+
+        let middlewares: Vec<Arc<dyn Middleware>> =
+            vec![Arc::new(Trace("a")), Arc::new(Trace("b"))];
+        let response = Next::new(&middlewares, &endpoint).run(&request);
+        assert_eq!(response.status(), 200);
+        // The first middleware is the outermost, so it post-processes last.
+        assert_eq!(response.body_bytes(), b"hba");
+
+        let middlewares: Vec<Arc<dyn Middleware>> = vec![Arc::new(Trace("a")), Arc::new(Deny)];
+        let response = Next::new(&middlewares, &endpoint).run(&request);
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -1,12 +1,42 @@
 //! Hello server with a cache.
 
 mod cache;
+mod compress;
+mod error;
+#[cfg(feature = "event-loop")]
+mod event_loop;
 mod handler;
+mod http;
+mod loadgen;
+mod log;
+mod metrics;
+mod middleware;
+mod rate_limit;
+mod response_cache;
+mod router;
+mod server;
 mod statistics;
 mod tcp;
 mod thread_pool;
+#[cfg(feature = "tls")]
+mod tls;
 
+pub use cache::CacheStats;
+pub use compress::Compression;
+pub use error::ServerError;
+#[cfg(feature = "event-loop")]
+pub use event_loop::{EventServer, EventShutdownHandle};
 pub use handler::Handler;
+pub use http::{Request, Response};
+pub use loadgen::{LoadGen, LoadReport};
+pub use log::{AsyncLogger, Logger, NullLogger, RequestLog, StdoutLogger};
+pub use middleware::{Middleware, Next};
+pub use rate_limit::RateLimiter;
+pub use response_cache::ResponseCache;
+pub use router::{Params, Router};
+pub use server::{Backpressure, Server, ServerBuilder, ShutdownHandle};
 pub use statistics::{Report, Statistics};
 pub use tcp::CancellableTcpListener;
-pub use thread_pool::ThreadPool;
+pub use thread_pool::{PoolMonitor, ThreadPool};
+#[cfg(feature = "tls")]
+pub use tls::TlsAcceptor;
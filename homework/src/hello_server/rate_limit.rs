@@ -0,0 +1,186 @@
+//! Per-client rate limiter.
+
+use crossbeam_epoch as epoch;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::hash_table::SplitOrderedList;
+use crate::map::NonblockingMap;
+
+/// How often `check` evicts the idle buckets.
+const EVICT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Token bucket of a client.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    /// The bucket is removed from the map, so a new one should be looked up.
+    evicted: bool,
+}
+
+impl TokenBucket {
+    /// Adds the tokens refilled until `now`, up to `burst`.
+    fn refill(&mut self, now: Instant, burst: f64, per_second: f64) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.last_refill = self.last_refill.max(now);
+    }
+}
+
+/// Token-bucket rate limiter keyed by client IP.
+///
+/// Each client has a bucket of `burst` tokens, refilled at `per_second` tokens per second, and
+/// each request takes a token. The buckets are kept in a lock-free map keyed by the hash of the
+/// IP, so clients with colliding hashes share a bucket. Every 10 seconds, `check` evicts the
+/// buckets that are full again, since they are the same as the new ones.
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: SplitOrderedList<Mutex<TokenBucket>>,
+    /// The keys of `buckets`, which the map can't iterate over.
+    keys: Mutex<Vec<usize>>,
+    last_eviction: Mutex<Instant>,
+    burst: f64,
+    per_second: f64,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter.
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        RateLimiter {
+            buckets: SplitOrderedList::new(),
+            keys: Mutex::new(Vec::new()),
+            last_eviction: Mutex::new(Instant::now()),
+            burst: f64::from(burst),
+            per_second,
+        }
+    }
+
+    /// Removes the buckets that are full, i.e. of the clients idle for long enough.
+    pub fn evict_idle(&self) {
+        let now = Instant::now();
+        let guard = &epoch::pin();
+        self.keys.lock().unwrap().retain(|key| {
+            let bucket = match self.buckets.lookup(key, guard) {
+                Some(bucket) => bucket,
+                None => return false,
+            };
+            let mut bucket = bucket.lock().unwrap();
+            bucket.refill(now, self.burst, self.per_second);
+            if bucket.tokens < self.burst {
+                return true;
+            }
+            bucket.evicted = true;
+            let _ = self.buckets.delete(key, guard);
+            false
+        });
+    }
+
+    /// Number of the buckets, i.e. of the clients seen recently.
+    pub fn len(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+
+    /// Returns `true` if there is no bucket.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes a token from the client's bucket. Returns `false` if the bucket is empty, i.e. the
+    /// client exceeded its budget.
+    pub fn check(&self, ip: IpAddr) -> bool {
+        let key = {
+            let mut hasher = DefaultHasher::new();
+            ip.hash(&mut hasher);
+            // `SplitOrderedList` requires the MSB of keys to be 0.
+            hasher.finish() as usize & (usize::MAX >> 1)
+        };
+        let now = Instant::now();
+        if let Ok(mut last_eviction) = self.last_eviction.try_lock() {
+            if now.saturating_duration_since(*last_eviction) >= EVICT_INTERVAL {
+                *last_eviction = now;
+                self.evict_idle();
+            }
+        }
+
+        let guard = &epoch::pin();
+        let mut bucket = loop {
+            if let Some(bucket) = self.buckets.lookup(&key, guard) {
+                let bucket = bucket.lock().unwrap();
+                if bucket.evicted {
+                    continue;
+                }
+                break bucket;
+            }
+            let bucket = TokenBucket {
+                tokens: self.burst,
+                last_refill: now,
+                evicted: false,
+            };
+            // If another thread inserted the bucket first, look it up again.
+            if self.buckets.insert(&key, Mutex::new(bucket), guard).is_ok() {
+                self.keys.lock().unwrap().push(key);
+            }
+        };
+
+        bucket.refill(now, self.burst, self.per_second);
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimiter;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    const ALICE: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const BOB: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn rate_limiter_burst() {
+        let limiter = RateLimiter::new(2, 0.0);
+        assert!(limiter.check(ALICE));
+        assert!(limiter.check(ALICE));
+        assert!(!limiter.check(ALICE));
+        assert!(limiter.check(BOB));
+    }
+
+    #[test]
+    fn rate_limiter_refill() {
+        let limiter = RateLimiter::new(1, 100.0);
+        assert!(limiter.check(ALICE));
+        assert!(!limiter.check(ALICE));
+        sleep(Duration::from_millis(50));
+        assert!(limiter.check(ALICE));
+    }
+
+    #[test]
+    fn rate_limiter_evict_idle() {
+        let limiter = RateLimiter::new(1, 100.0);
+        assert!(limiter.check(ALICE));
+        assert!(limiter.check(BOB));
+        assert_eq!(limiter.len(), 2);
+        limiter.evict_idle();
+        assert_eq!(limiter.len(), 2);
+
+        // Full again.
+        sleep(Duration::from_millis(50));
+        assert!(limiter.check(ALICE));
+        limiter.evict_idle();
+        assert_eq!(limiter.len(), 1);
+        assert!(limiter.check(BOB));
+        assert!(!limiter.check(BOB));
+        assert_eq!(limiter.len(), 2);
+    }
+}
@@ -0,0 +1,212 @@
+//! Middleware that caches the responses to GET requests.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use super::cache::Cache;
+use super::http::{Request, Response};
+use super::middleware::{Middleware, Next};
+
+/// The default maximum number of cached responses.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Cached response and when it expires.
+#[derive(Debug, Clone)]
+struct Entry {
+    response: Response,
+    expires: Instant,
+}
+
+/// Middleware that caches the successful responses to GET requests, keyed by the host and the
+/// request target.
+///
+/// A response is fresh for the `max-age` of its `Cache-Control` header, or for the default TTL if
+/// absent. Responses with `no-store`, `no-cache`, or `private` are not reused, and are not kept.
+/// Concurrent identical requests are deduplicated by the cache, so only one of them passes through
+/// the rest of the chain. Cached responses have the `X-Cache: HIT` header, and the others
+/// `X-Cache: MISS`.
+///
+/// At most `capacity` responses are kept. When the cache is full, the expired responses are
+/// evicted, and a new response is not kept if there is still no room for it.
+#[derive(Debug)]
+pub struct ResponseCache {
+    cache: Cache<String, Entry>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl ResponseCache {
+    /// Creates a new response cache, keeping up to 1024 responses, and the responses without
+    /// `max-age` for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_capacity(ttl, DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new response cache as `new`, keeping up to `capacity` responses.
+    pub fn with_capacity(ttl: Duration, capacity: usize) -> Self {
+        ResponseCache {
+            cache: Cache::default(),
+            ttl,
+            capacity,
+        }
+    }
+
+    /// Returns `true` if the entry just computed is kept: it can be reused, and there is room for
+    /// it, if need be after the expired entries are evicted.
+    fn admit(&self, entry: &Entry) -> bool {
+        let now = Instant::now();
+        if entry.expires <= now {
+            return false;
+        }
+        if self.cache.stats().entries > self.capacity {
+            self.cache.retain(|_, entry| entry.expires > now);
+        }
+        self.cache.stats().entries <= self.capacity
+    }
+
+    /// Returns how long the response is fresh, or `None` if it shouldn't be reused.
+    fn freshness(&self, response: &Response) -> Option<Duration> {
+        if response.status() != 200 {
+            return None;
+        }
+        let cache_control = match response.get_header("Cache-Control") {
+            Some(cache_control) => cache_control,
+            None => return Some(self.ttl),
+        };
+        let mut ttl = self.ttl;
+        for directive in cache_control.split(',').map(str::trim) {
+            let directive = directive.to_ascii_lowercase();
+            if directive == "no-store" || directive == "no-cache" || directive == "private" {
+                return None;
+            }
+            if let Some(max_age) = directive.strip_prefix("max-age=") {
+                ttl = Duration::from_secs(max_age.parse().ok()?);
+            }
+        }
+        Some(ttl)
+    }
+}
+
+impl Middleware for ResponseCache {
+    fn call(&self, request: &Request, next: Next<'_>) -> Response {
+        if request.method != "GET" {
+            return next.run(request);
+        }
+        let key = format!("{}{}", request.host().unwrap_or_default(), request.path);
+
+        let computed = Cell::new(false);
+        let mut next = Some(next);
+        let mut compute = |_| {
+            computed.set(true);
+            let response = next.take().unwrap().run(request);
+            let now = Instant::now();
+            let expires = self.freshness(&response).map_or(now, |ttl| now + ttl);
+            Entry { response, expires }
+        };
+
+        let mut entry = self.cache.get_or_insert_with(key.clone(), &mut compute);
+        if entry.expires <= Instant::now() && !computed.get() {
+            // Stale, or not reusable in the first place. Another thread may have refreshed it in
+            // the meantime, in which case the fresh entry is just computed again.
+            self.cache.remove(&key);
+            entry = self.cache.get_or_insert_with(key.clone(), &mut compute);
+        }
+
+        if computed.get() && !self.admit(&entry) {
+            // The others waiting for it still get it.
+            self.cache.remove(&key);
+        }
+
+        let status = if computed.get() { "MISS" } else { "HIT" };
+        entry.response.header("X-Cache", status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ResponseCache;
+    use crate::hello_server::{Middleware, Next, Request, Response};
+    use crossbeam_utils::thread::scope;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn get(path: &str) -> Request {
+        let head = format!("GET {} HTTP/1.1\r\n\r\n", path);
+        Request::parse(head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn response_cache_ttl() {
+        let cache = ResponseCache::new(Duration::from_millis(100));
+        let count = AtomicUsize::new(0);
+        let endpoint = |request: &Request| {
+            count.fetch_add(1, Ordering::Relaxed);
+            match request.path.as_str() {
+                "/private" => Response::new(200).header("Cache-Control", "private"),
+                "/error" => Response::new(500),
+                _ => Response::new(200),
+            }
+        };
+        let call = |path| cache.call(&get(path), Next::new(&[], &endpoint));
+
+        assert_eq!(call("/alice").get_header("X-Cache"), Some("MISS"));
+        assert_eq!(call("/alice").get_header("X-Cache"), Some("HIT"));
+        assert_eq!(call("/bob").get_header("X-Cache"), Some("MISS"));
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+
+        // Expired.
+        sleep(Duration::from_millis(150));
+        assert_eq!(call("/alice").get_header("X-Cache"), Some("MISS"));
+        assert_eq!(count.load(Ordering::Relaxed), 3);
+
+        // Not reusable.
+        call("/private");
+        assert_eq!(call("/private").get_header("X-Cache"), Some("MISS"));
+        call("/error");
+        assert_eq!(call("/error").status(), 500);
+        assert_eq!(count.load(Ordering::Relaxed), 7);
+        assert_eq!(cache.cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn response_cache_capacity() {
+        let cache = ResponseCache::with_capacity(Duration::from_millis(100), 2);
+        let endpoint = |_: &Request| Response::new(200);
+        let call = |path| cache.call(&get(path), Next::new(&[], &endpoint));
+
+        call("/alice");
+        call("/bob");
+        // Full.
+        assert_eq!(call("/carol").get_header("X-Cache"), Some("MISS"));
+        assert_eq!(call("/carol").get_header("X-Cache"), Some("MISS"));
+        assert_eq!(call("/alice").get_header("X-Cache"), Some("HIT"));
+        assert_eq!(cache.cache.stats().entries, 2);
+
+        // The expired responses are evicted.
+        sleep(Duration::from_millis(150));
+        call("/carol");
+        assert_eq!(call("/carol").get_header("X-Cache"), Some("HIT"));
+        assert_eq!(cache.cache.stats().entries, 1);
+    }
+
+    #[test]
+    fn response_cache_dedup() {
+        const NUM_THREADS: usize = 8;
+
+        let cache = ResponseCache::new(Duration::from_secs(60));
+        let count = AtomicUsize::new(0);
+        let endpoint = |_: &Request| {
+            count.fetch_add(1, Ordering::Relaxed);
+            sleep(Duration::from_millis(100));
+            Response::new(200)
+        };
+        scope(|s| {
+            for _ in 0..NUM_THREADS {
+                s.spawn(|_| cache.call(&get("/alice"), Next::new(&[], &endpoint)));
+            }
+        })
+        .unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,268 @@
+//! Routes requests to handlers by method and path pattern.
+
+use core::fmt;
+use std::sync::Arc;
+
+use super::http::{Request, Response};
+
+/// Parameters captured from the path by a pattern.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Params {
+    params: Vec<(String, String)>,
+}
+
+impl Params {
+    /// Returns the value captured by `:name`. The rest of the path matched by a wildcard is
+    /// captured as `*`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+type RouteFn = Arc<dyn Fn(&Request, &Params) -> Response + Send + Sync>;
+
+/// A segment of a path pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Matches the segment as it is.
+    Literal(String),
+    /// `:name`: matches any non-empty segment.
+    Param(String),
+    /// `*`: matches the rest of the path, including nothing.
+    Wildcard,
+}
+
+#[derive(Clone)]
+struct Route {
+    method: String,
+    pattern: String,
+    segments: Vec<Segment>,
+    f: RouteFn,
+}
+
+impl Route {
+    /// Matches the path segments against the pattern.
+    fn matches(&self, path: &[&str]) -> Option<Params> {
+        let mut params = Vec::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard => {
+                    params.push(("*".to_string(), path[i.min(path.len())..].join("/")));
+                    return Some(Params { params });
+                }
+                Segment::Literal(literal) => {
+                    if path.get(i) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => match path.get(i) {
+                    Some(value) if !value.is_empty() => {
+                        params.push((name.clone(), value.to_string()))
+                    }
+                    _ => return None,
+                },
+            }
+        }
+        if self.segments.len() == path.len() {
+            Some(Params { params })
+        } else {
+            None
+        }
+    }
+}
+
+/// Router.
+///
+/// Routes are tried in the order they are registered. If no route matches the path, the fallback
+/// generates 404. If some routes match the path but none of them matches the method, the fallback
+/// generates 405 with the `Allow` header.
+#[derive(Clone)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Arc<dyn Fn(u16) -> Response + Send + Sync>,
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.routes
+                    .iter()
+                    .map(|route| format!("{} {}", route.method, route.pattern)),
+            )
+            .finish()
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Router {
+    /// Creates a new router without routes.
+    pub fn new() -> Self {
+        Router {
+            routes: Vec::new(),
+            fallback: Arc::new(Response::new),
+        }
+    }
+
+    /// Registers a handler for requests with the given method and path pattern.
+    ///
+    /// A pattern is a `/`-separated list of segments. A segment `:name` matches any non-empty
+    /// segment and captures it as `name`, and a segment `*`, which should come last, matches the
+    /// rest of the path. Other segments match themselves.
+    pub fn route<F>(mut self, method: &str, pattern: &str, f: F) -> Self
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        let segments = split_path(pattern)
+            .into_iter()
+            .map(|segment| {
+                if segment == "*" {
+                    Segment::Wildcard
+                } else if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect();
+        self.routes.push(Route {
+            method: method.to_string(),
+            pattern: pattern.to_string(),
+            segments,
+            f: Arc::new(f),
+        });
+        self
+    }
+
+    /// Registers a handler for `GET` requests.
+    pub fn get<F>(self, pattern: &str, f: F) -> Self
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("GET", pattern, f)
+    }
+
+    /// Sets the function that generates the response for the given status code when no route
+    /// matches the request.
+    pub fn fallback<F>(mut self, f: F) -> Self
+    where
+        F: Fn(u16) -> Response + Send + Sync + 'static,
+    {
+        self.fallback = Arc::new(f);
+        self
+    }
+
+    /// Dispatches the request to the matching handler.
+    pub fn dispatch(&self, request: &Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let path = split_path(path);
+
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            if let Some(params) = route.matches(&path) {
+                if route.method == request.method {
+                    return (route.f)(request, &params);
+                }
+                let method = route.method.as_str();
+                if !allowed.contains(&method) {
+                    allowed.push(method);
+                }
+            }
+        }
+
+        if allowed.is_empty() {
+            (self.fallback)(404)
+        } else {
+            (self.fallback)(405).header("Allow", allowed.join(", "))
+        }
+    }
+}
+
+/// Splits the path into segments, ignoring the leading `/`.
+fn split_path(path: &str) -> Vec<&str> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    if path.is_empty() {
+        Vec::new()
+    } else {
+        path.split('/').collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Router;
+    use crate::hello_server::{Request, Response};
+
+    fn get(path: &str) -> Request {
+        Request::parse(format!("GET {} HTTP/1.1\r\n\r\n", path).as_bytes()).unwrap()
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .get("/", |_, _| Response::new(200).body("index"))
+            .get("/hello/:name", |_, params| {
+                Response::new(200).body(format!("hello {}", params.get("name").unwrap()))
+            })
+            .route("POST", "/hello/:name", |_, _| Response::new(200))
+            .get("/static/*", |_, params| {
+                Response::new(200).body(params.get("*").unwrap())
+            })
+    }
+
+    #[test]
+    fn router_params() {
+        let router = router();
+        assert_eq!(router.dispatch(&get("/")), Response::new(200).body("index"));
+        assert_eq!(
+            router.dispatch(&get("/hello/alice?x=1")),
+            Response::new(200).body("hello alice")
+        );
+        assert_eq!(router.dispatch(&get("/hello/")).status(), 404);
+        assert_eq!(router.dispatch(&get("/hello/alice/bob")).status(), 404);
+    }
+
+    #[test]
+    fn router_wildcard() {
+        let router = router();
+        assert_eq!(
+            router.dispatch(&get("/static/css/main.css")),
+            Response::new(200).body("css/main.css")
+        );
+        assert_eq!(
+            router.dispatch(&get("/static")),
+            Response::new(200).body("")
+        );
+    }
+
+    #[test]
+    fn router_method_not_allowed() {
+        let router = router().fallback(|status| Response::new(status).body("oops"));
+        let mut request = get("/hello/alice");
+        request.method = "DELETE".to_string();
+        assert_eq!(
+            router.dispatch(&request),
+            Response::new(405).body("oops").header("Allow", "GET, POST")
+        );
+        assert_eq!(
+            router.dispatch(&get("/nowhere")),
+            Response::new(404).body("oops")
+        );
+
+        // the methods of the routes that are not adjacent are listed once
+        let overlapping = self::router()
+            .get("/hello/*", |_, _| Response::new(200))
+            .fallback(|status| Response::new(status).body("oops"));
+        assert_eq!(
+            overlapping.dispatch(&request),
+            Response::new(405).body("oops").header("Allow", "GET, POST")
+        );
+    }
+}
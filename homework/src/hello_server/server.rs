@@ -0,0 +1,722 @@
+//! Hello server that can be shut down gracefully.
+
+use crossbeam_channel::{unbounded, Sender};
+use crossbeam_utils::thread::scope;
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use super::error::ServerError;
+use super::handler::Handler;
+use super::http::Response;
+use super::metrics::Metrics;
+use super::middleware::Middleware;
+use super::statistics::{Report, ServerStats, Statistics};
+use super::tcp::CancellableTcpListener;
+use super::thread_pool::ThreadPool;
+#[cfg(feature = "tls")]
+use super::tls::TlsAcceptor;
+
+/// Hello server.
+///
+/// The server may listen on multiple addresses, e.g. IPv4 and IPv6, or HTTP and HTTPS ports. Each
+/// listener accepts connections on its own thread, and all connections are handled in the shared
+/// thread pool with the same handler. One of the pool's threads is dedicated to aggregating the
+/// reports into the statistics.
+///
+/// On shutdown, the server stops accepting and drains the connections: the idle keep-alive ones are
+/// closed right away, and the busy ones are closed after their current response, which is sent with
+/// `Connection: close`. The connections that don't finish within the drain timeout are closed
+/// abruptly.
+///
+/// The server has the built-in health endpoints. `/healthz` (liveness) always responds with 200,
+/// and `/readyz` (readiness) responds with 503 once the shutdown has begun or while the thread
+/// pool is saturated. Both report the uptime.
+#[derive(Debug)]
+pub struct Server {
+    listeners: Vec<Listener>,
+    num_threads: usize,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    drain_timeout: Duration,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    handler: Handler,
+    max_connections: Option<(usize, Backpressure)>,
+    stats_path: Option<String>,
+    metrics_path: Option<String>,
+}
+
+/// Listener of a server.
+#[derive(Debug)]
+struct Listener {
+    inner: Arc<CancellableTcpListener>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+impl Listener {
+    /// Responds to the connection with 503 and closes it. TLS connections are just closed.
+    fn reject(&self, mut stream: TcpStream) {
+        #[cfg(feature = "tls")]
+        {
+            if self.tls.is_some() {
+                return;
+            }
+        }
+        let _ = Response::new(503)
+            .header("Connection", "close")
+            .header("Retry-After", "1")
+            .write_to(&mut stream);
+    }
+}
+
+/// Connections being handled, to drain them on shutdown.
+#[derive(Debug, Default)]
+struct Conns {
+    /// The connections by id, and whether each of them is idle between requests.
+    inner: Mutex<HashMap<usize, (TcpStream, Arc<AtomicBool>)>>,
+    empty: Condvar,
+}
+
+impl Conns {
+    /// Registers the connection, and returns its idle flag.
+    fn insert(&self, id: usize, stream: TcpStream) -> Arc<AtomicBool> {
+        let idle = Arc::new(AtomicBool::new(false));
+        let _ = self
+            .inner
+            .lock()
+            .unwrap()
+            .insert(id, (stream, idle.clone()));
+        idle
+    }
+
+    /// Unregisters the connection, and returns its stream.
+    fn remove(&self, id: usize) -> Option<TcpStream> {
+        let mut inner = self.inner.lock().unwrap();
+        let stream = inner.remove(&id).map(|(stream, _)| stream);
+        if inner.is_empty() {
+            self.empty.notify_all();
+        }
+        stream
+    }
+
+    /// Closes the idle connections. Their handlers see the end of the stream, and return.
+    fn close_idle(&self) {
+        for (stream, idle) in self.inner.lock().unwrap().values() {
+            if idle.load(Ordering::SeqCst) {
+                let _ = stream.shutdown(Shutdown::Read);
+            }
+        }
+    }
+
+    /// Waits until all connections are unregistered or the timeout elapses, and closes the
+    /// remaining ones. Returns the number of them.
+    fn drain(&self, timeout: Duration) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let (inner, _) = self
+            .empty
+            .wait_timeout_while(inner, timeout, |inner| !inner.is_empty())
+            .unwrap();
+        for (stream, _) in inner.values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        inner.len()
+    }
+}
+
+/// Address to bind a listener to.
+#[derive(Debug)]
+struct Bind {
+    addrs: io::Result<Vec<SocketAddr>>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+/// Builder of a `Server`.
+///
+/// ```no_run
+/// # use cs492_concur_homework::hello_server::Server;
+/// # use std::time::Duration;
+/// let server = Server::builder()
+///     .bind("127.0.0.1:0")
+///     .workers(4)
+///     .read_timeout(Duration::from_secs(1))
+///     .build()?;
+/// let port = server.local_addr()?.port();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ServerBuilder {
+    binds: Vec<Bind>,
+    workers: usize,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    drain_timeout: Duration,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl ServerBuilder {
+    /// Adds a listener bound to the address. Binding to port 0 lets the OS pick a free port, which
+    /// can be retrieved by `Server::local_addrs`.
+    pub fn bind<A: ToSocketAddrs>(mut self, addr: A) -> Self {
+        self.binds.push(Bind {
+            addrs: addr.to_socket_addrs().map(Iterator::collect),
+            #[cfg(feature = "tls")]
+            tls: None,
+        });
+        self
+    }
+
+    /// Adds a listener bound to the address that accepts TLS connections only.
+    #[cfg(feature = "tls")]
+    pub fn bind_tls<A: ToSocketAddrs>(mut self, addr: A, acceptor: TlsAcceptor) -> Self {
+        self.binds.push(Bind {
+            addrs: addr.to_socket_addrs().map(Iterator::collect),
+            tls: Some(acceptor),
+        });
+        self
+    }
+
+    /// Sets the number of threads in the thread pool. Panics if `workers < 2`, since one thread is
+    /// reserved for the reporter.
+    pub fn workers(mut self, workers: usize) -> Self {
+        assert!(workers >= 2);
+        self.workers = workers;
+        self
+    }
+
+    /// Sets how long the server waits for a request on a connection before closing it.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets how long the server waits for a client to receive a response before closing the
+    /// connection.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Sets how long the server waits for the busy connections to finish on shutdown before
+    /// closing them.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Adds a middleware inside the ones added before. The middlewares are layered outside the
+    /// handler's own ones.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Binds the listeners. Fails if no address is added or any of them cannot be bound.
+    pub fn build(self) -> io::Result<Server> {
+        if self.binds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no address to bind",
+            ));
+        }
+        let mut listeners = Vec::with_capacity(self.binds.len());
+        for bind in self.binds {
+            listeners.push(Listener {
+                inner: Arc::new(CancellableTcpListener::bind(&bind.addrs?[..])?),
+                #[cfg(feature = "tls")]
+                tls: bind.tls,
+            });
+        }
+        Ok(Server {
+            listeners,
+            num_threads: self.workers,
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            drain_timeout: self.drain_timeout,
+            middlewares: self.middlewares,
+            handler: Handler::default(),
+            max_connections: None,
+            stats_path: None,
+            metrics_path: None,
+        })
+    }
+}
+
+/// What to do with a new connection when the server is handling the maximum number of connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Stop accepting until a connection finishes, letting the OS backlog absorb new connections.
+    Block,
+    /// Accept the connection and immediately respond with 503.
+    Reject,
+}
+
+/// Handle for shutting down a running `Server` from another thread.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    listeners: Vec<Arc<CancellableTcpListener>>,
+}
+
+impl ShutdownHandle {
+    /// Signals the server to stop accepting new connections on all listeners. The server's `run`
+    /// returns after finishing the in-flight connections.
+    pub fn shutdown(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for listener in &self.listeners {
+            let cancelled = listener.cancel();
+            if result.is_ok() {
+                result = cancelled;
+            }
+        }
+        result
+    }
+
+    /// Returns whether the shutdown has begun.
+    pub fn is_shutdown(&self) -> bool {
+        self.listeners.iter().any(|listener| listener.is_canceled())
+    }
+}
+
+impl Server {
+    /// The default number of threads in the thread pool.
+    const DEFAULT_WORKERS: usize = 4;
+
+    /// The default time an idle keep-alive connection is kept open.
+    const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The default time a response is waited to be received.
+    const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The default time the busy connections are waited to finish on shutdown.
+    const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The path of the liveness endpoint.
+    pub const LIVENESS_PATH: &'static str = "/healthz";
+
+    /// The path of the readiness endpoint.
+    pub const READINESS_PATH: &'static str = "/readyz";
+
+    /// Returns a builder with the default pool size and timeouts. At least one address must be
+    /// added.
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder {
+            binds: Vec::new(),
+            workers: Self::DEFAULT_WORKERS,
+            read_timeout: Self::DEFAULT_READ_TIMEOUT,
+            write_timeout: Self::DEFAULT_WRITE_TIMEOUT,
+            drain_timeout: Self::DEFAULT_DRAIN_TIMEOUT,
+            middlewares: Vec::new(),
+        }
+    }
+
+    /// Binds the server to `addr`, with a thread pool of `num_threads` threads. Panics if
+    /// `num_threads < 2`, since one thread is reserved for the reporter.
+    pub fn bind<A: ToSocketAddrs>(addr: A, num_threads: usize) -> io::Result<Self> {
+        Self::builder().bind(addr).workers(num_threads).build()
+    }
+
+    /// Sets the handler of the requests.
+    pub fn handler(mut self, handler: Handler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Limits the number of connections handled at a time, either running or queued in the thread
+    /// pool. Panics if `max` is 0.
+    pub fn max_connections(mut self, max: usize, backpressure: Backpressure) -> Self {
+        assert!(max > 0);
+        self.max_connections = Some((max, backpressure));
+        self
+    }
+
+    /// Serves the statistics as JSON at `path`: the request counts, the status code histogram, the
+    /// cache statistics, the thread pool metrics, and the uptime. The request counts include the
+    /// requests on the closed connections only.
+    pub fn stats_endpoint(mut self, path: &str) -> Self {
+        self.stats_path = Some(path.to_string());
+        self
+    }
+
+    /// Serves the metrics in the Prometheus text exposition format at `path`: the request counts
+    /// by status, the latency histogram, the cache statistics, and the thread pool metrics.
+    pub fn metrics_endpoint(mut self, path: &str) -> Self {
+        self.metrics_path = Some(path.to_string());
+        self
+    }
+
+    /// Makes all listeners of the server accept TLS connections only.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, acceptor: TlsAcceptor) -> Self {
+        for listener in &mut self.listeners {
+            listener.tls = Some(acceptor.clone());
+        }
+        self
+    }
+
+    /// Returns the local address of the first listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listeners[0].inner.local_addr()
+    }
+
+    /// Returns the local addresses of the listeners, in the order they are added.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners
+            .iter()
+            .map(|listener| listener.inner.local_addr())
+            .collect()
+    }
+
+    /// Returns a handle for shutting down the server.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            listeners: self
+                .listeners
+                .iter()
+                .map(|listener| listener.inner.clone())
+                .collect(),
+        }
+    }
+
+    /// Runs the server until it is shut down, and returns the statistics.
+    ///
+    /// After the shutdown, the server stops accepting new connections and drains the in-flight
+    /// connections before flushing the statistics.
+    pub fn run(self) -> Statistics {
+        // The thread pool, bounded if the number of connections is limited. One more job is
+        // allowed for the reporter.
+        let pool = match self.max_connections {
+            Some((max, _)) => ThreadPool::bounded(self.num_threads, max + 1),
+            None => ThreadPool::new(self.num_threads),
+        };
+
+        // The live statistics, optionally served by the handler.
+        let stats = Arc::new(ServerStats::new(pool.monitor()));
+        let handler = self
+            .handler
+            .clone()
+            .wrap(&self.middlewares)
+            .health_endpoints(stats.clone(), self.shutdown_handle())
+            .drain_on(self.shutdown_handle());
+        let handler = match &self.stats_path {
+            Some(path) => handler.stats_endpoint(path.clone(), stats.clone()),
+            None => handler,
+        };
+        let handler = match &self.metrics_path {
+            Some(path) => {
+                handler.metrics_endpoint(path.clone(), Arc::new(Metrics::new(pool.monitor())))
+            }
+            None => handler,
+        };
+
+        // The (MPSC) channel of reports between workers and the reporter.
+        let (report_sender, report_receiver) = unbounded();
+
+        // Executes the reporter.
+        let reporter_stats = stats.clone();
+        pool.execute(move || {
+            for report in report_receiver {
+                reporter_stats.add_report(report);
+            }
+        });
+
+        // Runs the accept loop of each listener on its own thread.
+        let next_id = AtomicUsize::new(0);
+        let conns = Arc::new(Conns::default());
+        scope(|s| {
+            for listener in &self.listeners {
+                let report_sender = report_sender.clone();
+                let (server, pool, handler, next_id, conns) =
+                    (&self, &pool, &handler, &next_id, &conns);
+                s.spawn(move |_| {
+                    server.accept_loop(listener, pool, handler, next_id, conns, report_sender)
+                });
+            }
+        })
+        .unwrap();
+
+        // Drains the in-flight connections. The handler no longer keeps the connections alive, so
+        // only the idle ones need to be closed.
+        conns.close_idle();
+        let remaining = conns.drain(self.drain_timeout);
+        if remaining > 0 {
+            handler.log_event(format!(
+                "closed {} connections that didn't drain in time",
+                remaining
+            ));
+        }
+
+        // The reporter finishes when all the senders are gone.
+        drop(report_sender);
+        pool.join();
+        stats.statistics()
+    }
+
+    /// Accepts the connections on the listener, and sends a job to the thread pool for each of
+    /// them.
+    fn accept_loop(
+        &self,
+        listener: &Listener,
+        pool: &ThreadPool,
+        handler: &Handler,
+        next_id: &AtomicUsize,
+        conns: &Arc<Conns>,
+        report_sender: Sender<Report>,
+    ) {
+        for stream in listener.inner.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    handler.log_event(format!("failed to accept: {}", e));
+                    continue;
+                }
+            };
+            let peer = match stream.peer_addr() {
+                Ok(peer) => peer,
+                Err(e) => {
+                    handler.log_event(format!("failed to get peer address: {}", e));
+                    continue;
+                }
+            };
+            if let Err(e) = stream
+                .set_read_timeout(Some(self.read_timeout))
+                .and_then(|_| stream.set_write_timeout(Some(self.write_timeout)))
+            {
+                handler.log_event(format!("failed to set timeout: {}", e));
+                continue;
+            }
+            // Keeps the stream to close it when draining, or to respond to if it is rejected.
+            let tracked = match stream.try_clone() {
+                Ok(tracked) => tracked,
+                Err(e) => {
+                    handler.log_event(format!("failed to clone stream: {}", e));
+                    continue;
+                }
+            };
+            let id = next_id.fetch_add(1, Ordering::Relaxed);
+            let idle = conns.insert(id, tracked);
+            let job_conns = conns.clone();
+            let report_sender = report_sender.clone();
+            let handler = handler.clone();
+            #[cfg(feature = "tls")]
+            let tls = listener.tls.clone();
+            let job = move || {
+                // The handler catches the panics of the routes, so this is the last resort that
+                // keeps the worker alive, e.g. if the logger panics.
+                let reports = panic::catch_unwind(AssertUnwindSafe(|| {
+                    #[cfg(feature = "tls")]
+                    let reports = match tls {
+                        Some(tls) => handler.handle_conn_with(id, peer, tls.accept(stream), &idle),
+                        None => handler.handle_conn_with(id, peer, stream, &idle),
+                    };
+                    #[cfg(not(feature = "tls"))]
+                    let reports = handler.handle_conn_with(id, peer, stream, &idle);
+                    reports
+                }))
+                .unwrap_or_else(|payload| {
+                    let error = ServerError::from_panic(payload);
+                    handler.log_event(format!("connection from {} failed: {}", peer, error));
+                    vec![Report::failed(id, &error)]
+                });
+                for report in reports {
+                    // The reporter outlives the jobs.
+                    let _ = report_sender.send(report);
+                }
+                let _ = job_conns.remove(id);
+            };
+
+            match self.max_connections {
+                Some((_, Backpressure::Reject)) => {
+                    if pool.try_execute(job).is_err() {
+                        if let Some(rejected) = conns.remove(id) {
+                            listener.reject(rejected);
+                        }
+                    }
+                }
+                _ => pool.execute(job),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Backpressure, Server};
+    use crate::hello_server::{Handler, NullLogger, Response, Router};
+    use crossbeam_channel::bounded;
+    use crossbeam_utils::thread::scope;
+    use std::io::prelude::*;
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn server_shutdown_handle() {
+        let server = Server::bind("127.0.0.1:0", 4).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        let (done_sender, done_receiver) = bounded(0);
+        scope(|s| {
+            s.spawn(move |_| {
+                let _ = server.run();
+                done_sender.send(()).unwrap();
+            });
+
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut resp = String::new();
+            stream.read_to_string(&mut resp).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 404"));
+
+            handle.shutdown().unwrap();
+            done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn server_health_endpoints() {
+        let server = Server::bind("127.0.0.1:0", 4).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            s.spawn(move |_| server.run());
+
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut get = |path: &str| {
+                write!(stream, "GET {} HTTP/1.1\r\n\r\n", path).unwrap();
+                let mut buf = [0; 12];
+                stream.read_exact(&mut buf).unwrap();
+                let mut rest = [0; 1024];
+                let _ = stream.read(&mut rest).unwrap();
+                buf
+            };
+            assert_eq!(&get("/healthz"), b"HTTP/1.1 200");
+            assert_eq!(&get("/readyz"), b"HTTP/1.1 200");
+
+            // A request in flight during the shutdown is still served.
+            let mut draining = TcpStream::connect(addr).unwrap();
+            draining.write_all(b"GET /readyz HTTP/1.1\r\n").unwrap();
+            thread::sleep(Duration::from_millis(100));
+            handle.shutdown().unwrap();
+            draining.write_all(b"\r\n").unwrap();
+            let mut resp = String::new();
+            draining.read_to_string(&mut resp).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 503"));
+            assert!(resp.contains("\"status\":\"shutting_down\""));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn server_multiple_listeners() {
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .bind("127.0.0.1:0")
+            .build()
+            .unwrap();
+        let addrs = server.local_addrs().unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert_ne!(addrs[0], addrs[1]);
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            let server = s.spawn(move |_| server.run());
+            for addr in &addrs {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+                let mut resp = String::new();
+                stream.read_to_string(&mut resp).unwrap();
+                assert!(resp.starts_with("HTTP/1.1 404"));
+            }
+            handle.shutdown().unwrap();
+            assert_eq!(server.join().unwrap().num_responses(404), 2);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn server_max_connections_reject() {
+        let server = Server::bind("127.0.0.1:0", 4)
+            .unwrap()
+            .max_connections(1, Backpressure::Reject);
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            s.spawn(move |_| server.run());
+
+            // The first connection is kept alive.
+            let mut first = TcpStream::connect(addr).unwrap();
+            first.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            let mut buf = [0; 12];
+            first.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"HTTP/1.1 404");
+
+            let mut second = TcpStream::connect(addr).unwrap();
+            let mut resp = String::new();
+            second.read_to_string(&mut resp).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 503"));
+
+            drop(first);
+            handle.shutdown().unwrap();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn server_drain() {
+        let router = Router::new().get("/slow", |_, _| {
+            thread::sleep(Duration::from_millis(300));
+            Response::new(200)
+        });
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .read_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap()
+            .handler(Handler::new(router).logger(NullLogger));
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+
+        scope(|s| {
+            let server = s.spawn(move |_| server.run());
+
+            // Idle between requests.
+            let mut idle = TcpStream::connect(addr).unwrap();
+            idle.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            let mut buf = [0; 12];
+            idle.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"HTTP/1.1 404");
+
+            // Busy with a request when the server is shut down.
+            let mut busy = TcpStream::connect(addr).unwrap();
+            busy.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+            thread::sleep(Duration::from_millis(100));
+
+            let start = Instant::now();
+            handle.shutdown().unwrap();
+            let mut resp = String::new();
+            busy.read_to_string(&mut resp).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 200"));
+            assert!(resp.contains("Connection: close\r\n"));
+            let mut rest = Vec::new();
+            idle.read_to_end(&mut rest).unwrap();
+
+            // Doesn't wait for the read timeout.
+            assert_eq!(server.join().unwrap().num_requests(), 2);
+            assert!(start.elapsed() < Duration::from_secs(2));
+        })
+        .unwrap();
+    }
+}
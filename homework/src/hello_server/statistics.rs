@@ -1,31 +1,249 @@
 //! Server statisics
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::error::ServerError;
+use super::thread_pool::PoolMonitor;
 
 /// Report for each operation
 #[derive(Debug)]
 pub struct Report {
     id: usize,
     key: Option<String>, // None represents invalid request
+    status: Option<u16>,
+    error: Option<&'static str>,
 }
 
 impl Report {
     /// Creates a new report with the given id and key.
     pub fn new(id: usize, key: Option<String>) -> Self {
-        Report { id, key }
+        Report {
+            id,
+            key,
+            status: None,
+            error: None,
+        }
+    }
+
+    /// Creates a report of a connection that failed without a response, e.g. it was reset.
+    pub fn failed(id: usize, error: &ServerError) -> Self {
+        Report {
+            id,
+            key: None,
+            status: None,
+            error: Some(error.kind()),
+        }
+    }
+
+    /// Sets the status code of the response.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Sets the error that the response is for.
+    pub fn with_error(mut self, error: &ServerError) -> Self {
+        self.error = Some(error.kind());
+        self
     }
 }
 
 /// Operation statisics
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Statistics {
     hits: HashMap<Option<String>, usize>,
+    statuses: BTreeMap<u16, usize>,
+    errors: BTreeMap<&'static str, usize>,
 }
 
 impl Statistics {
     /// Add a report to the statisics.
     pub fn add_report(&mut self, report: Report) {
+        if let Some(error) = report.error {
+            *self.errors.entry(error).or_default() += 1;
+            if report.status.is_none() {
+                // Not a request.
+                return;
+            }
+        }
         let hits = self.hits.entry(report.key).or_default();
         *hits += 1;
+        if let Some(status) = report.status {
+            *self.statuses.entry(status).or_default() += 1;
+        }
+    }
+
+    /// Returns the number of requests.
+    pub fn num_requests(&self) -> usize {
+        self.hits.values().sum()
+    }
+
+    /// Returns the number of requests for the key. `None` counts the invalid requests.
+    pub fn hits(&self, key: Option<&str>) -> usize {
+        self.hits
+            .get(&key.map(String::from))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of responses with the status code.
+    pub fn num_responses(&self, status: u16) -> usize {
+        self.statuses.get(&status).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of errors of the class, e.g. `"parse"`. See `ServerError::kind`.
+    pub fn num_errors(&self, kind: &str) -> usize {
+        self.errors.get(kind).copied().unwrap_or_default()
+    }
+
+    /// Formats the statistics as a JSON object.
+    pub fn to_json(&self) -> String {
+        let keys = self
+            .hits
+            .iter()
+            .filter_map(|(key, hits)| key.as_ref().map(|key| (key, hits)))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut json = format!(
+            "{{\"requests\":{},\"invalid_requests\":{},\"keys\":{{",
+            self.num_requests(),
+            self.hits(None)
+        );
+        for (i, (key, hits)) in keys.into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "{}:{}", json_string(key), hits).unwrap();
+        }
+        json.push_str("},\"statuses\":{");
+        for (i, (status, count)) in self.statuses.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "\"{}\":{}", status, count).unwrap();
+        }
+        json.push_str("},\"errors\":{");
+        for (i, (kind, count)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "\"{}\":{}", kind, count).unwrap();
+        }
+        json.push_str("}}");
+        json
+    }
+}
+
+/// Formats the string as a JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Live statistics of a running server.
+#[derive(Debug)]
+pub(crate) struct ServerStats {
+    started: Instant,
+    statistics: Mutex<Statistics>,
+    pool: PoolMonitor,
+}
+
+impl ServerStats {
+    /// Creates the statistics of a server that starts now with the thread pool.
+    pub(crate) fn new(pool: PoolMonitor) -> Self {
+        ServerStats {
+            started: Instant::now(),
+            statistics: Mutex::new(Statistics::default()),
+            pool,
+        }
+    }
+
+    /// Add a report to the statistics.
+    pub(crate) fn add_report(&self, report: Report) {
+        self.statistics.lock().unwrap().add_report(report);
+    }
+
+    /// Returns a snapshot of the statistics.
+    pub(crate) fn statistics(&self) -> Statistics {
+        self.statistics.lock().unwrap().clone()
+    }
+
+    /// Returns the time since the server started.
+    pub(crate) fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns whether the thread pool has no room for more connections: the pool is full if
+    /// bounded, or there are jobs waiting for a thread otherwise.
+    pub(crate) fn is_saturated(&self) -> bool {
+        let jobs = self.pool.num_jobs();
+        match self.pool.max_jobs() {
+            Some(max) => jobs >= max,
+            None => jobs > self.pool.size(),
+        }
+    }
+
+    /// Formats the uptime, statistics, and pool metrics as JSON object fields.
+    pub(crate) fn json_fields(&self) -> String {
+        format!(
+            "\"uptime_secs\":{:.3},\"statistics\":{},\"pool\":{{\"threads\":{},\"jobs\":{},\"max_jobs\":{}}}",
+            self.uptime().as_secs_f64(),
+            self.statistics.lock().unwrap().to_json(),
+            self.pool.size(),
+            self.pool.num_jobs(),
+            self.pool
+                .max_jobs()
+                .map_or_else(|| "null".to_string(), |max| max.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{json_string, Report, Statistics};
+    use crate::hello_server::ServerError;
+    use std::io;
+
+    #[test]
+    fn statistics_to_json() {
+        let mut stats = Statistics::default();
+        stats.add_report(Report::new(0, Some("bob".to_string())).with_status(200));
+        stats.add_report(Report::new(1, Some("alice".to_string())).with_status(200));
+        stats.add_report(Report::new(2, Some("alice".to_string())).with_status(200));
+        stats.add_report(Report::new(3, None).with_status(404));
+        stats.add_report(
+            Report::new(4, None)
+                .with_status(400)
+                .with_error(&ServerError::Parse("invalid request head")),
+        );
+        // Not counted as a request.
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        stats.add_report(Report::failed(5, &ServerError::Io(reset)));
+        assert_eq!(stats.num_requests(), 5);
+        assert_eq!(stats.hits(Some("alice")), 2);
+        assert_eq!(stats.num_responses(404), 1);
+        assert_eq!(stats.num_errors("io"), 1);
+        assert_eq!(
+            stats.to_json(),
+            "{\"requests\":5,\"invalid_requests\":2,\"keys\":{\"alice\":2,\"bob\":1},\"statuses\":{\"200\":3,\"400\":1,\"404\":1},\"errors\":{\"io\":1,\"parse\":1}}"
+        );
+    }
+
+    #[test]
+    fn json_string_escape() {
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
     }
 }
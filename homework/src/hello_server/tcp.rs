@@ -1,7 +1,7 @@
 //! TcpListener that can be cancelled.
 
 use std::io;
-use std::net::ToSocketAddrs;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::net::{TcpListener, TcpStream};
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -34,6 +34,11 @@ impl CancellableTcpListener {
         })
     }
 
+    /// Wraps `TcpListener::local_addr`.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
     /// Signals the listener to stop accepting new connections.
     pub fn cancel(&self) -> io::Result<()> {
         // Set the flag first and make a bogus connection to itself to wake up the listener blocked
@@ -43,6 +48,11 @@ impl CancellableTcpListener {
         Ok(())
     }
 
+    /// Returns whether the listener is `cancel`led.
+    pub fn is_canceled(&self) -> bool {
+        self.is_canceled.load(Ordering::Acquire)
+    }
+
     /// Returns an iterator over the connections being received on this listener.  The returned
     /// iterator will return `None` if the listener is `cancel`led.
     pub fn incoming(&self) -> Incoming {
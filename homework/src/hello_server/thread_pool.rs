@@ -17,7 +17,6 @@ enum Message{
 
 #[derive(Debug)]
 struct Worker {
-    id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
 
@@ -37,13 +36,30 @@ impl Drop for Worker {
 struct ThreadPoolInner {
     job_count: Mutex<usize>,
     empty_condvar: Condvar,
+    /// The maximum number of jobs, if bounded.
+    max_jobs: Option<usize>,
+    not_full_condvar: Condvar,
 }
 
 impl ThreadPoolInner {
-    /// Increment the job count.
+    /// Increment the job count. If the pool is bounded, wait until the job count becomes less
+    /// than the maximum.
     fn start_job(&self) {
         let mut count = self.job_count.lock().unwrap();
+        while self.max_jobs.map_or(false, |max| *count >= max) {
+            count = self.not_full_condvar.wait(count).unwrap();
+        }
+        *count += 1;
+    }
+
+    /// Like `start_job`, but returns `false` instead of waiting.
+    fn try_start_job(&self) -> bool {
+        let mut count = self.job_count.lock().unwrap();
+        if self.max_jobs.map_or(false, |max| *count >= max) {
+            return false;
+        }
         *count += 1;
+        true
     }
 
     /// Decrement the job count.
@@ -54,6 +70,7 @@ impl ThreadPoolInner {
         if *count ==0 {
             self.empty_condvar.notify_one();
         }
+        self.not_full_condvar.notify_one();
     }
 
     /// Wait until the job count becomes 0.
@@ -68,6 +85,31 @@ impl ThreadPoolInner {
     }
 }
 
+/// Handle for observing the job status of a thread pool. Unlike the pool, it doesn't own the
+/// worker threads.
+#[derive(Debug, Clone)]
+pub struct PoolMonitor {
+    size: usize,
+    pool_inner: Arc<ThreadPoolInner>,
+}
+
+impl PoolMonitor {
+    /// Returns the number of threads.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of jobs, either running or queued.
+    pub fn num_jobs(&self) -> usize {
+        *self.pool_inner.job_count.lock().unwrap()
+    }
+
+    /// Returns the maximum number of jobs if the pool is bounded.
+    pub fn max_jobs(&self) -> Option<usize> {
+        self.pool_inner.max_jobs
+    }
+}
+
 /// Thread pool.
 #[derive(Debug)]
 pub struct ThreadPool {
@@ -79,6 +121,19 @@ pub struct ThreadPool {
 impl ThreadPool {
     /// Create a new ThreadPool with `size` threads. Panics if the size is 0.
     pub fn new(size: usize) -> Self {
+        Self::with_max_jobs(size, None)
+    }
+
+    /// Create a new bounded ThreadPool with `size` threads, which holds at most `max_jobs` jobs,
+    /// either running or queued, at a time. Panics if the size or `max_jobs` is 0.
+    ///
+    /// When the pool is full, `execute` blocks until a job finishes, and `try_execute` fails.
+    pub fn bounded(size: usize, max_jobs: usize) -> Self {
+        assert!(max_jobs > 0);
+        Self::with_max_jobs(size, Some(max_jobs))
+    }
+
+    fn with_max_jobs(size: usize, max_jobs: Option<usize>) -> Self {
         assert!(size > 0);
         
         let (sender, receiver) = unbounded();
@@ -88,29 +143,26 @@ impl ThreadPool {
         let pool_inner = ThreadPoolInner{
             job_count: Mutex::new(0),
             empty_condvar: Condvar::new(),
+            max_jobs,
+            not_full_condvar: Condvar::new(),
         };
         let pool_inner = Arc::new(pool_inner);
 
-        for id in 0..size {
+        for _ in 0..size {
             let worker_inner = pool_inner.clone();
             let worker_receiver = receiver.clone();
             let thread = thread::spawn(move || loop{
                 let msg:Message = worker_receiver.recv().unwrap();
                 match msg {
                     Message::NewJob(job) =>{
-                        println!("Worker {} got a job; executing.", id);
                         job.0();
                         worker_inner.finish_job();
                     }
-                    Message::Terminate => {
-                        println!("Worker {} was told to terminate.",id);
-                        break;
-                    }
+                    Message::Terminate => break,
                 }
             });
 
             let worker = Worker{
-                id,
                 thread: Some(thread),
             };
             workers.push(worker);
@@ -133,6 +185,27 @@ impl ThreadPool {
         self.job_sender.as_ref().unwrap().send(Message::NewJob(job)).unwrap();
     }
 
+    /// Like `execute`, but gives the job back instead of blocking if the pool is full.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if !self.pool_inner.try_start_job() {
+            return Err(f);
+        }
+        let job = Job(Box::new(f));
+        self.job_sender.as_ref().unwrap().send(Message::NewJob(job)).unwrap();
+        Ok(())
+    }
+
+    /// Returns a handle for observing the job status of the pool.
+    pub fn monitor(&self) -> PoolMonitor {
+        PoolMonitor {
+            size: self.workers.len(),
+            pool_inner: self.pool_inner.clone(),
+        }
+    }
+
     /// Block the current thread until all jobs in the pool have been executed.  NOTE: This method
     /// has nothing to do with `JoinHandle::join`.
     pub fn join(&self) {
@@ -148,8 +221,6 @@ impl Drop for ThreadPool {
             self.job_sender.as_ref().unwrap().send(Message::Terminate).unwrap();
         }
         for worker in &mut self.workers{
-            println!("Shutting down worker {}", worker.id);
-
             if let Some(thread) = worker.thread.take(){
                 thread.join().unwrap();
             }
@@ -218,6 +289,22 @@ mod test {
         assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
     }
 
+    /// `execute` blocks and `try_execute` fails while a bounded pool is full.
+    #[test]
+    fn thread_pool_bounded() {
+        let pool = ThreadPool::bounded(NUM_THREADS, 1);
+        let (quit_sender, quit_receiver) = bounded::<()>(0);
+        pool.execute(move || {
+            quit_receiver.recv().unwrap();
+        });
+        assert!(pool.try_execute(|| ()).is_err());
+        quit_sender.send(()).unwrap();
+        pool.join();
+        assert!(pool.try_execute(|| ()).is_ok());
+        pool.execute(|| ());
+        pool.join();
+    }
+
     /// This indirectly tests if the worker threads' `JoinHandle`s are joined when the pool is
     /// dropped.
     #[test]
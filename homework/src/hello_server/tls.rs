@@ -0,0 +1,67 @@
+//! TLS support for the server.
+
+use core::fmt;
+use rustls::internal::pemfile;
+use rustls::{Certificate, NoClientAuth, PrivateKey, ServerConfig, ServerSession, StreamOwned};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Wraps accepted TCP streams into TLS streams.
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    config: Arc<ServerConfig>,
+}
+
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsAcceptor").finish()
+    }
+}
+
+impl TlsAcceptor {
+    /// Creates a new acceptor with the given configuration.
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        TlsAcceptor { config }
+    }
+
+    /// Creates a new acceptor that presents the certificate chain and its private key, without
+    /// client authentication.
+    pub fn with_cert(cert_chain: Vec<Certificate>, key: PrivateKey) -> io::Result<Self> {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config
+            .set_single_cert(cert_chain, key)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Self::new(Arc::new(config)))
+    }
+
+    /// Like `with_cert`, but reads the certificate chain and the private key (PKCS#8 or RSA) from
+    /// PEM files.
+    pub fn from_pem_files<P: AsRef<Path>, Q: AsRef<Path>>(cert: P, key: Q) -> io::Result<Self> {
+        let invalid = |what| io::Error::new(io::ErrorKind::InvalidData, what);
+
+        let cert_chain = pemfile::certs(&mut BufReader::new(File::open(cert)?))
+            .map_err(|_| invalid("invalid certificate"))?;
+
+        let key = {
+            let mut reader = BufReader::new(File::open(&key)?);
+            let mut keys = pemfile::pkcs8_private_keys(&mut reader)
+                .map_err(|_| invalid("invalid private key"))?;
+            if keys.is_empty() {
+                let mut reader = BufReader::new(File::open(&key)?);
+                keys = pemfile::rsa_private_keys(&mut reader)
+                    .map_err(|_| invalid("invalid private key"))?;
+            }
+            keys.pop().ok_or_else(|| invalid("no private key"))?
+        };
+
+        Self::with_cert(cert_chain, key)
+    }
+
+    /// Wraps the stream. The handshake is performed lazily on the first read or write.
+    pub fn accept(&self, stream: TcpStream) -> StreamOwned<ServerSession, TcpStream> {
+        StreamOwned::new(ServerSession::new(&self.config), stream)
+    }
+}
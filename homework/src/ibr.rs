@@ -0,0 +1,499 @@
+//! Interval-based reclamation (IBR), the third reclamation scheme besides `hazard_pointer` and
+//! `ebr`/`qsbr`.
+//!
+//! A global era is advanced every `Local::ALLOC_FREQ` allocations of a thread. Each object is
+//! stamped with the era it is allocated in (`Stamped::new`) and the era it is retired in. A pinned
+//! thread reserves the interval of eras from the one it is pinned in to the last one it loads a
+//! pointer in (`Guard::protect`), and an object is freed once its lifetime doesn't intersect with
+//! any reserved interval. Unlike `ebr`, a stalled thread only keeps alive the objects that are
+//! allocated before its interval ends, so the garbage is bounded.
+//!
+//! This is the variant of the paper that reserves the interval with two eras per thread (2GEIBR).
+//! As with the hazard eras, the pointers are not validated after they are loaded.
+//!
+//! Reference: Haosen Wen, Joseph Izraelevitz, Wentao Cai, H. Alan Beadle, and Michael L. Scott.
+//! Interval-Based Memory Reclamation. PPoPP 2018.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use cs492_concur_homework::ibr::{collect, pin, retire, Atomic, Shared, Stamped};
+//!
+//! let atomic = Atomic::null();
+//! atomic.store(Stamped::new(1).into_shared(), Ordering::Release);
+//!
+//! let guard = pin();
+//! let shared = guard.protect(&atomic);
+//! assert_eq!(**unsafe { shared.deref() }, 1);
+//!
+//! atomic.store(Shared::null(), Ordering::Relaxed);
+//! retire(shared);
+//! drop(guard);
+//! collect();
+//! ```
+//!
+//! # Algorithm and Synchronization
+//!
+//! As with the hazard eras, the lower end of an interval is published before the thread loads a
+//! pointer, and the upper end is published before the pointer is used, with a SC fence after each
+//! of them. The era an object is retired in is loaded after it is unlinked, and `collect` scans the
+//! intervals after a SC fence, so either the interval of a reader is seen, or the reader doesn't
+//! load the object.
+
+use core::cell::{Cell, RefCell};
+use core::cmp;
+use core::iter;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+pub use crate::hazard_pointer::{Atomic, Owned, Shared};
+
+/// The global era.
+static ERA: AtomicUsize = AtomicUsize::new(0);
+
+/// The intervals reserved by all threads.
+static RESERVATIONS: Reservations = Reservations::new();
+
+/// The retired objects left by the exited threads.
+static GLOBAL_RETIRED: GlobalIbrRetirees = GlobalIbrRetirees::new();
+
+thread_local! {
+    /// Thread-local list of retired objects.
+    static RETIRED: RefCell<IbrRetirees> = RefCell::new(IbrRetirees::new());
+
+    /// The reservation of the current thread.
+    static LOCAL: Local = Local::new();
+}
+
+/// An object stamped with the era it is allocated in. The objects protected by `Guard` must be
+/// allocated with `Stamped::new`.
+///
+/// It is `#[repr(C)]`, so that a pointer to the data can be converted back to the `Stamped` (see
+/// `alloc_data`).
+#[derive(Debug)]
+#[repr(C)]
+pub struct Stamped<T> {
+    birth: usize,
+    data: T,
+}
+
+impl<T> Stamped<T> {
+    /// Allocates `data` stamped with the current era.
+    pub fn new(data: T) -> Owned<Self> {
+        LOCAL.with(|l| l.alloc());
+        Owned::new(Self {
+            birth: ERA.load(Ordering::SeqCst),
+            data,
+        })
+    }
+
+    /// Returns the era the object is allocated in.
+    pub fn birth(&self) -> usize {
+        self.birth
+    }
+
+    /// The offset of `data`, which follows `birth` as `Stamped` is `#[repr(C)]`.
+    fn data_offset() -> usize {
+        let align = mem::align_of::<T>();
+        (mem::size_of::<usize>() + align - 1) & !(align - 1)
+    }
+
+    /// Converts a pointer to the data returned by `alloc_data` back to the `Stamped`.
+    fn from_data(pointer: Shared<T>) -> Shared<Self> {
+        let data = pointer.as_raw() as *const u8;
+        Shared::from(data.wrapping_sub(Self::data_offset()) as *const Self)
+    }
+}
+
+impl<T> Deref for Stamped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Stamped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+/// The interval of eras reserved by a thread. It is empty (`lower > upper`) while the thread is not
+/// pinned.
+#[derive(Debug)]
+struct Reservation {
+    next: AtomicPtr<Reservation>,
+    /// Whether a thread owns the entry.
+    active: AtomicBool,
+    lower: AtomicUsize,
+    upper: AtomicUsize,
+}
+
+impl Reservation {
+    fn new() -> Self {
+        Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            lower: AtomicUsize::new(usize::MAX),
+            upper: AtomicUsize::new(0),
+        }
+    }
+
+    fn reserve(&self, era: usize) {
+        self.lower.store(era, Ordering::Release);
+        self.upper.store(era, Ordering::Release);
+        fence(Ordering::SeqCst);
+    }
+
+    fn release(&self) {
+        self.lower.store(usize::MAX, Ordering::Release);
+        self.upper.store(0, Ordering::Release);
+    }
+
+    /// Returns the interval as `(lower, upper)`.
+    fn interval(&self) -> (usize, usize) {
+        (
+            self.lower.load(Ordering::Acquire),
+            self.upper.load(Ordering::Acquire),
+        )
+    }
+}
+
+/// Append-only lock-free list of the reservations. The entry of an exited thread is reused by the
+/// next one, as with `Hazards::register`.
+#[derive(Debug)]
+struct Reservations {
+    head: AtomicPtr<Reservation>,
+}
+
+impl Reservations {
+    const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn register(&self) -> &Reservation {
+        let mut prev = &self.head;
+        let mut cur = prev.load(Ordering::Acquire);
+        loop {
+            if cur.is_null() {
+                let new = Box::into_raw(Box::new(Reservation::new()));
+                match prev.compare_exchange(cur, new, Ordering::Release, Ordering::Acquire) {
+                    Ok(_) => return unsafe { &*new },
+                    Err(current) => {
+                        unsafe { drop(Box::from_raw(new)) };
+                        cur = current;
+                        continue;
+                    }
+                }
+            }
+            let cur_ref = unsafe { &*cur };
+            if cur_ref
+                .active
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return cur_ref;
+            }
+            prev = &cur_ref.next;
+            cur = prev.load(Ordering::Acquire);
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Reservation> {
+        iter::successors(
+            unsafe { self.head.load(Ordering::Acquire).as_ref() },
+            |r| unsafe { r.next.load(Ordering::Acquire).as_ref() },
+        )
+    }
+}
+
+/// The state of the current thread: its reservation, the number of its guards, and the number of
+/// its allocations.
+#[derive(Debug)]
+struct Local {
+    reservation: &'static Reservation,
+    guards: Cell<usize>,
+    allocs: Cell<usize>,
+}
+
+impl Local {
+    /// The global era is advanced every this number of allocations of a thread.
+    const ALLOC_FREQ: usize = 32;
+
+    fn new() -> Self {
+        Self {
+            reservation: RESERVATIONS.register(),
+            guards: Cell::new(0),
+            allocs: Cell::new(0),
+        }
+    }
+
+    fn alloc(&self) {
+        let allocs = self.allocs.get() + 1;
+        if allocs == Self::ALLOC_FREQ {
+            let _ = ERA.fetch_add(1, Ordering::SeqCst);
+            self.allocs.set(0);
+        } else {
+            self.allocs.set(allocs);
+        }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // A guard may be leaked, or outlive the thread-local.
+        self.reservation.release();
+        self.reservation.active.store(false, Ordering::Release);
+    }
+}
+
+/// Pins the current thread, reserving the current era. The pointers loaded by `Guard::protect` are
+/// not freed until it is dropped.
+pub fn pin() -> Guard {
+    let reservation = LOCAL.with(|l| {
+        let guards = l.guards.get();
+        if guards == 0 {
+            l.reservation.reserve(ERA.load(Ordering::SeqCst));
+        }
+        l.guards.set(guards + 1);
+        l.reservation
+    });
+    Guard {
+        reservation,
+        _marker: PhantomData,
+    }
+}
+
+/// A witness that the current thread is pinned.
+///
+/// The guards may be nested, and the interval is released when the last one is dropped.
+#[derive(Debug)]
+pub struct Guard {
+    reservation: &'static Reservation,
+    /// The reservation is owned by the current thread.
+    _marker: PhantomData<*const ()>,
+}
+
+impl Guard {
+    /// Loads a pointer from `atomic` and protects it. Extends the interval to the current era first
+    /// if it ends before, and loads again until the era doesn't change.
+    pub fn protect<T>(&self, atomic: &Atomic<Stamped<T>>) -> Shared<Stamped<T>> {
+        self.protect_data(atomic)
+    }
+
+    /// Loads a pointer from `atomic` and protects it as `protect`. The pointer must be allocated by
+    /// `Stamped::new` or `alloc_data`.
+    pub(crate) fn protect_data<T>(&self, atomic: &Atomic<T>) -> Shared<T> {
+        let mut upper = self.reservation.upper.load(Ordering::Relaxed);
+        loop {
+            let pointer = atomic.load(Ordering::Acquire);
+            let era = ERA.load(Ordering::SeqCst);
+            if era == upper {
+                return pointer;
+            }
+            self.reservation.upper.store(era, Ordering::Release);
+            upper = era;
+            fence(Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = LOCAL.try_with(|l| {
+            let guards = l.guards.get();
+            debug_assert!(guards > 0);
+            if guards == 1 {
+                l.reservation.release();
+            }
+            l.guards.set(guards - 1);
+        });
+    }
+}
+
+/// Retires an object unlinked from the data structure. It is freed once its lifetime doesn't
+/// intersect with the interval of any thread, possibly by another thread, so `T` must be `Send`.
+pub fn retire<T: Send>(pointer: Shared<Stamped<T>>) {
+    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+}
+
+/// Frees the objects that are `retire`d by the current thread or left by the exited threads, and
+/// not protected by the interval of any thread.
+pub fn collect() {
+    RETIRED.with(|r| r.borrow_mut().collect());
+}
+
+/// Allocates `data` as a `Stamped` and returns the pointer to its data, e.g. for the data structures
+/// generic over the reclamation scheme (`hazard_pointer::reclaimer::Ibr`) whose pointers can't be
+/// to `Stamped`. The pointer is protected by `Guard::protect_data`, and is retired by
+/// `retire_data` or freed by `dealloc_data`.
+pub(crate) fn alloc_data<T>(data: T) -> Shared<T> {
+    let stamped = Stamped::new(data).into_shared();
+    let data = stamped.as_raw() as *const u8;
+    Shared::from(data.wrapping_add(Stamped::<T>::data_offset()) as *const T)
+}
+
+/// Retires a pointer returned by `alloc_data` as `retire`.
+///
+/// # Safety
+///
+/// Dropping the object in another thread must be safe, e.g. because `T: Send`.
+pub(crate) unsafe fn retire_data<T>(pointer: Shared<T>) {
+    let pointer = Stamped::from_data(pointer);
+    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+}
+
+/// Frees a pointer returned by `alloc_data` that is not shared, e.g. when the data structure is
+/// dropped.
+///
+/// # Safety
+///
+/// The pointer must not be accessed by the other threads, nor freed again.
+pub(crate) unsafe fn dealloc_data<T>(pointer: Shared<T>) {
+    drop(Stamped::from_data(pointer).into_owned());
+}
+
+/// Retired object with its lifetime.
+#[derive(Debug)]
+struct IbrRetired {
+    data: *mut (),
+    free: unsafe fn(*mut ()),
+    birth: usize,
+    retire: usize,
+}
+
+/// Thread-local list of retired objects.
+#[derive(Debug)]
+struct IbrRetirees {
+    inner: Vec<IbrRetired>,
+    /// Call `collect` if the length of `inner` becomes larger than this value.
+    threshold: usize,
+}
+
+impl IbrRetirees {
+    /// The min value of `threshold`. After `collect`, it is set to twice the number of objects still
+    /// protected if larger, so that `collect` is not called on each `retire` while the intervals of
+    /// the other threads protect many objects.
+    const THRESHOLD: usize = 64;
+
+    fn new() -> Self {
+        Self {
+            inner: Vec::new(),
+            threshold: Self::THRESHOLD,
+        }
+    }
+
+    fn retire<T>(&mut self, pointer: Shared<Stamped<T>>) {
+        unsafe fn free<T>(data: *mut ()) {
+            drop(Box::from_raw(data as *mut Stamped<T>))
+        }
+
+        // The object is not freed before it is retired.
+        let birth = unsafe { pointer.deref() }.birth;
+        let retire = ERA.load(Ordering::SeqCst);
+        self.inner.push(IbrRetired {
+            data: pointer.as_raw() as *mut (),
+            free: free::<T>,
+            birth,
+            retire,
+        });
+
+        if self.inner.len() > self.threshold {
+            self.collect();
+        }
+    }
+
+    fn collect(&mut self) {
+        self.inner.extend(GLOBAL_RETIRED.take());
+        fence(Ordering::SeqCst);
+        let intervals = RESERVATIONS
+            .iter()
+            .map(Reservation::interval)
+            .filter(|(lower, upper)| lower <= upper)
+            .collect::<Vec<_>>();
+
+        for retired in mem::take(&mut self.inner) {
+            if intervals
+                .iter()
+                .any(|&(lower, upper)| lower <= retired.retire && retired.birth <= upper)
+            {
+                self.inner.push(retired);
+            } else {
+                unsafe { (retired.free)(retired.data) };
+            }
+        }
+        self.threshold = cmp::max(Self::THRESHOLD, 2 * self.inner.len());
+    }
+}
+
+impl Drop for IbrRetirees {
+    fn drop(&mut self) {
+        self.collect();
+        if !self.inner.is_empty() {
+            GLOBAL_RETIRED.push(mem::take(&mut self.inner));
+        }
+    }
+}
+
+/// Global list of the retired objects left by the exited threads, as `GlobalRetirees` of
+/// `hazard_pointer` for the objects with lifetimes.
+///
+/// It is a lock-free stack of batches. The batches are pushed one by one and taken all at once, so
+/// there is no ABA problem.
+#[derive(Debug)]
+struct GlobalIbrRetirees {
+    head: AtomicPtr<Batch>,
+}
+
+#[derive(Debug)]
+struct Batch {
+    inner: Vec<IbrRetired>,
+    next: *mut Batch,
+}
+
+impl GlobalIbrRetirees {
+    const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn push(&self, inner: Vec<IbrRetired>) {
+        let batch = Box::into_raw(Box::new(Batch {
+            inner,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*batch).next = head };
+            match self
+                .head
+                .compare_exchange(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn take(&self) -> Vec<IbrRetired> {
+        let mut inner = Vec::new();
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return inner;
+        }
+        let mut batch = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        while !batch.is_null() {
+            let batch_ref = unsafe { Box::from_raw(batch) };
+            batch = batch_ref.next;
+            inner.extend(batch_ref.inner);
+        }
+        inner
+    }
+}
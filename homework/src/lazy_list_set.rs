@@ -0,0 +1,189 @@
+//! Concurrent sorted singly linked list with optimistic (lazy) synchronization.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use crossbeam_epoch::{self as epoch, unprotected, Atomic, Guard, Owned, Shared};
+use std::sync::Mutex;
+
+/// Link to the next node, with the lock and the logical deletion mark of the node it belongs to.
+#[derive(Debug)]
+struct Link<T> {
+    next: Atomic<Node<T>>,
+    lock: Mutex<()>,
+    /// Whether the node is logically deleted. Set before the node is unlinked.
+    marked: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    link: Link<T>,
+}
+
+impl<T> Link<T> {
+    fn new(next: Shared<'_, Node<T>>) -> Self {
+        Self {
+            next: Atomic::from(next),
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Concurrent sorted singly linked list using optimistic (lazy) synchronization.
+///
+/// Unlike `OrderedListSet`, which locks every node on the way with lock-coupling, the traversals
+/// take no locks. `insert` and `remove` lock only the nodes around the key, validate that they are
+/// still adjacent and not deleted, and retry otherwise. `contains` and `iter` never lock, and skip
+/// the nodes marked as deleted. The removed nodes are reclaimed with `crossbeam_epoch`.
+#[derive(Debug)]
+pub struct LazyListSet<T> {
+    head: Link<T>,
+}
+
+impl<T> LazyListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Link::new(Shared::null()),
+        }
+    }
+}
+
+impl<T: Ord> LazyListSet<T> {
+    /// Returns the link to the first node whose data is not less than the key, and the node. The
+    /// nodes may be concurrently deleted.
+    fn find<'g>(&'g self, key: &T, guard: &'g Guard) -> (&'g Link<T>, Shared<'g, Node<T>>) {
+        let mut pred = &self.head;
+        let mut curr = pred.next.load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            if node.data >= *key {
+                break;
+            }
+            pred = &node.link;
+            curr = node.link.next.load(Ordering::Acquire, guard);
+        }
+        (pred, curr)
+    }
+
+    /// Returns whether `pred` and `curr` are still adjacent and not deleted. The caller should hold
+    /// their locks.
+    fn validate(pred: &Link<T>, curr: Shared<'_, Node<T>>, guard: &Guard) -> bool {
+        !pred.marked.load(Ordering::Acquire)
+            && unsafe { curr.as_ref() }
+                .map_or(true, |curr| !curr.link.marked.load(Ordering::Acquire))
+            && pred.next.load(Ordering::Acquire, guard) == curr
+    }
+
+    /// Returns `true` if the set contains the key.
+    pub fn contains(&self, key: &T) -> bool {
+        let guard = &epoch::pin();
+        let (_, curr) = self.find(key, guard);
+        unsafe { curr.as_ref() }.map_or(false, |curr| {
+            curr.data == *key && !curr.link.marked.load(Ordering::Acquire)
+        })
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &epoch::pin();
+        loop {
+            let (pred, curr) = self.find(&key, guard);
+            let _pred = pred.lock.lock().unwrap();
+            if !Self::validate(pred, curr, guard) {
+                continue;
+            }
+            if unsafe { curr.as_ref() }.map_or(false, |curr| curr.data == key) {
+                return Err(key);
+            }
+            let node = Owned::new(Node {
+                data: key,
+                link: Link::new(curr),
+            });
+            pred.next.store(node, Ordering::Release);
+            return Ok(());
+        }
+    }
+}
+
+impl<T: Ord + Clone> LazyListSet<T> {
+    /// Remove the key from the set and return it.
+    ///
+    /// The key is cloned out of the removed node, since the concurrent traversals may still be
+    /// reading it.
+    pub fn remove(&self, key: &T) -> Result<T, ()> {
+        let guard = &epoch::pin();
+        loop {
+            let (pred, curr) = self.find(key, guard);
+            let _pred = pred.lock.lock().unwrap();
+            let node = unsafe { curr.as_ref() };
+            let _curr = node.map(|node| node.link.lock.lock().unwrap());
+            if !Self::validate(pred, curr, guard) {
+                continue;
+            }
+            let node = match node {
+                Some(node) if node.data == *key => node,
+                _ => return Err(()),
+            };
+            // Logically deletes the node first, so that the traversals ignore it from now on.
+            node.link.marked.store(true, Ordering::Release);
+            pred.next.store(
+                node.link.next.load(Ordering::Acquire, guard),
+                Ordering::Release,
+            );
+            let data = node.data.clone();
+            unsafe { guard.defer_destroy(curr) };
+            return Ok(data);
+        }
+    }
+}
+
+/// Iterator over the elements of a `LazyListSet`, protected by a guard.
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<T> LazyListSet<T> {
+    /// An iterator visiting all elements. The elements inserted or removed concurrently may or may
+    /// not be visited, but the visited ones are in order.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.next.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = unsafe { self.curr.as_ref() } {
+            self.curr = node.link.next.load(Ordering::Acquire, self.guard);
+            if !node.link.marked.load(Ordering::Acquire) {
+                return Some(&node.data);
+            }
+        }
+        None
+    }
+}
+
+impl<T> Drop for LazyListSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.next.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let node = curr.into_owned();
+                curr = node.link.next.load(Ordering::Relaxed, guard);
+            }
+        }
+    }
+}
+
+impl<T> Default for LazyListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
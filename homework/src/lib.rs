@@ -1,29 +1,71 @@
 //! Homeworks
 
+//!
+//! Without the default `std` feature, the crate is `no_std` and only provides `hazard_pointer`,
+//! which then needs only `alloc`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
 
+extern crate alloc;
+
 #[macro_use]
 mod utils;
 
-mod arc;
-mod art;
-mod bst;
-mod elim_stack;
-mod hash_table;
 pub mod hazard_pointer;
-pub mod hello_server;
-mod linked_list;
-mod list_set;
-mod map;
 
-pub use arc::Arc;
-pub use art::{Art, Entry};
-pub use bst::Bst;
-pub use elim_stack::ElimStack;
-pub use hash_table::{GrowableArray, SplitOrderedList};
-pub use linked_list::LinkedList;
-pub use list_set::OrderedListSet;
-pub use map::{
-    ConcurrentMap, NonblockingConcurrentMap, NonblockingMap, RandGen, SequentialMap, StrStringMap,
-};
+cfg_if::cfg_if! {
+    if #[cfg(feature = "std")] {
+        mod arc;
+        mod art;
+        mod bst;
+        pub mod ebr;
+        mod elim_stack;
+        mod flat_combining;
+        mod hash_table;
+        pub mod hello_server;
+        pub mod ibr;
+        mod lazy_list_set;
+        mod linked_list;
+        mod list_set;
+        mod lockfree_bst;
+        mod lockfree_list_set;
+        mod map;
+        pub mod qsbr;
+        pub mod rcu;
+        mod rwlock;
+        mod seqlock;
+        mod set;
+        mod skiplist;
+        mod snzi;
+
+        pub use arc::{Arc, Weak};
+        pub use art::{Art, Entry};
+        pub use bst::Bst;
+        pub use elim_stack::ElimStack;
+        pub use flat_combining::{
+            FcPriorityQueue, FcQueue, FlatCombining, PriorityQueueOp, QueueOp, Sequential,
+        };
+        pub use hash_table::{
+            GrowableArray, HopscotchMap, LockFreeIntMap, SplitOrderedList, StripedHashMap,
+        };
+        pub use lazy_list_set::LazyListSet;
+        pub use linked_list::LinkedList;
+        pub use list_set::{
+            Cursor, OrderedListMultiSet, OrderedListSet, RawRwLock, RawTryRwLock, SpinRwLock,
+            WouldBlock,
+        };
+        pub use lockfree_bst::LockFreeBst;
+        pub use lockfree_list_set::LockFreeListSet;
+        pub use map::{
+            ConcurrentMap, MutexHashMap, NonblockingConcurrentMap, NonblockingMap, PinnedMap,
+            RandGen, RwLockBTreeMap, SequentialMap, StrStringMap,
+        };
+        pub use rwlock::{PhaseFairRwLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+        pub use seqlock::{NoPadding, SeqLock};
+        pub use set::{NonblockingMapSet, NonblockingSet};
+        pub use skiplist::SkipList;
+        pub use snzi::{Arrival, Snzi};
+    }
+}
@@ -1,46 +1,338 @@
-#![allow(clippy::mutex_atomic)]
+use crossbeam_utils::Backoff;
+use lock::{RawLock, RawTryLock};
+use std::cell::UnsafeCell;
 use std::cmp;
+use std::fmt;
+use std::iter::{self, FromIterator};
+use std::mem::{self, MaybeUninit};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
 use std::ptr;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::vec;
+
+/// Raw lock protecting a link of `OrderedListSet`. The readers take it shared, and the writers
+/// exclusive.
+///
+/// It is implemented for every `RawLock`, which is then taken exclusive by the readers as well.
+pub trait RawRwLock: Default + Send + Sync {
+    /// Token of a held lock.
+    type Token: Clone;
+
+    /// Acquires the lock shared.
+    fn read(&self) -> Self::Token;
+
+    /// Acquires the lock exclusive.
+    fn write(&self) -> Self::Token;
+
+    /// Releases the lock.
+    ///
+    /// # Safety
+    ///
+    /// `unlock()` should be called with the token given by the corresponding `read()` or `write()`.
+    unsafe fn unlock(&self, token: Self::Token);
+}
+
+impl<L: RawLock> RawRwLock for L {
+    type Token = L::Token;
+
+    fn read(&self) -> Self::Token {
+        self.lock()
+    }
+
+    fn write(&self) -> Self::Token {
+        self.lock()
+    }
+
+    unsafe fn unlock(&self, token: Self::Token) {
+        RawLock::unlock(self, token)
+    }
+}
+
+/// Raw lock of `OrderedListSet` that can be acquired without blocking, for the `try_` operations.
+///
+/// It is implemented for every `RawTryLock`.
+pub trait RawTryRwLock: RawRwLock {
+    /// Tries to acquire the lock shared.
+    fn try_read(&self) -> Result<Self::Token, ()>;
+
+    /// Tries to acquire the lock exclusive.
+    fn try_write(&self) -> Result<Self::Token, ()>;
+}
+
+impl<L: RawTryLock> RawTryRwLock for L {
+    fn try_read(&self) -> Result<Self::Token, ()> {
+        self.try_lock()
+    }
+
+    fn try_write(&self) -> Result<Self::Token, ()> {
+        self.try_lock()
+    }
+}
+
+/// Read-write spinlock, the default lock of `OrderedListSet`. The waiting writers take precedence
+/// over the new readers.
+#[derive(Debug, Default)]
+pub struct SpinRwLock {
+    /// The number of readers, with the `WRITER` and `WAITING` bits.
+    state: AtomicUsize,
+}
+
+const WRITER: usize = 1;
+const WAITING: usize = 2;
+const READER: usize = 4;
+
+impl RawRwLock for SpinRwLock {
+    type Token = ();
+
+    fn read(&self) {
+        let backoff = Backoff::new();
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & (WRITER | WAITING) == 0
+                && self
+                    .state
+                    .compare_exchange_weak(
+                        state,
+                        state + READER,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                return;
+            }
+            backoff.snooze();
+        }
+    }
+
+    fn write(&self) {
+        let backoff = Backoff::new();
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & !WAITING == 0 {
+                if self
+                    .state
+                    .compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return;
+                }
+            } else if state & WAITING == 0 {
+                let _ = self.state.compare_exchange_weak(
+                    state,
+                    state | WAITING,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+            backoff.snooze();
+        }
+    }
+
+    unsafe fn unlock(&self, _token: ()) {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & WRITER != 0 {
+            self.state.fetch_and(!WRITER, Ordering::Release);
+        } else {
+            self.state.fetch_sub(READER, Ordering::Release);
+        }
+    }
+}
+
+impl RawTryRwLock for SpinRwLock {
+    fn try_read(&self) -> Result<(), ()> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WAITING) != 0 {
+            return Err(());
+        }
+        self.state
+            .compare_exchange(state, state + READER, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| ())
+    }
+
+    fn try_write(&self) -> Result<(), ()> {
+        // Unlike `write`, a failed attempt doesn't set `WAITING`, so it doesn't hold off the readers.
+        let state = self.state.load(Ordering::Relaxed);
+        if state & !WAITING != 0 {
+            return Err(());
+        }
+        self.state
+            .compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| ())
+    }
+}
+
+/// Error of the `try_` operations of `OrderedListSet` when a lock is held for too long by the
+/// others. It gives back the key of `try_insert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock<T = ()>(pub T);
+
+/// Link to a node, protected by a lock.
+struct Link<T, L: RawRwLock> {
+    lock: L,
+    next: UnsafeCell<*mut Node<T, L>>,
+}
+
+/// Held lock of a link, shared or exclusive.
+struct LinkGuard<'l, T, L: RawRwLock> {
+    link: &'l Link<T, L>,
+    token: L::Token,
+}
 
 #[derive(Debug)]
-struct Node<T> {
+struct Node<T, L: RawRwLock> {
     data: T,
-    next: Mutex<*mut Node<T>>,
+    next: Link<T, L>,
 }
 
-unsafe impl<T> Send for Node<T> {}
-unsafe impl<T> Sync for Node<T> {}
+unsafe impl<T, L: RawRwLock> Send for Node<T, L> {}
+unsafe impl<T, L: RawRwLock> Sync for Node<T, L> {}
+
+/// Allocation of a node, possibly without its contents.
+type Slot<T, L> = Box<MaybeUninit<Node<T, L>>>;
 
 /// Concurrent sorted singly linked list using lock-coupling.
+///
+/// The links are protected by locks of type `L`. With a read-write lock such as the default
+/// `SpinRwLock`, `contains` and `iter` couple shared locks, so they run concurrently with each
+/// other, and only `insert` and `remove` exclude the others. Any `RawLock` of the `lock` crate can
+/// be used instead, e.g. `OrderedListSet<T, SpinLock>`, to compare the lock implementations.
+pub struct OrderedListSet<T, L: RawRwLock = SpinRwLock> {
+    head: Link<T, L>,
+    /// The number of elements, updated while holding the lock of the link to the element.
+    len: AtomicUsize,
+    /// The allocations of the removed nodes, kept for reuse up to `pool_capacity`.
+    pool: Mutex<Vec<Slot<T, L>>>,
+    pool_capacity: usize,
+}
+
+unsafe impl<T, L: RawRwLock> Send for OrderedListSet<T, L> {}
+unsafe impl<T, L: RawRwLock> Sync for OrderedListSet<T, L> {}
+
+/// Cursor over an `OrderedListSet`, between two adjacent elements.
+///
+/// It holds the exclusive lock of the link to the current element, so several adjacent operations,
+/// e.g. replacing a run of elements, can be done under a single traversal instead of re-walking
+/// from the head. It only moves forward, and blocks the other operations at or after its position
+/// until it is dropped.
 #[derive(Debug)]
-pub struct OrderedListSet<T> {
-    head: Mutex<*mut Node<T>>,
+pub struct Cursor<'l, T, L: RawRwLock = SpinRwLock> {
+    // reference to the `next` field of previous node which points to the current node
+    link: LinkGuard<'l, T, L>,
+    /// The previous node, or null at the head.
+    prev: *mut Node<T, L>,
+    set: &'l OrderedListSet<T, L>,
+}
+
+impl<T, L: RawRwLock> Link<T, L> {
+    fn new(next: *mut Node<T, L>) -> Self {
+        Self {
+            lock: L::default(),
+            next: UnsafeCell::new(next),
+        }
+    }
+
+    fn read(&self) -> LinkGuard<'_, T, L> {
+        LinkGuard {
+            link: self,
+            token: self.lock.read(),
+        }
+    }
+
+    fn write(&self) -> LinkGuard<'_, T, L> {
+        LinkGuard {
+            link: self,
+            token: self.lock.write(),
+        }
+    }
+
+    fn get_mut(&mut self) -> &mut *mut Node<T, L> {
+        unsafe { &mut *self.next.get() }
+    }
+
+    fn into_inner(self) -> *mut Node<T, L> {
+        self.next.into_inner()
+    }
 }
 
-unsafe impl<T> Send for OrderedListSet<T> {}
-unsafe impl<T> Sync for OrderedListSet<T> {}
+impl<T, L: RawTryRwLock> Link<T, L> {
+    /// Tries to acquire the lock, retrying with backoff until it completes.
+    fn try_lock(
+        &self,
+        f: impl Fn(&L) -> Result<L::Token, ()>,
+    ) -> Result<LinkGuard<'_, T, L>, WouldBlock> {
+        let backoff = Backoff::new();
+        loop {
+            if let Ok(token) = f(&self.lock) {
+                return Ok(LinkGuard { link: self, token });
+            }
+            if backoff.is_completed() {
+                return Err(WouldBlock(()));
+            }
+            backoff.snooze();
+        }
+    }
 
-// reference to the `next` field of previous node which points to the current node
-struct Cursor<'l, T>(MutexGuard<'l, *mut Node<T>>);
+    fn try_read(&self) -> Result<LinkGuard<'_, T, L>, WouldBlock> {
+        self.try_lock(L::try_read)
+    }
+
+    fn try_write(&self) -> Result<LinkGuard<'_, T, L>, WouldBlock> {
+        self.try_lock(L::try_write)
+    }
+}
 
-impl<T> Node<T> {
+impl<T, L: RawRwLock> fmt::Debug for Link<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Link { .. }")
+    }
+}
+
+impl<'l, T, L: RawRwLock> Deref for LinkGuard<'l, T, L> {
+    type Target = *mut Node<T, L>;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.link.next.get() }
+    }
+}
+
+impl<'l, T, L: RawRwLock> DerefMut for LinkGuard<'l, T, L> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.link.next.get() }
+    }
+}
+
+impl<'l, T, L: RawRwLock> Drop for LinkGuard<'l, T, L> {
+    fn drop(&mut self) {
+        unsafe { self.link.lock.unlock(self.token.clone()) };
+    }
+}
+
+impl<'l, T, L: RawRwLock> fmt::Debug for LinkGuard<'l, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LinkGuard").field(&**self).finish()
+    }
+}
+
+impl<T, L: RawRwLock> Node<T, L> {
     fn new(data: T, next: *mut Self) -> *mut Self {
         Box::into_raw(Box::new(Self {
             data,
-            next: Mutex::new(next),
+            next: Link::new(next),
         }))
     }
 }
 
-impl<'l, T: Ord> Cursor<'l, T> {
+impl<'l, T: Ord, L: RawRwLock> Cursor<'l, T, L> {
     /// Move the cursor to the position of key in the sorted list. If the key is found in the list,
-    /// return `true`.
-    fn find(&mut self, key: &T) -> bool {
+    /// return `true`. If the key is before the cursor, it doesn't move.
+    pub fn seek(&mut self, key: &T) -> bool {
         unsafe{
             loop{
-                let start = &self.0;
-                let ptr = **start;
+                let ptr = *self.link;
                 if ptr.is_null(){
                     return false;
                 }
@@ -49,31 +341,223 @@ impl<'l, T: Ord> Cursor<'l, T> {
                 }else if &((*ptr).data) > key{
                     return false;
                 }
-                
-                self.0 = (*ptr).next.lock().unwrap();
+
+                self.prev = ptr;
+                self.link = (*ptr).next.write();
             }
         }
     }
+
+    /// Inserts the data before the current element, and moves the cursor past it. If it isn't
+    /// between the previous and the current elements, return the provided data in `Err`.
+    pub fn insert_here(&mut self, data: T) -> Result<(), T> {
+        let after_prev = unsafe { self.prev.as_ref() }.map_or(true, |prev| prev.data < data);
+        let before_curr = self.current().map_or(true, |curr| data < *curr);
+        if !(after_prev && before_curr) {
+            return Err(data);
+        }
+        self.link(data);
+        let _ = self.move_next();
+        Ok(())
+    }
+}
+
+impl<'l, T, L: RawRwLock> Cursor<'l, T, L> {
+    /// Returns the current element, or `None` at the end of the list.
+    pub fn current(&self) -> Option<&T> {
+        unsafe { self.link.as_ref() }.map(|node| &node.data)
+    }
+
+    /// Moves the cursor to the next element. Returns `false` at the end of the list.
+    pub fn move_next(&mut self) -> bool {
+        let ptr = *self.link;
+        if ptr.is_null() {
+            return false;
+        }
+        self.prev = ptr;
+        self.link = unsafe { (*ptr).next.write() };
+        true
+    }
+
+    /// Removes the current element and returns it. The cursor moves to the next element.
+    pub fn remove_here(&mut self) -> Option<T> {
+        if self.link.is_null() {
+            return None;
+        }
+        Some(unsafe { self.unlink() })
+    }
+
+    /// Link a node of the data at the cursor, which becomes the current element. The caller should
+    /// keep the list sorted.
+    fn link(&mut self, data: T) {
+        let node = Node {
+            data,
+            next: Link::new(*self.link),
+        };
+        *self.link = self.set.alloc(node);
+        self.set.len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Unlink the node at the cursor and return its data. The cursor moves to the next node.
+    ///
+    /// # Safety
+    ///
+    /// The cursor should not be at the end of the list.
+    unsafe fn unlink(&mut self) -> T {
+        // The write lock waits for the readers still on the removed node.
+        let next = (**self.link).next.write();
+        self.unlink_locked(next)
+    }
+
+    /// Unlink the node at the cursor, given the exclusive lock of its `next` link, and return its
+    /// data. The cursor moves to the next node.
+    ///
+    /// # Safety
+    ///
+    /// The cursor should not be at the end of the list, and `next` should be the lock of the link
+    /// from the current node.
+    unsafe fn unlink_locked(&mut self, mut next: LinkGuard<'_, T, L>) -> T {
+        let node = *self.link;
+        *self.link = mem::replace(&mut *next, ptr::null_mut());
+        drop(next);
+        self.set.len.fetch_sub(1, Ordering::Relaxed);
+        let data = ptr::read(&(*node).data);
+        ptr::drop_in_place(&mut (*node).next);
+        self.set.recycle(Box::from_raw(node as *mut MaybeUninit<_>));
+        data
+    }
 }
 
 impl<T> OrderedListSet<T> {
     /// Creates a new list.
     pub fn new() -> Self {
+        Self::with_lock()
+    }
+}
+
+impl<T, L: RawRwLock> OrderedListSet<T, L> {
+    /// Creates a new list whose links are protected by locks of type `L`, e.g.
+    /// `OrderedListSet::<T, SpinLock>::with_lock()`.
+    pub fn with_lock() -> Self {
         Self {
-            head: Mutex::new(ptr::null_mut()),
+            head: Link::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+            pool: Mutex::new(Vec::new()),
+            pool_capacity: 0,
+        }
+    }
+
+    /// Keeps up to `capacity` allocations of the removed nodes for reuse by the insertions,
+    /// instead of returning them to the allocator. Disabled by default.
+    pub fn node_pool(mut self, capacity: usize) -> Self {
+        self.pool_capacity = capacity;
+        self
+    }
+
+    /// Allocates the node, reusing an allocation of the pool if any.
+    fn alloc(&self, node: Node<T, L>) -> *mut Node<T, L> {
+        if self.pool_capacity > 0 {
+            let slot = self.pool.lock().unwrap().pop();
+            if let Some(mut slot) = slot {
+                unsafe { slot.as_mut_ptr().write(node) };
+                return Box::into_raw(slot) as *mut Node<T, L>;
+            }
+        }
+        Box::into_raw(Box::new(node))
+    }
+
+    /// Keeps the allocation of a removed node in the pool if it isn't full, or frees it.
+    fn recycle(&self, slot: Slot<T, L>) {
+        if self.pool_capacity > 0 {
+            let mut pool = self.pool.lock().unwrap();
+            if pool.len() < self.pool_capacity {
+                pool.push(slot);
+            }
+        }
+    }
+
+    /// Returns the number of elements, without traversing the list.
+    ///
+    /// The concurrent insertions and removals may or may not be counted.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set contains no element.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Creates a list by linking the elements in order. They should be strictly increasing.
+    fn from_sorted<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self::with_lock();
+        let mut len = 0;
+        let mut link = set.head.get_mut();
+        for data in iter {
+            let node = Node::new(data, ptr::null_mut());
+            *link = node;
+            link = unsafe { (*node).next.get_mut() };
+            len += 1;
+        }
+        *set.len.get_mut() = len;
+        set
+    }
+
+    /// Returns a cursor at the first element.
+    pub fn cursor(&self) -> Cursor<'_, T, L> {
+        Cursor {
+            link: self.head.write(),
+            prev: ptr::null_mut(),
+            set: self,
         }
     }
 }
 
-impl<T: Ord> OrderedListSet<T> {
-    fn find(&self, key: &T) -> (bool, Cursor<T>) {
-        let mut cursor = Cursor(self.head.lock().unwrap());
-        (cursor.find(key), cursor)
+impl<T: Ord, L: RawRwLock> OrderedListSet<T, L> {
+    fn find(&self, key: &T) -> (bool, Cursor<'_, T, L>) {
+        let mut cursor = self.cursor();
+        (cursor.seek(key), cursor)
     }
 
     /// Returns `true` if the set contains the key.
     pub fn contains(&self, key: &T) -> bool {
-        self.find(key).0
+        let mut guard = self.head.read();
+        loop {
+            let ptr = *guard;
+            if ptr.is_null() {
+                return false;
+            }
+            let node = unsafe { &*ptr };
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => guard = node.next.read(),
+                cmp::Ordering::Equal => return true,
+                cmp::Ordering::Greater => return false,
+            }
+        }
+    }
+
+    /// Returns whether the set contains each of the keys, in a single traversal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not sorted.
+    pub fn contains_all(&self, keys: &[T]) -> Vec<bool> {
+        assert!(keys.windows(2).all(|k| k[0] <= k[1]), "keys not sorted");
+        let mut guard = self.head.read();
+        keys.iter()
+            .map(|key| loop {
+                let ptr = *guard;
+                if ptr.is_null() {
+                    return false;
+                }
+                let node = unsafe { &*ptr };
+                match node.data.cmp(key) {
+                    cmp::Ordering::Less => guard = node.next.read(),
+                    cmp::Ordering::Equal => return true,
+                    cmp::Ordering::Greater => return false,
+                }
+            })
+            .collect()
     }
 
     /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
@@ -82,38 +566,238 @@ impl<T: Ord> OrderedListSet<T> {
         if succ{
             Err(key)
         }else{
-            *cursor.0 = Node::new(key,*cursor.0);
+            cursor.link(key);
             Ok(())
         }
     }
 
+    /// Returns a reference to the element equal to the key, inserting the key first if the set
+    /// doesn't have one, in a single traversal.
+    ///
+    /// The reference holds the shared lock of the link from the element, so the element can't be
+    /// removed while it is alive. As with `iter`, it blocks the writers after the element.
+    pub fn get_or_insert(&self, key: T) -> Ref<'_, T, L> {
+        let (succ, mut cursor) = self.find(&key);
+        if !succ {
+            cursor.link(key);
+        }
+        let node = *cursor.link;
+        // Locks the link from the element before releasing the link to it.
+        let guard = unsafe { (*node).next.read() };
+        drop(cursor);
+        Ref {
+            node,
+            _guard: guard,
+        }
+    }
+
     /// Remove the key from the set and return it.
     pub fn remove(&self, key: &T) -> Result<T, ()> {
         let (succ, mut cursor) = self.find(key);
         if succ {
-            unsafe{
-                let curnode = Box::from_raw(*cursor.0);
-                let nextlock = curnode.next.lock().unwrap();
-                *cursor.0 = *nextlock;
-                Ok(curnode.data)
-            }
+            Ok(unsafe { cursor.unlink() })
         }else{
             Err(())
         }
     }
+
+    /// Replaces the element equal to the value with it, and returns the replaced element. If the set
+    /// doesn't have one, return the provided value in `Err`.
+    ///
+    /// This is for elements ordered only by a part of them, e.g. the key of a key-value pair. The
+    /// value is swapped in place instead of unlinking the node, so the node and its position are
+    /// kept.
+    ///
+    /// The references yielded by `iter` are tied to the set rather than to the locks of the
+    /// iterator, so they may still be alive after the iterator has moved on. Hence `replace` takes
+    /// the set exclusively, which guarantees that no such reference is outstanding:
+    ///
+    /// ```compile_fail
+    /// # use cs492_concur_homework::OrderedListSet;
+    /// let mut set = OrderedListSet::new();
+    /// set.insert(1).unwrap();
+    /// let first = set.iter().next().unwrap();
+    /// set.replace(1).unwrap();
+    /// assert_eq!(*first, 1);
+    /// ```
+    pub fn replace(&mut self, value: T) -> Result<T, T> {
+        let (succ, cursor) = self.find(&value);
+        if !succ {
+            return Err(value);
+        }
+        let node = *cursor.link;
+        Ok(mem::replace(unsafe { &mut (*node).data }, value))
+    }
+
+    /// Remove the key from the set and return it if the element satisfies the predicate. The
+    /// element is checked and removed atomically.
+    pub fn remove_if<F: FnOnce(&T) -> bool>(&self, key: &T, f: F) -> Result<T, ()> {
+        let (succ, mut cursor) = self.find(key);
+        if succ && f(cursor.current().unwrap()) {
+            Ok(unsafe { cursor.unlink() })
+        } else {
+            Err(())
+        }
+    }
 }
 
+impl<T: Ord, L: RawTryRwLock> OrderedListSet<T, L> {
+    /// `find` that gives up with `WouldBlock` if a lock on the way can't be acquired with a bounded
+    /// number of retries.
+    fn try_find(&self, key: &T) -> Result<(bool, Cursor<'_, T, L>), WouldBlock> {
+        let mut cursor = Cursor {
+            link: self.head.try_write()?,
+            prev: ptr::null_mut(),
+            set: self,
+        };
+        loop {
+            let ptr = *cursor.link;
+            if ptr.is_null() {
+                return Ok((false, cursor));
+            }
+            let node = unsafe { &*ptr };
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => {
+                    cursor.prev = ptr;
+                    cursor.link = node.next.try_write()?;
+                }
+                cmp::Ordering::Equal => return Ok((true, cursor)),
+                cmp::Ordering::Greater => return Ok((false, cursor)),
+            }
+        }
+    }
+
+    /// `contains` that returns `WouldBlock` instead of waiting for a lock held by the others for
+    /// long, e.g. by a cursor or an iterator.
+    pub fn try_contains(&self, key: &T) -> Result<bool, WouldBlock> {
+        let mut guard = self.head.try_read()?;
+        loop {
+            let ptr = *guard;
+            if ptr.is_null() {
+                return Ok(false);
+            }
+            let node = unsafe { &*ptr };
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => guard = node.next.try_read()?,
+                cmp::Ordering::Equal => return Ok(true),
+                cmp::Ordering::Greater => return Ok(false),
+            }
+        }
+    }
+
+    /// `insert` that returns the key in `WouldBlock` instead of waiting for a lock held by the
+    /// others for long. The set is not changed then.
+    pub fn try_insert(&self, key: T) -> Result<Result<(), T>, WouldBlock<T>> {
+        let (succ, mut cursor) = match self.try_find(&key) {
+            Ok(found) => found,
+            Err(_) => return Err(WouldBlock(key)),
+        };
+        if succ {
+            Ok(Err(key))
+        } else {
+            cursor.link(key);
+            Ok(Ok(()))
+        }
+    }
+
+    /// `remove` that returns `WouldBlock` instead of waiting for a lock held by the others for
+    /// long. The set is not changed then.
+    pub fn try_remove(&self, key: &T) -> Result<Result<T, ()>, WouldBlock> {
+        let (succ, mut cursor) = self.try_find(key)?;
+        if !succ {
+            return Ok(Err(()));
+        }
+        let next = unsafe { (**cursor.link).next.try_write()? };
+        Ok(Ok(unsafe { cursor.unlink_locked(next) }))
+    }
+}
+
+impl<T, L: RawRwLock> OrderedListSet<T, L> {
+    /// Removes the smallest element and returns it, holding only the locks of the head and the
+    /// first node. With it, the set can be used as a simple concurrent priority queue.
+    pub fn pop_front(&self) -> Option<T> {
+        self.cursor().remove_here()
+    }
+
+    /// Detaches all elements at once, and returns an iterator removing them in order.
+    ///
+    /// The set has no element right after the call, but `len` counts the detached elements until
+    /// they are removed. The concurrent operations that were already past the head of the set keep
+    /// working on the detached elements, and the iterator waits for them as it reaches their
+    /// positions. The elements that are not iterated over are removed when the iterator is
+    /// dropped.
+    pub fn drain(&self) -> Drain<'_, T, L> {
+        let mut head = self.head.write();
+        let chain = mem::replace(&mut *head, ptr::null_mut());
+        Drain {
+            head: Link::new(chain),
+            set: self,
+        }
+    }
+
+    /// Retains only the elements satisfying the predicate, in a single lock-coupling traversal.
+    ///
+    /// The elements are visited in order, and the writers are blocked from the traversal position
+    /// to the end of the list until it is done.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, mut f: F) {
+        let mut cursor = self.cursor();
+        loop {
+            let keep = match cursor.current() {
+                Some(data) => f(data),
+                None => return,
+            };
+            if keep {
+                let _ = cursor.move_next();
+            } else {
+                let _ = cursor.remove_here();
+            }
+        }
+    }
+}
+
+/// Reference to an element of an `OrderedListSet`, returned by `get_or_insert`.
 #[derive(Debug)]
-pub struct Iter<'l, T>(Option<MutexGuard<'l, *mut Node<T>>>);
+pub struct Ref<'l, T, L: RawRwLock = SpinRwLock> {
+    node: *mut Node<T, L>,
+    _guard: LinkGuard<'l, T, L>,
+}
 
-impl<T> OrderedListSet<T> {
+impl<'l, T, L: RawRwLock> Deref for Ref<'l, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &(*self.node).data }
+    }
+}
+
+#[derive(Debug)]
+pub struct Iter<'l, T, L: RawRwLock = SpinRwLock>(Option<LinkGuard<'l, T, L>>);
+
+impl<T, L: RawRwLock> OrderedListSet<T, L> {
     /// An iterator visiting all elements.
-    pub fn iter(&self) -> Iter<T> {
-        Iter(Some(self.head.lock().unwrap()))
+    ///
+    /// The iterator holds the shared lock of the link to the next element until it is dropped, so
+    /// the elements it yields stay valid, and no element can be inserted or removed at or after its
+    /// position in the meantime. A long-lived iterator thus blocks the writers after it; use
+    /// `snapshot_iter` for such iterations instead.
+    pub fn iter(&self) -> Iter<'_, T, L> {
+        Iter(Some(self.head.read()))
     }
 }
 
-impl<'l, T> Iterator for Iter<'l, T> {
+impl<T: Clone, L: RawRwLock> OrderedListSet<T, L> {
+    /// An iterator visiting the clones of all elements, taken in a single traversal.
+    ///
+    /// The locks are released as soon as the elements are cloned, before the iterator is returned,
+    /// so it never blocks the writers. The clones are in order and consistent with a point of the
+    /// traversal: an element is included if it is not removed before the traversal passes its
+    /// position, and the elements inserted or removed after that are not reflected.
+    pub fn snapshot_iter(&self) -> vec::IntoIter<T> {
+        self.iter().cloned().collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<'l, T, L: RawRwLock> Iterator for Iter<'l, T, L> {
     type Item = &'l T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -125,7 +809,7 @@ impl<'l, T> Iterator for Iter<'l, T> {
                     None
                 }else{
                     unsafe{
-                        let next = (*ptr).next.lock().unwrap();
+                        let next = (*ptr).next.read();
                         let val = &((*ptr).data);
                         self.0 = Some(next);
                         Some(val)
@@ -137,13 +821,223 @@ impl<'l, T> Iterator for Iter<'l, T> {
     }
 }
 
-impl<T> Drop for OrderedListSet<T> {
+/// Iterator over the elements of an `OrderedListSet` within bounds.
+#[derive(Debug)]
+pub struct Range<'l, T, R, L: RawRwLock = SpinRwLock> {
+    iter: Iter<'l, T, L>,
+    bounds: R,
+}
+
+impl<T: Ord, L: RawRwLock> OrderedListSet<T, L> {
+    /// An iterator visiting the elements within the bounds, e.g. `set.range(lo..hi)`.
+    ///
+    /// The traversal starts from the head but takes only the shared locks up to the first element,
+    /// and releases the locks as soon as it passes the end, so the writers after the range are not
+    /// blocked by the iterator. Otherwise it holds the locks as `iter` does.
+    pub fn range<R: RangeBounds<T>>(&self, bounds: R) -> Range<'_, T, R, L> {
+        let mut guard = self.head.read();
+        loop {
+            let ptr = *guard;
+            if ptr.is_null() {
+                break;
+            }
+            let node = unsafe { &*ptr };
+            let before = match bounds.start_bound() {
+                Bound::Included(start) => node.data < *start,
+                Bound::Excluded(start) => node.data <= *start,
+                Bound::Unbounded => false,
+            };
+            if !before {
+                break;
+            }
+            guard = node.next.read();
+        }
+        Range {
+            iter: Iter(Some(guard)),
+            bounds,
+        }
+    }
+}
+
+impl<T: Ord + Clone, L: RawRwLock> OrderedListSet<T, L> {
+    /// Returns the smallest element, if any.
+    pub fn first(&self) -> Option<T> {
+        self.iter().next().cloned()
+    }
+
+    /// Returns the largest element, if any. It traverses the whole list.
+    pub fn last(&self) -> Option<T> {
+        self.iter().last().cloned()
+    }
+}
+
+impl<T: Ord + Clone, L: RawRwLock> OrderedListSet<T, L> {
+    /// Inserts the elements of the set into `dest`.
+    ///
+    /// The elements are merged into `dest` in a single lock-coupling traversal of `dest`, instead
+    /// of an `insert` from the head for each of them. They are taken with `snapshot_iter` first,
+    /// so the locks of the two sets are never held together.
+    pub fn union_into(&self, dest: &Self) {
+        let snapshot = self.snapshot_iter();
+        let mut cursor = dest.cursor();
+        for data in snapshot {
+            if !cursor.seek(&data) {
+                cursor.link(data);
+            }
+        }
+    }
+
+    /// Returns a new set of the elements in both sets.
+    ///
+    /// The elements of `other` are taken with `snapshot_iter`, and then merged with a single
+    /// traversal of the set, so the locks of the two sets are never held together.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut other = other.snapshot_iter().peekable();
+        Self::from_sorted(
+            self.iter()
+                .filter(|data| Self::skip_to(&mut other, data))
+                .cloned(),
+        )
+    }
+
+    /// Returns a new set of the elements in the set but not in `other`.
+    ///
+    /// The elements are merged as in `intersection`.
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut other = other.snapshot_iter().peekable();
+        Self::from_sorted(
+            self.iter()
+                .filter(|data| !Self::skip_to(&mut other, data))
+                .cloned(),
+        )
+    }
+
+    /// Skips the elements less than `data`, and returns whether the next one is equal to it.
+    fn skip_to(iter: &mut iter::Peekable<vec::IntoIter<T>>, data: &T) -> bool {
+        while iter.peek().map_or(false, |next| next < data) {
+            let _ = iter.next();
+        }
+        iter.peek() == Some(data)
+    }
+}
+
+impl<'l, T: Ord, R: RangeBounds<T>, L: RawRwLock> Iterator for Range<'l, T, R, L> {
+    type Item = &'l T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ptr = **self.iter.0.as_ref()?;
+        if ptr.is_null() {
+            self.iter.0 = None;
+            return None;
+        }
+        let data = unsafe { &(*ptr).data };
+        let past = match self.bounds.end_bound() {
+            Bound::Included(end) => data > end,
+            Bound::Excluded(end) => data >= end,
+            Bound::Unbounded => false,
+        };
+        if past {
+            self.iter.0 = None;
+            return None;
+        }
+        self.iter.next()
+    }
+}
+
+impl<T: Clone, L: RawRwLock> Clone for OrderedListSet<T, L> {
+    /// Creates a deep copy of the set, linking the clones of the elements in a single
+    /// lock-coupling traversal. The copy is consistent in the same sense as `snapshot_iter`.
+    fn clone(&self) -> Self {
+        Self::from_sorted(self.iter().cloned())
+    }
+}
+
+impl<T: Ord, L: RawRwLock> FromIterator<T> for OrderedListSet<T, L> {
+    /// Creates a set from the elements, keeping the first of the equal ones as `insert` does. The
+    /// elements are sorted first and then linked in order, instead of being inserted one by one.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elements = iter.into_iter().collect::<Vec<_>>();
+        elements.sort();
+        elements.dedup();
+        Self::from_sorted(elements)
+    }
+}
+
+impl<T: Ord, L: RawRwLock> Extend<T> for OrderedListSet<T, L> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for data in iter {
+            let _ = self.insert(data);
+        }
+    }
+}
+
+/// Iterator removing the detached elements of an `OrderedListSet`, returned by `drain`.
+#[derive(Debug)]
+pub struct Drain<'l, T, L: RawRwLock = SpinRwLock> {
+    head: Link<T, L>,
+    set: &'l OrderedListSet<T, L>,
+}
+
+impl<'l, T, L: RawRwLock> Iterator for Drain<'l, T, L> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut cursor = Cursor {
+            link: self.head.write(),
+            prev: ptr::null_mut(),
+            set: self.set,
+        };
+        cursor.remove_here()
+    }
+}
+
+impl<'l, T, L: RawRwLock> Drop for Drain<'l, T, L> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// Owning iterator over the elements of an `OrderedListSet`.
+#[derive(Debug)]
+pub struct IntoIter<T, L: RawRwLock = SpinRwLock>(OrderedListSet<T, L>);
+
+impl<T, L: RawRwLock> IntoIterator for OrderedListSet<T, L> {
+    type Item = T;
+    type IntoIter = IntoIter<T, L>;
+
+    /// Consumes the set into an iterator visiting all elements in order. No lock is taken, since
+    /// the set is owned.
+    fn into_iter(self) -> IntoIter<T, L> {
+        IntoIter(self)
+    }
+}
+
+impl<T, L: RawRwLock> Iterator for IntoIter<T, L> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let head = self.0.head.get_mut();
+        if head.is_null() {
+            return None;
+        }
+        let node = unsafe { Box::from_raw(*head) };
+        *head = node.next.into_inner();
+        *self.0.len.get_mut() -= 1;
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<T, L: RawRwLock> Drop for OrderedListSet<T, L> {
     fn drop(&mut self) {
-        let mut np = self.head.lock().unwrap();//node pointer
+        let mut np = self.head.write();//node pointer
         while !((*np).is_null()) {
             unsafe{
                 let node = Box::from_raw(*np);
-                let next = node.next.lock().unwrap();
+                let next = node.next.read();
                 *np = *next;
             }
         }
@@ -155,3 +1049,90 @@ impl<T> Default for OrderedListSet<T> {
         Self::new()
     }
 }
+
+impl<T, L: RawRwLock> fmt::Debug for OrderedListSet<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderedListSet")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Concurrent sorted singly linked list using lock-coupling, which may have equal elements.
+///
+/// It shares the implementation of `OrderedListSet`, but `insert` always links the key, before the
+/// elements equal to it.
+#[derive(Debug)]
+pub struct OrderedListMultiSet<T, L: RawRwLock = SpinRwLock>(OrderedListSet<T, L>);
+
+impl<T> OrderedListMultiSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self::with_lock()
+    }
+}
+
+impl<T, L: RawRwLock> OrderedListMultiSet<T, L> {
+    /// Creates a new list whose links are protected by locks of type `L`.
+    pub fn with_lock() -> Self {
+        Self(OrderedListSet::with_lock())
+    }
+
+    /// Returns the number of elements, counting the equal ones separately.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the multiset contains no element.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// An iterator visiting all elements in order, including the equal ones.
+    pub fn iter(&self) -> Iter<'_, T, L> {
+        self.0.iter()
+    }
+}
+
+impl<T: Ord, L: RawRwLock> OrderedListMultiSet<T, L> {
+    /// Returns `true` if the multiset contains the key at least once.
+    pub fn contains(&self, key: &T) -> bool {
+        self.0.contains(key)
+    }
+
+    /// Returns the number of elements equal to the key.
+    pub fn count(&self, key: &T) -> usize {
+        let mut guard = self.0.head.read();
+        let mut count = 0;
+        loop {
+            let ptr = *guard;
+            if ptr.is_null() {
+                return count;
+            }
+            let node = unsafe { &*ptr };
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => {}
+                cmp::Ordering::Equal => count += 1,
+                cmp::Ordering::Greater => return count,
+            }
+            guard = node.next.read();
+        }
+    }
+
+    /// Insert a key to the multiset, even if it already has equal ones.
+    pub fn insert(&self, key: T) {
+        let (_, mut cursor) = self.0.find(&key);
+        cursor.link(key);
+    }
+
+    /// Remove one element equal to the key and return it.
+    pub fn remove_one(&self, key: &T) -> Result<T, ()> {
+        self.0.remove(key)
+    }
+}
+
+impl<T> Default for OrderedListMultiSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,341 @@
+//! Lock-free external binary search tree (Natarajan and Mittal's tree).
+//!
+//! The key-value pairs are in the leaves, and the internal nodes route the traversals: a key less
+//! than an internal node's key is in its left subtree. An edge is marked as follows:
+//!
+//! - `FLAG`: the leaf it points to is deleted, and is to be removed with its parent.
+//! - `TAG`: the edge is not to be changed, since its sibling leaf is being removed, so the subtree
+//!   it points to is to be moved to the grandparent.
+//!
+//! A deletion flags the edge to the leaf, and then removes the parent and the flagged leaf by a CAS
+//! on the edge to the parent. Several of such removals may be pending on a path, in which case the
+//! CAS removes all of them at once: the edges from `successor` to `parent` are all tagged.
+//!
+//! Reference: Aravind Natarajan and Neeraj Mittal. Fast Concurrent Lock-Free Binary Search Trees.
+//! PPoPP 2014.
+
+use core::cmp::Ordering::{self, Greater};
+use core::ptr;
+use core::sync::atomic::Ordering as AtomicOrdering;
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+use crate::map::NonblockingMap;
+
+/// The leaf an edge points to is deleted.
+const FLAG: usize = 1;
+/// The edge is not to be changed.
+const TAG: usize = 2;
+
+/// A key, or one of the three sentinel keys that are greater than every key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Key<K> {
+    Fin(K),
+    Inf(u8),
+}
+
+impl<K: Ord> Key<K> {
+    fn cmp_key(&self, key: &K) -> Ordering {
+        match self {
+            Key::Fin(k) => k.cmp(key),
+            Key::Inf(_) => Greater,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node<K, V> {
+    key: Key<K>,
+    /// `None` for the internal nodes and the sentinel leaves.
+    value: Option<V>,
+    /// Both are null for the leaves.
+    left: Atomic<Node<K, V>>,
+    right: Atomic<Node<K, V>>,
+}
+
+impl<K, V> Node<K, V> {
+    fn leaf(key: Key<K>, value: Option<V>) -> Self {
+        Self {
+            key,
+            value,
+            left: Atomic::null(),
+            right: Atomic::null(),
+        }
+    }
+
+    fn internal(key: Key<K>, left: Shared<'_, Self>, right: Shared<'_, Self>) -> Self {
+        Self {
+            key,
+            value: None,
+            left: Atomic::from(left),
+            right: Atomic::from(right),
+        }
+    }
+}
+
+impl<K: Ord, V> Node<K, V> {
+    /// Returns the edge to the child on the path to the key, and the other one.
+    fn children(&self, key: &K) -> (&Atomic<Self>, &Atomic<Self>) {
+        if self.key.cmp_key(key) == Greater {
+            (&self.left, &self.right)
+        } else {
+            (&self.right, &self.left)
+        }
+    }
+}
+
+/// The result of `seek`. The edges from `ancestor` to `successor` and from `parent` to `leaf` may
+/// be untagged, and the ones in between are tagged.
+#[derive(Debug)]
+struct SeekRecord<'g, K, V> {
+    ancestor: &'g Node<K, V>,
+    successor: Shared<'g, Node<K, V>>,
+    parent: &'g Node<K, V>,
+    leaf: Shared<'g, Node<K, V>>,
+    /// The edge from `parent` to `leaf`, with its marks.
+    leaf_edge: Shared<'g, Node<K, V>>,
+}
+
+/// Lock-free map from `K` to `V`, sorted by the keys.
+#[derive(Debug)]
+pub struct LockFreeBst<K, V> {
+    /// The root with the key `Inf(2)`. Its left child is the internal node with the key `Inf(1)`,
+    /// whose left subtree has the other keys. The sentinel nodes are never removed.
+    root: Node<K, V>,
+}
+
+impl<K, V> Default for LockFreeBst<K, V> {
+    fn default() -> Self {
+        unsafe {
+            let guard = unprotected();
+            let s = Owned::new(Node::internal(
+                Key::Inf(1),
+                Owned::new(Node::leaf(Key::Inf(0), None)).into_shared(guard),
+                Owned::new(Node::leaf(Key::Inf(1), None)).into_shared(guard),
+            ));
+            Self {
+                root: Node::internal(
+                    Key::Inf(2),
+                    s.into_shared(guard),
+                    Owned::new(Node::leaf(Key::Inf(2), None)).into_shared(guard),
+                ),
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for LockFreeBst<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut nodes = vec![
+                self.root.left.load(AtomicOrdering::Relaxed, guard),
+                self.root.right.load(AtomicOrdering::Relaxed, guard),
+            ];
+            while let Some(node) = nodes.pop() {
+                let node = node.with_tag(0).into_owned();
+                for child in &[&node.left, &node.right] {
+                    let child = child.load(AtomicOrdering::Relaxed, guard);
+                    if !child.is_null() {
+                        nodes.push(child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> LockFreeBst<K, V> {
+    /// Creates a new tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the leaf on the path to the key.
+    fn seek<'g>(&'g self, key: &K, guard: &'g Guard) -> SeekRecord<'g, K, V> {
+        let successor = self.root.left.load(AtomicOrdering::Acquire, guard);
+        let mut record = SeekRecord {
+            ancestor: &self.root,
+            successor,
+            parent: unsafe { successor.deref() },
+            leaf: Shared::null(),
+            leaf_edge: Shared::null(),
+        };
+        record.leaf_edge = record.parent.left.load(AtomicOrdering::Acquire, guard);
+        record.leaf = record.leaf_edge.with_tag(0);
+
+        let mut current_edge = unsafe { record.leaf.deref() }
+            .children(key)
+            .0
+            .load(AtomicOrdering::Acquire, guard);
+        while let Some(current) = unsafe { current_edge.with_tag(0).as_ref() } {
+            if record.leaf_edge.tag() & TAG == 0 {
+                record.ancestor = record.parent;
+                record.successor = record.leaf;
+            }
+            record.parent = unsafe { record.leaf.deref() };
+            record.leaf = current_edge.with_tag(0);
+            record.leaf_edge = current_edge;
+            current_edge = current.children(key).0.load(AtomicOrdering::Acquire, guard);
+        }
+        record
+    }
+
+    /// Removes `record.parent` and the flagged leaf among its children, with the pending removals
+    /// above it. Returns `true` if it is removed by this call.
+    fn cleanup<'g>(&'g self, key: &K, record: &SeekRecord<'g, K, V>, guard: &'g Guard) -> bool {
+        let successor_edge = record.ancestor.children(key).0;
+        let (child_edge, sibling_edge) = record.parent.children(key);
+
+        // The sibling of the flagged child is kept. If both are flagged, the other one is kept.
+        let child = child_edge.load(AtomicOrdering::Acquire, guard);
+        let (kept_edge, removed_edge) = if child.tag() & FLAG == 0 {
+            (child_edge, sibling_edge)
+        } else {
+            (sibling_edge, child_edge)
+        };
+        let kept = kept_edge.fetch_or(TAG, AtomicOrdering::AcqRel, guard);
+
+        if successor_edge
+            .compare_and_set(
+                record.successor,
+                kept.with_tag(kept.tag() & FLAG),
+                AtomicOrdering::AcqRel,
+                guard,
+            )
+            .is_err()
+        {
+            return false;
+        }
+
+        // The removed nodes are the ones from `successor` to `parent` and their removed leaves.
+        // Their edges are all marked, so they don't change anymore.
+        let mut node = record.successor;
+        loop {
+            let node_ref = unsafe { node.deref() };
+            let (next_edge, leaf_edge) = if ptr::eq(node_ref, record.parent) {
+                (None, removed_edge)
+            } else {
+                let (next_edge, leaf_edge) = node_ref.children(key);
+                (Some(next_edge), leaf_edge)
+            };
+            unsafe {
+                guard.defer_destroy(leaf_edge.load(AtomicOrdering::Acquire, guard).with_tag(0));
+                guard.defer_destroy(node);
+            }
+            node = some_or!(next_edge, return true)
+                .load(AtomicOrdering::Acquire, guard)
+                .with_tag(0);
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> NonblockingMap<K, V> for LockFreeBst<K, V> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
+        let record = self.seek(key, guard);
+        let leaf = unsafe { record.leaf.deref() };
+        if leaf.key.cmp_key(key) != Ordering::Equal || record.leaf_edge.tag() & FLAG != 0 {
+            return None;
+        }
+        leaf.value.as_ref()
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
+        let mut new_leaf = Owned::new(Node::leaf(Key::Fin(key.clone()), Some(value)));
+        loop {
+            let record = self.seek(key, guard);
+            let leaf = unsafe { record.leaf.deref() };
+            if leaf.key.cmp_key(key) == Ordering::Equal {
+                return Err(new_leaf.into_box().value.unwrap());
+            }
+
+            let new_leaf_shared = new_leaf.into_shared(guard);
+            let new_internal = if leaf.key.cmp_key(key) == Greater {
+                Node::internal(leaf.key.clone(), new_leaf_shared, record.leaf)
+            } else {
+                Node::internal(Key::Fin(key.clone()), record.leaf, new_leaf_shared)
+            };
+
+            let child_edge = record.parent.children(key).0;
+            match child_edge.compare_and_set(
+                record.leaf,
+                Owned::new(new_internal),
+                AtomicOrdering::AcqRel,
+                guard,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    new_leaf = unsafe { new_leaf_shared.into_owned() };
+                    // Helps the removal that blocks the insertion.
+                    if e.current.with_tag(0) == record.leaf && e.current.tag() != 0 {
+                        let _ = self.cleanup(key, &record, guard);
+                    }
+                }
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        // The leaf flagged by this call.
+        let mut flagged = None;
+        loop {
+            let record = self.seek(key, guard);
+            match flagged {
+                None => {
+                    let leaf = unsafe { record.leaf.deref() };
+                    if leaf.key.cmp_key(key) != Ordering::Equal {
+                        return Err(());
+                    }
+
+                    let child_edge = record.parent.children(key).0;
+                    match child_edge.compare_and_set(
+                        record.leaf,
+                        record.leaf.with_tag(FLAG),
+                        AtomicOrdering::AcqRel,
+                        guard,
+                    ) {
+                        Ok(_) => {
+                            flagged = Some(leaf);
+                            if self.cleanup(key, &record, guard) {
+                                return Ok(leaf.value.as_ref().unwrap());
+                            }
+                        }
+                        Err(e) => {
+                            if e.current.with_tag(0) == record.leaf && e.current.tag() != 0 {
+                                let _ = self.cleanup(key, &record, guard);
+                            }
+                        }
+                    }
+                }
+                Some(leaf) => {
+                    // Removed by the others, or by this call.
+                    if !ptr::eq(record.leaf.as_raw(), leaf) || self.cleanup(key, &record, guard) {
+                        return Ok(leaf.value.as_ref().unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    fn for_each<'a, F>(&'a self, guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        // In the order of the keys, skipping the flagged leaves.
+        let mut edges = vec![self.root.left.load(AtomicOrdering::Acquire, guard)];
+        while let Some(edge) = edges.pop() {
+            let node = unsafe { edge.with_tag(0).deref() };
+            let left = node.left.load(AtomicOrdering::Acquire, guard);
+            if left.is_null() {
+                if let (Key::Fin(key), Some(value)) = (&node.key, &node.value) {
+                    if edge.tag() & FLAG == 0 {
+                        f(key, value);
+                    }
+                }
+                continue;
+            }
+            edges.push(node.right.load(AtomicOrdering::Acquire, guard));
+            edges.push(left);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+//! Lock-free sorted singly linked list (Harris's list).
+
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{self as epoch, unprotected, Atomic, Guard, Owned, Shared};
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    /// Tagged with 1 if the node is logically deleted.
+    next: Atomic<Node<T>>,
+}
+
+/// Lock-free sorted singly linked list, following Harris's list.
+///
+/// `remove` marks the `next` link of the node first, so that no node is inserted after it, and
+/// then unlinks it. The traversals of `insert` and `remove` unlink the chains of the marked nodes
+/// on their way. `contains` and `iter` never write, and skip the marked nodes. The removed nodes
+/// are reclaimed with `crossbeam_epoch`.
+#[derive(Debug)]
+pub struct LockFreeListSet<T> {
+    head: Atomic<Node<T>>,
+}
+
+/// The link to the current node, and the current node.
+struct Cursor<'g, T> {
+    prev: &'g Atomic<Node<T>>,
+    curr: Shared<'g, Node<T>>,
+}
+
+impl<'g, T: Ord> Cursor<'g, T> {
+    /// Moves the cursor to the first unmarked node whose data is not less than the key, unlinking
+    /// the marked nodes between the previous unmarked node and it. Returns whether the data is
+    /// equal to the key, or `Err` if the marked nodes were concurrently changed.
+    fn find(&mut self, key: &T, guard: &'g Guard) -> Result<bool, ()> {
+        let mut prev_next = self.curr;
+        let found = loop {
+            let curr_node = some_or!(unsafe { self.curr.as_ref() }, break false);
+            let next = curr_node.next.load(Ordering::Acquire, guard);
+
+            if next.tag() != 0 {
+                self.curr = next.with_tag(0);
+                continue;
+            }
+
+            match curr_node.data.cmp(key) {
+                Less => {
+                    self.curr = next;
+                    self.prev = &curr_node.next;
+                    prev_next = next;
+                }
+                Equal => break true,
+                Greater => break false,
+            }
+        };
+
+        if prev_next == self.curr {
+            return Ok(found);
+        }
+
+        self.prev
+            .compare_and_set(prev_next, self.curr, Ordering::Release, guard)
+            .map_err(|_| ())?;
+
+        let mut node = prev_next;
+        while node != self.curr {
+            unsafe {
+                let next = node.deref().next.load(Ordering::Acquire, guard);
+                guard.defer_destroy(node);
+                node = next.with_tag(0);
+            }
+        }
+
+        Ok(found)
+    }
+}
+
+impl<T> LockFreeListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+}
+
+impl<T: Ord> LockFreeListSet<T> {
+    fn find<'g>(&'g self, key: &T, guard: &'g Guard) -> (bool, Cursor<'g, T>) {
+        loop {
+            let mut cursor = Cursor {
+                prev: &self.head,
+                curr: self.head.load(Ordering::Acquire, guard),
+            };
+            if let Ok(found) = cursor.find(key, guard) {
+                return (found, cursor);
+            }
+        }
+    }
+
+    /// Returns `true` if the set contains the key.
+    pub fn contains(&self, key: &T) -> bool {
+        let guard = &epoch::pin();
+        let mut curr = self.head.load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let next = node.next.load(Ordering::Acquire, guard);
+            match node.data.cmp(key) {
+                Less => curr = next.with_tag(0),
+                Equal => return next.tag() == 0,
+                Greater => return false,
+            }
+        }
+        false
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &epoch::pin();
+        let mut node = Owned::new(Node {
+            data: key,
+            next: Atomic::null(),
+        });
+        loop {
+            let (found, cursor) = self.find(&node.data, guard);
+            if found {
+                return Err(node.into_box().data);
+            }
+            node.next.store(cursor.curr, Ordering::Relaxed);
+            match cursor
+                .prev
+                .compare_and_set(cursor.curr, node, Ordering::Release, guard)
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => node = e.new,
+            }
+        }
+    }
+}
+
+impl<T: Ord + Clone> LockFreeListSet<T> {
+    /// Remove the key from the set and return it.
+    ///
+    /// The key is cloned out of the removed node, since the concurrent traversals may still be
+    /// reading it.
+    pub fn remove(&self, key: &T) -> Result<T, ()> {
+        let guard = &epoch::pin();
+        loop {
+            let (found, cursor) = self.find(key, guard);
+            if !found {
+                return Err(());
+            }
+            let node = unsafe { cursor.curr.deref() };
+            let next = node.next.fetch_or(1, Ordering::AcqRel, guard);
+            if next.tag() != 0 {
+                // Concurrently removed.
+                continue;
+            }
+            if cursor
+                .prev
+                .compare_and_set(cursor.curr, next, Ordering::Release, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(cursor.curr) };
+            }
+            return Ok(node.data.clone());
+        }
+    }
+}
+
+/// Iterator over the elements of a `LockFreeListSet`, protected by a guard.
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<T> LockFreeListSet<T> {
+    /// An iterator visiting all elements. The elements inserted or removed concurrently may or may
+    /// not be visited, but the visited ones are in order.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = unsafe { self.curr.as_ref() } {
+            let next = node.next.load(Ordering::Acquire, self.guard);
+            self.curr = next.with_tag(0);
+            if next.tag() == 0 {
+                return Some(&node.data);
+            }
+        }
+        None
+    }
+}
+
+impl<T> Drop for LockFreeListSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let node = curr.into_owned();
+                curr = node.next.load(Ordering::Relaxed, guard).with_tag(0);
+            }
+        }
+    }
+}
+
+impl<T> Default for LockFreeListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
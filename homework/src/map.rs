@@ -1,7 +1,10 @@
+use core::hash::Hash;
 use core::marker::PhantomData;
-use crossbeam_epoch::Guard;
+use crossbeam_epoch::{self as epoch, Guard, Shared};
 use lock::{Lock, RawLock};
 use rand::{distributions::Alphanumeric, rngs::ThreadRng, Rng};
+use std::collections::{btree_map, hash_map, BTreeMap, HashMap};
+use std::sync::{Mutex, RwLock};
 
 /// Types that has random generator
 pub trait RandGen {
@@ -70,6 +73,82 @@ pub trait NonblockingMap<K: ?Sized, V> {
 
     /// Deletes the given key and its value.
     fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()>;
+
+    /// Calls `f` with each key-value pair. The pairs inserted or deleted concurrently may or may not
+    /// be visited.
+    ///
+    /// Returns `Err(())` if the map doesn't support iteration, which is the default.
+    fn for_each<'a, F>(&'a self, guard: &'a Guard, f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        let _ = (guard, f);
+        Err(())
+    }
+
+    /// Returns the value of the key, inserting the value returned by `f` if it is absent.
+    ///
+    /// `f` is called again if the inserted value is deleted concurrently before it is looked up.
+    fn get_or_insert_with<'a, F>(&'a self, key: &K, mut f: F, guard: &'a Guard) -> &'a V
+    where
+        F: FnMut() -> V,
+    {
+        let mut value = None;
+        loop {
+            if let Some(v) = self.lookup(key, guard) {
+                return v;
+            }
+            let v = value.take().unwrap_or_else(&mut f);
+            if let Err(v) = self.insert(key, v, guard) {
+                value = Some(v);
+            }
+        }
+    }
+
+    /// Returns the value of the key, inserting a clone of `value` if it is absent.
+    fn get_or_insert<'a>(&'a self, key: &K, value: V, guard: &'a Guard) -> &'a V
+    where
+        V: Clone,
+    {
+        self.get_or_insert_with(key, || value.clone(), guard)
+    }
+
+    /// Lookups each of `keys`, and returns the values in the order of the keys.
+    ///
+    /// As for the other bulk operations, the operation on each key is linearizable, but not the
+    /// bulk operation as a whole. The default runs them one by one, and an implementation may run
+    /// them in another order, e.g. sorted in a single pass, keeping the order of the same keys.
+    fn lookup_all<'a>(&'a self, keys: &[K], guard: &'a Guard) -> Vec<Option<&'a V>>
+    where
+        K: Sized,
+    {
+        keys.iter().map(|key| self.lookup(key, guard)).collect()
+    }
+
+    /// Inserts each of `keys` with the value at the same position in `values`, and returns the
+    /// results in the order of the keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `values` have different lengths.
+    fn insert_all(&self, keys: &[K], values: Vec<V>, guard: &Guard) -> Vec<Result<(), V>>
+    where
+        K: Sized,
+    {
+        assert_eq!(keys.len(), values.len());
+        keys.iter()
+            .zip(values)
+            .map(|(key, value)| self.insert(key, value, guard))
+            .collect()
+    }
+
+    /// Deletes each of `keys`, and returns the results in the order of the keys.
+    fn delete_all<'a>(&'a self, keys: &[K], guard: &'a Guard) -> Vec<Result<&'a V, ()>>
+    where
+        K: Sized,
+    {
+        keys.iter().map(|key| self.delete(key, guard)).collect()
+    }
 }
 
 /// Converts str sequential map into string sequential map
@@ -141,3 +220,158 @@ impl<K: ?Sized, V: Clone, M: NonblockingMap<K, V>> ConcurrentMap<K, V>
         self.inner.delete(key, guard).map(|v| v.clone())
     }
 }
+
+/// Converts nonblocking map into a map that pins the epoch internally and returns owned values, so
+/// that its API is like `HashMap`'s.
+///
+/// The trait name `ConcurrentMap` is taken by the guard-taking interface above.
+#[derive(Default, Debug)]
+pub struct PinnedMap<K: ?Sized, V, M: NonblockingMap<K, V>> {
+    inner: M,
+    _marker: PhantomData<(Box<K>, V)>,
+}
+
+impl<K: ?Sized, V, M: NonblockingMap<K, V>> PinnedMap<K, V, M> {
+    /// Wraps the given map.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the inner map.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Returns `true` if the map contains the key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.lookup(key, &epoch::pin()).is_some()
+    }
+
+    /// Inserts a key-value pair. Returns the value back if the key is already in the map.
+    pub fn insert(&self, key: &K, value: V) -> Result<(), V> {
+        self.inner.insert(key, value, &epoch::pin())
+    }
+}
+
+impl<K: ?Sized, V: Clone, M: NonblockingMap<K, V>> PinnedMap<K, V, M> {
+    /// Returns a clone of the value of the key.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.inner.lookup(key, &epoch::pin()).cloned()
+    }
+
+    /// Removes the key and returns a clone of its value.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.inner.delete(key, &epoch::pin()).ok().cloned()
+    }
+}
+
+/// Returns a reference to the value in `value`, which stays valid after the box is removed from a
+/// locked map, since it is dropped only when `guard` is unpinned.
+///
+/// # Safety
+///
+/// `guard` must be pinned, and no other reference to the box may be used to drop it.
+pub(crate) unsafe fn defer_drop<V: Send>(value: Box<V>, guard: &Guard) -> &V {
+    let value = Box::into_raw(value);
+    guard.defer_destroy(Shared::from(value as *const V));
+    &*value
+}
+
+/// `Mutex<HashMap>` as a nonblocking map, a baseline for the lock-free maps.
+///
+/// The values are boxed, so that the references to them stay valid after the lock is released.
+#[derive(Debug)]
+pub struct MutexHashMap<K, V> {
+    inner: Mutex<HashMap<K, Box<V>>>,
+}
+
+impl<K, V> Default for MutexHashMap<K, V> {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Send + Sync> NonblockingMap<K, V> for MutexHashMap<K, V> {
+    fn lookup<'a>(&'a self, key: &K, _guard: &'a Guard) -> Option<&'a V> {
+        let inner = self.inner.lock().unwrap();
+        inner.get(key).map(|v| unsafe { &*(&**v as *const V) })
+    }
+
+    fn insert(&self, key: &K, value: V, _guard: &Guard) -> Result<(), V> {
+        match self.inner.lock().unwrap().entry(key.clone()) {
+            hash_map::Entry::Occupied(_) => Err(value),
+            hash_map::Entry::Vacant(e) => {
+                let _ = e.insert(Box::new(value));
+                Ok(())
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let value = self.inner.lock().unwrap().remove(key).ok_or(())?;
+        Ok(unsafe { defer_drop(value, guard) })
+    }
+
+    fn for_each<'a, F>(&'a self, _guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        for (k, v) in self.inner.lock().unwrap().iter() {
+            f(k, unsafe { &*(&**v as *const V) });
+        }
+        Ok(())
+    }
+}
+
+/// `RwLock<BTreeMap>` as a nonblocking map, a baseline for the lock-free maps.
+///
+/// The values are boxed, so that the references to them stay valid after the lock is released.
+#[derive(Debug)]
+pub struct RwLockBTreeMap<K, V> {
+    inner: RwLock<BTreeMap<K, Box<V>>>,
+}
+
+impl<K: Ord, V> Default for RwLockBTreeMap<K, V> {
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Send + Sync> NonblockingMap<K, V> for RwLockBTreeMap<K, V> {
+    fn lookup<'a>(&'a self, key: &K, _guard: &'a Guard) -> Option<&'a V> {
+        let inner = self.inner.read().unwrap();
+        inner.get(key).map(|v| unsafe { &*(&**v as *const V) })
+    }
+
+    fn insert(&self, key: &K, value: V, _guard: &Guard) -> Result<(), V> {
+        match self.inner.write().unwrap().entry(key.clone()) {
+            btree_map::Entry::Occupied(_) => Err(value),
+            btree_map::Entry::Vacant(e) => {
+                let _ = e.insert(Box::new(value));
+                Ok(())
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let value = self.inner.write().unwrap().remove(key).ok_or(())?;
+        Ok(unsafe { defer_drop(value, guard) })
+    }
+
+    fn for_each<'a, F>(&'a self, _guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        for (k, v) in self.inner.read().unwrap().iter() {
+            f(k, unsafe { &*(&**v as *const V) });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Quiescent-state-based reclamation (QSBR).
+//!
+//! Instead of being pinned during each operation as in `ebr`, an online thread announces the
+//! quiescent states between its operations, where it holds no pointer loaded from the shared
+//! objects. The retired pointers and the deferred callbacks are freed or called after a grace
+//! period, i.e. once all online threads have passed a quiescent state. This suits the loops whose
+//! iterations are independent, e.g. a server handling one request per iteration.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use cs492_concur_homework::qsbr::{self, Atomic, Owned};
+//!
+//! let atomic = Atomic::new(0);
+//! qsbr::online();
+//! for request in 1..10 {
+//!     let old = atomic.load(Ordering::Acquire);
+//!     atomic.store(Owned::new(request).into_shared(), Ordering::Release);
+//!     assert_eq!(unsafe { *old.deref() }, request - 1);
+//!     qsbr::retire(old);
+//!
+//!     // no pointer loaded in this iteration is used afterwards
+//!     qsbr::quiescent_state();
+//! }
+//! qsbr::offline();
+//! # qsbr::retire(atomic.load(Ordering::Relaxed));
+//! ```
+//!
+//! # Algorithm and Synchronization
+//!
+//! It reuses the domain of `ebr`: an online thread is always pinned, and a quiescent state unpins
+//! and pins it again at the global epoch. So the global epoch advances once all online threads
+//! have passed a quiescent state, and the retired pointers are freed two epochs later as in `ebr`.
+//! An offline thread is unpinned, so it doesn't block the grace periods, but it must not access
+//! the shared objects.
+//!
+//! A read-side critical section (`read_lock`) borrows the quiescent states of the current thread:
+//! until its guard is dropped, `quiescent_state` and `offline` panic, so that the pointers loaded in
+//! it are not freed while it may still use them.
+//!
+//! Compared to `ebr`, the readers don't even need a fence for each operation, but a thread that is
+//! online and doesn't pass a quiescent state blocks the reclamation of all threads.
+
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::ebr::bag::Bags;
+use crate::ebr::global::Participant;
+use crate::hazard_pointer::{Free, Retired};
+
+pub use crate::ebr::Global;
+pub use crate::hazard_pointer::{Atomic, Owned, Shared};
+
+/// Global domain of the threads using QSBR. It is separate from `ebr::GLOBAL`, so that a thread
+/// that doesn't pass a quiescent state doesn't block the reclamation of `ebr`.
+pub static GLOBAL: Global = Global::new();
+
+/// The state of a thread in `GLOBAL`.
+#[derive(Debug)]
+struct Local {
+    participant: &'static Participant,
+    online: Cell<bool>,
+    /// The number of `ReadGuard`s of the thread.
+    readers: Cell<usize>,
+    bags: RefCell<Bags>,
+}
+
+impl Local {
+    fn new() -> Self {
+        Self {
+            participant: GLOBAL.register(),
+            online: Cell::new(false),
+            readers: Cell::new(0),
+            bags: RefCell::new(Bags::default()),
+        }
+    }
+
+    fn online(&self) {
+        if !self.online.replace(true) {
+            self.participant.pin(&GLOBAL);
+        }
+    }
+
+    fn offline(&self) {
+        self.assert_no_readers();
+        if self.online.replace(false) {
+            self.participant.unpin();
+        }
+    }
+
+    fn quiescent_state(&self) {
+        self.assert_no_readers();
+        if self.online.get() {
+            // The accesses before happen before the pointers retired afterwards are freed.
+            self.participant.unpin();
+            self.participant.pin(&GLOBAL);
+        }
+        self.collect();
+    }
+
+    fn read_lock(&self) {
+        assert!(self.online.get(), "reading while offline");
+        self.readers.set(self.readers.get() + 1);
+    }
+
+    fn assert_no_readers(&self) {
+        assert_eq!(
+            self.readers.get(),
+            0,
+            "quiescent state inside a read-side critical section"
+        );
+    }
+
+    fn retire(&self, retired: Retired) {
+        let full = self.bags.borrow_mut().push(retired);
+        if full {
+            self.collect();
+        }
+    }
+
+    fn collect(&self) {
+        let expired = {
+            let mut bags = self.bags.borrow_mut();
+            bags.seal(GLOBAL.epoch());
+            bags.adopt(GLOBAL.take_orphans());
+            let epoch = GLOBAL.try_advance();
+            bags.take_expired(epoch)
+        };
+        for bag in expired {
+            bag.free();
+        }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        // No `ReadGuard` of the thread can be used anymore, even if one is leaked.
+        if self.online.replace(false) {
+            self.participant.unpin();
+        }
+        self.collect();
+        let mut bags = self.bags.borrow_mut();
+        if !bags.is_empty() {
+            GLOBAL.push_orphans(bags.take_sealed());
+        }
+        GLOBAL.unregister(self.participant);
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::new();
+}
+
+/// Makes the current thread online, so that it may access the shared objects until the next
+/// quiescent state.
+pub fn online() {
+    LOCAL.with(|l| l.online());
+}
+
+/// Makes the current thread offline, e.g. before it blocks. The pointers loaded while online must
+/// not be used afterwards.
+///
+/// # Panics
+///
+/// Panics if the current thread holds a `ReadGuard`.
+pub fn offline() {
+    LOCAL.with(|l| l.offline());
+}
+
+/// Returns `true` if the current thread is online.
+pub fn is_online() -> bool {
+    LOCAL.with(|l| l.online.get())
+}
+
+/// Starts a read-side critical section of the current thread, which must be online. The pointers
+/// loaded while the returned guard is alive are not freed before it is dropped: `quiescent_state`
+/// and `offline` panic until then.
+///
+/// # Panics
+///
+/// Panics if the current thread is offline.
+pub fn read_lock() -> ReadGuard {
+    LOCAL.with(|l| l.read_lock());
+    ReadGuard {
+        _marker: PhantomData,
+    }
+}
+
+/// A read-side critical section of the current thread. See `read_lock`.
+#[derive(Debug)]
+pub struct ReadGuard {
+    /// The guard counts towards the readers of the thread that created it, so it is `!Send`.
+    _marker: PhantomData<*const ()>,
+}
+
+impl Drop for ReadGuard {
+    fn drop(&mut self) {
+        // The thread-local state is gone if the guard outlives it, e.g. in another thread-local.
+        let _ = LOCAL.try_with(|l| l.readers.set(l.readers.get() - 1));
+    }
+}
+
+/// Announces that the current thread holds no pointer loaded from the shared objects, and frees
+/// the pointers retired before the last grace period.
+///
+/// # Panics
+///
+/// Panics if the current thread holds a `ReadGuard`.
+pub fn quiescent_state() {
+    LOCAL.with(|l| l.quiescent_state());
+}
+
+/// Retires a pointer. It is freed after the next grace period, possibly by another thread, so `T`
+/// must be `Send`.
+pub fn retire<T: Send>(pointer: Shared<T>) {
+    unsafe fn free<T>(data: *mut ()) {
+        drop(Box::from_raw(data as *mut T))
+    }
+    retire_with(pointer, free::<T>);
+}
+
+/// Retires a pointer to an object not allocated as a `Box<T>`. It is freed by calling `free` with
+/// the pointer without tag, cast to `*mut ()`.
+pub fn retire_with<T: Send>(pointer: Shared<T>, free: unsafe fn(*mut ())) {
+    let data = pointer.as_raw() as *mut ();
+    LOCAL.with(|l| l.retire((data, Free::Object(free), mem::size_of::<T>())));
+}
+
+/// Defers `f` after the next grace period.
+///
+/// `f` is called in a quiescent state of a thread, and must not call the functions of this module.
+pub fn defer<F: FnOnce() + Send + 'static>(f: F) {
+    unsafe fn call<F: FnOnce()>(data: *mut ()) {
+        let f = Box::from_raw(data as *mut F);
+        f()
+    }
+    let pointer = Shared::from(Box::into_raw(Box::new(f)) as *const F);
+    retire_with(pointer, call::<F>);
+}
@@ -0,0 +1,221 @@
+//! Read-copy-update (RCU).
+//!
+//! An `Rcu<T>` holds a pointer to the current version of the data. The readers access the version
+//! they load without any synchronization other than that of the grace periods, and an update copies
+//! the version, modifies the copy, and publishes it. The old version is freed after a grace period,
+//! i.e. once all readers that may access it are done. The grace periods are detected by `ebr`
+//! (`Epoch`, the default) or by `qsbr` (`Qsbr`).
+//!
+//! # Example
+//!
+//! ```
+//! use cs492_concur_homework::rcu::Rcu;
+//!
+//! let rcu: Rcu<_> = Rcu::new(vec![1, 2]);
+//! let old = rcu.read();
+//! rcu.update(|v| {
+//!     let mut v = v.clone();
+//!     v.push(3);
+//!     v
+//! });
+//! assert_eq!(*old, vec![1, 2]);
+//! drop(old);
+//! assert_eq!(*rcu.read(), vec![1, 2, 3]);
+//!
+//! // waits until the old version is freed
+//! rcu.synchronize();
+//! ```
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::hazard_pointer::{Atomic, Owned, Shared};
+use crate::{ebr, qsbr};
+
+/// How the grace periods are detected.
+pub trait GracePeriod {
+    /// Keeps the versions loaded while it is alive from being freed.
+    type ReadGuard;
+
+    /// Starts a read-side critical section.
+    fn read_lock() -> Self::ReadGuard;
+
+    /// Retires a version. It is freed after a grace period, possibly by another thread.
+    fn retire<T: Send>(pointer: Shared<T>);
+
+    /// Defers `f` after a grace period.
+    fn defer<F: FnOnce() + Send + 'static>(f: F);
+
+    /// Frees the versions and calls the deferred functions whose grace periods have ended, helping
+    /// the grace periods to end if necessary.
+    fn poll();
+}
+
+/// Grace periods detected by `ebr`: a reader is pinned while it holds a `RcuReadGuard`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Epoch;
+
+impl GracePeriod for Epoch {
+    type ReadGuard = ebr::Guard;
+
+    fn read_lock() -> ebr::Guard {
+        ebr::pin()
+    }
+
+    fn retire<T: Send>(pointer: Shared<T>) {
+        ebr::retire(pointer);
+    }
+
+    fn defer<F: FnOnce() + Send + 'static>(f: F) {
+        ebr::defer(f);
+    }
+
+    fn poll() {
+        debug_assert!(!ebr::is_pinned(), "waiting for a grace period while pinned");
+        ebr::collect();
+    }
+}
+
+/// Grace periods detected by `qsbr`: a reader must be online, and a `RcuReadGuard` is a
+/// `qsbr::ReadGuard`, so the thread can't pass a quiescent state while holding it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Qsbr;
+
+impl GracePeriod for Qsbr {
+    type ReadGuard = qsbr::ReadGuard;
+
+    fn read_lock() -> qsbr::ReadGuard {
+        qsbr::read_lock()
+    }
+
+    fn retire<T: Send>(pointer: Shared<T>) {
+        qsbr::retire(pointer);
+    }
+
+    fn defer<F: FnOnce() + Send + 'static>(f: F) {
+        qsbr::defer(f);
+    }
+
+    fn poll() {
+        qsbr::quiescent_state();
+    }
+}
+
+/// A shared pointer to the data that is read without locking and updated by copying.
+pub struct Rcu<T, G: GracePeriod = Epoch> {
+    inner: Atomic<T>,
+    /// The grace periods are detected by functions, so `G` doesn't affect `Send` and `Sync`.
+    _marker: PhantomData<fn() -> G>,
+}
+
+impl<T, G: GracePeriod> Rcu<T, G> {
+    /// Creates a new `Rcu` with the initial version.
+    pub fn new(data: T) -> Self {
+        Self {
+            inner: Atomic::new(data),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the current version. It is not freed while the guard is alive, even if
+    /// it is replaced.
+    pub fn read(&self) -> RcuReadGuard<'_, T, G> {
+        let guard = G::read_lock();
+        let data = unsafe { &*(self.inner.load(Ordering::Acquire).into_usize() as *const T) };
+        RcuReadGuard {
+            _guard: guard,
+            data,
+        }
+    }
+
+    /// Publishes the version returned by `f` applied to the current version, and retires the
+    /// current version. If another update publishes a version in the meantime, `f` is called again
+    /// with that version. The current version may be freed by another thread, so `T` must be `Send`.
+    pub fn update<F: FnMut(&T) -> T>(&self, mut f: F)
+    where
+        T: Send,
+    {
+        let _guard = G::read_lock();
+        let mut cur = self.inner.load(Ordering::Acquire);
+        loop {
+            let new = Owned::new(f(unsafe { cur.deref() })).into_shared();
+            match self
+                .inner
+                .compare_and_set(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    G::retire(cur);
+                    return;
+                }
+                Err(current) => {
+                    unsafe { drop(new.into_owned()) };
+                    cur = current;
+                }
+            }
+        }
+    }
+
+    /// Waits until the readers that may access the versions retired so far are done, and frees the
+    /// versions retired by the current thread.
+    ///
+    /// With `Epoch`, the current thread must not hold a `RcuReadGuard`. With `Qsbr`, this is a
+    /// quiescent state of the current thread, so it panics if the thread holds a `RcuReadGuard`.
+    pub fn synchronize(&self) {
+        let done = Arc::new(AtomicBool::new(false));
+        let done_clone = done.clone();
+        G::defer(move || done_clone.store(true, Ordering::Release));
+        loop {
+            G::poll();
+            if done.load(Ordering::Acquire) {
+                return;
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+impl<T, G: GracePeriod> Drop for Rcu<T, G> {
+    fn drop(&mut self) {
+        unsafe { drop(self.inner.load(Ordering::Relaxed).into_owned()) };
+    }
+}
+
+impl<T: Default, G: GracePeriod> Default for Rcu<T, G> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug, G: GracePeriod> fmt::Debug for Rcu<T, G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = G::read_lock();
+        let data = unsafe { &*(self.inner.load(Ordering::Acquire).into_usize() as *const T) };
+        let result = f.debug_tuple("Rcu").field(data).finish();
+        drop(guard);
+        result
+    }
+}
+
+/// A reference to a version of the data in `Rcu`.
+pub struct RcuReadGuard<'r, T, G: GracePeriod> {
+    _guard: G::ReadGuard,
+    data: &'r T,
+}
+
+impl<T, G: GracePeriod> Deref for RcuReadGuard<'_, T, G> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<T: fmt::Debug, G: GracePeriod> fmt::Debug for RcuReadGuard<'_, T, G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.data, f)
+    }
+}
@@ -0,0 +1,315 @@
+//! Phase-fair reader-writer lock.
+//!
+//! `PhaseFairRwLock` is the ticket-based phase-fair lock (PF-T) of Brandenburg and Anderson. The
+//! readers and the writers alternate in phases: a writer waits for the readers that arrived before
+//! it, and the readers that arrive while a writer is waiting or writing wait for that writer only,
+//! not for the writers behind it. The writers are served in FIFO order. So neither the readers nor
+//! the writers starve, unlike `SpinRwLock`, where the waiting writers hold off the new readers.
+//!
+//! `RwLock` wraps a raw lock with poisoning, like `std::sync::RwLock`.
+//!
+//! Reference: Björn B. Brandenburg and James H. Anderson. Spin-Based Reader-Writer Synchronization
+//! for Multiprocessor Real-Time Systems. Real-Time Systems 46(1), 2010.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crossbeam_utils::{Backoff, CachePadded};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::thread;
+
+use crate::list_set::{RawRwLock, RawTryRwLock};
+
+/// The unit of the reader counts.
+const RINC: usize = 0x100;
+/// The bits of `rin` for the writer.
+const WBITS: usize = 0x3;
+/// A writer is present.
+const PRES: usize = 0x2;
+/// The phase of the present writer, to distinguish consecutive writers.
+const PHID: usize = 0x1;
+
+/// Ticket-based phase-fair reader-writer spinlock.
+///
+/// The token is `true` if the lock is held exclusive.
+#[derive(Debug, Default)]
+pub struct PhaseFairRwLock {
+    /// The number of readers that have arrived, in `RINC`s, with the `WBITS` of the writer.
+    rin: CachePadded<AtomicUsize>,
+    /// The number of readers that have left, in `RINC`s.
+    rout: CachePadded<AtomicUsize>,
+    /// The ticket of the next writer.
+    win: CachePadded<AtomicUsize>,
+    /// The ticket of the writer being served.
+    wout: CachePadded<AtomicUsize>,
+}
+
+impl RawRwLock for PhaseFairRwLock {
+    type Token = bool;
+
+    fn read(&self) -> bool {
+        let w = self.rin.fetch_add(RINC, Ordering::Acquire) & WBITS;
+        if w != 0 {
+            // Waits only for the writer of the current phase.
+            let backoff = Backoff::new();
+            while self.rin.load(Ordering::Acquire) & WBITS == w {
+                backoff.snooze();
+            }
+        }
+        false
+    }
+
+    fn write(&self) -> bool {
+        let ticket = self.win.fetch_add(1, Ordering::Relaxed);
+        let backoff = Backoff::new();
+        while self.wout.load(Ordering::Acquire) != ticket {
+            backoff.snooze();
+        }
+
+        // Blocks the new readers, and waits for the present ones.
+        let w = PRES | (ticket & PHID);
+        let readers = self.rin.fetch_add(w, Ordering::Relaxed);
+        let backoff = Backoff::new();
+        while self.rout.load(Ordering::Acquire) != readers {
+            backoff.snooze();
+        }
+        true
+    }
+
+    unsafe fn unlock(&self, write: bool) {
+        if write {
+            self.rin.fetch_and(!WBITS, Ordering::Release);
+            let ticket = self.wout.load(Ordering::Relaxed);
+            self.wout.store(ticket.wrapping_add(1), Ordering::Release);
+        } else {
+            self.rout.fetch_add(RINC, Ordering::Release);
+        }
+    }
+}
+
+impl RawTryRwLock for PhaseFairRwLock {
+    fn try_read(&self) -> Result<bool, ()> {
+        let rin = self.rin.load(Ordering::Relaxed);
+        if rin & WBITS != 0 {
+            return Err(());
+        }
+        self.rin
+            .compare_exchange(
+                rin,
+                rin.wrapping_add(RINC),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .map(|_| false)
+            .map_err(|_| ())
+    }
+
+    fn try_write(&self) -> Result<bool, ()> {
+        let ticket = self.wout.load(Ordering::Relaxed);
+        let readers = self.rin.load(Ordering::Relaxed);
+        if self.rout.load(Ordering::Relaxed) != readers
+            || self
+                .win
+                .compare_exchange(
+                    ticket,
+                    ticket.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return Err(());
+        }
+
+        let w = PRES | (ticket & PHID);
+        let readers = self.rin.fetch_add(w, Ordering::Relaxed);
+        if self.rout.load(Ordering::Acquire) != readers {
+            // A reader has arrived in the meantime. Gives the lock to the next writer or the
+            // readers, as if it were released right away.
+            unsafe { self.unlock(true) };
+            return Err(());
+        }
+        Ok(true)
+    }
+}
+
+/// Reader-writer lock with poisoning, protecting a data of type `T` by a raw lock of type `L`.
+///
+/// As with `std::sync::RwLock`, the lock is poisoned if a writer panics while holding it, and the
+/// lock operations then return `Err` with the guard. The readers don't poison the lock.
+pub struct RwLock<T, L: RawRwLock = PhaseFairRwLock> {
+    lock: L,
+    poison: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send, L: RawRwLock> Send for RwLock<T, L> {}
+unsafe impl<T: Send + Sync, L: RawRwLock> Sync for RwLock<T, L> {}
+
+impl<T, L: RawRwLock> RwLock<T, L> {
+    /// Creates a new lock.
+    pub fn new(data: T) -> Self {
+        Self {
+            lock: L::default(),
+            poison: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Acquires the lock shared.
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T, L>> {
+        let token = self.lock.read();
+        self.poison_result(RwLockReadGuard { lock: self, token })
+    }
+
+    /// Acquires the lock exclusive.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T, L>> {
+        let token = self.lock.write();
+        self.poison_result(RwLockWriteGuard::new(self, token))
+    }
+
+    /// Returns `true` if a writer has panicked while holding the lock.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.load(Ordering::Relaxed)
+    }
+
+    /// Returns the data.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = unsafe { &mut *self.data.get() };
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn poison_result<G>(&self, guard: G) -> LockResult<G> {
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+impl<T, L: RawTryRwLock> RwLock<T, L> {
+    /// Tries to acquire the lock shared.
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T, L>> {
+        let token = self.lock.try_read().map_err(|_| TryLockError::WouldBlock)?;
+        Ok(self.poison_result(RwLockReadGuard { lock: self, token })?)
+    }
+
+    /// Tries to acquire the lock exclusive.
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T, L>> {
+        let token = self
+            .lock
+            .try_write()
+            .map_err(|_| TryLockError::WouldBlock)?;
+        Ok(self.poison_result(RwLockWriteGuard::new(self, token))?)
+    }
+}
+
+impl<T: Default, L: RawRwLock> Default for RwLock<T, L> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug, L: RawTryRwLock> fmt::Debug for RwLock<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(TryLockError::Poisoned(err)) => d.field("data", &&*err.into_inner()),
+            Err(TryLockError::WouldBlock) => d.field("data", &format_args!("<locked>")),
+        };
+        d.field("poisoned", &self.is_poisoned()).finish()
+    }
+}
+
+/// Shared guard of `RwLock`.
+pub struct RwLockReadGuard<'l, T, L: RawRwLock = PhaseFairRwLock> {
+    lock: &'l RwLock<T, L>,
+    token: L::Token,
+}
+
+impl<T, L: RawRwLock> Deref for RwLockReadGuard<'_, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T, L: RawRwLock> Drop for RwLockReadGuard<'_, T, L> {
+    fn drop(&mut self) {
+        unsafe { self.lock.lock.unlock(self.token.clone()) };
+    }
+}
+
+impl<T: fmt::Debug, L: RawRwLock> fmt::Debug for RwLockReadGuard<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive guard of `RwLock`.
+pub struct RwLockWriteGuard<'l, T, L: RawRwLock = PhaseFairRwLock> {
+    lock: &'l RwLock<T, L>,
+    token: L::Token,
+    /// Whether the thread was panicking when the lock is acquired. Then the lock is not poisoned
+    /// by that panic.
+    panicking: bool,
+}
+
+impl<'l, T, L: RawRwLock> RwLockWriteGuard<'l, T, L> {
+    fn new(lock: &'l RwLock<T, L>, token: L::Token) -> Self {
+        Self {
+            lock,
+            token,
+            panicking: thread::panicking(),
+        }
+    }
+}
+
+impl<T, L: RawRwLock> Deref for RwLockWriteGuard<'_, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T, L: RawRwLock> DerefMut for RwLockWriteGuard<'_, T, L> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T, L: RawRwLock> Drop for RwLockWriteGuard<'_, T, L> {
+    fn drop(&mut self) {
+        if !self.panicking && thread::panicking() {
+            self.lock.poison.store(true, Ordering::Relaxed);
+        }
+        unsafe { self.lock.lock.unlock(self.token.clone()) };
+    }
+}
+
+impl<T: fmt::Debug, L: RawRwLock> fmt::Debug for RwLockWriteGuard<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
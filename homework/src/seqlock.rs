@@ -0,0 +1,238 @@
+//! Sequence lock for small `Copy` data.
+//!
+//! The writers are serialized by a sequence number that is odd while a writer is writing. A reader
+//! copies the data optimistically, and retries if the sequence number has changed meanwhile, so
+//! the readers never block the writers. It suits the data that is read much more often than it is
+//! written, e.g. statistics counters.
+//!
+//! # Example
+//!
+//! ```
+//! use cs492_concur_homework::SeqLock;
+//!
+//! let lock = SeqLock::new([0u64, 0u64]);
+//! lock.write([1, 2]);
+//! lock.update(|[requests, bytes]| [requests + 1, bytes + 10]);
+//! assert_eq!(lock.read(), [2, 12]);
+//! ```
+//!
+//! # Synchronization
+//!
+//! The data is stored in words accessed with relaxed atomics, so a racing read is not undefined
+//! behavior, only possibly torn. The fences make sure a torn read is detected (see Hans-J. Boehm.
+//! Can Seqlocks Get Along With Programming Language Memory Models? MSPC 2012):
+//!
+//! ```text
+//! (W1) seq: s -> s + 1 (CAS)           | (R1) load seq = s (acquire)
+//! (W2) fence(Release)                  | (R2) load the data (relaxed)
+//! (W3) store the data (relaxed)        | (R3) fence(Acquire)
+//! (W4) store seq = s + 2 (release)     | (R4) load seq, check it is s (relaxed)
+//! ```
+//!
+//! If `R2` reads a value written by `W3`, `W2` synchronizes with `R3`, so `R4` reads `s + 1` or a
+//! later value, and the read is retried. Otherwise, if `R1` reads the value written by `W4`, all
+//! of the data written by the writer is visible to `R2`.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread::yield_now;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(not(feature = "check-loom"))]
+use std::thread::yield_now;
+
+/// The maximum number of words of the data.
+const MAX_WORDS: usize = 8;
+
+/// `Copy` types without padding bytes, whose bytes can be copied to words.
+///
+/// # Safety
+///
+/// All bytes of a value of the type must be initialized, e.g. a `#[repr(C)]` struct of `NoPadding`
+/// fields whose offsets and size leave no gap.
+pub unsafe trait NoPadding: Copy {}
+
+macro_rules! impl_no_padding {
+    ($($t:ty),*) => {
+        $(unsafe impl NoPadding for $t {})*
+    };
+}
+
+impl_no_padding!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+macro_rules! impl_no_padding_array {
+    ($($n:expr),*) => {
+        // The elements of an array are contiguous, and a size is a multiple of the alignment.
+        $(unsafe impl<T: NoPadding> NoPadding for [T; $n] {})*
+    };
+}
+
+impl_no_padding_array!(
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31, 32, 48, 64
+);
+
+/// Sequence lock for small `Copy` data, up to `8 * size_of::<usize>()` bytes.
+///
+/// The data is copied word by word, so `T` must not have padding bytes (see `NoPadding`).
+pub struct SeqLock<T> {
+    seq: AtomicUsize,
+    data: Box<[AtomicUsize]>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SeqLock<T> {}
+unsafe impl<T: Send> Sync for SeqLock<T> {}
+
+impl<T: NoPadding> SeqLock<T> {
+    /// Creates a new sequence lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is larger than `8 * size_of::<usize>()` bytes.
+    pub fn new(data: T) -> Self {
+        let words = Self::words();
+        assert!(words <= MAX_WORDS, "`SeqLock` is only for small data");
+        let lock = Self {
+            seq: AtomicUsize::new(0),
+            data: (0..words).map(|_| AtomicUsize::new(0)).collect(),
+            _marker: PhantomData,
+        };
+        lock.store(data);
+        lock
+    }
+
+    fn words() -> usize {
+        (mem::size_of::<T>() + mem::size_of::<usize>() - 1) / mem::size_of::<usize>()
+    }
+
+    /// Returns a copy of the data. Retries while a writer is writing.
+    pub fn read(&self) -> T {
+        let mut buf = [0usize; MAX_WORDS];
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 1 {
+                yield_now();
+                continue;
+            }
+            for (word, data) in buf.iter_mut().zip(self.data.iter()) {
+                *word = data.load(Ordering::Relaxed);
+            }
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return unsafe { ptr::read_unaligned(buf.as_ptr() as *const T) };
+            }
+        }
+    }
+
+    /// Replaces the data.
+    pub fn write(&self, data: T) {
+        self.update(|_| data);
+    }
+
+    /// Replaces the data with `f` applied to it. The other writers wait until it returns. If `f`
+    /// panics, the data is not changed.
+    pub fn update<F: FnOnce(T) -> T>(&self, f: F) {
+        let guard = self.write_lock();
+        // The data is not changed by the others.
+        let data = self.read_locked();
+        self.store(f(data));
+        drop(guard);
+    }
+
+    fn write_lock(&self) -> WriteGuard<'_, T> {
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange(
+                        seq,
+                        seq.wrapping_add(1),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                // The data is not stored before the readers may see the odd sequence number.
+                fence(Ordering::Release);
+                return WriteGuard { lock: self, seq };
+            }
+            yield_now();
+        }
+    }
+
+    /// Returns the data, which must not be changed concurrently.
+    fn read_locked(&self) -> T {
+        let mut buf = [0usize; MAX_WORDS];
+        for (word, data) in buf.iter_mut().zip(self.data.iter()) {
+            *word = data.load(Ordering::Relaxed);
+        }
+        unsafe { ptr::read_unaligned(buf.as_ptr() as *const T) }
+    }
+
+    /// Stores the data, which must not be accessed concurrently by the other writers.
+    fn store(&self, data: T) {
+        let mut buf = [0usize; MAX_WORDS];
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &data as *const T as *const u8,
+                buf.as_mut_ptr() as *mut u8,
+                mem::size_of::<T>(),
+            )
+        };
+        for (word, data) in buf.iter().zip(self.data.iter()) {
+            data.store(*word, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Releases the sequence number acquired by `write_lock`, also if the writer panics.
+struct WriteGuard<'s, T> {
+    lock: &'s SeqLock<T>,
+    seq: usize,
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .seq
+            .store(self.seq.wrapping_add(2), Ordering::Release);
+    }
+}
+
+impl<T: NoPadding + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: NoPadding + fmt::Debug> fmt::Debug for SeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SeqLock").field(&self.read()).finish()
+    }
+}
@@ -0,0 +1,108 @@
+//! Sets with a common interface.
+
+use core::marker::PhantomData;
+use crossbeam_epoch as epoch;
+
+use crate::lazy_list_set::LazyListSet;
+use crate::list_set::{OrderedListSet, RawRwLock};
+use crate::lockfree_list_set::LockFreeListSet;
+use crate::map::NonblockingMap;
+
+/// Trait for a concurrent set, so that the set-like structures are tested and benchmarked alike.
+///
+/// Unlike `NonblockingMap`, the operations pin the epoch internally if needed, since the removed
+/// keys are returned by value.
+pub trait NonblockingSet<K> {
+    /// Inserts a key. If the set already has the key, returns the provided key in `Err`.
+    fn insert(&self, key: K) -> Result<(), K>;
+
+    /// Returns `true` if the set contains the key.
+    fn contains(&self, key: &K) -> bool;
+
+    /// Removes the key from the set and returns it.
+    fn remove(&self, key: &K) -> Result<K, ()>;
+}
+
+/// Converts nonblocking map to `()` into the set of its keys.
+///
+/// The removed key is cloned from the given one, since the map only returns the value. A blanket
+/// implementation for the maps would conflict with the implementations for the other sets.
+#[derive(Default, Debug)]
+pub struct NonblockingMapSet<K, M: NonblockingMap<K, ()>> {
+    inner: M,
+    _marker: PhantomData<K>,
+}
+
+impl<K, M: NonblockingMap<K, ()>> NonblockingMapSet<K, M> {
+    /// Wraps the given map.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the inner map.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<K: Clone, M: NonblockingMap<K, ()>> NonblockingSet<K> for NonblockingMapSet<K, M> {
+    fn insert(&self, key: K) -> Result<(), K> {
+        let guard = &epoch::pin();
+        self.inner.insert(&key, (), guard).map_err(|()| key)
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        let guard = &epoch::pin();
+        self.inner.lookup(key, guard).is_some()
+    }
+
+    fn remove(&self, key: &K) -> Result<K, ()> {
+        let guard = &epoch::pin();
+        self.inner.delete(key, guard).map(|_| key.clone())
+    }
+}
+
+impl<T: Ord, L: RawRwLock> NonblockingSet<T> for OrderedListSet<T, L> {
+    fn insert(&self, key: T) -> Result<(), T> {
+        OrderedListSet::insert(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        OrderedListSet::contains(self, key)
+    }
+
+    fn remove(&self, key: &T) -> Result<T, ()> {
+        OrderedListSet::remove(self, key)
+    }
+}
+
+impl<T: Ord + Clone> NonblockingSet<T> for LazyListSet<T> {
+    fn insert(&self, key: T) -> Result<(), T> {
+        LazyListSet::insert(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        LazyListSet::contains(self, key)
+    }
+
+    fn remove(&self, key: &T) -> Result<T, ()> {
+        LazyListSet::remove(self, key)
+    }
+}
+
+impl<T: Ord + Clone> NonblockingSet<T> for LockFreeListSet<T> {
+    fn insert(&self, key: T) -> Result<(), T> {
+        LockFreeListSet::insert(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        LockFreeListSet::contains(self, key)
+    }
+
+    fn remove(&self, key: &T) -> Result<T, ()> {
+        LockFreeListSet::remove(self, key)
+    }
+}
@@ -0,0 +1,277 @@
+//! Lock-free skiplist.
+//!
+//! Each level is a Harris list: a node is deleted at a level when its next link at the level is
+//! marked, and then unlinked by a traversal. A node is deleted when it is marked at level 0, after
+//! the upper levels. As in the paper, a node may be linked at an upper level after it is unlinked
+//! at level 0, e.g. by a slow insertion, so it counts the levels it is linked at, and is retired
+//! once this becomes zero.
+//!
+//! Reference: Maurice Herlihy and Nir Shavit. The Art of Multiprocessor Programming, Chapter 14.4.
+
+use core::cmp::Ordering::Less;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use lock::Backoff;
+use rand::prelude::*;
+
+use crate::map::NonblockingMap;
+
+/// The maximum height of a node.
+const MAX_HEIGHT: usize = 32;
+
+#[derive(Debug)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// The number of levels the node is linked at, plus one while it is being inserted.
+    refs: AtomicUsize,
+    /// The next links of each level. Mark: tag(), the node is deleted at the level
+    next: Box<[Atomic<Node<K, V>>]>,
+}
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V, height: usize) -> Self {
+        Self {
+            key,
+            value,
+            refs: AtomicUsize::new(0),
+            next: (0..height).map(|_| Atomic::null()).collect(),
+        }
+    }
+
+    /// Returns a random height, which is `h` with probability `2^-h`.
+    fn random_height() -> usize {
+        let bits = thread_rng().gen::<u32>();
+        (bits.trailing_ones() as usize + 1).min(MAX_HEIGHT)
+    }
+}
+
+/// The position of a key: the next links to the key and the nodes after them at each level.
+#[derive(Debug)]
+struct Position<'g, K, V> {
+    found: bool,
+    preds: [&'g Atomic<Node<K, V>>; MAX_HEIGHT],
+    succs: [Shared<'g, Node<K, V>>; MAX_HEIGHT],
+}
+
+/// Lock-free map from `K` to `V`, sorted by the keys.
+#[derive(Debug)]
+pub struct SkipList<K, V> {
+    /// The next links of the head of each level.
+    head: Box<[Atomic<Node<K, V>>]>,
+}
+
+impl<K, V> Default for SkipList<K, V> {
+    fn default() -> Self {
+        Self {
+            head: (0..MAX_HEIGHT).map(|_| Atomic::null()).collect(),
+        }
+    }
+}
+
+impl<K, V> Drop for SkipList<K, V> {
+    fn drop(&mut self) {
+        // Each node is freed at the lowest level it is linked at, from the top level.
+        unsafe {
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut curr = self.head[level].load(Ordering::Relaxed, unprotected());
+                while let Some(curr_ref) = curr.as_ref() {
+                    let next = curr_ref.next[level].load(Ordering::Relaxed, unprotected());
+                    if curr_ref.refs.fetch_sub(1, Ordering::Relaxed) == 1 {
+                        drop(curr.into_owned());
+                    }
+                    curr = next.with_tag(0);
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> SkipList<K, V> {
+    /// Creates a new skiplist.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops a reference to the node, and retires it if it is the last one.
+    unsafe fn release<'g>(node: Shared<'g, Node<K, V>>, guard: &'g Guard) {
+        if node.deref().refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            guard.defer_destroy(node);
+        }
+    }
+
+    /// Finds the position of the key, unlinking the deleted nodes on the way.
+    fn find<'g>(&'g self, key: &K, guard: &'g Guard) -> Position<'g, K, V> {
+        'retry: loop {
+            let mut preds = [&self.head[0]; MAX_HEIGHT];
+            let mut succs = [Shared::null(); MAX_HEIGHT];
+            let mut pred = &self.head[..];
+
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut curr = pred[level].load(Ordering::Acquire, guard);
+                if curr.tag() != 0 {
+                    continue 'retry;
+                }
+
+                while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                    let succ = curr_ref.next[level].load(Ordering::Acquire, guard);
+                    if succ.tag() != 0 {
+                        let succ = succ.with_tag(0);
+                        if pred[level]
+                            .compare_and_set(curr, succ, Ordering::Release, guard)
+                            .is_err()
+                        {
+                            continue 'retry;
+                        }
+                        unsafe { Self::release(curr, guard) };
+                        curr = succ;
+                        continue;
+                    }
+
+                    if curr_ref.key.cmp(key) != Less {
+                        break;
+                    }
+                    pred = &curr_ref.next;
+                    curr = succ;
+                }
+
+                preds[level] = &pred[level];
+                succs[level] = curr;
+            }
+
+            let found = unsafe { succs[0].as_ref() }.map_or(false, |n| n.key == *key);
+            return Position {
+                found,
+                preds,
+                succs,
+            };
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> NonblockingMap<K, V> for SkipList<K, V> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
+        // Doesn't unlink the deleted nodes, so it doesn't CAS.
+        let mut pred = &self.head[..];
+        let mut curr = Shared::null();
+        for level in (0..MAX_HEIGHT).rev() {
+            curr = pred[level].load(Ordering::Acquire, guard).with_tag(0);
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                let succ = curr_ref.next[level].load(Ordering::Acquire, guard);
+                if succ.tag() == 0 && curr_ref.key.cmp(key) != Less {
+                    break;
+                }
+                if succ.tag() == 0 {
+                    pred = &curr_ref.next;
+                }
+                curr = succ.with_tag(0);
+            }
+        }
+
+        let node = unsafe { curr.as_ref() }?;
+        if node.key == *key {
+            Some(&node.value)
+        } else {
+            None
+        }
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
+        let height = Node::<K, V>::random_height();
+        let mut node = Owned::new(Node::new(key.clone(), value, height));
+
+        // Backs off after losing a race, to let the winner make progress.
+        let backoff = Backoff::new();
+        let (mut position, node) = loop {
+            let position = self.find(key, guard);
+            if position.found {
+                return Err(node.into_box().value);
+            }
+
+            for (level, next) in node.next.iter().enumerate() {
+                next.store(position.succs[level], Ordering::Relaxed);
+            }
+            // The insertion and the link at level 0.
+            node.refs.store(2, Ordering::Relaxed);
+            match position.preds[0].compare_and_set(
+                position.succs[0],
+                node,
+                Ordering::Release,
+                guard,
+            ) {
+                Ok(node) => break (position, node),
+                Err(e) => node = e.new,
+            }
+            backoff.spin();
+        };
+        let node_ref = unsafe { node.deref() };
+
+        // Links the upper levels, until the node is deleted.
+        'levels: for level in 1..height {
+            loop {
+                let succ = position.succs[level];
+                let next = node_ref.next[level].load(Ordering::Acquire, guard);
+                if next.tag() != 0
+                    || node_ref.next[level]
+                        .compare_and_set(next, succ, Ordering::Release, guard)
+                        .is_err()
+                {
+                    break 'levels;
+                }
+
+                node_ref.refs.fetch_add(1, Ordering::Relaxed);
+                if position.preds[level]
+                    .compare_and_set(succ, node, Ordering::Release, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                node_ref.refs.fetch_sub(1, Ordering::Relaxed);
+
+                position = self.find(key, guard);
+                if position.succs[0] != node {
+                    break 'levels;
+                }
+            }
+        }
+
+        unsafe { Self::release(node, guard) };
+        Ok(())
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let position = self.find(key, guard);
+        if !position.found {
+            return Err(());
+        }
+
+        let node_ref = unsafe { position.succs[0].deref() };
+        for next in node_ref.next[1..].iter().rev() {
+            let _ = next.fetch_or(1, Ordering::AcqRel, guard);
+        }
+        if node_ref.next[0].fetch_or(1, Ordering::AcqRel, guard).tag() != 0 {
+            // Deleted concurrently.
+            return Err(());
+        }
+
+        // Unlinks the node.
+        let _ = self.find(key, guard);
+        Ok(&node_ref.value)
+    }
+
+    fn for_each<'a, F>(&'a self, guard: &'a Guard, mut f: F) -> Result<(), ()>
+    where
+        F: FnMut(&K, &'a V),
+    {
+        // In the order of the keys.
+        let mut curr = self.head[0].load(Ordering::Acquire, guard);
+        while let Some(curr_ref) = unsafe { curr.as_ref() } {
+            let next = curr_ref.next[0].load(Ordering::Acquire, guard);
+            if next.tag() == 0 {
+                f(&curr_ref.key, &curr_ref.value);
+            }
+            curr = next.with_tag(0);
+        }
+        Ok(())
+    }
+}
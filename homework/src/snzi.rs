@@ -0,0 +1,230 @@
+//! Scalable non-zero indicator (SNZI).
+//!
+//! A SNZI is a counter that only tells whether it is zero. The threads arrive at and depart from
+//! the leaves of a tree, and a node arrives at its parent only when its surplus becomes nonzero,
+//! and departs when it becomes zero. So most of the arrivals and departures only touch a leaf, and
+//! the root, which `query` reads, changes only when the whole surplus becomes zero or nonzero.
+//! It suits the components that only need to know whether there is any pending work, e.g. to wait
+//! for the jobs of a thread pool, where a central counter is contended.
+//!
+//! # Example
+//!
+//! ```
+//! use cs492_concur_homework::Snzi;
+//!
+//! let snzi = Snzi::new();
+//! assert!(!snzi.query());
+//! let first = snzi.arrive();
+//! let second = snzi.arrive();
+//! snzi.depart(first);
+//! assert!(snzi.query());
+//! snzi.depart(second);
+//! assert!(!snzi.query());
+//! ```
+//!
+//! # Algorithm
+//!
+//! The root is a plain counter of the arrivals of its children. A non-root node has a surplus and
+//! a version, updated together by CAS. The arrival that makes the surplus nonzero first sets it to
+//! `1/2`, arrives at the parent, and then sets it to 1. The arrivals that see `1/2` help it, so
+//! they don't wait for the first arrival, and depart from the parent if the help turns out to be
+//! unnecessary. The version prevents a stale `1/2` from being set to 1 after the surplus becomes
+//! zero and `1/2` again.
+//!
+//! Reference: Faith Ellen, Yossi Lev, Victor Luchangco, and Mark Moir. SNZI: Scalable NonZero
+//! Indicators. PODC 2007.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crossbeam_utils::CachePadded;
+
+/// The default number of leaves.
+const DEFAULT_LEAVES: usize = 16;
+
+/// The surplus of a node is stored in the upper half of its word, in halves.
+const SURPLUS_SHIFT: u32 = 32;
+/// The surplus `1/2`.
+const HALF: u64 = 1;
+/// The surplus 1.
+const ONE: u64 = 2;
+
+/// The seeds of the threads' leaves, so that the threads arrive at different leaves.
+static NEXT_HINT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HINT: usize = NEXT_HINT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The surplus, in halves, and the version of a non-root node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    surplus: u64,
+    version: u32,
+}
+
+impl State {
+    fn unpack(word: u64) -> Self {
+        Self {
+            surplus: word >> SURPLUS_SHIFT,
+            version: word as u32,
+        }
+    }
+
+    fn pack(self) -> u64 {
+        self.surplus << SURPLUS_SHIFT | u64::from(self.version)
+    }
+}
+
+/// The leaf a thread has arrived at, to depart from.
+#[derive(Debug)]
+pub struct Arrival {
+    node: usize,
+}
+
+/// Scalable non-zero indicator.
+///
+/// The nodes are numbered as in a binary heap: the root is 1 and the children of `n` are `2n` and
+/// `2n + 1`.
+pub struct Snzi {
+    /// The surplus of the root.
+    root: CachePadded<AtomicUsize>,
+    /// The non-root nodes, from 2.
+    nodes: Box<[CachePadded<AtomicU64>]>,
+    leaves: usize,
+}
+
+impl Default for Snzi {
+    fn default() -> Self {
+        Self::with_leaves(DEFAULT_LEAVES)
+    }
+}
+
+impl Snzi {
+    /// Creates a new indicator with 16 leaves.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new indicator with `leaves` leaves, rounded up to a power of two. A single leaf
+    /// is a central counter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaves` is 0.
+    pub fn with_leaves(leaves: usize) -> Self {
+        assert!(leaves > 0, "no leaf to arrive at");
+        let leaves = leaves.next_power_of_two();
+        Self {
+            root: CachePadded::new(AtomicUsize::new(0)),
+            nodes: (2..2 * leaves)
+                .map(|_| CachePadded::new(AtomicU64::new(0)))
+                .collect(),
+            leaves,
+        }
+    }
+
+    /// Increments the surplus at the current thread's leaf. The returned arrival is passed to
+    /// `depart`, possibly by another thread.
+    pub fn arrive(&self) -> Arrival {
+        let node = self.leaves + HINT.with(|hint| *hint) % self.leaves;
+        self.arrive_at(node);
+        Arrival { node }
+    }
+
+    /// Decrements the surplus of the leaf arrived at.
+    pub fn depart(&self, arrival: Arrival) {
+        self.depart_from(arrival.node);
+    }
+
+    /// Returns `true` if there are more arrivals than departures.
+    pub fn query(&self) -> bool {
+        self.root.load(Ordering::Acquire) > 0
+    }
+
+    fn arrive_at(&self, node: usize) {
+        if node == 1 {
+            let _ = self.root.fetch_add(1, Ordering::AcqRel);
+            return;
+        }
+
+        let word = &self.nodes[node - 2];
+        let mut succeeded = false;
+        let mut undo = 0;
+        while !succeeded {
+            let mut state = State::unpack(word.load(Ordering::Acquire));
+            if state.surplus >= ONE {
+                let new = State {
+                    surplus: state.surplus + ONE,
+                    ..state
+                };
+                succeeded = self.cas(word, state, new);
+            } else if state.surplus == 0 {
+                let new = State {
+                    surplus: HALF,
+                    version: state.version.wrapping_add(1),
+                };
+                if self.cas(word, state, new) {
+                    succeeded = true;
+                    state = new;
+                }
+            }
+            if state.surplus == HALF {
+                // Arrives at the parent for the first arrival, which may be another thread's.
+                self.arrive_at(node / 2);
+                let new = State {
+                    surplus: ONE,
+                    ..state
+                };
+                if !self.cas(word, state, new) {
+                    undo += 1;
+                }
+            }
+        }
+        for _ in 0..undo {
+            self.depart_from(node / 2);
+        }
+    }
+
+    fn depart_from(&self, node: usize) {
+        if node == 1 {
+            let surplus = self.root.fetch_sub(1, Ordering::AcqRel);
+            debug_assert!(surplus > 0, "departed more than arrived");
+            return;
+        }
+
+        let word = &self.nodes[node - 2];
+        loop {
+            let state = State::unpack(word.load(Ordering::Acquire));
+            debug_assert!(state.surplus >= ONE, "departed more than arrived");
+            let new = State {
+                surplus: state.surplus - ONE,
+                ..state
+            };
+            if self.cas(word, state, new) {
+                if state.surplus == ONE {
+                    self.depart_from(node / 2);
+                }
+                return;
+            }
+        }
+    }
+
+    fn cas(&self, word: &AtomicU64, current: State, new: State) -> bool {
+        word.compare_exchange(
+            current.pack(),
+            new.pack(),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_ok()
+    }
+}
+
+impl fmt::Debug for Snzi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snzi")
+            .field("leaves", &self.leaves)
+            .field("nonzero", &self.query())
+            .finish()
+    }
+}
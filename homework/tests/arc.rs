@@ -144,6 +144,108 @@ mod basic {
         }
         assert_eq!(count.load(Relaxed), 8 * 128);
     }
+
+    #[test]
+    fn test_weak_count() {
+        let a = Arc::new(0);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let w = Arc::downgrade(&a);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 1);
+        let x = w.clone();
+        assert!(Arc::weak_count(&a) == 2);
+        drop(w);
+        drop(x);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let c = a.clone();
+        assert!(Arc::count(&a) == 2);
+        assert!(Arc::weak_count(&a) == 0);
+        let d = Arc::downgrade(&c);
+        assert!(Arc::weak_count(&c) == 1);
+        assert!(d.strong_count() == 2);
+        assert!(d.weak_count() == 1);
+        drop(a);
+        drop(c);
+        assert!(d.strong_count() == 0);
+        assert!(d.weak_count() == 0);
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let x = Arc::new(5);
+        let y = Arc::downgrade(&x);
+        assert!(y.upgrade().is_some());
+        drop(x);
+        assert!(y.upgrade().is_none());
+    }
+
+    #[test]
+    fn drop_weak_after_arc() {
+        let canary = AtomicUsize::new(0);
+        let x = Arc::new(Canary(&canary as *const AtomicUsize));
+        let y = Arc::downgrade(&x);
+        drop(x);
+        assert!(canary.load(Relaxed) == 1);
+        drop(y);
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn test_get_mut_weak() {
+        let mut x = Arc::new(3);
+        let y = Arc::downgrade(&x);
+        assert!(Arc::get_mut(&mut x).is_none());
+        drop(y);
+        *Arc::get_mut(&mut x).unwrap() = 4;
+        assert_eq!(*x, 4);
+    }
+
+    #[test]
+    fn test_try_unwrap_weak() {
+        let x = Arc::new(3);
+        let y = Arc::downgrade(&x);
+        assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+        assert!(y.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_cowarc_clone_weak() {
+        let mut cow0 = Arc::new(75);
+        let cow1_weak = Arc::downgrade(&cow0);
+
+        assert!(75 == *cow0);
+        assert!(75 == *cow1_weak.upgrade().unwrap());
+
+        *Arc::make_mut(&mut cow0) += 1;
+
+        assert!(76 == *cow0);
+        assert!(cow1_weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_weak_stress() {
+        let arc = Arc::new(AtomicUsize::new(0));
+        let handles = (0..8)
+            .map(|_| {
+                let weak = Arc::downgrade(&arc);
+                thread::spawn(move || {
+                    for _ in 0..128 {
+                        if let Some(arc) = weak.upgrade() {
+                            arc.fetch_add(1, Relaxed);
+                        }
+                        drop(weak.clone());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(arc.load(Relaxed), 8 * 128);
+        assert_eq!(Arc::weak_count(&arc), 0);
+    }
 }
 
 mod correctness {
@@ -245,4 +347,53 @@ mod correctness {
             assert_eq!(canary.load(Relaxed), 1);
         })
     }
+
+    #[test]
+    /// value:=123 → upgraded drop → weak drop → get_mut success
+    fn get_mut_weak_sync() {
+        model(|| {
+            let mut value = Arc::new(AtomicUsize::new(0));
+            {
+                let weak = Arc::downgrade(&value);
+                thread::spawn(move || {
+                    weak.upgrade().unwrap().store(123, Relaxed);
+                });
+            }
+            if let Some(val) = Arc::get_mut(&mut value) {
+                assert_eq!(val.load(Relaxed), 123);
+            }
+        })
+    }
+
+    #[test]
+    /// accesses → last drop → data drop → weak drop → dealloc
+    fn weak_drop_sync() {
+        model(|| {
+            let canary = AtomicUsize::new(0);
+            let arc1 = Arc::new(Canary(&canary as *const AtomicUsize));
+            let weak = Arc::downgrade(&arc1);
+            let arc2 = arc1.clone();
+            let handle = thread::spawn(move || {
+                drop(weak.upgrade());
+                drop(weak);
+            });
+            drop(arc1);
+            drop(arc2);
+            handle.join().unwrap();
+            assert_eq!(canary.load(Relaxed), 1);
+        })
+    }
+
+    #[test]
+    /// try_unwrap success ⇒ upgrade failure
+    fn try_unwrap_upgrade() {
+        model(|| {
+            let arc = Arc::new(AtomicUsize::new(0));
+            let weak = Arc::downgrade(&arc);
+            let handle = thread::spawn(move || weak.upgrade());
+            let unwrapped = Arc::try_unwrap(arc).is_ok();
+            let upgraded = handle.join().unwrap().is_some();
+            assert!(!(unwrapped && upgraded));
+        })
+    }
 }
@@ -0,0 +1,173 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::ebr::{
+    collect, defer, is_pinned, pin, retire, retire_with, Atomic, Owned, Shared,
+};
+
+/// Calls `collect` until `done` returns `true`. The other tests may keep the global epoch from
+/// advancing for a while.
+fn collect_until<F: Fn() -> bool>(done: F) {
+    for _ in 0..1000 {
+        collect();
+        if done() {
+            return;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    panic!("the retired pointers are not reclaimed");
+}
+
+#[test]
+fn counter() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let count = Atomic::new(0usize);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let mut new = Owned::new(0);
+                    loop {
+                        let guard = pin();
+                        let cur = count.load(Acquire);
+                        let value = unsafe { *cur.deref() };
+                        *new = value + 1;
+                        let new_shared = new.into_shared();
+                        if count
+                            .compare_and_set(cur, new_shared, AcqRel, Acquire)
+                            .is_ok()
+                        {
+                            retire(cur);
+                            drop(guard);
+                            break;
+                        } else {
+                            new = unsafe { new_shared.into_owned() };
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    let cur = count.load(Acquire);
+    // exclusive access
+    assert_eq!(unsafe { *cur.deref() }, THREADS * ITER);
+    retire(cur);
+}
+
+#[test]
+fn nested_guards() {
+    assert!(!is_pinned());
+    let outer = pin();
+    let inner = pin();
+    drop(outer);
+    assert!(is_pinned());
+    drop(inner);
+    assert!(!is_pinned());
+}
+
+#[test]
+fn retire_with_custom_free() {
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+    unsafe fn free(data: *mut ()) {
+        assert_eq!(*(data as *const usize), 42);
+        drop(Box::from_raw(data as *mut usize));
+        FREED.fetch_add(1, Relaxed);
+    }
+
+    for _ in 0..3 {
+        retire_with(Owned::new(42usize).with_tag(1).into_shared(), free);
+    }
+    collect_until(|| FREED.load(Relaxed) == 3);
+}
+
+#[test]
+fn defer_runs_after_collect() {
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let count = count.clone();
+        defer(move || {
+            count.fetch_add(1, Relaxed);
+        });
+    }
+    collect_until(|| count.load(Relaxed) == 3);
+
+    // left to the other threads when the thread exits
+    let th = {
+        let count = count.clone();
+        std::thread::spawn(move || {
+            defer(move || {
+                count.fetch_add(1, Relaxed);
+            })
+        })
+    };
+    th.join().unwrap();
+    collect_until(|| count.load(Relaxed) == 4);
+}
+
+// the destructors and the deferred functions may retire and defer more.
+#[test]
+fn retire_in_free() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Chain(usize);
+    impl Drop for Chain {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Release);
+            if self.0 > 0 {
+                retire(Owned::new(Chain(self.0 - 1)).into_shared());
+            }
+        }
+    }
+
+    let count = Arc::new(AtomicUsize::new(0));
+    {
+        let count = count.clone();
+        defer(move || {
+            defer(move || {
+                count.fetch_add(1, Relaxed);
+            })
+        });
+    }
+    retire(Owned::new(Chain(2)).into_shared());
+    collect_until(|| count.load(Relaxed) == 1 && DROPPED.load(Acquire) == 3);
+}
+
+// a thread exits while its retired pointer may be accessed by another thread.
+#[test]
+fn exit_while_pinned() {
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    struct Flag;
+    impl Drop for Flag {
+        fn drop(&mut self) {
+            DROPPED.store(true, Release);
+        }
+    }
+
+    let atomic = Atomic::new(Flag);
+    let guard = pin();
+    let _flag = unsafe { atomic.load(Acquire).deref() };
+    scope(|s| {
+        s.spawn(|_| {
+            let shared = atomic.load(Relaxed);
+            atomic.store(Shared::null(), Relaxed);
+            retire(shared);
+            for _ in 0..10 {
+                collect();
+            }
+        });
+    })
+    .unwrap();
+    for _ in 0..10 {
+        collect();
+    }
+    assert!(!DROPPED.load(Acquire));
+
+    // the pointer left by the exited thread is reclaimed by the others
+    drop(guard);
+    collect_until(|| DROPPED.load(Acquire));
+}
@@ -0,0 +1,156 @@
+use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{FcPriorityQueue, FcQueue, FlatCombining, Sequential};
+use std::panic::{self, AssertUnwindSafe};
+
+const THREADS: usize = 4;
+const ITER: usize = 1024 * 4;
+
+#[test]
+fn queue_smoke() {
+    let queue = FcQueue::new();
+    assert_eq!(queue.pop(), None);
+    for i in 0..10 {
+        queue.push(i);
+    }
+    for i in 0..5 {
+        assert_eq!(queue.pop(), Some(i));
+    }
+    assert_eq!(queue.into_vec(), [5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn priority_queue_smoke() {
+    let queue = FcPriorityQueue::new();
+    assert_eq!(queue.pop(), None);
+    for &i in &[3, 1, 4, 1, 5, 9, 2, 6] {
+        queue.push(i);
+    }
+    assert_eq!(queue.pop(), Some(9));
+    assert_eq!(queue.pop(), Some(6));
+    assert_eq!(queue.into_sorted_vec(), [1, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn queue_stress() {
+    // each consumer sees the elements of each producer in order.
+    let queue = FcQueue::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let queue = &queue;
+            s.spawn(move |_| {
+                for i in 0..ITER {
+                    queue.push((t, i));
+                }
+            });
+            s.spawn(move |_| {
+                let mut last = [None; THREADS];
+                let mut popped = 0;
+                while popped < ITER {
+                    match queue.pop() {
+                        Some((t, i)) => {
+                            assert!(last[t] < Some(i));
+                            last[t] = Some(i);
+                            popped += 1;
+                        }
+                        None => std::thread::yield_now(),
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(queue.into_vec().is_empty());
+}
+
+#[test]
+fn priority_queue_stress() {
+    let queue = FcPriorityQueue::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let queue = &queue;
+            s.spawn(move |_| {
+                for i in 0..ITER {
+                    queue.push(i * THREADS + t);
+                    if i % 2 == 1 {
+                        assert!(queue.pop().is_some());
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    let sorted = queue.into_sorted_vec();
+    assert_eq!(sorted.len(), THREADS * ITER / 2);
+    assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[derive(Default)]
+struct Counter(usize);
+
+impl Sequential for Counter {
+    type Op = usize;
+    type Result = usize;
+
+    fn apply(&mut self, op: usize) -> usize {
+        self.0 += op;
+        self.0
+    }
+}
+
+#[test]
+fn few_records() {
+    // the threads wait for free records.
+    let counter = FlatCombining::with_capacity(Counter::default(), 2);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let _ = counter.apply(1);
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(counter.into_inner().0, THREADS * ITER);
+}
+
+/// A counter that panics when 0 is added.
+#[derive(Default)]
+struct PositiveCounter(usize);
+
+impl Sequential for PositiveCounter {
+    type Op = usize;
+    type Result = usize;
+
+    fn apply(&mut self, op: usize) -> usize {
+        assert!(op > 0, "adding 0");
+        self.0 += op;
+        self.0
+    }
+}
+
+#[test]
+fn panicking_op() {
+    // the panic of an operation goes to its thread, and the others keep combining.
+    const ITER: usize = 64;
+
+    let counter = FlatCombining::new(PositiveCounter::default());
+    let panics = AtomicUsize::new(0);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for i in 0..ITER {
+                    let op = i % 2;
+                    if panic::catch_unwind(AssertUnwindSafe(|| counter.apply(op))).is_err() {
+                        assert_eq!(op, 0);
+                        let _ = panics.fetch_add(1, Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(panics.into_inner(), THREADS * ITER / 2);
+    assert_eq!(counter.into_inner().0, THREADS * ITER / 2);
+}
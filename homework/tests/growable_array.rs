@@ -145,3 +145,13 @@ fn log_concurrent() {
     const STEPS: usize = 4096 * 12;
     map::log_concurrent::<u32, NonblockingConcurrentMap<_, _, ArrayMap<usize>>>(THREADS, STEPS);
 }
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<u32, NonblockingConcurrentMap<_, _, ArrayMap<usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
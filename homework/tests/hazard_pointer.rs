@@ -1,12 +1,17 @@
 use core::mem::ManuallyDrop;
 use core::ptr;
-use core::sync::atomic::Ordering::*;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use crossbeam_utils::thread::scope;
+use cs492_concur_homework::hazard_pointer::eras::{self, EraShield, Stamped};
+use cs492_concur_homework::hazard_pointer::queue::{Node as QueueNode, Queue};
+use cs492_concur_homework::hazard_pointer::reclaimer::{Epoch, Hp, Ibr, Reclaimer};
 use cs492_concur_homework::hazard_pointer::{
-    collect, get_protected, protect, retire, Atomic, Owned,
+    collect, defer, get_protected, protect, protect_tagged, retire, retire_slice, retire_unchecked,
+    retire_with, shield_set, Atomic, Owned, Shared, Shield, ShieldSet, HAZARDS,
 };
 
 #[test]
@@ -131,6 +136,389 @@ fn counter_tag() {
     retire(cur);
 }
 
+#[test]
+fn shield_protect() {
+    let atomic = Atomic::new(1);
+    let mut shield = protect(Shared::null()).unwrap();
+    let shared = shield.protect(&atomic);
+    assert!(shield.validate(shared));
+    assert_eq!(unsafe { *shield.deref() }, 1);
+
+    // the shield moves to the new pointer, keeping its tag
+    atomic.store(Owned::new(2).with_tag(1).into_shared(), Release);
+    retire(shared);
+    let shared = shield.protect(&atomic);
+    assert_eq!(shared.tag(), 1);
+    assert_eq!(shield.shared().tag(), 1);
+    assert_eq!(unsafe { *shield.deref() }, 2);
+
+    atomic.store(Shared::null(), Relaxed);
+    retire(shared);
+    assert!(shield.protect(&atomic).is_null());
+    assert!(shield.is_null());
+}
+
+#[test]
+fn compare_exchange_protected() {
+    let atomic = Atomic::new(1);
+    let mut shield = get_protected(&atomic).unwrap();
+    let old = shield.shared();
+
+    // the new pointer is protected on success
+    let new = Owned::new(2).into_shared();
+    let result = atomic.compare_exchange_protected(old, new, AcqRel, Acquire, &mut shield);
+    assert_eq!(result.unwrap().into_usize(), new.into_usize());
+    assert!(shield.validate(new));
+    assert!(HAZARDS.all_hazards().contains(&new.into_usize()));
+    assert!(!HAZARDS.all_hazards().contains(&old.into_usize()));
+    retire(old);
+
+    // the current pointer is protected on failure
+    let other = Owned::new(3).into_shared();
+    let result = atomic.compare_exchange_protected(old, other, AcqRel, Acquire, &mut shield);
+    assert_eq!(result.unwrap_err().into_usize(), new.into_usize());
+    assert!(shield.validate(new));
+    assert!(!HAZARDS.all_hazards().contains(&other.into_usize()));
+    assert_eq!(unsafe { *shield.deref() }, 2);
+
+    atomic.store(Shared::null(), Relaxed);
+    retire(new);
+    unsafe { drop(other.into_owned()) };
+}
+
+#[test]
+fn shield_protect_tagged() {
+    let tagged = Owned::new(1).with_tag(1).into_shared();
+    let shield = protect_tagged(tagged).unwrap();
+    assert!(shield.validate(tagged));
+    assert_eq!(shield.shared().tag(), 1);
+    assert_eq!(unsafe { *shield.deref() }, 1);
+
+    // the hazard is the pointer without tag, which is retired
+    assert!(HAZARDS
+        .all_hazards()
+        .contains(&tagged.with_tag(0).into_usize()));
+    retire(tagged);
+    collect();
+    assert_eq!(unsafe { *shield.deref() }, 1);
+    drop(shield);
+    collect();
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "protecting a tagged pointer")]
+fn protect_tagged_by_mistake() {
+    let tagged = Owned::new(1).with_tag(1).into_shared();
+    let _shield = protect(tagged);
+}
+
+#[test]
+fn shield_reset_swap() {
+    let a = Owned::new(1).into_shared();
+    let b = Owned::new(2).into_shared();
+    let mut shield_a = protect(a).unwrap();
+    let mut shield_b = protect(b).unwrap();
+
+    shield_a.swap(&mut shield_b);
+    assert!(shield_a.validate(b));
+    assert!(shield_b.validate(a));
+    assert!(HAZARDS.all_hazards().contains(&a.into_usize()));
+
+    shield_b.reset();
+    assert!(shield_b.is_null());
+    assert!(!HAZARDS.all_hazards().contains(&a.into_usize()));
+    drop(shield_a);
+    assert!(!HAZARDS.all_hazards().contains(&b.into_usize()));
+
+    retire(a);
+    retire(b);
+}
+
+// traverse a list keeping the previous and the current nodes protected
+#[test]
+fn shield_set_traversal() {
+    struct Node {
+        data: usize,
+        next: Atomic<Node>,
+    }
+
+    let head = Atomic::null();
+    for data in (0..3).rev() {
+        let next = head.load(Relaxed);
+        let node = Owned::new(Node {
+            data,
+            next: Atomic::null(),
+        });
+        node.next.store(next, Relaxed);
+        head.store(node.into_shared(), Relaxed);
+    }
+
+    let mut shields: ShieldSet<'_, Node, [Shield<'_, Node>; 2]> = shield_set();
+    let mut visited = Vec::new();
+    let mut curr = shields.protect(1, &head);
+    while !curr.is_null() {
+        shields.swap(0, 1);
+        let pred = curr;
+        let node = unsafe { pred.deref() };
+        visited.push(node.data);
+        assert!(HAZARDS
+            .all_hazards()
+            .contains(&shields.get(0).shared().into_usize()));
+        curr = shields.protect(1, &node.next);
+    }
+    assert_eq!(visited, [0, 1, 2]);
+    assert!(shields.get(1).is_null());
+    shields.reset(0);
+    assert!(shields.get(0).is_null());
+
+    let mut curr = head.load(Relaxed);
+    while !curr.is_null() {
+        let node = unsafe { curr.into_owned() };
+        curr = node.next.load(Relaxed);
+    }
+}
+
+#[test]
+fn retire_with_custom_free() {
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+    unsafe fn free(data: *mut ()) {
+        assert_eq!(*(data as *const usize), 42);
+        drop(Box::from_raw(data as *mut usize));
+        FREED.fetch_add(1, Relaxed);
+    }
+
+    for _ in 0..3 {
+        retire_with(Owned::new(42usize).with_tag(1).into_shared(), free);
+    }
+    collect();
+    assert_eq!(FREED.load(Relaxed), 3);
+}
+
+#[test]
+fn defer_runs_at_collect() {
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let count = count.clone();
+        defer(move || {
+            count.fetch_add(1, Relaxed);
+        });
+    }
+    collect();
+    assert_eq!(count.load(Relaxed), 3);
+
+    // called when the thread exits
+    let th = {
+        let count = count.clone();
+        std::thread::spawn(move || {
+            defer(move || {
+                count.fetch_add(1, Relaxed);
+            })
+        })
+    };
+    th.join().unwrap();
+    assert_eq!(count.load(Relaxed), 4);
+}
+
+#[test]
+fn retire_slice_drops_elements() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Elem(usize);
+    impl Drop for Elem {
+        fn drop(&mut self) {
+            assert!(self.0 < 5);
+            DROPPED.fetch_add(1, Relaxed);
+        }
+    }
+
+    for len in &[0, 1, 5] {
+        let slice = (0..*len).map(Elem).collect::<Box<[_]>>();
+        let pointer = Box::into_raw(slice) as *mut Elem;
+        retire_slice(Shared::from(pointer as *const Elem), *len);
+    }
+    collect();
+    assert_eq!(DROPPED.load(Relaxed), 6);
+}
+
+// a thread exits while its retired pointer is protected by another thread.
+#[test]
+fn exit_while_protected() {
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    struct Flag;
+    impl Drop for Flag {
+        fn drop(&mut self) {
+            DROPPED.store(true, Release);
+        }
+    }
+
+    let atomic = Atomic::new(Flag);
+    let shield = get_protected(&atomic).unwrap();
+    scope(|s| {
+        s.spawn(|_| {
+            let shared = atomic.load(Relaxed);
+            atomic.store(Shared::null(), Relaxed);
+            retire(shared);
+        });
+    })
+    .unwrap();
+    assert!(!DROPPED.load(Acquire));
+
+    // the pointer left by the exited thread is reclaimed by the others
+    drop(shield);
+    for _ in 0..1000 {
+        collect();
+        if DROPPED.load(Acquire) {
+            return;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    panic!("the retired pointer is not reclaimed");
+}
+
+// the pointers left by the threads of every round, e.g. of a pool whose threads come and go, are
+// reclaimed by the others, so the garbage doesn't grow with the number of exited threads
+#[test]
+fn exit_churn() {
+    const ROUNDS: usize = 8;
+    const THREADS: usize = 4;
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Counted;
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Release);
+        }
+    }
+
+    for round in 0..ROUNDS {
+        let atomics = (0..THREADS)
+            .map(|_| Atomic::new(Counted))
+            .collect::<Vec<_>>();
+        let shields = atomics
+            .iter()
+            .map(|atomic| get_protected(atomic).unwrap())
+            .collect::<Vec<_>>();
+        scope(|s| {
+            for atomic in &atomics {
+                s.spawn(move |_| {
+                    let shared = atomic.load(Relaxed);
+                    atomic.store(Shared::null(), Relaxed);
+                    retire(shared);
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(DROPPED.load(Acquire), round * THREADS);
+
+        drop(shields);
+        let expected = (round + 1) * THREADS;
+        for _ in 0..1000 {
+            collect();
+            if DROPPED.load(Acquire) == expected {
+                break;
+            }
+            sleep(Duration::from_millis(1));
+        }
+        assert_eq!(DROPPED.load(Acquire), expected);
+    }
+}
+
+fn queue_with<R: Reclaimer<QueueNode<i32>> + Reclaimer<QueueNode<Vec<i32>>>>() {
+    let queue = Queue::<_, R>::with_reclaimer();
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop(), None);
+    for i in 0..10 {
+        queue.push(i);
+    }
+    assert!(!queue.is_empty());
+    for i in 0..5 {
+        assert_eq!(queue.pop(), Some(i));
+    }
+    queue.push(10);
+    for i in 5..11 {
+        assert_eq!(queue.pop(), Some(i));
+    }
+    assert_eq!(queue.pop(), None);
+
+    // the remaining elements are dropped with the queue
+    let queue = Queue::<_, R>::with_reclaimer();
+    queue.push(vec![1]);
+    queue.push(vec![2]);
+    assert_eq!(queue.pop(), Some(vec![1]));
+}
+
+#[test]
+fn queue() {
+    queue_with::<Hp>();
+}
+
+#[test]
+fn queue_epoch() {
+    queue_with::<Epoch>();
+}
+
+#[test]
+fn queue_ibr() {
+    queue_with::<Ibr>();
+}
+
+fn queue_concurrent_with<R: Reclaimer<QueueNode<(usize, usize)>>>() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 8;
+
+    let queue = Queue::<_, R>::with_reclaimer();
+    let sums = scope(|s| {
+        for t in 0..THREADS {
+            let queue = &queue;
+            s.spawn(move |_| {
+                for i in 0..ITER {
+                    queue.push((t, i));
+                }
+            });
+        }
+        (0..THREADS)
+            .map(|_| {
+                s.spawn(|_| {
+                    let mut last = [None; THREADS];
+                    let mut sum = 0;
+                    let mut count = 0;
+                    while count < ITER {
+                        if let Some((t, i)) = queue.pop() {
+                            // the elements of each producer are popped in order
+                            assert!(last[t].map_or(true, |last| last < i));
+                            last[t] = Some(i);
+                            sum += i;
+                            count += 1;
+                        }
+                    }
+                    sum
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>()
+    })
+    .unwrap();
+    assert_eq!(sums, THREADS * ITER * (ITER - 1) / 2);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn queue_concurrent() {
+    queue_concurrent_with::<Hp>();
+}
+
+#[test]
+fn queue_concurrent_epoch() {
+    queue_concurrent_with::<Epoch>();
+}
+
+#[test]
+fn queue_concurrent_ibr() {
+    queue_concurrent_with::<Ibr>();
+}
+
 #[test]
 fn stack() {
     const THREADS: usize = 8;
@@ -229,7 +617,8 @@ impl<T: 'static> Stack<T> {
                 .is_ok()
             {
                 unsafe {
-                    retire(head_shield.shared());
+                    // The data is moved out, so the node can be dropped in any thread.
+                    retire_unchecked(head_shield.shared());
                     return Some(ManuallyDrop::into_inner(ptr::read(
                         &(*head_shield.deref()).data,
                     )));
@@ -250,6 +639,77 @@ impl<T> Drop for Stack<T> {
     }
 }
 
+#[test]
+fn hazard_eras() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Elem;
+    impl Drop for Elem {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Relaxed);
+        }
+    }
+
+    // not freed while a shield publishes an era within its lifetime
+    let atomic = Atomic::null();
+    atomic.store(Stamped::new(Elem).into_shared(), Release);
+    let mut shield = EraShield::new();
+    let shared = shield.protect(&atomic);
+    atomic.store(Shared::null(), Relaxed);
+    eras::retire(shared);
+    eras::collect();
+    assert_eq!(DROPPED.load(Relaxed), 0);
+    drop(shield);
+    eras::collect();
+    assert_eq!(DROPPED.load(Relaxed), 1);
+
+    // Treiber's stack
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 8;
+
+    struct Node {
+        data: usize,
+        next: Atomic<Stamped<Node>>,
+    }
+
+    let head = Atomic::<Stamped<Node>>::null();
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for i in 0..ITER {
+                    let new = Stamped::new(Node {
+                        data: i,
+                        next: Atomic::null(),
+                    })
+                    .into_shared();
+                    loop {
+                        let old = head.load(Relaxed);
+                        unsafe { new.deref() }.next.store(old, Relaxed);
+                        if head.compare_and_set(old, new, Release, Relaxed).is_ok() {
+                            break;
+                        }
+                    }
+
+                    // each thread pushes before popping, so the stack is not empty
+                    let mut shield = EraShield::new();
+                    loop {
+                        let old = shield.protect(&head);
+                        assert!(!old.is_null());
+                        let old_ref = unsafe { old.deref() };
+                        let next = old_ref.next.load(Relaxed);
+                        assert!(old_ref.data < ITER);
+                        if head.compare_and_set(old, next, Relaxed, Relaxed).is_ok() {
+                            eras::retire(old);
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(head.load(Relaxed).is_null());
+}
+
 mod mock;
 
 mod sync {
@@ -288,6 +748,61 @@ mod sync {
         })
     }
 
+    // a thread retires a pointer protected by another thread, and exits.
+    #[test]
+    fn exit_while_protected_sync() {
+        model(|| {
+            let atomic = Arc::new(Atomic::new(123));
+            let shield = get_protected(&atomic).unwrap();
+
+            let th = {
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shared = atomic.load(Relaxed);
+                    atomic.store(Shared::null(), Relaxed);
+                    retire(shared);
+                })
+            };
+
+            th.join().unwrap();
+            // the pointer left by the exited thread is not freed while protected
+            assert_eq!(unsafe { *shield.deref() }, 123);
+            drop(shield);
+            collect();
+        })
+    }
+
+    // the retiring thread exits while the other thread protects the pointer.
+    #[test]
+    fn retire_exit_protect_sync() {
+        model(|| {
+            let atomic = Arc::new(Atomic::new(123));
+
+            let th = {
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shield = get_protected(&atomic).unwrap();
+                    if !shield.is_null() {
+                        assert_eq!(unsafe { *shield.deref() }, 123);
+                    }
+                })
+            };
+
+            let retirer = {
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shared = atomic.load(Relaxed);
+                    atomic.store(Shared::null(), Relaxed);
+                    retire(shared);
+                })
+            };
+
+            retirer.join().unwrap();
+            th.join().unwrap();
+            collect();
+        })
+    }
+
     #[test]
     fn get_protected_collect_sync() {
         model(|| {
@@ -0,0 +1,96 @@
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::hello_server::{Middleware, Next, Request, Response, Server};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::time::Duration;
+
+fn get(stream: &mut TcpStream, path: &str) -> String {
+    write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn server_builder_port_zero() {
+    let server = Server::builder()
+        .bind("127.0.0.1:0")
+        .workers(2)
+        .read_timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    assert_ne!(addr.port(), 0);
+    let handle = server.shutdown_handle();
+
+    scope(|s| {
+        s.spawn(move |_| server.run());
+        let response = get(&mut TcpStream::connect(addr).unwrap(), "/");
+        assert!(response.starts_with("HTTP/1.1 404"));
+        handle.shutdown().unwrap();
+    })
+    .unwrap();
+}
+
+#[test]
+fn server_builder_read_timeout() {
+    let server = Server::builder()
+        .bind("127.0.0.1:0")
+        .read_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+
+    scope(|s| {
+        s.spawn(move |_| server.run());
+        // The idle connection is closed by the server.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(3)))
+            .unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(stream.read_to_end(&mut buf).unwrap(), 0);
+        handle.shutdown().unwrap();
+    })
+    .unwrap();
+}
+
+#[test]
+fn server_builder_no_address() {
+    assert!(Server::builder().build().is_err());
+}
+
+/// Answers `/teapot` with 418.
+#[derive(Debug)]
+struct Teapot;
+
+impl Middleware for Teapot {
+    fn call(&self, request: &Request, next: Next<'_>) -> Response {
+        if request.path == "/teapot" {
+            return Response::new(418);
+        }
+        next.run(request)
+    }
+}
+
+#[test]
+fn server_builder_middleware() {
+    let server = Server::builder()
+        .bind("127.0.0.1:0")
+        .middleware(Teapot)
+        .build()
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+
+    scope(|s| {
+        s.spawn(move |_| server.run());
+        let response = get(&mut TcpStream::connect(addr).unwrap(), "/teapot");
+        assert!(response.starts_with("HTTP/1.1 418"));
+        let response = get(&mut TcpStream::connect(addr).unwrap(), "/");
+        assert!(response.starts_with("HTTP/1.1 404"));
+        handle.shutdown().unwrap();
+    })
+    .unwrap();
+}
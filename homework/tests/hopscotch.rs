@@ -0,0 +1,162 @@
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{HopscotchMap, NonblockingConcurrentMap, NonblockingMap};
+use std::hash::{BuildHasherDefault, Hasher};
+
+pub mod map;
+
+#[test]
+pub fn smoke() {
+    let map = HopscotchMap::<usize, usize>::new();
+
+    let guard = epoch::pin();
+
+    assert_eq!(map.insert(&37, 37, &guard), Ok(()));
+    assert_eq!(map.insert(&37, 38, &guard), Err(38));
+    assert_eq!(map.lookup(&42, &guard), None);
+    assert_eq!(map.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(map.insert(&42, 42, &guard), Ok(()));
+    assert_eq!(map.lookup(&42, &guard), Some(&42));
+    assert_eq!(map.len(), 2);
+
+    assert_eq!(map.delete(&37, &guard), Ok(&37));
+    assert_eq!(map.delete(&37, &guard), Err(()));
+    assert_eq!(map.lookup(&37, &guard), None);
+    assert_eq!(map.lookup(&42, &guard), Some(&42));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn resize() {
+    const KEYS: usize = 1024 * 16;
+
+    // a single segment grows from 64 buckets, and the values are not moved.
+    let map = HopscotchMap::<usize, usize>::with_segments(1);
+    let guard = epoch::pin();
+    let values = (0..KEYS)
+        .map(|i| {
+            assert_eq!(map.insert(&i, i, &guard), Ok(()));
+            map.lookup(&i, &guard).unwrap() as *const usize
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(map.len(), KEYS);
+    for (i, value) in values.into_iter().enumerate() {
+        assert_eq!(map.lookup(&i, &guard).map(|v| v as *const _), Some(value));
+    }
+    for i in (0..KEYS).step_by(2) {
+        assert_eq!(map.delete(&i, &guard), Ok(&i));
+    }
+    assert_eq!(map.len(), KEYS / 2);
+
+    let mut pairs = Vec::new();
+    assert_eq!(map.for_each(&guard, |&k, &v| pairs.push((k, v))), Ok(()));
+    pairs.sort_unstable();
+    let expected = (1..KEYS).step_by(2).map(|i| (i, i)).collect::<Vec<_>>();
+    assert_eq!(pairs, expected);
+}
+
+#[test]
+fn same_hash() {
+    const KEYS: usize = 100;
+
+    /// All keys have the same hash.
+    #[derive(Default)]
+    struct ZeroHasher;
+    impl Hasher for ZeroHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    // more keys than a neighborhood holds overflow without growing the segment forever
+    let map = HopscotchMap::<usize, usize, _>::with_segments_and_hasher(
+        1,
+        BuildHasherDefault::<ZeroHasher>::default(),
+    );
+    let guard = epoch::pin();
+    for i in 0..KEYS {
+        assert_eq!(map.insert(&i, i, &guard), Ok(()));
+    }
+    assert_eq!(map.insert(&0, 0, &guard), Err(0));
+    assert_eq!(map.len(), KEYS);
+    for i in 0..KEYS {
+        assert_eq!(map.lookup(&i, &guard), Some(&i));
+    }
+    for i in (0..KEYS).step_by(2) {
+        assert_eq!(map.delete(&i, &guard), Ok(&i));
+    }
+    assert_eq!(map.len(), KEYS / 2);
+    for i in 0..KEYS {
+        let expected = if i % 2 == 0 { None } else { Some(&i) };
+        assert_eq!(map.lookup(&i, &guard), expected);
+    }
+}
+
+#[test]
+fn concurrent_resize() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024 * 4;
+
+    let map = HopscotchMap::<usize, usize>::with_segments(2);
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move |_| {
+                let guard = epoch::pin();
+                for i in (t..STEPS).step_by(THREADS) {
+                    assert_eq!(map.insert(&i, i, &guard), Ok(()));
+                    assert_eq!(map.lookup(&i, &guard), Some(&i));
+                    if i % 2 == 0 {
+                        assert_eq!(map.delete(&i, &guard), Ok(&i));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    for i in 0..STEPS {
+        let expected = if i % 2 == 0 { None } else { Some(&i) };
+        assert_eq!(map.lookup(&i, &guard), expected);
+    }
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        usize,
+        NonblockingConcurrentMap<_, _, HopscotchMap<usize, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, HopscotchMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, HopscotchMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, NonblockingConcurrentMap<_, _, HopscotchMap<usize, usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
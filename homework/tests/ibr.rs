@@ -0,0 +1,121 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::ibr::{collect, pin, retire, Atomic, Shared, Stamped};
+
+#[test]
+fn counter() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let count = Atomic::null();
+    count.store(Stamped::new(0usize).into_shared(), Release);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let mut new = Stamped::new(0);
+                    loop {
+                        let guard = pin();
+                        let cur = guard.protect(&count);
+                        let value = **unsafe { cur.deref() };
+                        **new = value + 1;
+                        let new_shared = new.into_shared();
+                        if count
+                            .compare_and_set(cur, new_shared, AcqRel, Acquire)
+                            .is_ok()
+                        {
+                            retire(cur);
+                            break;
+                        } else {
+                            new = unsafe { new_shared.into_owned() };
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    let cur = count.load(Acquire);
+    // exclusive access
+    assert_eq!(**unsafe { cur.deref() }, THREADS * ITER);
+    retire(cur);
+}
+
+#[test]
+fn protect_extends_interval() {
+    let atomic = Atomic::null();
+    let guard = pin();
+    for i in 0..256usize {
+        atomic.store(Stamped::new(i).into_shared(), Release);
+        let shared = guard.protect(&atomic);
+        assert_eq!(**unsafe { shared.deref() }, i);
+        retire(shared);
+    }
+    drop(guard);
+    collect();
+}
+
+// a stalled reader keeps alive the objects it may have loaded, but not the ones allocated later.
+#[test]
+fn stalled_reader_bounds_garbage() {
+    static OLD_DROPPED: AtomicBool = AtomicBool::new(false);
+    static NEW_DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Old;
+    impl Drop for Old {
+        fn drop(&mut self) {
+            OLD_DROPPED.store(true, Release);
+        }
+    }
+    struct New;
+    impl Drop for New {
+        fn drop(&mut self) {
+            NEW_DROPPED.fetch_add(1, Release);
+        }
+    }
+
+    let atomic = Atomic::null();
+    atomic.store(Stamped::new(Old).into_shared(), Release);
+    let guard = pin();
+    let _old = unsafe { guard.protect(&atomic).deref() };
+
+    const COUNT: usize = 256;
+    scope(|s| {
+        s.spawn(|_| {
+            let shared = atomic.load(Relaxed);
+            atomic.store(Shared::null(), Relaxed);
+            retire(shared);
+
+            // advances the era past the stalled reader's interval
+            let news = (0..COUNT)
+                .map(|_| Stamped::new(New).into_shared())
+                .collect::<Vec<_>>();
+            for new in news {
+                retire(new);
+            }
+        });
+    })
+    .unwrap();
+
+    // the objects left by the exited thread are freed by the others, even by the stalled reader
+    collect_until(|| NEW_DROPPED.load(Acquire) > 0);
+    assert!(!OLD_DROPPED.load(Acquire));
+
+    drop(guard);
+    collect_until(|| OLD_DROPPED.load(Acquire) && NEW_DROPPED.load(Acquire) == COUNT);
+}
+
+/// Calls `collect` until `done` returns `true`. The other tests may keep the objects alive for a
+/// while.
+fn collect_until<F: Fn() -> bool>(done: F) {
+    for _ in 0..1000 {
+        collect();
+        if done() {
+            return;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    panic!("the retired objects are not freed");
+}
@@ -1,25 +1,22 @@
 use crossbeam_utils::thread;
 use rand::distributions::Alphanumeric;
 use rand::prelude::*;
-use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{
     AtomicBool,
     Ordering::{Acquire, Release},
 };
 
-use cs492_concur_homework::OrderedListSet;
+use cs492_concur_homework::{
+    OrderedListMultiSet, OrderedListSet, PhaseFairRwLock, RawRwLock, RawTryRwLock, SpinRwLock,
+    WouldBlock,
+};
+use lock::{ClhLock, McsLock, SpinLock, TicketLock, TtasLock};
+
+mod sorted_set;
 
 #[test]
 fn smoke() {
-    let set = OrderedListSet::new();
-    set.insert(1).unwrap();
-    set.insert(2).unwrap();
-    set.insert(3).unwrap();
-    assert_eq!(set.remove(&2), Ok(2));
-    for i in set.iter() {
-        println!("{}", i);
-    }
-    assert_eq!(set.remove(&3), Ok(3));
+    sorted_set::smoke::<OrderedListSet<_>>();
 }
 
 #[test]
@@ -42,273 +39,521 @@ fn parallel_iter_end() {
 }
 
 #[test]
-fn stress_sequential() {
-    #[derive(Debug)]
-    enum Ops {
-        ContainsSome,
-        ContainsNone,
-        Insert,
-        RemoveSome,
-        RemoveNone,
-        Iterate,
-    }
-
-    let ops = [
-        Ops::ContainsSome,
-        Ops::ContainsNone,
-        Ops::Insert,
-        Ops::RemoveSome,
-        Ops::RemoveNone,
-        Ops::Iterate,
-    ];
-    let mut rng = thread_rng();
-    let set = OrderedListSet::default();
-    let mut hashset = HashSet::<String>::new();
-
-    const OPS: usize = 4096;
-
-    for i in 0..OPS {
-        let op = ops.choose(&mut rng).unwrap();
-
-        match op {
-            Ops::ContainsSome => {
-                if let Some(key) = hashset.iter().choose(&mut rng) {
-                    println!("iteration {}: contains({:?}) (existing)", i, key);
-                    assert_eq!(set.contains(key), hashset.contains(key));
-                }
-            }
-            Ops::ContainsNone => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: contains({:?}) (non-existing)", i, key);
-                assert_eq!(set.contains(&key), hashset.contains(&key));
-            }
-            Ops::Insert => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: insert({:?})", i, key);
-                assert_eq!(set.insert(key.clone()).is_ok(), hashset.insert(key));
-            }
-            Ops::RemoveSome => {
-                let key = hashset.iter().choose(&mut rng).map(Clone::clone);
-                if let Some(key) = key {
-                    println!("iteration {}: remove({:?}) (existing)", i, key);
-                    assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
-                }
-            }
-            Ops::RemoveNone => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: remove({:?}) (non-existing)", i, key);
-                assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
-            }
-            Ops::Iterate => {
-                let result = set.iter().map(Clone::clone).collect::<HashSet<_>>();
-                println!("iteration {}: iter() → {:?}", i, result);
-                assert_eq!(result, hashset);
-            }
-        }
-    }
+fn parallel_readers() {
+    let set = OrderedListSet::new();
+    set.insert(1).unwrap();
+    set.insert(2).unwrap();
+    set.insert(3).unwrap();
+    let mut iter = set.iter();
+    assert_eq!(iter.next(), Some(&1));
+    thread::scope(|s| {
+        s.spawn(|_| {
+            // these shouldn't block on the iterator in the middle of the list
+            assert!(set.contains(&3));
+            assert_eq!(set.iter().collect::<Vec<_>>(), [&1, &2, &3]);
+        });
+    })
+    .unwrap();
+    assert_eq!(iter.next(), Some(&2));
 }
 
-const THREADS: usize = 16;
-const STEPS: usize = 4096 * 8;
+#[test]
+fn snapshot_iter() {
+    let set = OrderedListSet::new();
+    set.insert(1).unwrap();
+    set.insert(2).unwrap();
+    set.insert(3).unwrap();
+    let mut iter = set.snapshot_iter();
+    assert_eq!(iter.next(), Some(1));
+    // these shouldn't block on the iterator
+    set.insert(4).unwrap();
+    assert_eq!(set.remove(&2), Ok(2));
+    assert_eq!(iter.collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(set.snapshot_iter().collect::<Vec<_>>(), [1, 3, 4]);
+}
 
-fn generate_random_string(rng: &mut ThreadRng) -> String {
-    rng.sample_iter(&Alphanumeric).take(1).collect()
+#[test]
+fn range() {
+    let set = OrderedListSet::new();
+    assert_eq!(set.first(), None);
+    assert_eq!(set.last(), None);
+    for i in (0..10).rev() {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.range(3..6).collect::<Vec<_>>(), [&3, &4, &5]);
+    assert_eq!(set.range(3..=6).collect::<Vec<_>>(), [&3, &4, &5, &6]);
+    assert_eq!(set.range(..2).collect::<Vec<_>>(), [&0, &1]);
+    assert_eq!(set.range(8..).collect::<Vec<_>>(), [&8, &9]);
+    assert_eq!(set.range(5..5).count(), 0);
+    assert_eq!(set.range(20..).count(), 0);
+    assert_eq!(set.first(), Some(0));
+    assert_eq!(set.last(), Some(9));
+
+    let mut range = set.range(..2);
+    assert_eq!(range.next(), Some(&0));
+    assert_eq!(range.next(), Some(&1));
+    assert_eq!(range.next(), None);
+    // the range passed its end, so this shouldn't block
+    set.insert(10).unwrap();
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Ops {
-    Contains,
-    Insert,
-    Remove,
+#[test]
+fn get_or_insert() {
+    let set = OrderedListSet::new();
+    set.insert(1).unwrap();
+    assert_eq!(*set.get_or_insert(1), 1);
+    assert_eq!(*set.get_or_insert(2), 2);
+    assert_eq!(set.len(), 2);
+    assert_eq!(set.iter().collect::<Vec<_>>(), [&1, &2]);
+
+    // intern the strings concurrently: every thread gets the same element
+    let set = OrderedListSet::new();
+    let ptrs = thread::scope(|s| {
+        let handles = (0..THREADS)
+            .map(|_| {
+                s.spawn(|_| {
+                    (0..100)
+                        .map(|i| &*set.get_or_insert(i.to_string()) as *const String as usize)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+    assert_eq!(set.len(), 100);
+    assert!(ptrs.windows(2).all(|p| p[0] == p[1]));
 }
 
-#[derive(Debug, Clone)]
-enum Log {
-    Contains { key: String, result: bool },
-    Insert { key: String, result: bool },
-    Remove { key: String, result: bool },
+#[test]
+fn remove_if() {
+    let set = OrderedListSet::new();
+    for i in 0..10 {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.remove_if(&3, |_| false), Err(()));
+    assert_eq!(set.remove_if(&3, |&i| i == 3), Ok(3));
+    assert_eq!(set.remove_if(&3, |_| true), Err(()));
+
+    set.retain(|i| i % 2 == 0);
+    assert_eq!(set.iter().collect::<Vec<_>>(), [&0, &2, &4, &6, &8]);
+    assert_eq!(set.len(), 5);
+    set.retain(|_| false);
+    assert!(set.is_empty());
+    assert_eq!(set.iter().count(), 0);
 }
 
-impl Log {
-    fn key(&self) -> &String {
-        match self {
-            Self::Contains { key, .. } => key,
-            Self::Insert { key, .. } => key,
-            Self::Remove { key, .. } => key,
-        }
+#[test]
+fn set_algebra() {
+    let evens = OrderedListSet::new();
+    let threes = OrderedListSet::new();
+    for i in 0..10 {
+        evens.insert(2 * i).unwrap();
+        threes.insert(3 * i).unwrap();
     }
+    assert_eq!(
+        evens.intersection(&threes).iter().collect::<Vec<_>>(),
+        [&0, &6, &12, &18]
+    );
+    assert_eq!(
+        threes.difference(&evens).iter().collect::<Vec<_>>(),
+        [&3, &9, &15, &21, &24, &27]
+    );
+    assert_eq!(evens.difference(&evens).len(), 0);
+    assert_eq!(evens.intersection(&evens).len(), 10);
+
+    evens.union_into(&threes);
+    assert_eq!(threes.len(), 16);
+    let expected = (0..30)
+        .filter(|i| (i % 2 == 0 && *i < 20) || i % 3 == 0)
+        .collect::<Vec<_>>();
+    assert_eq!(threes.snapshot_iter().collect::<Vec<_>>(), expected);
+    threes.union_into(&threes);
+    assert_eq!(threes.len(), 16);
 }
 
 #[test]
-fn stress_concurrent() {
-    let ops = [Ops::Contains, Ops::Insert, Ops::Remove, Ops::Remove];
+fn collect() {
+    let mut set = vec![3, 1, 4, 1, 5, 9, 2, 6]
+        .into_iter()
+        .collect::<OrderedListSet<_>>();
+    assert_eq!(set.len(), 7);
+    assert_eq!(set.iter().collect::<Vec<_>>(), [&1, &2, &3, &4, &5, &6, &9]);
+    set.extend(vec![5, 3, 5, 8]);
+    assert_eq!(set.len(), 8);
+    assert!(set.contains(&8));
+
+    let mut iter = set.into_iter();
+    assert_eq!(iter.size_hint(), (8, Some(8)));
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.collect::<Vec<_>>(), [2, 3, 4, 5, 6, 8, 9]);
+
+    // the rest of the elements are dropped with the iterator
+    let set = (0..10)
+        .map(|i| i.to_string())
+        .collect::<OrderedListSet<_>>();
+    let mut iter = set.into_iter();
+    assert_eq!(iter.next(), Some("0".to_string()));
+}
 
-    let set = OrderedListSet::new();
+#[test]
+fn clone() {
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    let copy = set.clone();
+    set.retain(|i| i % 2 == 0);
+    copy.insert(10).unwrap();
+    assert_eq!(set.len(), 5);
+    assert_eq!(copy.len(), 11);
+    assert_eq!(
+        copy.into_iter().collect::<Vec<_>>(),
+        (0..=10).collect::<Vec<_>>()
+    );
+
+    // clone while the odd numbers are inserted and removed
+    let set = (0..100).step_by(2).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            while !done.load(Acquire) {
+                let key = 2 * rng.gen_range(0, 50) + 1;
+                if rng.gen() {
+                    let _ = set.insert(key);
+                } else {
+                    let _ = set.remove(&key);
+                }
+            }
+        });
+        for _ in 0..1000 {
+            let copy = set.clone();
+            assert_eq!(copy.len(), copy.iter().count());
+            let copy = copy.into_iter().collect::<Vec<_>>();
+            assert!(copy.windows(2).all(|k| k[0] < k[1]));
+            assert!((0..100).step_by(2).all(|i| copy.contains(&i)));
+        }
+        done.store(true, Release);
+    })
+    .unwrap();
+}
 
+fn stress_lock<L: RawRwLock>() {
+    let set = OrderedListSet::<_, L>::with_lock();
     thread::scope(|s| {
         for _ in 0..THREADS {
             s.spawn(|_| {
                 let mut rng = thread_rng();
-                for _ in 0..STEPS {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Contains => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.contains(&value);
-                        }
-                        Ops::Insert => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.insert(value);
-                        }
-                        Ops::Remove => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.remove(&value);
-                        }
+                for _ in 0..4096 {
+                    let key = rng.gen_range(0, 64);
+                    match rng.gen_range(0, 3) {
+                        0 => drop(set.contains(&key)),
+                        1 => drop(set.insert(key)),
+                        _ => drop(set.remove(&key)),
                     }
                 }
             });
         }
     })
     .unwrap();
+    let snapshot = set.iter().copied().collect::<Vec<_>>();
+    assert!(snapshot.windows(2).all(|k| k[0] < k[1]));
+    assert_eq!(set.len(), snapshot.len());
 }
 
-fn assert_logs_consistent(logs: &Vec<Vec<Log>>) {
-    let mut per_key_logs = HashMap::<String, Vec<Log>>::new();
-    for ls in logs {
-        for l in ls {
-            per_key_logs
-                .entry(l.key().clone())
-                .or_insert_with(|| Vec::new())
-                .push(l.clone());
-        }
-    }
-
-    for (k, logs) in &per_key_logs {
-        let mut inserts = HashMap::<String, usize>::new();
-        let mut deletes = HashMap::<String, usize>::new();
-
-        for l in logs {
-            match l {
-                Log::Insert { result: true, .. } => *inserts.entry(k.clone()).or_insert(0) += 1,
-                Log::Remove { result: true, .. } => *deletes.entry(k.clone()).or_insert(0) += 1,
-                _ => (),
-            }
-        }
+#[test]
+fn lock_types() {
+    stress_lock::<SpinLock>();
+    stress_lock::<TicketLock>();
+    stress_lock::<TtasLock>();
+    stress_lock::<ClhLock>();
+    stress_lock::<McsLock>();
+    stress_lock::<PhaseFairRwLock>();
+}
 
-        for l in logs {
-            match l {
-                Log::Contains { key, result: true } => assert!(inserts.contains_key(key)),
-                _ => (),
-            }
-        }
+#[test]
+fn pop_front() {
+    let set = (0..THREADS * 100).rev().collect::<OrderedListSet<_>>();
+    let popped = thread::scope(|s| {
+        let handles = (0..THREADS)
+            .map(|_| {
+                s.spawn(|_| {
+                    let mut popped = Vec::new();
+                    while let Some(i) = set.pop_front() {
+                        popped.push(i);
+                    }
+                    popped
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+    assert!(set.is_empty());
+    assert_eq!(set.pop_front(), None);
+    // each thread pops in order, and every element is popped once
+    assert!(popped.iter().all(|p| p.windows(2).all(|k| k[0] < k[1])));
+    let mut popped = popped.concat();
+    popped.sort();
+    assert_eq!(popped, (0..THREADS * 100).collect::<Vec<_>>());
+}
 
-        for (k, v) in &deletes {
-            assert!(inserts.get(k).unwrap() >= v);
-        }
+#[test]
+fn cursor() {
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    {
+        // replace 3, 4, 5 with 4 under a single traversal
+        let mut cursor = set.cursor();
+        assert!(cursor.seek(&3));
+        assert_eq!(cursor.remove_here(), Some(3));
+        assert_eq!(cursor.remove_here(), Some(4));
+        assert_eq!(cursor.remove_here(), Some(5));
+        assert_eq!(cursor.current(), Some(&6));
+        assert_eq!(cursor.insert_here(2), Err(2));
+        assert_eq!(cursor.insert_here(6), Err(6));
+        assert_eq!(cursor.insert_here(4), Ok(()));
+        assert_eq!(cursor.insert_here(4), Err(4));
+        assert_eq!(cursor.insert_here(5), Ok(()));
+        assert_eq!(cursor.current(), Some(&6));
+
+        // the cursor only moves forward
+        assert!(!cursor.seek(&1));
+        assert_eq!(cursor.current(), Some(&6));
+        assert!(!cursor.seek(&20));
+        assert_eq!(cursor.current(), None);
+        assert!(!cursor.move_next());
+        assert_eq!(cursor.remove_here(), None);
+        assert_eq!(cursor.insert_here(10), Ok(()));
     }
+    assert_eq!(set.len(), 10);
+    assert_eq!(
+        set.into_iter().collect::<Vec<_>>(),
+        [0, 1, 2, 4, 5, 6, 7, 8, 9, 10]
+    );
 }
 
 #[test]
-fn log_concurrent() {
-    let ops = [Ops::Contains, Ops::Insert, Ops::Remove];
-
-    const THREADS: usize = 16;
-    const STEPS: usize = 4096 * 12;
-
-    let set = OrderedListSet::new();
-
-    let logs = thread::scope(|s| {
-        let mut handles = Vec::new();
+fn node_pool() {
+    let set = OrderedListSet::new().node_pool(16);
+    thread::scope(|s| {
         for _ in 0..THREADS {
-            let handle = s.spawn(|_| {
+            s.spawn(|_| {
                 let mut rng = thread_rng();
-                let mut logs = Vec::new();
-                for _ in 0..STEPS {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Contains => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.contains(&key);
-                            logs.push(Log::Contains {
-                                key: key.clone(),
-                                result,
-                            });
-                        }
-                        Ops::Insert => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.insert(key.clone());
-                            logs.push(Log::Insert {
-                                key,
-                                result: result.is_ok(),
-                            });
-                        }
-                        Ops::Remove => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.remove(&key);
-                            logs.push(Log::Remove {
-                                key: key.clone(),
-                                result: result.is_ok(),
-                            });
-                        }
+                for _ in 0..4096 {
+                    let key = generate_random_string(&mut rng);
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
                     }
                 }
-                logs
             });
-            handles.push(handle);
         }
-        handles
-            .into_iter()
-            .map(|h| h.join().unwrap())
-            .collect::<Vec<_>>()
     })
     .unwrap();
-
-    assert_logs_consistent(&logs);
+    let snapshot = set.snapshot_iter().collect::<Vec<_>>();
+    assert!(snapshot.windows(2).all(|k| k[0] < k[1]));
+    assert_eq!(set.len(), snapshot.len());
+    set.retain(|_| false);
+    assert!(set.is_empty());
+    for key in snapshot {
+        set.insert(key).unwrap();
+    }
 }
 
 #[test]
-fn iter_consistent() {
-    const THREADS: usize = 15;
-    const STEPS: usize = 4096 * 12;
-
+fn drain() {
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    let mut drain = set.drain();
+    assert_eq!(set.iter().next(), None);
+    assert_eq!(set.len(), 10);
+    // the set can be used while draining
+    set.insert(20).unwrap();
+    assert_eq!(drain.next(), Some(0));
+    assert_eq!(drain.next(), Some(1));
+    drop(drain);
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.drain().collect::<Vec<_>>(), [20]);
+    assert!(set.is_empty());
+
+    // drain while the others insert and remove
     let set = OrderedListSet::new();
-
-    // pre-fill with even numbers
-    for i in (0..100).step_by(2).rev() {
-        let _ = set.insert(i);
-    }
-    let evens = set.iter().copied().collect::<HashSet<_>>();
-
     let done = AtomicBool::new(false);
     thread::scope(|s| {
-        // insert or remove odd numbers
         for _ in 0..THREADS {
             s.spawn(|_| {
                 let mut rng = thread_rng();
-                for _ in 0..STEPS {
-                    let key = 2 * rng.gen_range(0, 50) + 1;
+                while !done.load(Acquire) {
+                    let key = rng.gen_range(0, 64);
                     if rng.gen() {
                         let _ = set.insert(key);
                     } else {
                         let _ = set.remove(&key);
                     }
                 }
-                done.store(true, Release);
             });
         }
-        // iterator consistency check
-        s.spawn(|_| {
-            while !done.load(Acquire) {
-                let snapshot = set.iter().copied().collect::<Vec<_>>();
-                // sorted
-                assert!(snapshot.windows(2).all(|k| k[0] <= k[1]));
-                // even numbers are not touched
-                let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
-                assert!(evens.is_subset(&snapshot));
-            }
-        });
+        for _ in 0..100 {
+            let drained = set.drain().collect::<Vec<_>>();
+            assert!(drained.windows(2).all(|k| k[0] < k[1]));
+        }
+        done.store(true, Release);
     })
     .unwrap();
+    assert_eq!(set.len(), set.iter().count());
+}
+
+#[test]
+fn contains_all() {
+    let set = (0..10).map(|i| 2 * i).collect::<OrderedListSet<_>>();
+    assert_eq!(
+        set.contains_all(&[-1, 0, 0, 3, 4, 17, 18, 20]),
+        [false, true, true, false, true, false, true, false]
+    );
+    assert_eq!(set.contains_all(&[]), []);
+}
+
+#[test]
+#[should_panic(expected = "keys not sorted")]
+fn contains_all_unsorted() {
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    let _ = set.contains_all(&[3, 1]);
+}
+
+#[test]
+fn replace() {
+    #[derive(Debug, Clone)]
+    struct Entry(usize, &'static str);
+    impl PartialEq for Entry {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+    impl Eq for Entry {}
+    impl PartialOrd for Entry {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Entry {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    let mut set = OrderedListSet::new();
+    set.insert(Entry(1, "a")).unwrap();
+    set.insert(Entry(2, "b")).unwrap();
+    assert_eq!(set.replace(Entry(2, "c")).unwrap().1, "b");
+    assert_eq!(set.replace(Entry(3, "d")).unwrap_err().1, "d");
+    assert_eq!(set.iter().map(|e| e.1).collect::<Vec<_>>(), ["a", "c"]);
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn try_ops() {
+    fn check<L: RawTryRwLock>() {
+        let set = OrderedListSet::<i32, L>::with_lock();
+        set.insert(1).unwrap();
+        set.insert(3).unwrap();
+
+        // a cursor holds the lock of the head
+        let cursor = set.cursor();
+        assert_eq!(set.try_contains(&1), Err(WouldBlock(())));
+        assert_eq!(set.try_insert(2), Err(WouldBlock(2)));
+        assert_eq!(set.try_remove(&1), Err(WouldBlock(())));
+        drop(cursor);
+
+        assert_eq!(set.try_contains(&1), Ok(true));
+        assert_eq!(set.try_contains(&2), Ok(false));
+        assert_eq!(set.try_insert(2), Ok(Ok(())));
+        assert_eq!(set.try_insert(2), Ok(Err(2)));
+
+        // a reference holds the lock of the link from the element
+        let elem = set.get_or_insert(2);
+        assert_eq!(set.try_remove(&2), Err(WouldBlock(())));
+        assert_eq!(set.try_insert(4), Err(WouldBlock(4)));
+        assert_eq!(set.try_remove(&1), Ok(Ok(1)));
+        drop(elem);
+
+        assert_eq!(set.try_remove(&2), Ok(Ok(2)));
+        assert_eq!(set.try_remove(&2), Ok(Err(())));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), [3]);
+        assert_eq!(set.len(), 1);
+    }
+    check::<SpinRwLock>();
+    check::<SpinLock>();
+    check::<PhaseFairRwLock>();
+}
+
+#[test]
+fn multiset() {
+    let set = OrderedListMultiSet::new();
+    for i in &[3, 1, 3, 2, 3, 1] {
+        set.insert(*i);
+    }
+    assert_eq!(set.len(), 6);
+    assert_eq!(set.count(&1), 2);
+    assert_eq!(set.count(&3), 3);
+    assert_eq!(set.count(&4), 0);
+    assert_eq!(set.iter().cloned().collect::<Vec<_>>(), [1, 1, 2, 3, 3, 3]);
+    assert_eq!(set.remove_one(&3), Ok(3));
+    assert_eq!(set.count(&3), 2);
+    assert_eq!(set.remove_one(&2), Ok(2));
+    assert_eq!(set.remove_one(&2), Err(()));
+    assert!(!set.contains(&2));
+    assert_eq!(set.len(), 4);
+
+    // every thread inserts each key once, then removes it once
+    let set = OrderedListMultiSet::new();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for key in 0..100 {
+                    set.insert(key);
+                }
+                for key in (0..100).rev() {
+                    assert_eq!(set.remove_one(&key), Ok(key));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(set.is_empty());
+    assert_eq!(set.count(&0), 0);
+}
+
+const THREADS: usize = 16;
+const STEPS: usize = 4096 * 8;
+
+fn generate_random_string(rng: &mut ThreadRng) -> String {
+    rng.sample_iter(&Alphanumeric).take(1).collect()
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    sorted_set::stress_sequential::<OrderedListSet<_>>(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    sorted_set::stress_concurrent::<OrderedListSet<_>>(THREADS, STEPS);
+}
+
+#[test]
+fn log_concurrent() {
+    const STEPS: usize = 4096 * 12;
+    sorted_set::log_concurrent::<OrderedListSet<_>>(THREADS, STEPS);
+}
+
+#[test]
+fn iter_consistent() {
+    const THREADS: usize = 15;
+    const STEPS: usize = 4096 * 12;
+    sorted_set::iter_consistent::<OrderedListSet<_>>(THREADS, STEPS);
 }
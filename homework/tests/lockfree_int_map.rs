@@ -0,0 +1,180 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{LockFreeIntMap, NonblockingConcurrentMap, NonblockingMap};
+
+pub mod map;
+
+#[test]
+pub fn smoke() {
+    let map = LockFreeIntMap::<usize>::new();
+
+    let guard = epoch::pin();
+
+    assert_eq!(map.insert(&37, 37, &guard), Ok(()));
+    assert_eq!(map.insert(&37, 38, &guard), Err(38));
+    assert_eq!(map.lookup(&42, &guard), None);
+    assert_eq!(map.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(map.insert(&0, 0, &guard), Ok(()));
+    assert_eq!(map.lookup(&0, &guard), Some(&0));
+    assert_eq!(map.lookup(&usize::MAX, &guard), None);
+    assert_eq!(map.len(), 2);
+
+    assert_eq!(map.delete(&37, &guard), Ok(&37));
+    assert_eq!(map.delete(&37, &guard), Err(()));
+    assert_eq!(map.lookup(&37, &guard), None);
+    assert_eq!(map.insert(&37, 39, &guard), Ok(()));
+    assert_eq!(map.lookup(&37, &guard), Some(&39));
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn resize() {
+    const KEYS: usize = 1024 * 16;
+
+    // the map grows from 16 slots, and shrinks after the deletions.
+    let map = LockFreeIntMap::<usize>::new();
+    let guard = epoch::pin();
+    for i in 0..KEYS {
+        assert_eq!(map.insert(&i, i, &guard), Ok(()));
+    }
+    assert_eq!(map.len(), KEYS);
+    for i in 0..KEYS {
+        assert_eq!(map.lookup(&i, &guard), Some(&i));
+    }
+    for round in 0..4 {
+        for i in (round..KEYS).step_by(4) {
+            assert_eq!(map.delete(&i, &guard), Ok(&i));
+        }
+        for i in KEYS..KEYS + KEYS / 8 {
+            assert_eq!(map.insert(&i, i, &guard), Ok(()));
+            assert_eq!(map.delete(&i, &guard), Ok(&i));
+        }
+    }
+    assert!(map.is_empty());
+
+    for i in 0..KEYS / 2 {
+        assert_eq!(map.insert(&i, i, &guard), Ok(()));
+    }
+    let mut pairs = Vec::new();
+    assert_eq!(map.for_each(&guard, |&k, &v| pairs.push((k, v))), Ok(()));
+    pairs.sort_unstable();
+    let expected = (0..KEYS / 2).map(|i| (i, i)).collect::<Vec<_>>();
+    assert_eq!(pairs, expected);
+}
+
+#[test]
+fn concurrent_resize() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024 * 4;
+
+    let map = LockFreeIntMap::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move |_| {
+                for i in (t..STEPS).step_by(THREADS) {
+                    let guard = epoch::pin();
+                    assert_eq!(map.insert(&i, i, &guard), Ok(()));
+                    assert_eq!(map.lookup(&i, &guard), Some(&i));
+                    if i % 2 == 0 {
+                        assert_eq!(map.delete(&i, &guard), Ok(&i));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    assert_eq!(map.len(), STEPS / 2);
+    for i in 0..STEPS {
+        let expected = if i % 2 == 0 { None } else { Some(&i) };
+        assert_eq!(map.lookup(&i, &guard), expected);
+    }
+}
+
+#[test]
+fn drop_values() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug)]
+    struct Value;
+
+    impl Drop for Value {
+        fn drop(&mut self) {
+            let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // each value is dropped exactly once, while the map is resized.
+    let map = LockFreeIntMap::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move |_| {
+                for i in (t..STEPS).step_by(THREADS) {
+                    let guard = epoch::pin();
+                    assert!(map.insert(&i, Value, &guard).is_ok());
+                    assert!(map.insert(&i, Value, &guard).is_err());
+                    if i % 2 == 0 {
+                        assert!(map.delete(&i, &guard).is_ok());
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    drop(map);
+
+    // the deleted values are dropped once the guards are unpinned.
+    let mut drops = 0;
+    for _ in 0..1024 {
+        drops = DROPS.load(Ordering::Relaxed);
+        if drops == STEPS * 2 {
+            break;
+        }
+        epoch::pin().flush();
+    }
+    assert_eq!(drops, STEPS * 2);
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<usize, NonblockingConcurrentMap<_, _, LockFreeIntMap<usize>>>(
+        STEPS,
+    );
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, LockFreeIntMap<usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 4;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, LockFreeIntMap<usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, NonblockingConcurrentMap<_, _, LockFreeIntMap<usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
@@ -0,0 +1,35 @@
+use cs492_concur_homework::LockFreeListSet;
+
+mod sorted_set;
+
+#[test]
+fn smoke() {
+    sorted_set::smoke::<LockFreeListSet<_>>();
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    sorted_set::stress_sequential::<LockFreeListSet<_>>(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 8;
+    sorted_set::stress_concurrent::<LockFreeListSet<_>>(THREADS, STEPS);
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 12;
+    sorted_set::log_concurrent::<LockFreeListSet<_>>(THREADS, STEPS);
+}
+
+#[test]
+fn iter_consistent() {
+    const THREADS: usize = 15;
+    const STEPS: usize = 4096 * 12;
+    sorted_set::iter_consistent::<LockFreeListSet<_>>(THREADS, STEPS);
+}
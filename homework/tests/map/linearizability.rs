@@ -0,0 +1,169 @@
+//! Linearizability checker for concurrent maps.
+//!
+//! Each thread records the operations it runs with their invocation and response times, read from
+//! a global clock. The history is then checked against a sequential map with the algorithm of
+//! Wing and Gong, with the memoization of Lowe: a search over the orders of the operations that
+//! respect the real-time order, pruning the (linearized operations, map state) pairs already seen.
+//!
+//! Linearizability is local, and each key of a map is an independent object, so the history of
+//! each key is checked on its own. The keys are drawn from a small pool so that the operations on
+//! a key overlap.
+//!
+//! References:
+//! - Jeannette M. Wing and Chun Gong. Testing and Verifying Concurrent Objects. JPDC 1993.
+//! - Gavin Lowe. Testing for linearizability. Concurrency and Computation: Practice and Experience
+//!   2017.
+
+use core::fmt;
+use core::hash::Hash;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cs492_concur_homework::{ConcurrentMap, RandGen};
+use std::collections::{HashMap, HashSet};
+
+use rand::prelude::*;
+
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread;
+
+use super::{Log, Ops};
+
+/// An operation with its invocation and response times.
+#[derive(Debug, Clone)]
+struct Event<K, V> {
+    call: usize,
+    ret: usize,
+    log: Log<K, V>,
+}
+
+impl<K, V: Clone + Eq> Log<K, V> {
+    /// Applies the operation to the value of its key in a sequential map. Returns the new value if
+    /// the sequential map returns the same result as the logged one.
+    fn apply(&self, state: &Option<V>) -> Option<Option<V>> {
+        match (self, state) {
+            (Self::Lookup { value, .. }, _) if value == state => Some(state.clone()),
+            (Self::Insert { value: Ok(v), .. }, None) => Some(Some(v.clone())),
+            (Self::Insert { value: Err(()), .. }, Some(_)) => Some(state.clone()),
+            (Self::Delete { value: Ok(v), .. }, Some(w)) if v == w => Some(None),
+            (Self::Delete { value: Err(()), .. }, None) => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// Returns `true` if the history of a key, starting from an absent key, is linearizable.
+fn is_linearizable<K, V: Clone + Eq + Hash>(events: &[Event<K, V>]) -> bool {
+    // The bitset of the linearized events, and the value of the key after them.
+    type Config<V> = (Vec<u64>, Option<V>);
+
+    let mut visited = HashSet::<Config<V>>::new();
+    let mut stack: Vec<Config<V>> = vec![(vec![0; (events.len() + 63) / 64], None)];
+
+    while let Some((linearized, state)) = stack.pop() {
+        let pending = |i: usize| linearized[i / 64] & (1 << (i % 64)) == 0;
+
+        // An event may be linearized next if it is invoked before all pending events respond.
+        let min_ret = match (0..events.len())
+            .filter(|&i| pending(i))
+            .map(|i| events[i].ret)
+            .min()
+        {
+            Some(min_ret) => min_ret,
+            None => return true,
+        };
+
+        for (i, event) in events.iter().enumerate() {
+            if !pending(i) || event.call > min_ret {
+                continue;
+            }
+            if let Some(next) = event.log.apply(&state) {
+                let mut linearized = linearized.clone();
+                linearized[i / 64] |= 1 << (i % 64);
+                let config = (linearized, next);
+                if visited.insert(config.clone()) {
+                    stack.push(config);
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Runs random operations on the keys of a pool of size `keys` from `threads` threads, and checks
+/// that the resulting history is linearizable.
+pub fn linearizable_concurrent<
+    K: fmt::Debug + Clone + Eq + Hash + Send + Sync + RandGen,
+    M: Default + Sync + ConcurrentMap<K, usize>,
+>(
+    threads: usize,
+    steps: usize,
+    keys: usize,
+) {
+    let ops = [Ops::Lookup, Ops::Insert, Ops::Delete];
+
+    let map = M::default();
+    let clock = AtomicUsize::new(0);
+    let pool = {
+        let mut rng = thread_rng();
+        (0..keys).map(|_| K::rand_gen(&mut rng)).collect::<Vec<_>>()
+    };
+
+    let events = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..threads {
+            let handle = s.spawn(|_| {
+                let mut rng = thread_rng();
+                let mut events = Vec::new();
+                for _ in 0..steps {
+                    let op = ops.choose(&mut rng).unwrap();
+                    let key = pool.choose(&mut rng).unwrap().clone();
+
+                    let call = clock.fetch_add(1, Ordering::SeqCst);
+                    let log = match op {
+                        Ops::Lookup => {
+                            let value = map.lookup(&key, &pin(), |value| value.cloned());
+                            Log::Lookup { key, value }
+                        }
+                        Ops::Insert => {
+                            let value = rng.gen::<usize>();
+                            let result = map.insert(&key, value, &pin());
+                            let value = result.map(|_| value).map_err(|_| ());
+                            Log::Insert { key, value }
+                        }
+                        Ops::Delete => {
+                            let value = map.delete(&key, &pin());
+                            Log::Delete { key, value }
+                        }
+                    };
+                    let ret = clock.fetch_add(1, Ordering::SeqCst);
+                    events.push(Event { call, ret, log });
+                }
+                events
+            });
+            handles.push(handle);
+        }
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+
+    let mut per_key_events = HashMap::<K, Vec<Event<K, usize>>>::new();
+    for event in events {
+        per_key_events
+            .entry(event.log.key().clone())
+            .or_default()
+            .push(event);
+    }
+
+    for (key, mut events) in per_key_events {
+        events.sort_by_key(|e| e.call);
+        assert!(
+            is_linearizable(&events),
+            "history of {:?} is not linearizable: {:?}",
+            key,
+            events
+        );
+    }
+}
@@ -9,6 +9,10 @@ use rand::prelude::*;
 use crossbeam_epoch::pin;
 use crossbeam_utils::thread;
 
+mod linearizability;
+
+pub use linearizability::linearizable_concurrent;
+
 pub fn stress_sequential<
     K: fmt::Debug + Clone + Eq + Hash + RandGen,
     M: Default + SequentialMap<K, usize>,
@@ -0,0 +1,78 @@
+use cs492_concur_homework::{MutexHashMap, NonblockingConcurrentMap, RwLockBTreeMap};
+
+mod map;
+
+#[test]
+fn mutex_hash_map_stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        usize,
+        NonblockingConcurrentMap<_, _, MutexHashMap<usize, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn mutex_hash_map_stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, MutexHashMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn mutex_hash_map_log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, MutexHashMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn rwlock_btree_map_stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        usize,
+        NonblockingConcurrentMap<_, _, RwLockBTreeMap<usize, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn rwlock_btree_map_stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, RwLockBTreeMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn rwlock_btree_map_log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, RwLockBTreeMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn mutex_hash_map_linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, NonblockingConcurrentMap<_, _, MutexHashMap<usize, usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
+
+#[test]
+fn rwlock_btree_map_linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<
+        usize,
+        NonblockingConcurrentMap<_, _, RwLockBTreeMap<usize, usize>>,
+    >(THREADS, STEPS, KEYS);
+}
@@ -0,0 +1,143 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering::*};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::qsbr::{
+    defer, is_online, offline, online, quiescent_state, read_lock, retire, Atomic, Owned, Shared,
+};
+
+/// Passes quiescent states until `done` returns `true`. The other tests may keep the grace periods
+/// from ending for a while.
+fn quiescent_until<F: Fn() -> bool>(done: F) {
+    for _ in 0..1000 {
+        quiescent_state();
+        if done() {
+            return;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    panic!("the grace period doesn't end");
+}
+
+#[test]
+fn counter() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let count = Atomic::new(0usize);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                online();
+                for _ in 0..ITER {
+                    let mut new = Owned::new(0);
+                    loop {
+                        let cur = count.load(Acquire);
+                        let value = unsafe { *cur.deref() };
+                        *new = value + 1;
+                        let new_shared = new.into_shared();
+                        if count
+                            .compare_and_set(cur, new_shared, AcqRel, Acquire)
+                            .is_ok()
+                        {
+                            retire(cur);
+                            break;
+                        } else {
+                            new = unsafe { new_shared.into_owned() };
+                        }
+                    }
+                    quiescent_state();
+                }
+                offline();
+            });
+        }
+    })
+    .unwrap();
+    let cur = count.load(Acquire);
+    // exclusive access
+    assert_eq!(unsafe { *cur.deref() }, THREADS * ITER);
+    retire(cur);
+}
+
+#[test]
+fn online_offline() {
+    assert!(!is_online());
+    online();
+    online();
+    assert!(is_online());
+    offline();
+    assert!(!is_online());
+}
+
+#[test]
+fn defer_runs_after_grace_period() {
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let count = count.clone();
+        defer(move || {
+            count.fetch_add(1, Relaxed);
+        });
+    }
+    quiescent_until(|| count.load(Relaxed) == 3);
+
+    // left to the other threads when the thread exits
+    let th = {
+        let count = count.clone();
+        std::thread::spawn(move || {
+            defer(move || {
+                count.fetch_add(1, Relaxed);
+            })
+        })
+    };
+    th.join().unwrap();
+    quiescent_until(|| count.load(Relaxed) == 4);
+}
+
+// a pointer is not freed while an online thread that may access it doesn't pass a quiescent state.
+#[test]
+fn online_blocks_grace_period() {
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    struct Flag;
+    impl Drop for Flag {
+        fn drop(&mut self) {
+            DROPPED.store(true, Release);
+        }
+    }
+
+    let atomic = Atomic::new(Flag);
+    online();
+    let _flag = unsafe { atomic.load(Acquire).deref() };
+    scope(|s| {
+        s.spawn(|_| {
+            let shared = atomic.load(Relaxed);
+            atomic.store(Shared::null(), Relaxed);
+            retire(shared);
+            for _ in 0..10 {
+                quiescent_state();
+            }
+        });
+    })
+    .unwrap();
+    assert!(!DROPPED.load(Acquire));
+
+    // the pointer left by the exited thread is freed after this thread's quiescent state
+    quiescent_until(|| DROPPED.load(Acquire));
+    offline();
+}
+
+#[test]
+#[should_panic(expected = "quiescent state inside a read-side critical section")]
+fn quiescent_state_in_read_lock() {
+    online();
+    let _guard = read_lock();
+    quiescent_state();
+}
+
+#[test]
+#[should_panic(expected = "reading while offline")]
+fn read_lock_offline() {
+    offline();
+    let _guard = read_lock();
+}
@@ -0,0 +1,130 @@
+use core::sync::atomic::{AtomicUsize, Ordering::*};
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::qsbr;
+use cs492_concur_homework::rcu::{Epoch, GracePeriod, Qsbr, Rcu};
+
+fn smoke<G: GracePeriod>() {
+    let rcu = Rcu::<_, G>::new(vec![1, 2]);
+    let old = rcu.read();
+    rcu.update(|v| {
+        let mut v = v.clone();
+        v.push(3);
+        v
+    });
+    assert_eq!(*old, vec![1, 2]);
+    drop(old);
+    assert_eq!(*rcu.read(), vec![1, 2, 3]);
+    rcu.synchronize();
+}
+
+#[test]
+fn smoke_epoch() {
+    smoke::<Epoch>();
+}
+
+#[test]
+fn smoke_qsbr() {
+    qsbr::online();
+    smoke::<Qsbr>();
+    qsbr::offline();
+}
+
+#[test]
+#[should_panic(expected = "quiescent state inside a read-side critical section")]
+fn synchronize_while_reading_qsbr() {
+    qsbr::online();
+    let rcu = Rcu::<_, Qsbr>::new(0);
+    let _value = rcu.read();
+    rcu.synchronize();
+}
+
+/// A version whose fields are always equal.
+#[derive(Debug, Clone)]
+struct Pair {
+    left: usize,
+    right: usize,
+}
+
+fn stress<G: GracePeriod>(online: fn(), offline: fn(), quiescent: fn()) {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 4;
+
+    let rcu = Rcu::<_, G>::new(Pair { left: 0, right: 0 });
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                online();
+                for _ in 0..ITER {
+                    rcu.update(|pair| Pair {
+                        left: pair.left + 1,
+                        right: pair.right + 1,
+                    });
+                    quiescent();
+                }
+                offline();
+            });
+            s.spawn(|_| {
+                online();
+                let mut last = 0;
+                for _ in 0..ITER {
+                    let pair = rcu.read();
+                    assert_eq!(pair.left, pair.right);
+                    assert!(pair.left >= last);
+                    last = pair.left;
+                    drop(pair);
+                    quiescent();
+                }
+                offline();
+            });
+        }
+    })
+    .unwrap();
+    online();
+    assert_eq!(rcu.read().left, THREADS * ITER);
+    offline();
+}
+
+#[test]
+fn stress_epoch() {
+    stress::<Epoch>(|| (), || (), || ());
+}
+
+#[test]
+fn stress_qsbr() {
+    stress::<Qsbr>(qsbr::online, qsbr::offline, qsbr::quiescent_state);
+}
+
+/// Counts the versions that are dropped.
+#[derive(Debug)]
+struct Version<'c>(&'c AtomicUsize);
+
+impl Drop for Version<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Relaxed);
+    }
+}
+
+fn synchronize_frees<G: GracePeriod>(dropped: &'static AtomicUsize) {
+    let rcu = Rcu::<_, G>::new(Version(dropped));
+    for i in 1..=3 {
+        rcu.update(|v| Version(v.0));
+        // the versions retired so far are freed
+        rcu.synchronize();
+        assert_eq!(dropped.load(Relaxed), i);
+    }
+}
+
+#[test]
+fn synchronize_frees_epoch() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    synchronize_frees::<Epoch>(&DROPPED);
+}
+
+#[test]
+fn synchronize_frees_qsbr() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    qsbr::online();
+    synchronize_frees::<Qsbr>(&DROPPED);
+    qsbr::offline();
+}
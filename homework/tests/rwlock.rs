@@ -0,0 +1,124 @@
+use core::sync::atomic::{AtomicUsize, Ordering::*};
+use std::sync::TryLockError;
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{PhaseFairRwLock, RawRwLock, RwLock, SpinRwLock};
+
+#[test]
+fn smoke() {
+    let lock = RwLock::<_>::new(0);
+    *lock.write().unwrap() += 1;
+    {
+        let r1 = lock.read().unwrap();
+        let r2 = lock.read().unwrap();
+        assert_eq!(*r1 + *r2, 2);
+        assert!(lock.try_write().is_err());
+    }
+    *lock.try_write().unwrap() += 1;
+    {
+        let _w = lock.write().unwrap();
+        assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+    }
+    assert_eq!(*lock.try_read().unwrap(), 2);
+    assert_eq!(lock.into_inner().unwrap(), 2);
+}
+
+fn stress<L: RawRwLock>() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 4;
+
+    // the writers keep the two fields equal
+    let lock = RwLock::<_, L>::new((0usize, 0usize));
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let mut guard = lock.write().unwrap();
+                    guard.0 += 1;
+                    thread::yield_now();
+                    guard.1 += 1;
+                }
+            });
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let guard = lock.read().unwrap();
+                    assert_eq!(guard.0, guard.1);
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(*lock.read().unwrap(), (THREADS * ITER, THREADS * ITER));
+}
+
+#[test]
+fn stress_phase_fair() {
+    stress::<PhaseFairRwLock>();
+}
+
+#[test]
+fn stress_spin() {
+    stress::<SpinRwLock>();
+}
+
+// the readers arriving while a writer is waiting go after it, but not after the next writer.
+#[test]
+fn phase_fair() {
+    let lock = RwLock::<_>::new(Vec::new());
+    let first = lock.read().unwrap();
+    let waiting = AtomicUsize::new(0);
+    scope(|s| {
+        s.spawn(|_| {
+            waiting.fetch_add(1, SeqCst);
+            lock.write().unwrap().push("writer");
+        });
+        while waiting.load(SeqCst) == 0 {
+            thread::yield_now();
+        }
+        sleep(Duration::from_millis(50));
+
+        // the writer holds off the new readers
+        assert!(lock.try_read().is_err());
+        s.spawn(|_| {
+            let guard = lock.read().unwrap();
+            assert_eq!(*guard, ["writer"]);
+        });
+        drop(first);
+    })
+    .unwrap();
+}
+
+#[test]
+fn poison() {
+    let lock = RwLock::<_>::new(1);
+    let result = scope(|s| {
+        s.spawn(|_| {
+            let _guard = lock.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join()
+    })
+    .unwrap();
+    assert!(result.is_err());
+    assert!(lock.is_poisoned());
+    assert!(lock.read().is_err());
+    assert!(matches!(lock.try_write(), Err(TryLockError::Poisoned(_))));
+    *lock.write().unwrap_err().into_inner() += 1;
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), 2);
+
+    // a panicking reader doesn't poison the lock
+    let lock = RwLock::<_>::new(1);
+    let result = scope(|s| {
+        s.spawn(|_| {
+            let _guard = lock.read().unwrap();
+            panic!("doesn't poison the lock");
+        })
+        .join()
+    })
+    .unwrap();
+    assert!(result.is_err());
+    assert!(!lock.is_poisoned());
+}
@@ -0,0 +1,122 @@
+mod mock;
+
+#[cfg(not(feature = "check-loom"))]
+mod basic {
+    use crossbeam_utils::thread::scope;
+    use cs492_concur_homework::SeqLock;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn smoke() {
+        let lock = SeqLock::new([0u64, 0u64]);
+        assert_eq!(lock.read(), [0, 0]);
+        lock.write([1, 2]);
+        lock.update(|[requests, bytes]| [requests + 1, bytes + 10]);
+        assert_eq!(lock.read(), [2, 12]);
+    }
+
+    #[test]
+    fn update_panic() {
+        let lock = SeqLock::new(1usize);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            lock.update(|_| panic!("update"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(lock.read(), 1);
+        lock.update(|data| data + 1);
+        assert_eq!(lock.read(), 2);
+    }
+
+    #[test]
+    fn unaligned_size() {
+        let lock = SeqLock::new([1u8, 2, 3]);
+        lock.update(|[a, b, c]| [c, b, a]);
+        assert_eq!(lock.read(), [3, 2, 1]);
+        let lock = SeqLock::new(());
+        lock.write(());
+        lock.read();
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_large() {
+        let _ = SeqLock::new([0usize; 9]);
+    }
+
+    #[test]
+    fn stress() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024 * 16;
+
+        let lock = SeqLock::new([0usize; 8]);
+        scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|_| {
+                    for _ in 0..ITER {
+                        lock.update(|mut data| {
+                            for word in data.iter_mut() {
+                                *word += 1;
+                            }
+                            data
+                        });
+                    }
+                });
+                s.spawn(|_| {
+                    let mut last = 0;
+                    for _ in 0..ITER {
+                        let data = lock.read();
+                        assert!(data.iter().all(|&word| word == data[0]));
+                        assert!(data[0] >= last);
+                        last = data[0];
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(lock.read(), [THREADS * ITER; 8]);
+    }
+}
+
+mod sync {
+    use super::mock::model;
+    use super::mock::sync::Arc;
+    use super::mock::thread;
+    use cs492_concur_homework::SeqLock;
+
+    // a reader never sees the data half-written.
+    #[test]
+    fn read_write_sync() {
+        model(|| {
+            let lock = Arc::new(SeqLock::new([0usize, 0usize]));
+
+            let th = {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    let [left, right] = lock.read();
+                    assert_eq!(left, right);
+                })
+            };
+
+            lock.write([1, 1]);
+            th.join().unwrap();
+            assert_eq!(lock.read(), [1, 1]);
+        })
+    }
+
+    // the writers are serialized.
+    #[test]
+    fn update_update_sync() {
+        model(|| {
+            let lock = Arc::new(SeqLock::new(0usize));
+
+            let th = {
+                let lock = lock.clone();
+                thread::spawn(move || lock.update(|data| data + 1))
+            };
+
+            lock.update(|data| data + 1);
+            th.join().unwrap();
+            assert_eq!(lock.read(), 2);
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{
+    LazyListSet, LockFreeListSet, NonblockingMapSet, NonblockingSet, OrderedListSet, SkipList,
+    SplitOrderedList,
+};
+
+fn smoke<S: NonblockingSet<usize>>(set: S) {
+    assert_eq!(set.insert(37), Ok(()));
+    assert!(!set.contains(&42));
+    assert!(set.contains(&37));
+    assert_eq!(set.insert(37), Err(37));
+
+    assert_eq!(set.insert(42), Ok(()));
+    assert_eq!(set.remove(&37), Ok(37));
+    assert!(set.contains(&42));
+    assert!(!set.contains(&37));
+    assert_eq!(set.remove(&37), Err(()));
+}
+
+fn stress_concurrent<S: NonblockingSet<usize> + Sync>(set: S) {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    scope(|s| {
+        for t in 0..THREADS {
+            let set = &set;
+            s.spawn(move |_| {
+                for i in (t..STEPS).step_by(THREADS) {
+                    assert_eq!(set.insert(i), Ok(()));
+                    assert!(set.contains(&i));
+                    if i % 2 == 0 {
+                        assert_eq!(set.remove(&i), Ok(i));
+                        assert!(!set.contains(&i));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    for i in 0..STEPS {
+        assert_eq!(set.contains(&i), i % 2 == 1);
+    }
+}
+
+#[test]
+fn ordered_list_set() {
+    smoke(OrderedListSet::new());
+    stress_concurrent(OrderedListSet::new());
+}
+
+#[test]
+fn lazy_list_set() {
+    smoke(LazyListSet::new());
+    stress_concurrent(LazyListSet::new());
+}
+
+#[test]
+fn lock_free_list_set() {
+    smoke(LockFreeListSet::new());
+    stress_concurrent(LockFreeListSet::new());
+}
+
+#[test]
+fn split_ordered_list() {
+    smoke(NonblockingMapSet::new(SplitOrderedList::<()>::new()));
+    stress_concurrent(NonblockingMapSet::new(SplitOrderedList::<()>::new()));
+}
+
+#[test]
+fn skiplist() {
+    smoke(NonblockingMapSet::<_, SkipList<usize, ()>>::default());
+    stress_concurrent(NonblockingMapSet::<_, SkipList<usize, ()>>::default());
+}
@@ -0,0 +1,99 @@
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{NonblockingConcurrentMap, NonblockingMap, SkipList};
+
+pub mod map;
+
+#[test]
+pub fn smoke() {
+    let list = SkipList::<usize, usize>::new();
+
+    let guard = epoch::pin();
+
+    assert_eq!(list.insert(&37, 37, &guard), Ok(()));
+    assert_eq!(list.lookup(&42, &guard), None);
+    assert_eq!(list.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(list.insert(&42, 42, &guard), Ok(()));
+    assert_eq!(list.lookup(&42, &guard), Some(&42));
+    assert_eq!(list.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(list.insert(&37, 0, &guard), Err(0));
+    assert_eq!(list.delete(&37, &guard), Ok(&37));
+    assert_eq!(list.lookup(&42, &guard), Some(&42));
+    assert_eq!(list.lookup(&37, &guard), None);
+
+    assert_eq!(list.delete(&37, &guard), Err(()));
+    assert_eq!(list.lookup(&42, &guard), Some(&42));
+    assert_eq!(list.lookup(&37, &guard), None);
+}
+
+#[test]
+fn for_each_ordered() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    let list = SkipList::<usize, usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move |_| {
+                let guard = epoch::pin();
+                for i in (t..STEPS).step_by(THREADS).rev() {
+                    assert_eq!(list.insert(&i, i * 2, &guard), Ok(()));
+                    if i % 3 == 0 {
+                        assert_eq!(list.delete(&i, &guard), Ok(&(i * 2)));
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    // In the order of the keys, unlike the hash maps.
+    let guard = epoch::pin();
+    let mut pairs = Vec::new();
+    assert_eq!(list.for_each(&guard, |&k, &v| pairs.push((k, v))), Ok(()));
+    let expected = (0..STEPS)
+        .filter(|i| i % 3 != 0)
+        .map(|i| (i, i * 2))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, expected);
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        usize,
+        NonblockingConcurrentMap<_, _, SkipList<usize, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipList<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 24;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipList<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipList<usize, usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
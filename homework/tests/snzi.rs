@@ -0,0 +1,69 @@
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::Snzi;
+
+const THREADS: usize = 4;
+const ITER: usize = 1024 * 16;
+
+#[test]
+fn smoke() {
+    for &leaves in &[1, 2, 3, 16] {
+        let snzi = Snzi::with_leaves(leaves);
+        assert!(!snzi.query());
+        let arrivals = (0..10).map(|_| snzi.arrive()).collect::<Vec<_>>();
+        assert!(snzi.query());
+        for arrival in arrivals {
+            assert!(snzi.query());
+            snzi.depart(arrival);
+        }
+        assert!(!snzi.query());
+    }
+}
+
+#[test]
+fn stress() {
+    // the indicator is nonzero while a thread has arrived.
+    let snzi = Snzi::with_leaves(THREADS);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                for _ in 0..ITER {
+                    let arrival = snzi.arrive();
+                    assert!(snzi.query());
+                    snzi.depart(arrival);
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(!snzi.query());
+}
+
+#[test]
+fn depart_on_other_thread() {
+    let snzi = Snzi::new();
+    let arrivals = scope(|s| {
+        let handles = (0..THREADS)
+            .map(|_| s.spawn(|_| (0..ITER).map(|_| snzi.arrive()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+    assert!(snzi.query());
+
+    scope(|s| {
+        for arrivals in arrivals {
+            let snzi = &snzi;
+            s.spawn(move |_| {
+                for arrival in arrivals {
+                    assert!(snzi.query());
+                    snzi.depart(arrival);
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(!snzi.query());
+}
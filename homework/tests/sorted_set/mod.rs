@@ -0,0 +1,324 @@
+use core::fmt;
+use core::hash::Hash;
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread;
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Acquire, Release},
+};
+
+use cs492_concur_homework::{
+    LazyListSet, LockFreeListSet, NonblockingSet, OrderedListSet, RawRwLock,
+};
+
+/// A set that iterates its keys in order, so that the list sets run the same tests.
+pub trait SortedSet<T>: Default + Sync + NonblockingSet<T> {
+    /// Returns the keys in the order of the set's iterator.
+    fn keys(&self) -> Vec<T>;
+
+    /// Returns the number of keys in the set.
+    fn len(&self) -> usize {
+        self.keys().len()
+    }
+
+    /// Returns `true` if the set has no keys.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Ord + Clone, L: RawRwLock> SortedSet<T> for OrderedListSet<T, L>
+where
+    Self: Default,
+{
+    fn keys(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        OrderedListSet::len(self)
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync> SortedSet<T> for LazyListSet<T> {
+    fn keys(&self) -> Vec<T> {
+        self.iter(&pin()).cloned().collect()
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync> SortedSet<T> for LockFreeListSet<T> {
+    fn keys(&self) -> Vec<T> {
+        self.iter(&pin()).cloned().collect()
+    }
+}
+
+fn generate_random_string(rng: &mut ThreadRng) -> String {
+    rng.sample_iter(&Alphanumeric).take(1).collect()
+}
+
+pub fn smoke<S: SortedSet<usize>>() {
+    let set = S::default();
+    set.insert(1).unwrap();
+    set.insert(2).unwrap();
+    set.insert(3).unwrap();
+    assert_eq!(set.remove(&2), Ok(2));
+    assert_eq!(set.keys(), [1, 3]);
+    assert_eq!(set.remove(&3), Ok(3));
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.remove(&1), Ok(1));
+    assert!(set.is_empty());
+}
+
+pub fn stress_sequential<S: SortedSet<String>>(steps: usize) {
+    #[derive(Debug)]
+    enum Ops {
+        ContainsSome,
+        ContainsNone,
+        Insert,
+        RemoveSome,
+        RemoveNone,
+        Iterate,
+    }
+
+    let ops = [
+        Ops::ContainsSome,
+        Ops::ContainsNone,
+        Ops::Insert,
+        Ops::RemoveSome,
+        Ops::RemoveNone,
+        Ops::Iterate,
+    ];
+    let mut rng = thread_rng();
+    let set = S::default();
+    let mut hashset = HashSet::<String>::new();
+
+    for i in 0..steps {
+        let op = ops.choose(&mut rng).unwrap();
+
+        match op {
+            Ops::ContainsSome => {
+                if let Some(key) = hashset.iter().choose(&mut rng) {
+                    println!("iteration {}: contains({:?}) (existing)", i, key);
+                    assert_eq!(set.contains(key), hashset.contains(key));
+                }
+            }
+            Ops::ContainsNone => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: contains({:?}) (non-existing)", i, key);
+                assert_eq!(set.contains(&key), hashset.contains(&key));
+            }
+            Ops::Insert => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: insert({:?})", i, key);
+                assert_eq!(set.insert(key.clone()).is_ok(), hashset.insert(key));
+            }
+            Ops::RemoveSome => {
+                let key = hashset.iter().choose(&mut rng).cloned();
+                if let Some(key) = key {
+                    println!("iteration {}: remove({:?}) (existing)", i, key);
+                    assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
+                }
+            }
+            Ops::RemoveNone => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: remove({:?}) (non-existing)", i, key);
+                assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
+            }
+            Ops::Iterate => {
+                let result = set.keys().into_iter().collect::<HashSet<_>>();
+                println!("iteration {}: iter() → {:?}", i, result);
+                assert_eq!(result, hashset);
+                assert_eq!(set.len(), hashset.len());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Ops {
+    Contains,
+    Insert,
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+enum Log<K> {
+    Contains { key: K, result: bool },
+    Insert { key: K, result: bool },
+    Remove { key: K, result: bool },
+}
+
+impl<K> Log<K> {
+    fn key(&self) -> &K {
+        match self {
+            Self::Contains { key, .. } => key,
+            Self::Insert { key, .. } => key,
+            Self::Remove { key, .. } => key,
+        }
+    }
+}
+
+pub fn stress_concurrent<S: SortedSet<String>>(threads: usize, steps: usize) {
+    let ops = [Ops::Contains, Ops::Insert, Ops::Remove, Ops::Remove];
+
+    let set = S::default();
+
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..steps {
+                    let op = ops.choose(&mut rng).unwrap();
+
+                    match op {
+                        Ops::Contains => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.contains(&value);
+                        }
+                        Ops::Insert => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.insert(value);
+                        }
+                        Ops::Remove => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.remove(&value);
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+fn assert_logs_consistent<K: fmt::Debug + Clone + Eq + Hash>(logs: &[Vec<Log<K>>]) {
+    let mut per_key_logs = HashMap::<K, Vec<Log<K>>>::new();
+    for ls in logs {
+        for l in ls {
+            per_key_logs
+                .entry(l.key().clone())
+                .or_default()
+                .push(l.clone());
+        }
+    }
+
+    for (k, logs) in &per_key_logs {
+        let mut inserts = HashMap::<K, usize>::new();
+        let mut deletes = HashMap::<K, usize>::new();
+
+        for l in logs {
+            match l {
+                Log::Insert { result: true, .. } => *inserts.entry(k.clone()).or_insert(0) += 1,
+                Log::Remove { result: true, .. } => *deletes.entry(k.clone()).or_insert(0) += 1,
+                _ => (),
+            }
+        }
+
+        for l in logs {
+            if let Log::Contains { key, result: true } = l {
+                assert!(inserts.contains_key(key));
+            }
+        }
+
+        for (k, v) in &deletes {
+            assert!(inserts.get(k).unwrap() >= v);
+        }
+    }
+}
+
+pub fn log_concurrent<S: SortedSet<String>>(threads: usize, steps: usize) {
+    let ops = [Ops::Contains, Ops::Insert, Ops::Remove];
+
+    let set = S::default();
+
+    let logs = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..threads {
+            let handle = s.spawn(|_| {
+                let mut rng = thread_rng();
+                let mut logs = Vec::new();
+                for _ in 0..steps {
+                    let op = ops.choose(&mut rng).unwrap();
+
+                    match op {
+                        Ops::Contains => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.contains(&key);
+                            logs.push(Log::Contains { key, result });
+                        }
+                        Ops::Insert => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.insert(key.clone());
+                            logs.push(Log::Insert {
+                                key,
+                                result: result.is_ok(),
+                            });
+                        }
+                        Ops::Remove => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.remove(&key);
+                            logs.push(Log::Remove {
+                                key,
+                                result: result.is_ok(),
+                            });
+                        }
+                    }
+                }
+                logs
+            });
+            handles.push(handle);
+        }
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+
+    assert_logs_consistent(&logs);
+    assert_eq!(set.len(), set.keys().len());
+}
+
+pub fn iter_consistent<S: SortedSet<usize>>(threads: usize, steps: usize) {
+    let set = S::default();
+
+    // pre-fill with even numbers
+    for i in (0..100).step_by(2).rev() {
+        let _ = set.insert(i);
+    }
+    let evens = set.keys().into_iter().collect::<HashSet<_>>();
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        // insert or remove odd numbers
+        for _ in 0..threads {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..steps {
+                    let key = 2 * rng.gen_range(0, 50) + 1;
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        // iterator consistency check
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let snapshot = set.keys();
+                // sorted
+                assert!(snapshot.windows(2).all(|k| k[0] <= k[1]));
+                // even numbers are not touched
+                let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
+                assert!(evens.is_subset(&snapshot));
+            }
+        });
+    })
+    .unwrap();
+}
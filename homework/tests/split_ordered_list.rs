@@ -1,5 +1,10 @@
 use crossbeam_epoch as epoch;
-use cs492_concur_homework::{NonblockingConcurrentMap, NonblockingMap, SplitOrderedList};
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::{
+    MutexHashMap, NonblockingConcurrentMap, NonblockingMap, PinnedMap, SplitOrderedList,
+};
+use lockfree::list::Find;
+use rand::prelude::*;
 
 pub mod map;
 
@@ -26,6 +31,168 @@ pub fn smoke() {
     assert_eq!(list.lookup(&37, &guard), None);
 }
 
+#[test]
+fn for_each() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+
+    for i in 0..100 {
+        assert_eq!(list.insert(&i, i * 2, &guard), Ok(()));
+    }
+    for i in (0..100).step_by(3) {
+        assert_eq!(list.delete(&i, &guard), Ok(&(i * 2)));
+    }
+
+    let mut pairs = Vec::new();
+    assert_eq!(list.for_each(&guard, |&k, &v| pairs.push((k, v))), Ok(()));
+    pairs.sort_unstable();
+    let expected = (0..100)
+        .filter(|i| i % 3 != 0)
+        .map(|i| (i, i * 2))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, expected);
+}
+
+#[test]
+fn find_strategies() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    for &find in &[Find::Harris, Find::HarrisMichael, Find::HarrisHerlihyShavit] {
+        let list = SplitOrderedList::<usize>::with_find(find);
+        scope(|s| {
+            for t in 0..THREADS {
+                let list = &list;
+                s.spawn(move |_| {
+                    let guard = epoch::pin();
+                    for i in (t..STEPS).step_by(THREADS) {
+                        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+                        assert_eq!(list.lookup(&i, &guard), Some(&i));
+                        if i % 2 == 0 {
+                            assert_eq!(list.delete(&i, &guard), Ok(&i));
+                            assert_eq!(list.lookup(&i, &guard), None);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let guard = epoch::pin();
+        for i in 0..STEPS {
+            let expected = if i % 2 == 0 { None } else { Some(&i) };
+            assert_eq!(list.lookup(&i, &guard), expected);
+        }
+    }
+}
+
+#[test]
+fn get_or_insert() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 256;
+
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move |_| {
+                let guard = epoch::pin();
+                for k in 0..KEYS {
+                    let v = *list.get_or_insert(&k, t, &guard);
+                    assert!(v < THREADS);
+                    assert_eq!(list.get_or_insert_with(&k, || unreachable!(), &guard), &v);
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    assert_eq!(list.get_or_insert_with(&KEYS, || 42, &guard), &42);
+}
+
+#[test]
+fn bulk() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 64;
+    const BATCH: usize = 32;
+    const KEYS: usize = 256;
+
+    // The same results as the default one-by-one operations, with duplicate keys.
+    let list = SplitOrderedList::<usize>::new();
+    let baseline = MutexHashMap::<usize, usize>::default();
+    let mut rng = thread_rng();
+    let guard = epoch::pin();
+    for _ in 0..STEPS {
+        let keys = (0..BATCH)
+            .map(|_| rng.gen_range(0, KEYS))
+            .collect::<Vec<_>>();
+        let values = (0..BATCH).map(|_| rng.gen()).collect::<Vec<usize>>();
+        assert_eq!(
+            list.insert_all(&keys, values.clone(), &guard),
+            baseline.insert_all(&keys, values, &guard)
+        );
+        let keys = (0..BATCH)
+            .map(|_| rng.gen_range(0, KEYS))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            list.lookup_all(&keys, &guard),
+            baseline.lookup_all(&keys, &guard)
+        );
+        assert_eq!(
+            list.delete_all(&keys, &guard),
+            baseline.delete_all(&keys, &guard)
+        );
+    }
+
+    // Concurrent batches of the keys of each thread.
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move |_| {
+                let guard = epoch::pin();
+                let keys = (t..KEYS).step_by(THREADS).collect::<Vec<_>>();
+                let results = list.insert_all(&keys, keys.clone(), &guard);
+                assert!(results.iter().all(Result::is_ok));
+                let values = list.lookup_all(&keys, &guard);
+                assert_eq!(values, keys.iter().map(Some).collect::<Vec<_>>());
+                let values = list.delete_all(&keys, &guard);
+                assert_eq!(values, keys.iter().map(Ok).collect::<Vec<_>>());
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn pinned_map() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    let map = PinnedMap::<usize, String, SplitOrderedList<String>>::default();
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move |_| {
+                for i in (t..STEPS).step_by(THREADS) {
+                    assert_eq!(map.insert(&i, i.to_string()), Ok(()));
+                    assert_eq!(map.insert(&i, String::new()), Err(String::new()));
+                    assert_eq!(map.get(&i), Some(i.to_string()));
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    for i in 0..STEPS {
+        assert!(map.contains_key(&i));
+        assert_eq!(map.remove(&i), Some(i.to_string()));
+        assert_eq!(map.remove(&i), None);
+        assert_eq!(map.get(&i), None);
+    }
+}
+
 #[test]
 fn stress_sequential() {
     const STEPS: usize = 4096;
@@ -52,3 +219,13 @@ fn log_concurrent() {
         THREADS, STEPS,
     );
 }
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>>(
+        THREADS, STEPS, KEYS,
+    );
+}
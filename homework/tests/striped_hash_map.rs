@@ -0,0 +1,102 @@
+use crossbeam_utils::thread::scope;
+use cs492_concur_homework::StripedHashMap;
+
+pub mod map;
+
+#[test]
+fn smoke() {
+    let map = StripedHashMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert("a".to_string(), 1), None);
+    assert_eq!(map.insert("b".to_string(), 2), None);
+    assert_eq!(map.insert("a".to_string(), 3), Some(1));
+    assert_eq!(map.get("a"), Some(3));
+    assert!(map.contains_key("b"));
+    assert!(!map.contains_key("c"));
+    assert_eq!(map.get_with("b", |v| v.copied()), Some(2));
+    assert_eq!(map.update("b", |v| *v += 10), Some(()));
+    assert_eq!(map.update("c", |v| *v += 10), None);
+    assert_eq!(map.get_or_insert_with("c".to_string(), || 4), 4);
+    assert_eq!(
+        map.get_or_insert_with("c".to_string(), || unreachable!()),
+        4
+    );
+    assert_eq!(map.len(), 3);
+
+    assert_eq!(map.remove_if("c", |&v| v == 5), None);
+    assert_eq!(map.remove_if("c", |&v| v == 4), Some(4));
+    assert_eq!(map.remove("a"), Some(3));
+    assert_eq!(map.remove("a"), None);
+    map.retain(|_, v| *v > 10);
+    assert_eq!(map.len(), 1);
+    let mut pairs = map.into_inner().into_iter().collect::<Vec<_>>();
+    pairs.sort();
+    assert_eq!(pairs, [("b".to_string(), 12)]);
+}
+
+#[test]
+fn shards() {
+    for &shards in &[1, 3, 64] {
+        let map = (0..1024).map(|i| (i, i)).collect::<StripedHashMap<_, _>>();
+        let other = StripedHashMap::with_shards(shards);
+        other.extend((0..1024).map(|i| (i, i)));
+        assert_eq!(map.len(), 1024);
+        assert_eq!(other.len(), 1024);
+        let mut sum = 0;
+        other.for_each(|_, v| sum += v);
+        assert_eq!(sum, 1023 * 1024 / 2);
+        other.clear();
+        assert!(other.is_empty());
+    }
+}
+
+#[test]
+fn get_or_insert_with_concurrent() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    // the value of each key is computed once.
+    let map = StripedHashMap::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            s.spawn(move |_| {
+                for k in 0..KEYS {
+                    let v = map.get_or_insert_with(k, || t);
+                    assert!(v < THREADS);
+                    assert_eq!(map.get(&k), Some(v));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(map.len(), KEYS);
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<usize, StripedHashMap<usize, usize>>(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::stress_concurrent::<usize, StripedHashMap<usize, usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::log_concurrent::<usize, StripedHashMap<usize, usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn linearizable_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+    const KEYS: usize = 8;
+    map::linearizable_concurrent::<usize, StripedHashMap<usize, usize>>(THREADS, STEPS, KEYS);
+}
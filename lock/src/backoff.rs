@@ -0,0 +1,91 @@
+use core::cell::Cell;
+use core::sync::atomic::spin_loop_hint;
+use std::thread;
+use std::time::Duration;
+
+/// The steps of spinning, each spinning twice as long as the previous one.
+const SPIN_LIMIT: u32 = 6;
+/// The steps of yielding, after spinning.
+const YIELD_LIMIT: u32 = 10;
+/// The steps of parking, after yielding, each parking twice as long as the previous one up to
+/// `2^(PARK_LIMIT - YIELD_LIMIT)` microseconds.
+const PARK_LIMIT: u32 = 20;
+
+/// Exponential backoff for the spin loops, escalating from spinning to yielding and parking.
+///
+/// Use `spin` in the retry loops after a failed CAS, where the other threads are making progress,
+/// and `snooze` in the loops waiting for another thread, e.g. to release a lock.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&self) {
+        self.step.set(0);
+    }
+
+    /// Spins for a while, twice as long as the previous call up to `2^SPIN_LIMIT` iterations.
+    pub fn spin(&self) {
+        let step = self.step.get().min(SPIN_LIMIT);
+        for _ in 0..1 << step {
+            spin_loop_hint();
+        }
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Spins, yields, or parks the thread for a while, escalating at each call.
+    ///
+    /// The thread is parked with a timeout, so it needs not be unparked, but it is woken up early
+    /// if it is.
+    pub fn snooze(&self) {
+        let step = self.step.get();
+        if step <= SPIN_LIMIT {
+            for _ in 0..1 << step {
+                spin_loop_hint();
+            }
+        } else if step <= YIELD_LIMIT {
+            thread::yield_now();
+        } else {
+            thread::park_timeout(Duration::from_micros(
+                1 << (step.min(PARK_LIMIT) - YIELD_LIMIT),
+            ));
+        }
+        if step <= PARK_LIMIT {
+            self.step.set(step + 1);
+        }
+    }
+
+    /// Returns `true` if `snooze` parks the thread, so the caller may rather block on a condition.
+    pub fn is_completed(&self) -> bool {
+        self.step.get() > YIELD_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backoff::Backoff;
+
+    #[test]
+    fn escalate() {
+        let backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.spin();
+        }
+        assert!(!backoff.is_completed());
+        while !backoff.is_completed() {
+            backoff.snooze();
+        }
+        for _ in 0..100 {
+            backoff.snooze();
+        }
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+}
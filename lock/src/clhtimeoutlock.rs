@@ -0,0 +1,200 @@
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::time::{Duration, Instant};
+
+use crossbeam_utils::{Backoff, CachePadded};
+
+use crate::lock::*;
+
+/// `Node::pred` of a released node.
+const AVAILABLE: *mut CachePadded<Node> = 1 as *mut _;
+
+struct Node {
+    /// Null while the owner holds or waits for the lock, `AVAILABLE` once it is released, and the
+    /// predecessor to wait for instead once the owner abandons the waiting.
+    pred: AtomicPtr<CachePadded<Node>>,
+}
+
+#[derive(Clone)]
+pub struct Token(*mut CachePadded<Node>);
+
+/// CLH lock whose waiting can be abandoned (Scott and Scherer's CLH-try).
+///
+/// A node is freed by its successor once the successor has read that it is released or abandoned,
+/// or by its owner if it is still the tail.
+pub struct ClhTimeoutLock {
+    tail: AtomicPtr<CachePadded<Node>>,
+}
+
+impl Node {
+    const fn new() -> Self {
+        Self {
+            pred: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl Default for ClhTimeoutLock {
+    fn default() -> Self {
+        Self {
+            tail: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl Drop for ClhTimeoutLock {
+    fn drop(&mut self) {
+        // The last node is not freed by its successor.
+        let tail = *self.tail.get_mut();
+        if !tail.is_null() {
+            drop(unsafe { Box::from_raw(tail) });
+        }
+    }
+}
+
+impl ClhTimeoutLock {
+    /// Waits for the lock until `deadline`, if any.
+    fn lock_until(&self, deadline: Option<Instant>) -> Result<Token, ()> {
+        let node = Box::into_raw(Box::new(CachePadded::new(Node::new())));
+        let mut pred = self.tail.swap(node, Ordering::AcqRel);
+        if pred.is_null() {
+            return Ok(Token(node));
+        }
+
+        let backoff = Backoff::new();
+        loop {
+            let pred_pred = unsafe { (*pred).pred.load(Ordering::Acquire) };
+            if pred_pred == AVAILABLE {
+                drop(unsafe { Box::from_raw(pred) });
+                return Ok(Token(node));
+            }
+            if !pred_pred.is_null() {
+                // The predecessor has abandoned, so waits for its predecessor instead.
+                drop(unsafe { Box::from_raw(pred) });
+                pred = pred_pred;
+                continue;
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                break;
+            }
+            backoff.snooze();
+        }
+
+        // Abandons the waiting. If there is no successor, the node is removed from the queue.
+        if self
+            .tail
+            .compare_exchange(node, pred, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            drop(unsafe { Box::from_raw(node) });
+        } else {
+            unsafe { (*node).pred.store(pred, Ordering::Release) };
+        }
+        Err(())
+    }
+}
+
+impl RawLock for ClhTimeoutLock {
+    type Token = Token;
+
+    fn lock(&self) -> Self::Token {
+        self.lock_until(None).unwrap()
+    }
+
+    unsafe fn unlock(&self, token: Self::Token) {
+        let node = token.0;
+        if self
+            .tail
+            .compare_exchange(node, ptr::null_mut(), Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            drop(Box::from_raw(node));
+        } else {
+            (*node).pred.store(AVAILABLE, Ordering::Release);
+        }
+    }
+}
+
+impl RawTryLock for ClhTimeoutLock {
+    fn try_lock(&self) -> Result<Self::Token, ()> {
+        self.try_lock_for(Duration::from_secs(0))
+    }
+}
+
+impl RawTimedLock for ClhTimeoutLock {
+    fn try_lock_for(&self, timeout: Duration) -> Result<Self::Token, ()> {
+        self.lock_until(Some(Instant::now() + timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use crossbeam_utils::thread::scope;
+
+    use crate::clhtimeoutlock::ClhTimeoutLock;
+    use crate::lock::Lock;
+
+    #[test]
+    fn smoke() {
+        crate::lock::tests::smoke::<ClhTimeoutLock>();
+    }
+
+    #[test]
+    fn timeout() {
+        let lock = Lock::<ClhTimeoutLock, usize>::new(0);
+        let guard = lock.lock();
+        scope(|s| {
+            s.spawn(|_| {
+                assert!(lock.try_lock().is_err());
+                assert!(lock.try_lock_for(Duration::from_millis(10)).is_err());
+            });
+        })
+        .unwrap();
+        drop(guard);
+        *lock.try_lock_for(Duration::from_millis(10)).unwrap() += 1;
+        *lock.try_lock().unwrap() += 1;
+        assert_eq!(*lock.lock(), 2);
+    }
+
+    #[test]
+    fn abandon() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024;
+
+        // the waiters time out while the others wait behind them
+        let lock = Lock::<ClhTimeoutLock, usize>::new(0);
+        let acquired = scope(|s| {
+            let handles = (0..THREADS)
+                .map(|i| {
+                    let lock = &lock;
+                    s.spawn(move |_| {
+                        let mut acquired = 0;
+                        for _ in 0..ITER {
+                            let guard = if i == 0 {
+                                Some(lock.lock())
+                            } else {
+                                lock.try_lock_for(Duration::from_micros(i as u64)).ok()
+                            };
+                            if let Some(mut guard) = guard {
+                                *guard += 1;
+                                acquired += 1;
+                                thread::yield_now();
+                            }
+                        }
+                        acquired
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .sum::<usize>()
+        })
+        .unwrap();
+        assert!(acquired >= ITER);
+        assert_eq!(*lock.lock(), acquired);
+    }
+}
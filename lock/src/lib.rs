@@ -1,16 +1,22 @@
 extern crate crossbeam_utils;
 
+mod backoff;
 mod clhlock;
+mod clhtimeoutlock;
 mod lock;
 mod mcslock;
 mod mcsparkinglock;
 pub mod seqlock;
 mod spinlock;
 mod ticketlock;
+mod ttaslock;
 
+pub use crate::backoff::Backoff;
 pub use crate::clhlock::ClhLock;
-pub use crate::lock::{Lock, LockGuard, RawLock, RawTryLock};
+pub use crate::clhtimeoutlock::ClhTimeoutLock;
+pub use crate::lock::{Lock, LockGuard, RawLock, RawTimedLock, RawTryLock};
 pub use crate::mcslock::McsLock;
 pub use crate::mcsparkinglock::McsParkingLock;
 pub use crate::spinlock::SpinLock;
 pub use crate::ticketlock::TicketLock;
+pub use crate::ttaslock::TtasLock;
@@ -2,6 +2,7 @@ use core::cell::UnsafeCell;
 use core::marker::PhantomData;
 use core::mem;
 use core::ops::{Deref, DerefMut};
+use std::time::Duration;
 
 pub trait RawLock: Default + Send + Sync {
     type Token: Clone;
@@ -18,6 +19,11 @@ pub trait RawTryLock: RawLock {
     fn try_lock(&self) -> Result<Self::Token, ()>;
 }
 
+pub trait RawTimedLock: RawTryLock {
+    /// Waits for the lock at most for `timeout`.
+    fn try_lock_for(&self, timeout: Duration) -> Result<Self::Token, ()>;
+}
+
 #[repr(C)]
 pub struct Lock<L: RawLock, T> {
     lock: L,
@@ -59,6 +65,16 @@ impl<L: RawTryLock, T> Lock<L, T> {
     }
 }
 
+impl<L: RawTimedLock, T> Lock<L, T> {
+    pub fn try_lock_for(&self, timeout: Duration) -> Result<LockGuard<L, T>, ()> {
+        self.lock.try_lock_for(timeout).map(|token| LockGuard {
+            lock: self,
+            token,
+            _marker: PhantomData,
+        })
+    }
+}
+
 impl<L: RawLock, T> Lock<L, T> {
     pub unsafe fn unlock_unchecked(&self, token: L::Token) {
         self.lock.unlock(token);
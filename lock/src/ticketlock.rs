@@ -1,9 +1,16 @@
-use core::sync::atomic::{AtomicUsize, Ordering};
-
-use crossbeam_utils::Backoff;
+use core::sync::atomic::{spin_loop_hint, AtomicUsize, Ordering};
+use std::thread;
 
 use crate::lock::*;
 
+/// Spins per waiter ahead, as each critical section takes about the same time.
+const SPINS_PER_WAITER: usize = 32;
+/// Yields instead if more waiters than this are ahead, e.g. if there are more threads than cores.
+const YIELD_LIMIT: usize = 8;
+/// Yields instead if the lock has not been handed over for this many waits, e.g. if the holder is
+/// preempted.
+const STALL_LIMIT: usize = 4;
+
 pub struct TicketLock {
     curr: AtomicUsize,
     next: AtomicUsize,
@@ -23,13 +30,31 @@ impl RawLock for TicketLock {
 
     fn lock(&self) -> usize {
         let ticket = self.next.fetch_add(1, Ordering::Relaxed);
-        let backoff = Backoff::new();
+        let mut last = ticket;
+        let mut stalls = 0;
 
-        while self.curr.load(Ordering::Acquire) != ticket {
-            backoff.snooze();
-        }
+        loop {
+            let curr = self.curr.load(Ordering::Acquire);
+            if curr == ticket {
+                return ticket;
+            }
+            if curr == last {
+                stalls += 1;
+            } else {
+                last = curr;
+                stalls = 0;
+            }
 
-        ticket
+            // Proportional backoff: waits longer if more waiters are ahead.
+            let ahead = ticket.wrapping_sub(curr);
+            if ahead > YIELD_LIMIT || stalls > STALL_LIMIT {
+                thread::yield_now();
+            } else {
+                for _ in 0..ahead * SPINS_PER_WAITER {
+                    spin_loop_hint();
+                }
+            }
+        }
     }
 
     unsafe fn unlock(&self, ticket: usize) {
@@ -37,12 +62,37 @@ impl RawLock for TicketLock {
     }
 }
 
+impl RawTryLock for TicketLock {
+    fn try_lock(&self) -> Result<usize, ()> {
+        let ticket = self.curr.load(Ordering::Relaxed);
+        self.next
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .map_err(|_| ())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::lock::Lock;
     use crate::ticketlock::TicketLock;
 
     #[test]
     fn smoke() {
         crate::lock::tests::smoke::<TicketLock>();
     }
+
+    #[test]
+    fn try_lock() {
+        let lock = Lock::<TicketLock, usize>::new(0);
+        let guard = lock.lock();
+        assert!(lock.try_lock().is_err());
+        drop(guard);
+        *lock.try_lock().unwrap() += 1;
+        assert_eq!(*lock.lock(), 1);
+    }
 }
@@ -0,0 +1,60 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::backoff::Backoff;
+use crate::lock::*;
+
+/// Test-and-test-and-set spinlock with exponential backoff. The waiters spin on a load, which
+/// doesn't invalidate the others' caches, and back off after losing the race for the lock.
+pub struct TtasLock {
+    inner: AtomicBool,
+}
+
+impl Default for TtasLock {
+    fn default() -> Self {
+        Self {
+            inner: AtomicBool::new(false),
+        }
+    }
+}
+
+impl RawLock for TtasLock {
+    type Token = ();
+
+    fn lock(&self) {
+        let backoff = Backoff::new();
+
+        loop {
+            while self.inner.load(Ordering::Relaxed) {
+                backoff.snooze();
+            }
+            if !self.inner.swap(true, Ordering::Acquire) {
+                return;
+            }
+            backoff.spin();
+        }
+    }
+
+    unsafe fn unlock(&self, _token: ()) {
+        self.inner.store(false, Ordering::Release);
+    }
+}
+
+impl RawTryLock for TtasLock {
+    fn try_lock(&self) -> Result<(), ()> {
+        if !self.inner.load(Ordering::Relaxed) && !self.inner.swap(true, Ordering::Acquire) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ttaslock::TtasLock;
+
+    #[test]
+    fn smoke() {
+        crate::lock::tests::smoke::<TtasLock>();
+    }
+}
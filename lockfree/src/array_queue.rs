@@ -0,0 +1,274 @@
+//! Bounded lock-free queue.
+//!
+//! Usable with any number of producers and consumers.
+//!
+//! Dmitry Vyukov. Bounded MPMC queue.
+//! http://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue
+
+use core::cell::UnsafeCell;
+use core::cmp;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_utils::CachePadded;
+
+/// Error of `ArrayQueue::push` when the queue is full. It gives back the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full<T>(pub T);
+
+/// A slot of the buffer.
+struct Slot<T> {
+    /// The sequence number of the slot. For the slot at `i`, it is `pos` if the slot is empty and
+    /// is to be pushed at the position `pos`, and `pos + 1` if it is full and is to be popped at
+    /// `pos`, where `pos & (capacity - 1) == i`.
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Bounded lock-free queue, following Vyukov's.
+///
+/// Each slot of the buffer has a sequence number, so that a `push` or a `pop` claims a position by
+/// a CAS on `tail` or `head` only when the slot at the position is ready, and then passes the slot
+/// to the other side by updating its sequence number.
+pub struct ArrayQueue<T> {
+    /// The position to pop at.
+    head: CachePadded<AtomicUsize>,
+    /// The position to push at.
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[Slot<T>]>,
+}
+
+// Any particular `T` should never be accessed concurrently, so no need for `Sync`.
+unsafe impl<T: Send> Sync for ArrayQueue<T> {}
+unsafe impl<T: Send> Send for ArrayQueue<T> {}
+
+impl<T> ArrayQueue<T> {
+    /// Creates a new, empty queue that holds at most `capacity` elements, rounded up to a power of
+    /// two.
+    ///
+    /// The positions are wrapping counters, and the slot of a position is given by its lower bits.
+    /// The capacity is a power of two, so that the slots stay in order when the counters wrap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        let buffer = (0..capacity.next_power_of_two())
+            .map(|i| Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+        Self {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            buffer,
+        }
+    }
+
+    /// Returns the maximum number of elements.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn slot(&self, pos: usize) -> &Slot<T> {
+        &self.buffer[pos & (self.capacity() - 1)]
+    }
+
+    /// Adds `t` to the back of the queue. Returns it back if the queue is full.
+    pub fn push(&self, t: T) -> Result<(), Full<T>> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(tail);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(tail) as isize;
+
+            match diff.cmp(&0) {
+                cmp::Ordering::Equal => {
+                    // The slot is empty. Claims it.
+                    match self.tail.compare_exchange_weak(
+                        tail,
+                        tail.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            unsafe { (*slot.value.get()).as_mut_ptr().write(t) };
+                            slot.seq.store(tail.wrapping_add(1), Ordering::Release);
+                            return Ok(());
+                        }
+                        Err(current) => tail = current,
+                    }
+                }
+                // The slot is not popped yet since the last round.
+                cmp::Ordering::Less => return Err(Full(t)),
+                // Pushed concurrently.
+                cmp::Ordering::Greater => tail = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(head);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(head.wrapping_add(1)) as isize;
+
+            match diff.cmp(&0) {
+                cmp::Ordering::Equal => {
+                    // The slot is full. Claims it.
+                    match self.head.compare_exchange_weak(
+                        head,
+                        head.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            let t = unsafe { (*slot.value.get()).as_ptr().read() };
+                            // Ready for the push at the same slot in the next round.
+                            slot.seq
+                                .store(head.wrapping_add(self.capacity()), Ordering::Release);
+                            return Some(t);
+                        }
+                        Err(current) => head = current,
+                    }
+                }
+                // The slot is not pushed yet.
+                cmp::Ordering::Less => return None,
+                // Popped concurrently.
+                cmp::Ordering::Greater => head = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is observed to be empty.
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = self.tail.load(Ordering::SeqCst);
+        head == tail
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for ArrayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrayQueue")
+            .field("head", &self.head.load(Ordering::Relaxed))
+            .field("tail", &self.tail.load(Ordering::Relaxed))
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread;
+    use std::thread::yield_now;
+
+    const CONC_COUNT: i64 = 100000;
+
+    #[test]
+    fn push_pop() {
+        let q = ArrayQueue::with_capacity(2);
+        assert!(q.is_empty());
+        assert_eq!(q.push(37), Ok(()));
+        assert_eq!(q.push(48), Ok(()));
+        assert_eq!(q.push(59), Err(Full(59)));
+        assert_eq!(q.pop(), Some(37));
+        assert_eq!(q.push(59), Ok(()));
+        assert_eq!(q.pop(), Some(48));
+        assert_eq!(q.pop(), Some(59));
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn drop_remaining() {
+        let q = ArrayQueue::with_capacity(4);
+        for i in 0..3 {
+            q.push(i.to_string()).unwrap();
+        }
+        assert_eq!(q.pop(), Some("0".to_string()));
+    }
+
+    #[test]
+    fn push_pop_many_spsc() {
+        let q: ArrayQueue<i64> = ArrayQueue::with_capacity(3);
+        assert_eq!(q.capacity(), 4);
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                let mut next = 0;
+                while next < CONC_COUNT {
+                    match q.pop() {
+                        Some(elem) => {
+                            assert_eq!(elem, next);
+                            next += 1;
+                        }
+                        None => yield_now(),
+                    }
+                }
+            });
+
+            for i in 0..CONC_COUNT {
+                while q.push(i).is_err() {
+                    yield_now();
+                }
+            }
+        })
+        .unwrap();
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn push_pop_many_mpmc() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 10000;
+
+        let q: ArrayQueue<usize> = ArrayQueue::with_capacity(16);
+        let popped = (0..THREADS)
+            .map(|_| AtomicUsize::new(0))
+            .collect::<Vec<_>>();
+
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|_| {
+                    for i in 0..COUNT {
+                        while q.push(i % THREADS).is_err() {
+                            yield_now();
+                        }
+                    }
+                });
+                scope.spawn(|_| {
+                    for _ in 0..COUNT {
+                        let t = loop {
+                            if let Some(t) = q.pop() {
+                                break t;
+                            }
+                            yield_now();
+                        };
+                        popped[t].fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        for p in popped {
+            assert_eq!(p.into_inner(), COUNT);
+        }
+        assert!(q.is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+//! Lock-free doubly linked list.
+//!
+//! The next links form the list, as in the singly linked list, and the prev links are hints that
+//! are corrected lazily (`DList::correct_prev`) after an insertion or a deletion. A node is deleted
+//! when its next link is marked, and then its prev link is marked so that it is never corrected
+//! again.
+//!
+//! Reference: Håkan Sundell and Philippas Tsigas. Lock-free deques and doubly linked lists. JPDC
+//! 2008.
+//!
+//! # Reclamation
+//!
+//! A deleted node may still be reached through the prev links of the other nodes after it is
+//! unlinked, e.g. by `Cursor::move_prev`, and the epoch doesn't tell when it is not anymore. The
+//! paper uses reference counting for that. Here, the deleted nodes are kept in a list of their own
+//! and freed when the list is dropped, so the values returned by `Cursor::delete` live as long as
+//! the list.
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+use std::sync::atomic::Ordering;
+
+/// Doubly linked list node.
+#[derive(Debug)]
+pub struct Node<T> {
+    /// `None` in the sentinel nodes.
+    value: Option<T>,
+    /// Mark: tag(), set after `next` is marked
+    prev: Atomic<Node<T>>,
+    /// Mark: tag(), the node is deleted
+    next: Atomic<Node<T>>,
+    /// The next node in the list of deleted nodes.
+    retired: Atomic<Node<T>>,
+}
+
+/// Doubly linked list, whose entries are ordered by their positions.
+#[derive(Debug)]
+pub struct DList<T> {
+    /// The sentinel node before the first entry.
+    head: Atomic<Node<T>>,
+    /// The sentinel node after the last entry.
+    tail: Atomic<Node<T>>,
+    /// The deleted nodes, which are freed when the list is dropped.
+    retired: Atomic<Node<T>>,
+}
+
+/// Doubly linked list cursor. It is at an entry, or at one of the ends of the list: before the
+/// first entry or after the last one.
+#[derive(Debug)]
+pub struct Cursor<'g, T> {
+    list: &'g DList<T>,
+    node: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Clone for Cursor<'g, T> {
+    fn clone(&self) -> Self {
+        Self {
+            list: self.list,
+            node: self.node,
+            guard: self.guard,
+        }
+    }
+}
+
+impl<T> Node<T> {
+    fn new(value: Option<T>) -> Self {
+        Self {
+            value,
+            prev: Atomic::null(),
+            next: Atomic::null(),
+            retired: Atomic::null(),
+        }
+    }
+}
+
+impl<T> Default for DList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for DList<T> {
+    fn drop(&mut self) {
+        unsafe {
+            // The nodes not deleted are in the list, and the deleted ones in `retired`.
+            let mut curr = self.head.load(Ordering::Relaxed, unprotected());
+            while !curr.is_null() {
+                let next = curr.deref().next.load(Ordering::Relaxed, unprotected());
+                if next.tag() == 0 {
+                    drop(curr.into_owned());
+                }
+                curr = next.with_tag(0);
+            }
+
+            let mut curr = self.retired.load(Ordering::Relaxed, unprotected());
+            while !curr.is_null() {
+                let next = curr.deref().retired.load(Ordering::Relaxed, unprotected());
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+impl<T> DList<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        unsafe {
+            let head = Owned::new(Node::new(None)).into_shared(unprotected());
+            let tail = Owned::new(Node::new(None)).into_shared(unprotected());
+            head.deref().next.store(tail, Ordering::Relaxed);
+            tail.deref().prev.store(head, Ordering::Relaxed);
+            Self {
+                head: Atomic::from(head),
+                tail: Atomic::from(tail),
+                retired: Atomic::null(),
+            }
+        }
+    }
+
+    /// Creates a cursor before the first entry.
+    #[inline]
+    pub fn head<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, T> {
+        Cursor {
+            list: self,
+            node: self.head.load(Ordering::Relaxed, guard),
+            guard,
+        }
+    }
+
+    /// Creates a cursor after the last entry.
+    #[inline]
+    pub fn tail<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, T> {
+        Cursor {
+            list: self,
+            node: self.tail.load(Ordering::Relaxed, guard),
+            guard,
+        }
+    }
+
+    /// Inserts a value before the first entry.
+    pub fn push_front(&self, value: T, guard: &Guard) {
+        self.head(guard).insert_after(value);
+    }
+
+    /// Inserts a value after the last entry.
+    pub fn push_back(&self, value: T, guard: &Guard) {
+        self.tail(guard).insert_before(value);
+    }
+
+    /// Deletes the first entry and returns its value. Returns `None` if the list is empty.
+    pub fn pop_front<'g>(&'g self, guard: &'g Guard) -> Option<&'g T> {
+        loop {
+            let mut cursor = self.head(guard);
+            if !cursor.move_next() {
+                return None;
+            }
+            if let Ok(value) = cursor.delete() {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Deletes the last entry and returns its value. Returns `None` if the list is empty.
+    pub fn pop_back<'g>(&'g self, guard: &'g Guard) -> Option<&'g T> {
+        loop {
+            let mut cursor = self.tail(guard);
+            if !cursor.move_prev() {
+                return None;
+            }
+            if let Ok(value) = cursor.delete() {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Makes the prev link of `node` point to the node before it, starting from `prev`, which is
+    /// before `node`. The deleted nodes on the way are unlinked. Returns the node before `node`,
+    /// or one before it if `node` is deleted concurrently.
+    fn correct_prev<'g>(
+        &self,
+        mut prev: Shared<'g, Node<T>>,
+        node: Shared<'g, Node<T>>,
+        guard: &'g Guard,
+    ) -> Shared<'g, Node<T>> {
+        let node_ref = unsafe { node.deref() };
+        // The node before `prev`, if `prev` is reached by its next link.
+        let mut last: Option<Shared<'g, Node<T>>> = None;
+        loop {
+            let link = node_ref.prev.load(Ordering::Acquire, guard);
+            if link.tag() == 1 {
+                break;
+            }
+
+            let prev_ref = unsafe { prev.deref() };
+            let prev_next = prev_ref.next.load(Ordering::Acquire, guard);
+            if prev_next.tag() == 1 {
+                // `prev` is deleted: unlinks it if possible, or goes backward.
+                match last.take() {
+                    Some(last) => {
+                        let _ = prev_ref.prev.fetch_or(1, Ordering::AcqRel, guard);
+                        let _ = unsafe { last.deref() }.next.compare_and_set(
+                            prev,
+                            prev_next.with_tag(0),
+                            Ordering::Release,
+                            guard,
+                        );
+                        prev = last;
+                    }
+                    None => prev = prev_ref.prev.load(Ordering::Acquire, guard).with_tag(0),
+                }
+                continue;
+            }
+
+            if prev_next != node {
+                last = Some(prev);
+                prev = prev_next;
+                continue;
+            }
+
+            if node_ref
+                .prev
+                .compare_and_set(link, prev, Ordering::Release, guard)
+                .is_ok()
+            {
+                // `prev` may have been deleted just before.
+                if unsafe { prev.deref() }
+                    .prev
+                    .load(Ordering::Acquire, guard)
+                    .tag()
+                    == 1
+                {
+                    continue;
+                }
+                break;
+            }
+        }
+        prev
+    }
+
+    /// Adds a deleted node to the nodes freed when the list is dropped.
+    fn retire<'g>(&self, node: Shared<'g, Node<T>>, guard: &'g Guard) {
+        let node_ref = unsafe { node.deref() };
+        let mut head = self.retired.load(Ordering::Relaxed, guard);
+        loop {
+            node_ref.retired.store(head, Ordering::Relaxed);
+            match self
+                .retired
+                .compare_and_set(head, node, Ordering::Release, guard)
+            {
+                Ok(_) => return,
+                Err(e) => head = e.current,
+            }
+        }
+    }
+}
+
+impl<'g, T> Cursor<'g, T> {
+    fn is_head(&self) -> bool {
+        self.node == self.list.head.load(Ordering::Relaxed, self.guard)
+    }
+
+    fn is_tail(&self) -> bool {
+        self.node == self.list.tail.load(Ordering::Relaxed, self.guard)
+    }
+
+    fn node(&self) -> &'g Node<T> {
+        unsafe { self.node.deref() }
+    }
+
+    /// Returns the value of the current entry, or `None` at the ends of the list. The value is
+    /// returned even if the entry is deleted.
+    #[inline]
+    pub fn lookup(&self) -> Option<&'g T> {
+        self.node().value.as_ref()
+    }
+
+    /// Returns `true` if the current entry is deleted.
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        self.node().next.load(Ordering::Acquire, self.guard).tag() == 1
+    }
+
+    /// Moves the cursor to the next entry that is not deleted, unlinking the deleted nodes on the
+    /// way. Returns `false` if it moves past the last entry, or is already there.
+    pub fn move_next(&mut self) -> bool {
+        loop {
+            if self.is_tail() {
+                return false;
+            }
+
+            let node = self.node();
+            let link = node.next.load(Ordering::Acquire, self.guard);
+            let next = link.with_tag(0);
+            let next_ref = unsafe { next.deref() };
+            let deleted = next_ref.next.load(Ordering::Acquire, self.guard).tag() == 1;
+
+            // Unlinks `next`, unless the current node is deleted as well.
+            if deleted && link.tag() == 0 {
+                let _ = next_ref.prev.fetch_or(1, Ordering::AcqRel, self.guard);
+                let next_next = next_ref
+                    .next
+                    .load(Ordering::Acquire, self.guard)
+                    .with_tag(0);
+                let _ = node
+                    .next
+                    .compare_and_set(next, next_next, Ordering::Release, self.guard);
+                continue;
+            }
+
+            self.node = next;
+            if !deleted {
+                return !self.is_tail();
+            }
+        }
+    }
+
+    /// Moves the cursor to the previous entry that is not deleted. Returns `false` if it moves
+    /// before the first entry, or is already there. If the current entry is deleted, it first
+    /// moves to the next entry that is not deleted.
+    pub fn move_prev(&mut self) -> bool {
+        loop {
+            if self.is_head() {
+                return false;
+            }
+
+            let node = self.node();
+            let prev = node.prev.load(Ordering::Acquire, self.guard).with_tag(0);
+            let prev_ref = unsafe { prev.deref() };
+            let deleted = self.is_deleted();
+
+            if prev_ref.next.load(Ordering::Acquire, self.guard) == self.node && !deleted {
+                self.node = prev;
+                return !self.is_head();
+            } else if deleted {
+                let _ = self.move_next();
+            } else {
+                let _ = self.list.correct_prev(prev, self.node, self.guard);
+            }
+        }
+    }
+
+    /// Inserts a value before the current entry, and moves the cursor to it. If the current entry
+    /// is deleted, it is inserted before the next entry that is not deleted. At the beginning of
+    /// the list, it is inserted before the first entry.
+    pub fn insert_before(&mut self, value: T) {
+        self.insert_node_before(Owned::new(Node::new(Some(value))));
+    }
+
+    /// Inserts a value after the current entry, and moves the cursor to it. If the current entry
+    /// is deleted, it is inserted as `insert_before` does. At the end of the list, it is inserted
+    /// after the last entry.
+    pub fn insert_after(&mut self, value: T) {
+        self.insert_node_after(Owned::new(Node::new(Some(value))));
+    }
+
+    fn insert_node_before(&mut self, mut node: Owned<Node<T>>) {
+        if self.is_head() {
+            return self.insert_node_after(node);
+        }
+
+        let mut next = self.clone();
+        let mut prev = next
+            .node()
+            .prev
+            .load(Ordering::Acquire, self.guard)
+            .with_tag(0);
+        let new = loop {
+            while next.is_deleted() {
+                let _ = next.move_next();
+                prev = self.list.correct_prev(prev, next.node, self.guard);
+            }
+
+            node.prev.store(prev, Ordering::Relaxed);
+            node.next.store(next.node, Ordering::Relaxed);
+            match unsafe { prev.deref() }.next.compare_and_set(
+                next.node,
+                node,
+                Ordering::Release,
+                self.guard,
+            ) {
+                Ok(new) => break new,
+                Err(e) => {
+                    node = e.new;
+                    prev = self.list.correct_prev(prev, next.node, self.guard);
+                }
+            }
+        };
+
+        self.node = new;
+        let _ = self.list.correct_prev(prev, next.node, self.guard);
+    }
+
+    fn insert_node_after(&mut self, mut node: Owned<Node<T>>) {
+        if self.is_tail() {
+            return self.insert_node_before(node);
+        }
+
+        let prev = self.node;
+        let mut next = self.node().next.load(Ordering::Acquire, self.guard);
+        let new = loop {
+            if next.tag() == 1 {
+                return self.insert_node_before(node);
+            }
+
+            node.prev.store(prev, Ordering::Relaxed);
+            node.next.store(next, Ordering::Relaxed);
+            match self
+                .node()
+                .next
+                .compare_and_set(next, node, Ordering::Release, self.guard)
+            {
+                Ok(new) => break new,
+                Err(e) => {
+                    node = e.new;
+                    next = e.current;
+                }
+            }
+        };
+
+        self.node = new;
+        let _ = self.list.correct_prev(prev, next, self.guard);
+    }
+
+    /// Deletes the current entry and returns its value, keeping the cursor at it. Fails if it is
+    /// already deleted, or if the cursor is at one of the ends of the list.
+    pub fn delete(&self) -> Result<&'g T, ()> {
+        if self.is_head() || self.is_tail() {
+            return Err(());
+        }
+
+        let node = self.node();
+        let next = node.next.fetch_or(1, Ordering::AcqRel, self.guard);
+        if next.tag() == 1 {
+            return Err(());
+        }
+        let _ = node.prev.fetch_or(1, Ordering::AcqRel, self.guard);
+
+        let prev = node.prev.load(Ordering::Acquire, self.guard).with_tag(0);
+        let _ = self.list.correct_prev(prev, next, self.guard);
+        self.list.retire(self.node, self.guard);
+        Ok(node.value.as_ref().unwrap())
+    }
+}
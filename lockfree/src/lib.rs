@@ -8,10 +8,17 @@ extern crate crossbeam_utils;
 
 #[macro_use]
 mod utils;
+pub mod array_queue;
+pub mod dlist;
 pub mod list;
-mod queue;
+pub mod queue;
+pub mod spsc;
 mod stack;
+pub mod versioned;
 
+pub use array_queue::{ArrayQueue, Full};
+pub use dlist::DList;
 pub use list::List;
 pub use queue::Queue;
 pub use stack::Stack;
+pub use versioned::VersionedList;
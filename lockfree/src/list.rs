@@ -3,7 +3,7 @@
 use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared};
 
 use std::cmp::Ordering::{Equal, Greater, Less};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicIsize, Ordering};
 
 /// Linked list node.
 #[derive(Debug)]
@@ -18,6 +18,9 @@ pub struct Node<K, V> {
 #[derive(Debug)]
 pub struct List<K, V> {
     head: Atomic<Node<K, V>>,
+    /// The number of insertions minus the number of deletions. It may be transiently negative, as
+    /// a node is counted after it is linked.
+    len: AtomicIsize,
 }
 
 impl<K, V> Default for List<K, V>
@@ -50,6 +53,36 @@ pub struct Cursor<'g, K, V> {
     curr: Shared<'g, Node<K, V>>,
 }
 
+/// Strategy to find a key, which decides how the logically removed nodes are unlinked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Find {
+    /// Unlinks a chain of removed nodes at once (`Cursor::find_harris`).
+    Harris,
+    /// Unlinks the removed nodes one by one (`Cursor::find_harris_michael`).
+    HarrisMichael,
+    /// Doesn't unlink, so it never fails (`Cursor::find_harris_herlihy_shavit`). It is meant for
+    /// lookups: insertions and deletions use `HarrisMichael` instead.
+    HarrisHerlihyShavit,
+}
+
+impl Find {
+    /// Returns the strategy to use for insertions and deletions.
+    #[inline]
+    pub fn for_update(self) -> Self {
+        match self {
+            Find::HarrisHerlihyShavit => Find::HarrisMichael,
+            find => find,
+        }
+    }
+}
+
+/// Iterator over the entries of a list that are not logically removed, in order.
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    curr: Shared<'g, Node<K, V>>,
+    guard: &'g Guard,
+}
+
 impl<'g, K, V> Clone for Cursor<'g, K, V> {
     fn clone(&self) -> Self {
         Self {
@@ -69,6 +102,11 @@ impl<K, V> Node<K, V> {
         }
     }
 
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
     /// Extracts the inner value.
     pub fn into_value(self) -> V {
         self.value
@@ -96,6 +134,43 @@ where
         self.curr
     }
 
+    /// Returns the key of the current node, or `None` at the end of the list.
+    #[inline]
+    pub fn key(&self) -> Option<&'g K> {
+        unsafe { self.curr.as_ref().map(|n| &n.key) }
+    }
+
+    /// Returns `true` if the cursor is at the end of the list.
+    #[inline]
+    pub fn is_at_end(&self) -> bool {
+        self.curr.is_null()
+    }
+
+    /// Returns the entry after the current node that is not logically removed, without moving the
+    /// cursor. Returns `None` if there is none, or at the end of the list.
+    #[inline]
+    pub fn peek_next(&self, guard: &'g Guard) -> Option<(&'g K, &'g V)> {
+        let curr_node = unsafe { self.curr.as_ref() }?;
+        let mut iter = Iter {
+            curr: curr_node.next.load(Ordering::Acquire, guard).with_tag(0),
+            guard,
+        };
+        iter.next()
+    }
+
+    /// Reloads the current node from the previous one, so that the cursor can be moved on to find
+    /// a larger key after the list is modified. Returns `false` if the previous node is removed, in
+    /// which case the cursor should be created again.
+    #[inline]
+    pub fn reload(&mut self, guard: &'g Guard) -> bool {
+        let curr = self.prev.load(Ordering::Acquire, guard);
+        if curr.tag() != 0 {
+            return false;
+        }
+        self.curr = curr;
+        true
+    }
+
     /// Clean up a chain of logically removed nodes in each traversal.
     #[inline]
     pub fn find_harris(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
@@ -184,7 +259,7 @@ where
         }
     }
 
-    /// Gotta go fast. Doesn't fail.
+    /// Gotta go fast. Doesn't fail, and doesn't CAS.
     #[inline]
     pub fn find_harris_herlihy_shavit(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
         Ok(loop {
@@ -202,6 +277,16 @@ where
         })
     }
 
+    /// Finds a key with the given strategy.
+    #[inline]
+    pub fn find(&mut self, key: &K, find: Find, guard: &'g Guard) -> Result<bool, ()> {
+        match find {
+            Find::Harris => self.find_harris(key, guard),
+            Find::HarrisMichael => self.find_harris_michael(key, guard),
+            Find::HarrisHerlihyShavit => self.find_harris_herlihy_shavit(key, guard),
+        }
+    }
+
     /// Lookups the value.
     #[inline]
     pub fn lookup(&self) -> Option<&'g V> {
@@ -228,6 +313,61 @@ where
         }
     }
 
+    /// Inserts a node before the current node like `insert`, but keeps the cursor at the current
+    /// node, e.g. to insert several nodes in order without finding each position. The caller must
+    /// keep the list sorted.
+    #[inline]
+    pub fn insert_before(
+        &mut self,
+        node: Owned<Node<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<(), Owned<Node<K, V>>> {
+        node.next.store(self.curr, Ordering::Relaxed);
+        match self
+            .prev
+            .compare_and_set(self.curr, node, Ordering::Release, guard)
+        {
+            Ok(node) => {
+                self.prev = unsafe { &node.deref().next };
+                Ok(())
+            }
+            Err(e) => Err(e.new),
+        }
+    }
+
+    /// Inserts a node after the current node, keeping the cursor at the current node. Fails if the
+    /// cursor is at the end of the list, if the current node is removed, or if another node is
+    /// inserted after it concurrently. The caller must keep the list sorted.
+    #[inline]
+    pub fn insert_after(
+        &mut self,
+        node: Owned<Node<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<(), Owned<Node<K, V>>> {
+        let curr_node = some_or!(unsafe { self.curr.as_ref() }, return Err(node));
+        let next = curr_node.next.load(Ordering::Acquire, guard);
+        if next.tag() != 0 {
+            return Err(node);
+        }
+
+        node.next.store(next, Ordering::Relaxed);
+        curr_node
+            .next
+            .compare_and_set(next, node, Ordering::Release, guard)
+            .map(|_| ())
+            .map_err(|e| e.new)
+    }
+
+    /// Moves the cursor to the next node, which may be logically removed. Returns `false` if the
+    /// cursor is at the end of the list.
+    #[inline]
+    pub fn move_next(&mut self, guard: &'g Guard) -> bool {
+        let curr_node = some_or!(unsafe { self.curr.as_ref() }, return false);
+        self.prev = &curr_node.next;
+        self.curr = curr_node.next.load(Ordering::Acquire, guard).with_tag(0);
+        true
+    }
+
     /// Deletes the current node.
     #[inline]
     pub fn delete(self, guard: &'g Guard) -> Result<&'g V, ()> {
@@ -248,6 +388,17 @@ where
 
         Ok(&curr_node.value)
     }
+
+    /// Deletes the current node like `delete`, and returns a clone of its value that is detached
+    /// from the list, e.g. to keep it after the guard is dropped. The value itself can't be moved
+    /// out, as the other threads may read it until the node is freed.
+    #[inline]
+    pub fn detach(self, guard: &'g Guard) -> Result<V, ()>
+    where
+        V: Clone,
+    {
+        self.delete(guard).map(V::clone)
+    }
 }
 
 impl<K, V> List<K, V>
@@ -258,6 +409,7 @@ where
     pub fn new() -> Self {
         List {
             head: Atomic::null(),
+            len: AtomicIsize::new(0),
         }
     }
 
@@ -270,6 +422,48 @@ where
         }
     }
 
+    /// Returns the approximate number of entries. It counts the insertions and deletions by the
+    /// methods of `List`, including `insert_at` and `delete_at`, but not those by `Cursor` alone.
+    #[inline]
+    pub fn len_hint(&self) -> usize {
+        self.len.load(Ordering::Relaxed).max(0) as usize
+    }
+
+    /// Inserts a node at the cursor with `Cursor::insert`, and counts it in `len_hint`.
+    #[inline]
+    pub fn insert_at<'g>(
+        &'g self,
+        cursor: &mut Cursor<'g, K, V>,
+        node: Owned<Node<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<(), Owned<Node<K, V>>> {
+        cursor.insert(node, guard)?;
+        self.len.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Deletes the node at the cursor with `Cursor::delete`, and counts it in `len_hint`.
+    #[inline]
+    pub fn delete_at<'g>(
+        &'g self,
+        cursor: Cursor<'g, K, V>,
+        guard: &'g Guard,
+    ) -> Result<&'g V, ()> {
+        let value = cursor.delete(guard)?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    /// Creates an iterator over the entries. The entries inserted or removed concurrently may or
+    /// may not be visited.
+    #[inline]
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            curr: self.head.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+
     /// Finds a key using the given find strategy.
     #[inline]
     fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g Guard) -> (bool, Cursor<'g, K, V>)
@@ -310,7 +504,7 @@ where
                 return false;
             }
 
-            match cursor.insert(node, guard) {
+            match self.insert_at(&mut cursor, node, guard) {
                 Err(n) => node = n,
                 Ok(()) => return true,
             }
@@ -328,13 +522,48 @@ where
                 return None;
             }
 
-            match cursor.delete(guard) {
+            match self.delete_at(cursor, guard) {
                 Err(()) => continue,
                 Ok(value) => return Some(value),
             }
         }
     }
 
+    /// Returns the value of the key. It never helps to unlink the removed nodes, so it doesn't CAS
+    /// and traverses the list only once: unlike the other lookups, it is wait-free, which is
+    /// preferable for read-mostly lists.
+    #[inline]
+    pub fn get<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let mut cursor = self.head(guard);
+        match cursor.find_harris_herlihy_shavit(key, guard) {
+            Ok(true) => cursor.lookup(),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the list contains the key. It is wait-free, as `get`.
+    #[inline]
+    pub fn contains(&self, key: &K, guard: &Guard) -> bool {
+        self.get(key, guard).is_some()
+    }
+
+    /// Lookups a key with the given find strategy.
+    pub fn lookup_with<'g>(&'g self, key: &K, find: Find, guard: &'g Guard) -> Option<&'g V> {
+        self.lookup(key, |c, k, g| c.find(k, find, g), guard)
+    }
+
+    /// Inserts a key-value pair with the given find strategy, or `find.for_update()`.
+    pub fn insert_with(&self, key: K, value: V, find: Find, guard: &Guard) -> bool {
+        let find = find.for_update();
+        self.insert(key, value, |c, k, g| c.find(k, find, g), guard)
+    }
+
+    /// Deletes a key with the given find strategy, or `find.for_update()`.
+    pub fn delete_with<'g>(&'g self, key: &K, find: Find, guard: &'g Guard) -> Option<&'g V> {
+        let find = find.for_update();
+        self.delete(key, |c, k, g| c.find(k, find, g), guard)
+    }
+
     /// Omitted
     pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
         self.lookup(key, Cursor::find_harris, guard)
@@ -380,3 +609,18 @@ where
         self.delete(key, Cursor::find_harris_michael, guard)
     }
 }
+
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref() }?;
+            let next = node.next.load(Ordering::Acquire, self.guard);
+            self.curr = next.with_tag(0);
+            if next.tag() == 0 {
+                return Some((&node.key, &node.value));
+            }
+        }
+    }
+}
@@ -107,6 +107,14 @@ impl<T> Queue<T> {
         }
     }
 
+    /// Returns `true` if the queue is observed to be empty. It is only a hint, since the elements
+    /// may be pushed or popped concurrently.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        let head = self.head.load(Ordering::Acquire, guard);
+        let h = unsafe { head.deref() };
+        h.next.load(Ordering::Acquire, guard).is_null()
+    }
+
     /// Attempts to dequeue from the front.
     ///
     /// Returns `None` if the queue is observed to be empty.
@@ -178,9 +186,7 @@ mod test {
 
         pub fn is_empty(&self) -> bool {
             let guard = &pin();
-            let head = self.queue.head.load(Ordering::Acquire, guard);
-            let h = unsafe { head.deref() };
-            h.next.load(Ordering::Acquire, guard).is_null()
+            self.queue.is_empty(guard)
         }
 
         pub fn try_pop(&self) -> Option<T> {
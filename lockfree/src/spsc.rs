@@ -0,0 +1,296 @@
+//! Bounded single-producer single-consumer queue.
+//!
+//! The producer and the consumer each own one of the indices of a ring buffer, and cache the other
+//! one, so that they read the other's cache line only when the buffer looks full or empty.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crossbeam_utils::CachePadded;
+
+use crate::array_queue::Full;
+
+/// The ring buffer shared by the producer and the consumer.
+struct Buffer<T> {
+    /// The position to pop at, written by the consumer.
+    head: CachePadded<AtomicUsize>,
+    /// The position to push at, written by the producer.
+    tail: CachePadded<AtomicUsize>,
+    /// The slots in `head..tail` are full, modulo the capacity.
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+// Each slot is accessed by either of the producer and the consumer at a time.
+unsafe impl<T: Send> Sync for Buffer<T> {}
+unsafe impl<T: Send> Send for Buffer<T> {}
+
+impl<T> Buffer<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.slots[pos % self.capacity()].get()
+    }
+}
+
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut pos = head;
+        while pos != tail {
+            unsafe { (*self.slot(pos)).as_mut_ptr().drop_in_place() };
+            pos = pos.wrapping_add(1);
+        }
+    }
+}
+
+/// The producer side of a queue.
+pub struct Producer<T> {
+    buffer: Arc<Buffer<T>>,
+    /// The position to push at.
+    tail: usize,
+    /// The last observed position of the consumer.
+    head: usize,
+}
+
+/// The consumer side of a queue.
+pub struct Consumer<T> {
+    buffer: Arc<Buffer<T>>,
+    /// The position to pop at.
+    head: usize,
+    /// The last observed position of the producer.
+    tail: usize,
+}
+
+/// Creates a queue that holds at most `capacity` elements, and returns its producer and consumer.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be positive");
+    let buffer = Arc::new(Buffer {
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+    });
+    let producer = Producer {
+        buffer: buffer.clone(),
+        tail: 0,
+        head: 0,
+    };
+    let consumer = Consumer {
+        buffer,
+        head: 0,
+        tail: 0,
+    };
+    (producer, consumer)
+}
+
+impl<T> Producer<T> {
+    /// Returns the maximum number of elements.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Returns the number of free slots, reloading the consumer's position if there are fewer
+    /// than `wanted`.
+    fn free(&mut self, wanted: usize) -> usize {
+        let free = self.capacity() - self.tail.wrapping_sub(self.head);
+        if free >= wanted {
+            return free;
+        }
+        self.head = self.buffer.head.load(Ordering::Acquire);
+        self.capacity() - self.tail.wrapping_sub(self.head)
+    }
+
+    /// Adds `t` to the back of the queue. Returns it back if the queue is full.
+    pub fn try_push(&mut self, t: T) -> Result<(), Full<T>> {
+        if self.free(1) == 0 {
+            return Err(Full(t));
+        }
+        unsafe { (*self.buffer.slot(self.tail)).as_mut_ptr().write(t) };
+        self.tail = self.tail.wrapping_add(1);
+        self.buffer.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Adds the elements from `iter` to the back of the queue until it is full, publishing them
+    /// at once. Returns the number of pushed elements.
+    ///
+    /// No element is taken from `iter` if it doesn't fit.
+    pub fn try_push_batch<I: Iterator<Item = T>>(&mut self, iter: &mut I) -> usize {
+        let free = self.free(usize::MAX);
+        let mut pushed = 0;
+        for t in iter.take(free) {
+            let pos = self.tail.wrapping_add(pushed);
+            unsafe { (*self.buffer.slot(pos)).as_mut_ptr().write(t) };
+            pushed += 1;
+        }
+        if pushed > 0 {
+            self.tail = self.tail.wrapping_add(pushed);
+            self.buffer.tail.store(self.tail, Ordering::Release);
+        }
+        pushed
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Returns the maximum number of elements.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Returns the number of full slots, reloading the producer's position if there are fewer
+    /// than `wanted`.
+    fn full(&mut self, wanted: usize) -> usize {
+        let full = self.tail.wrapping_sub(self.head);
+        if full >= wanted {
+            return full;
+        }
+        self.tail = self.buffer.tail.load(Ordering::Acquire);
+        self.tail.wrapping_sub(self.head)
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn try_pop(&mut self) -> Option<T> {
+        if self.full(1) == 0 {
+            return None;
+        }
+        let t = unsafe { (*self.buffer.slot(self.head)).as_ptr().read() };
+        self.head = self.head.wrapping_add(1);
+        self.buffer.head.store(self.head, Ordering::Release);
+        Some(t)
+    }
+
+    /// Dequeues at most `max` elements from the front into `out`, releasing their slots at once.
+    /// Returns the number of popped elements.
+    pub fn try_pop_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        let popped = self.full(max).min(max);
+        out.reserve(popped);
+        for i in 0..popped {
+            let pos = self.head.wrapping_add(i);
+            out.push(unsafe { (*self.buffer.slot(pos)).as_ptr().read() });
+        }
+        if popped > 0 {
+            self.head = self.head.wrapping_add(popped);
+            self.buffer.head.store(self.head, Ordering::Release);
+        }
+        popped
+    }
+}
+
+impl<T> fmt::Debug for Producer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer")
+            .field("tail", &self.tail)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T> fmt::Debug for Consumer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer")
+            .field("head", &self.head)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread;
+    use std::thread::yield_now;
+
+    const CONC_COUNT: usize = 100000;
+
+    #[test]
+    fn push_pop() {
+        let (mut p, mut c) = channel(2);
+        assert_eq!(c.try_pop(), None);
+        assert_eq!(p.try_push(37), Ok(()));
+        assert_eq!(p.try_push(48), Ok(()));
+        assert_eq!(p.try_push(59), Err(Full(59)));
+        assert_eq!(c.try_pop(), Some(37));
+        assert_eq!(p.try_push(59), Ok(()));
+        assert_eq!(c.try_pop(), Some(48));
+        assert_eq!(c.try_pop(), Some(59));
+        assert_eq!(c.try_pop(), None);
+    }
+
+    #[test]
+    fn batch() {
+        let (mut p, mut c) = channel(4);
+        let mut iter = 0..6;
+        assert_eq!(p.try_push_batch(&mut iter), 4);
+        assert_eq!(iter.next(), Some(4));
+
+        let mut out = Vec::new();
+        assert_eq!(c.try_pop_batch(&mut out, 3), 3);
+        assert_eq!(out, vec![0, 1, 2]);
+        assert_eq!(p.try_push_batch(&mut iter), 1);
+        assert_eq!(c.try_pop_batch(&mut out, 3), 2);
+        assert_eq!(out, vec![0, 1, 2, 3, 5]);
+        assert_eq!(c.try_pop_batch(&mut out, 3), 0);
+    }
+
+    #[test]
+    fn drop_remaining() {
+        let (mut p, c) = channel(4);
+        for i in 0..3 {
+            p.try_push(i.to_string()).unwrap();
+        }
+        drop(c);
+        p.try_push(3.to_string()).unwrap();
+    }
+
+    #[test]
+    fn push_pop_many() {
+        let (mut p, mut c) = channel(16);
+
+        thread::scope(|scope| {
+            scope.spawn(move |_| {
+                let mut next = 0;
+                let mut out = Vec::new();
+                while next < CONC_COUNT {
+                    if c.try_pop_batch(&mut out, 8) == 0 {
+                        match c.try_pop() {
+                            Some(elem) => out.push(elem),
+                            None => yield_now(),
+                        }
+                    }
+                    for elem in out.drain(..) {
+                        assert_eq!(elem, next);
+                        next += 1;
+                    }
+                }
+            });
+
+            let mut iter = 0..CONC_COUNT;
+            while !iter.is_empty() {
+                if iter.len() % 2 == 0 {
+                    if p.try_push_batch(&mut iter) == 0 {
+                        yield_now();
+                    }
+                } else {
+                    let i = iter.next().unwrap();
+                    while p.try_push(i).is_err() {
+                        yield_now();
+                    }
+                }
+            }
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+//! Lock-free list with versioned values (MVCC).
+//!
+//! Each key keeps a chain of the versions of its value, newest first, and a read at a snapshot
+//! returns the newest version that is not newer than the snapshot. The versions are stamped as in
+//! vCAS: a version is installed with a pending timestamp, which is set to the current time by the
+//! first thread that sees it, and taking a snapshot advances the time. So a snapshot sees exactly
+//! the versions installed before it is taken.
+//!
+//! A key is never removed from the list: deleting it installs a version without value. Only the
+//! `VersionedList::MAX_VERSIONS` newest versions of each key are kept, so a read at an older
+//! snapshot may fail.
+//!
+//! Reference: Yuanhao Wei, Naama Ben-David, Guy E. Blelloch, Panagiota Fatourou, Eric Ruppert, and
+//! Yihan Sun. Constant-Time Snapshots with Applications to Concurrent Data Structures. PPoPP 2021.
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::list::{Find, List, Node};
+
+/// The timestamp of a version that is just installed.
+const PENDING: usize = usize::MAX;
+
+/// A version of a value.
+#[derive(Debug)]
+struct Version<V> {
+    ts: AtomicUsize,
+    /// `None` if the key is deleted.
+    value: Option<V>,
+    /// The previous version. Tag 1 if the older versions are pruned.
+    prev: Atomic<Version<V>>,
+}
+
+/// Chain of the versions of a value, newest first.
+#[derive(Debug)]
+struct Versions<V> {
+    head: Atomic<Version<V>>,
+}
+
+impl<V> Drop for Versions<V> {
+    fn drop(&mut self) {
+        unsafe {
+            let mut curr = self.head.load(Ordering::Relaxed, unprotected());
+            while !curr.is_null() {
+                let prev = curr.deref().prev.load(Ordering::Relaxed, unprotected());
+                drop(curr.into_owned());
+                curr = prev.with_tag(0);
+            }
+        }
+    }
+}
+
+/// Sorted singly linked list whose values are versioned, supporting snapshot reads.
+#[derive(Debug)]
+pub struct VersionedList<K, V> {
+    list: List<K, Versions<V>>,
+    /// The current time.
+    clock: AtomicUsize,
+}
+
+impl<K, V> Default for VersionedList<K, V>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> VersionedList<K, V>
+where
+    K: Ord,
+{
+    /// The number of versions kept for each key.
+    pub const MAX_VERSIONS: usize = 16;
+
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            list: List::new(),
+            clock: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a snapshot of the list, and returns its timestamp for `lookup_at`.
+    pub fn snapshot(&self) -> usize {
+        let ts = self.clock.load(Ordering::SeqCst);
+        let _ = self
+            .clock
+            .compare_exchange(ts, ts + 1, Ordering::SeqCst, Ordering::SeqCst);
+        ts
+    }
+
+    /// Sets the timestamp of a version to the current time if it is pending.
+    fn init_ts(&self, version: &Version<V>) {
+        if version.ts.load(Ordering::SeqCst) == PENDING {
+            let now = self.clock.load(Ordering::SeqCst);
+            let _ = version
+                .ts
+                .compare_exchange(PENDING, now, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns the versions of the key, inserting the key without versions if it is absent.
+    fn versions<'g>(&'g self, key: K, guard: &'g Guard) -> &'g Versions<V> {
+        if let Some(versions) = self.list.lookup_with(&key, Find::Harris, guard) {
+            return versions;
+        }
+
+        let versions = Versions {
+            head: Atomic::null(),
+        };
+        let mut node = Owned::new(Node::new(key, versions));
+        loop {
+            let mut cursor = self.list.head(guard);
+            match cursor.find(node.key(), Find::Harris, guard) {
+                Ok(true) => return cursor.lookup().unwrap(),
+                Ok(false) => match self.list.insert_at(&mut cursor, node, guard) {
+                    Ok(()) => return cursor.lookup().unwrap(),
+                    Err(n) => node = n,
+                },
+                Err(()) => {}
+            }
+        }
+    }
+
+    /// Installs `value` as the newest version if `cond` holds for the current value. Returns the
+    /// current value, and whether `value` is installed.
+    fn install<'g, F>(
+        &'g self,
+        versions: &'g Versions<V>,
+        value: Option<V>,
+        cond: F,
+        guard: &'g Guard,
+    ) -> (Option<&'g V>, bool)
+    where
+        F: Fn(Option<&V>) -> bool,
+    {
+        let mut new = Owned::new(Version {
+            ts: AtomicUsize::new(PENDING),
+            value,
+            prev: Atomic::null(),
+        });
+        loop {
+            let head = versions.head.load(Ordering::Acquire, guard);
+            // The current version is stamped before it is replaced.
+            let current = unsafe { head.as_ref() }.and_then(|version| {
+                self.init_ts(version);
+                version.value.as_ref()
+            });
+            if !cond(current) {
+                return (current, false);
+            }
+
+            new.prev.store(head, Ordering::Relaxed);
+            match versions
+                .head
+                .compare_and_set(head, new, Ordering::AcqRel, guard)
+            {
+                Ok(new) => {
+                    self.init_ts(unsafe { new.deref() });
+                    self.prune(new, guard);
+                    return (current, true);
+                }
+                Err(e) => new = e.new,
+            }
+        }
+    }
+
+    /// Prunes the versions older than the `MAX_VERSIONS` newest ones from `version`.
+    fn prune<'g>(&self, version: Shared<'g, Version<V>>, guard: &'g Guard) {
+        let mut oldest = unsafe { version.deref() };
+        for _ in 1..Self::MAX_VERSIONS {
+            let prev = oldest.prev.load(Ordering::Acquire, guard);
+            oldest = some_or!(unsafe { prev.as_ref() }, return);
+        }
+
+        // Each pruned version is taken from the next one by a swap, so it is freed once even if
+        // several threads prune the same chain.
+        let mut link = &oldest.prev;
+        loop {
+            let pruned = link.swap(Shared::null().with_tag(1), Ordering::AcqRel, guard);
+            let pruned_ref = some_or!(unsafe { pruned.as_ref() }, return);
+            unsafe { guard.defer_destroy(pruned) };
+            link = &pruned_ref.prev;
+        }
+    }
+
+    /// Returns the current value of the key.
+    pub fn lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let versions = self.list.lookup_with(key, Find::Harris, guard)?;
+        let head = versions.head.load(Ordering::Acquire, guard);
+        let head_ref = unsafe { head.as_ref() }?;
+        self.init_ts(head_ref);
+        head_ref.value.as_ref()
+    }
+
+    /// Returns the value of the key at the snapshot `ts`. Returns `Err(())` if the version at the
+    /// snapshot is pruned.
+    pub fn lookup_at<'g>(
+        &'g self,
+        key: &K,
+        ts: usize,
+        guard: &'g Guard,
+    ) -> Result<Option<&'g V>, ()> {
+        let versions = some_or!(
+            self.list.lookup_with(key, Find::Harris, guard),
+            return Ok(None)
+        );
+        let mut curr = versions.head.load(Ordering::Acquire, guard);
+        loop {
+            let version = match unsafe { curr.as_ref() } {
+                Some(version) => version,
+                None if curr.tag() == 0 => return Ok(None),
+                None => return Err(()),
+            };
+            // Only the newest version may be pending.
+            self.init_ts(version);
+            if version.ts.load(Ordering::SeqCst) <= ts {
+                return Ok(version.value.as_ref());
+            }
+            curr = version.prev.load(Ordering::Acquire, guard);
+        }
+    }
+
+    /// Inserts a key-value pair. Returns `false` if the key is present.
+    pub fn insert(&self, key: K, value: V, guard: &Guard) -> bool {
+        let versions = self.versions(key, guard);
+        self.install(versions, Some(value), |v| v.is_none(), guard)
+            .1
+    }
+
+    /// Stores a value for the key whether it is present or not. Returns the previous value.
+    pub fn store<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> Option<&'g V> {
+        let versions = self.versions(key, guard);
+        self.install(versions, Some(value), |_| true, guard).0
+    }
+
+    /// Deletes the key. Returns its value, or `None` if it is absent.
+    pub fn delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let versions = self.list.lookup_with(key, Find::Harris, guard)?;
+        self.install(versions, None, |v| v.is_some(), guard).0
+    }
+}